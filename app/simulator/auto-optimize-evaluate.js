@@ -19,9 +19,10 @@ import { validateSimulatorInputs } from './simulator-input-validation.js';
  * @param {number} maxDauer - Max. Simulationsdauer in Jahren
  * @param {Array<number>} seeds - Seed-Array
  * @param {object} constraints - Constraints (optional, für Early Exit)
+ * @param {Function} runBatch - MC-Batch-Runner (Standard: Worker-Pool, nativ: serieller Runner)
  * @returns {Promise<object|null>} Aggregierte Ergebnisse oder null wenn Constraints verletzt
  */
-export async function evaluateCandidate(candidate, baseInputs, runsPerCandidate, maxDauer, seeds, constraints = null) {
+export async function evaluateCandidate(candidate, baseInputs, runsPerCandidate, maxDauer, seeds, constraints = null, runBatch = runMonteCarloAutoOptimize) {
     // Deep-clone inputs und Override anwenden
     const inputs = deepClone(baseInputs);
 
@@ -88,7 +89,7 @@ export async function evaluateCandidate(candidate, baseInputs, runsPerCandidate,
             methode: 'regime_markov'
         };
 
-        const { aggregatedResults, failCount } = await runMonteCarloAutoOptimize({
+        const { aggregatedResults, failCount } = await runBatch({
             inputs,
             widowOptions,
             monteCarloParams,
@@ -66,3 +66,33 @@ export function checkConstraints(results, constraints) {
 
     return true;
 }
+
+function computeDynamicFlexSafetyPenalty(results, objective) {
+    const sr = Number(results?.successProbFloor);
+    const dd = Number(results?.worst5Drawdown);
+    const ts = Number(results?.timeShareWRgt45);
+    const wr = Number(results?.medianWithdrawalRate);
+
+    const srPenalty = Number.isFinite(sr) ? Math.max(0, (0.97 - sr) / 0.05) : 0;
+    const ddPenalty = Number.isFinite(dd) ? Math.max(0, (dd - 0.50) / 0.20) : 0;
+    const tsPenalty = Number.isFinite(ts) ? Math.max(0, (ts - 0.12) / 0.20) : 0;
+    const wrPenalty = Number.isFinite(wr) ? Math.max(0, (wr - 0.055) / 0.020) : 0;
+    const combined = (0.30 * srPenalty) + (0.35 * ddPenalty) + (0.25 * tsPenalty) + (0.10 * wrPenalty);
+    if (combined <= 0) return 0;
+
+    if (objective?.metric === 'EndWealth_P50' || objective?.metric === 'EndWealth_P25') {
+        const wealthBase = Math.max(50000, Number(results?.medianEndWealth) || 0);
+        return wealthBase * Math.min(0.75, combined * 0.35);
+    }
+    return Math.min(0.8, combined * 0.2);
+}
+
+/**
+ * Objective inkl. Dynamic-Flex-Safety-Penalty (nur wenn Safety-Guards aktiv)
+ */
+export function computeObjectiveWithSafety(results, objective, useSafetyGuards) {
+    const baseObjective = getObjectiveValue(results, objective);
+    if (!useSafetyGuards) return baseObjective;
+    const penalty = computeDynamicFlexSafetyPenalty(results, objective);
+    return baseObjective - penalty;
+}
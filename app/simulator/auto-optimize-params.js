@@ -10,7 +10,7 @@
 /**
  * Whitelist der erlaubten Parameter-Keys
  */
-const ALLOWED_PARAM_KEYS = [
+export const ALLOWED_PARAM_KEYS = [
     'runwayMinM',
     'runwayTargetM',
     'goldTargetPct',
@@ -127,3 +127,74 @@ export function isValidCandidate(candidate, goldCap) {
 
     return true;
 }
+
+function resolveDynamicFlexMode(modeRaw) {
+    const mode = String(modeRaw || 'inherit').toLowerCase();
+    if (mode === 'force_on' || mode === 'force_off' || mode === 'inherit') return mode;
+    return 'inherit';
+}
+
+/**
+ * Wendet den Dynamic-Flex-Modus des Optimizers (inherit/force_on/force_off) auf die Basis-Inputs an
+ */
+export function applyDynamicFlexMode(baseInputs, modeRaw) {
+    const mode = resolveDynamicFlexMode(modeRaw);
+    const resolved = { ...(baseInputs || {}) };
+    if (mode === 'force_on') {
+        resolved.dynamicFlex = true;
+    } else if (mode === 'force_off') {
+        resolved.dynamicFlex = false;
+    }
+    return { inputs: resolved, mode };
+}
+
+const DYNAMIC_FLEX_OPTIMIZER_KEYS = new Set(['horizonYears', 'survivalQuantile', 'goGoMultiplier']);
+
+/**
+ * true, wenn eine der Dynamic-Flex-Stellschrauben optimiert wird
+ */
+export function hasDynamicFlexOptimizerParams(params) {
+    return Object.keys(params || {}).some(key => DYNAMIC_FLEX_OPTIMIZER_KEYS.has(key));
+}
+
+/**
+ * Aktuelle Konfiguration im Kandidaten-Format (Vergleichsbasis für Delta vs. Current)
+ * @param {object} params - Parameter-Ranges
+ * @param {object} baseInputs - Basis-Config
+ * @returns {object} Kandidat mit den Werten aus baseInputs
+ */
+export function buildCurrentConfig(params, baseInputs) {
+    const currentConfig = {};
+    if (params.runwayMinM !== undefined) {
+        currentConfig.runwayMinM = baseInputs.runwayMinMonths || 24;
+    }
+    if (params.runwayTargetM !== undefined) {
+        currentConfig.runwayTargetM = baseInputs.runwayTargetMonths || 36;
+    }
+    if (params.goldTargetPct !== undefined) {
+        currentConfig.goldTargetPct = baseInputs.goldAllokationProzent || 0;
+    }
+    if (params.targetEq !== undefined) {
+        currentConfig.targetEq = baseInputs.targetEq || 60;
+    }
+    if (params.rebalBand !== undefined) {
+        currentConfig.rebalBand = baseInputs.rebalBand || 5;
+    }
+    if (params.maxSkimPct !== undefined) {
+        currentConfig.maxSkimPct = baseInputs.maxSkimPctOfEq || 25;
+    }
+    if (params.maxBearRefillPct !== undefined) {
+        currentConfig.maxBearRefillPct = baseInputs.maxBearRefillPctOfEq || 50;
+    }
+    if (params.horizonYears !== undefined) {
+        currentConfig.horizonYears = baseInputs.horizonYears || 30;
+    }
+    if (params.survivalQuantile !== undefined) {
+        currentConfig.survivalQuantile = baseInputs.survivalQuantile || 0.85;
+    }
+    if (params.goGoMultiplier !== undefined) {
+        currentConfig.goGoMultiplier = baseInputs.goGoMultiplier || 1.0;
+    }
+
+    return currentConfig;
+}
@@ -51,3 +51,15 @@ export function tieBreaker(a, b) {
 
     return 0;
 }
+
+/**
+ * Sortier-Comparator: höheres Objective zuerst, bei (nahezu) Gleichstand Tie-Breaker
+ * @param {object} a - {objValue, results}
+ * @param {object} b - {objValue, results}
+ * @returns {number}
+ */
+export function compareByObjective(a, b) {
+    const diff = b.objValue - a.objValue;
+    if (Math.abs(diff) > 0.0001) return diff;
+    return tieBreaker(a, b);
+}
@@ -20,6 +20,7 @@ import {
 } from './monte-carlo-chunk-result.js';
 import { compileScenario, getDataVersion } from './simulator-engine-helpers.js';
 import { WorkerPool } from '../../workers/worker-pool.js';
+import { withHistoricalDatasetOverlay } from './simulator-data.js';
 import {
     normalizeMonteCarloParametersV1,
     normalizeMonteCarloResourceConfigV1,
@@ -73,6 +74,31 @@ function buildAggregatesFromBatch(inputs, batch) {
     });
 }
 
+/**
+ * Runs one candidate batch as a single chunk on the calling thread. Used
+ * without Worker support and by the native optimizer, which parallelises
+ * across candidates instead of run ranges.
+ */
+export async function runMonteCarloAutoOptimizeSerial({ inputs, widowOptions, monteCarloParams, useCapeSampling, engine = null }) {
+    const { anzahl } = monteCarloParams;
+    const chunk = await runMonteCarloChunk({
+        inputs,
+        widowOptions,
+        monteCarloParams,
+        useCapeSampling,
+        runRange: { start: 0, count: anzahl },
+        logIndices: [],
+        engine
+    });
+    const batch = finalizeChunkBatch(anzahl, [chunk]);
+    const aggregatedResults = buildAggregatesFromBatch(inputs, batch);
+    return finalizeAutoOptimizeBatch({
+        aggregatedResults,
+        technicalInventory: batch.technicalInventory,
+        failCount: batch.totals.failCount
+    });
+}
+
 let autoOptimizePool = null;
 let autoOptimizePoolSize = 0;
 
@@ -82,7 +108,7 @@ function getAutoOptimizePool(workerCount) {
             autoOptimizePool.dispose();
         }
         autoOptimizePool = new WorkerPool({
-            workerUrl: new URL('../../workers/mc-worker.js', import.meta.url),
+            workerUrl: withHistoricalDatasetOverlay(new URL('../../workers/mc-worker.js', import.meta.url)),
             size: workerCount,
             type: 'module',
             telemetryName: 'AutoOptimizePool',
@@ -106,21 +132,7 @@ export async function runMonteCarloAutoOptimize({ inputs, widowOptions, monteCar
 
     // Fallback: ohne Worker läuft alles seriell im Main Thread.
     if (typeof Worker === 'undefined') {
-        const chunk = await runMonteCarloChunk({
-            inputs,
-            widowOptions,
-            monteCarloParams,
-            useCapeSampling,
-            runRange: { start: 0, count: anzahl },
-            logIndices: []
-        });
-        const batch = finalizeChunkBatch(anzahl, [chunk]);
-        const aggregatedResults = buildAggregatesFromBatch(inputs, batch);
-        return finalizeAutoOptimizeBatch({
-            aggregatedResults,
-            technicalInventory: batch.technicalInventory,
-            failCount: batch.totals.failCount
-        });
+        return runMonteCarloAutoOptimizeSerial({ inputs, widowOptions, monteCarloParams, useCapeSampling });
     }
 
     const pool = getAutoOptimizePool(workerCount);
@@ -209,21 +221,7 @@ export async function runMonteCarloAutoOptimize({ inputs, widowOptions, monteCar
             autoOptimizePoolSize = 0;
         }
         console.error('[AUTO_OPT] Worker execution failed, falling back to serial.', error);
-        const chunk = await runMonteCarloChunk({
-            inputs,
-            widowOptions,
-            monteCarloParams,
-            useCapeSampling,
-            runRange: { start: 0, count: anzahl },
-            logIndices: []
-        });
-        const batch = finalizeChunkBatch(anzahl, [chunk]);
-        const aggregatedResults = buildAggregatesFromBatch(inputs, batch);
-        return finalizeAutoOptimizeBatch({
-            aggregatedResults,
-            technicalInventory: batch.technicalInventory,
-            failCount: batch.totals.failCount
-        });
+        return runMonteCarloAutoOptimizeSerial({ inputs, widowOptions, monteCarloParams, useCapeSampling });
     } finally {
         if (autoOptimizePool?.telemetry && autoOptimizePool.telemetry.enabled) {
             autoOptimizePool.telemetry.printReport();
@@ -27,64 +27,14 @@
 
 import { rng } from './simulator-utils.js';
 import { getCommonInputs, prepareHistoricalData } from './simulator-portfolio.js';
-import { isValidCandidate } from './auto-optimize-params.js';
+import { applyDynamicFlexMode, buildCurrentConfig, hasDynamicFlexOptimizerParams, isValidCandidate } from './auto-optimize-params.js';
 import { latinHypercubeSample, generateNeighborsReduced } from './auto-optimize-sampling.js';
 import { evaluateCandidate } from './auto-optimize-evaluate.js';
-import { CandidateCache, tieBreaker } from './auto-optimize-utils.js';
-import { checkConstraints, getObjectiveValue } from './auto-optimize-metrics.js';
+import { CandidateCache, compareByObjective } from './auto-optimize-utils.js';
+import { checkConstraints, computeObjectiveWithSafety } from './auto-optimize-metrics.js';
 
 export { getObjectiveValue } from './auto-optimize-metrics.js';
 
-function resolveDynamicFlexMode(modeRaw) {
-    const mode = String(modeRaw || 'inherit').toLowerCase();
-    if (mode === 'force_on' || mode === 'force_off' || mode === 'inherit') return mode;
-    return 'inherit';
-}
-
-function applyDynamicFlexMode(baseInputs, modeRaw) {
-    const mode = resolveDynamicFlexMode(modeRaw);
-    const resolved = { ...(baseInputs || {}) };
-    if (mode === 'force_on') {
-        resolved.dynamicFlex = true;
-    } else if (mode === 'force_off') {
-        resolved.dynamicFlex = false;
-    }
-    return { inputs: resolved, mode };
-}
-
-const DYNAMIC_FLEX_OPTIMIZER_KEYS = new Set(['horizonYears', 'survivalQuantile', 'goGoMultiplier']);
-
-function hasDynamicFlexOptimizerParams(params) {
-    return Object.keys(params || {}).some(key => DYNAMIC_FLEX_OPTIMIZER_KEYS.has(key));
-}
-
-function computeDynamicFlexSafetyPenalty(results, objective) {
-    const sr = Number(results?.successProbFloor);
-    const dd = Number(results?.worst5Drawdown);
-    const ts = Number(results?.timeShareWRgt45);
-    const wr = Number(results?.medianWithdrawalRate);
-
-    const srPenalty = Number.isFinite(sr) ? Math.max(0, (0.97 - sr) / 0.05) : 0;
-    const ddPenalty = Number.isFinite(dd) ? Math.max(0, (dd - 0.50) / 0.20) : 0;
-    const tsPenalty = Number.isFinite(ts) ? Math.max(0, (ts - 0.12) / 0.20) : 0;
-    const wrPenalty = Number.isFinite(wr) ? Math.max(0, (wr - 0.055) / 0.020) : 0;
-    const combined = (0.30 * srPenalty) + (0.35 * ddPenalty) + (0.25 * tsPenalty) + (0.10 * wrPenalty);
-    if (combined <= 0) return 0;
-
-    if (objective?.metric === 'EndWealth_P50' || objective?.metric === 'EndWealth_P25') {
-        const wealthBase = Math.max(50000, Number(results?.medianEndWealth) || 0);
-        return wealthBase * Math.min(0.75, combined * 0.35);
-    }
-    return Math.min(0.8, combined * 0.2);
-}
-
-function computeObjectiveWithSafety(results, objective, useSafetyGuards) {
-    const baseObjective = getObjectiveValue(results, objective);
-    if (!useSafetyGuards) return baseObjective;
-    const penalty = computeDynamicFlexSafetyPenalty(results, objective);
-    return baseObjective - penalty;
-}
-
 /**
  * Hauptfunktion: Auto-Optimize
  * @param {object} config - Konfiguration
@@ -299,11 +249,7 @@ export async function runAutoOptimize(config) {
     }
 
     // Neu sortieren
-    evaluated.sort((a, b) => {
-        const diff = b.objValue - a.objValue;
-        if (Math.abs(diff) > 0.0001) return diff;
-        return tieBreaker(a, b);
-    });
+    evaluated.sort(compareByObjective);
 
     // Top-3 für Test-Validierung
     const top3 = evaluated.slice(0, 3);
@@ -342,14 +288,10 @@ export async function runAutoOptimize(config) {
     }
 
     // Champion-Auswahl (basierend auf Test-Objective)
-    validated.sort((a, b) => {
-        const diff = b.testObjValue - a.testObjValue;
-        if (Math.abs(diff) > 0.0001) return diff;
-        return tieBreaker(
-            { results: a.testResults },
-            { results: b.testResults }
-        );
-    });
+    validated.sort((a, b) => compareByObjective(
+        { objValue: a.testObjValue, results: a.testResults },
+        { objValue: b.testObjValue, results: b.testResults }
+    ));
 
     const champion = validated[0];
 
@@ -357,40 +299,8 @@ export async function runAutoOptimize(config) {
     // Vereinfachte Metrik: Verhältnis Train-Objective zu Test-Objective
     const stability = Math.min(1, champion.trainObjValue / (champion.testObjValue + 0.0001));
 
-    // Delta vs. Current - dynamically build current config from baseInputs
-    const currentConfig = {};
-
-    // Map all possible parameters from baseInputs to candidate format
-    if (params.runwayMinM !== undefined) {
-        currentConfig.runwayMinM = baseInputs.runwayMinMonths || 24;
-    }
-    if (params.runwayTargetM !== undefined) {
-        currentConfig.runwayTargetM = baseInputs.runwayTargetMonths || 36;
-    }
-    if (params.goldTargetPct !== undefined) {
-        currentConfig.goldTargetPct = baseInputs.goldAllokationProzent || 0;
-    }
-    if (params.targetEq !== undefined) {
-        currentConfig.targetEq = baseInputs.targetEq || 60;
-    }
-    if (params.rebalBand !== undefined) {
-        currentConfig.rebalBand = baseInputs.rebalBand || 5;
-    }
-    if (params.maxSkimPct !== undefined) {
-        currentConfig.maxSkimPct = baseInputs.maxSkimPctOfEq || 25;
-    }
-    if (params.maxBearRefillPct !== undefined) {
-        currentConfig.maxBearRefillPct = baseInputs.maxBearRefillPctOfEq || 50;
-    }
-    if (params.horizonYears !== undefined) {
-        currentConfig.horizonYears = baseInputs.horizonYears || 30;
-    }
-    if (params.survivalQuantile !== undefined) {
-        currentConfig.survivalQuantile = baseInputs.survivalQuantile || 0.85;
-    }
-    if (params.goGoMultiplier !== undefined) {
-        currentConfig.goGoMultiplier = baseInputs.goGoMultiplier || 1.0;
-    }
+    // Delta vs. Current
+    const currentConfig = buildCurrentConfig(params, baseInputs);
 
     const currentResults = await evaluate(
         currentConfig,
@@ -17,6 +17,7 @@ import {
     canonicalizeHistoricalContractValue,
     sha256Hex
 } from './historical-backtest-contract.js';
+import { HISTORICAL_DATA_MANIFEST } from './simulator-data.js';
 
 export const MONTE_CARLO_EXPORT_SCHEMA_ID = 'de.ruhestandsapp.monte-carlo.run';
 export const MONTE_CARLO_EXPORT_VERSION = 'MonteCarloExportV1';
@@ -112,7 +113,23 @@ function requireString(value, field) {
     return value;
 }
 
-export function captureMonteCarloEngineProvenance(engineApi) {
+// Same shape as the dataset provenance of the backtest export, so both name
+// the embedded dataset, the overlay or a user dataset the same way.
+function captureDatasetProvenance(manifest) {
+    if (!manifest || typeof manifest !== 'object') return null;
+    return {
+        datasetId: manifest.datasetId || null,
+        revision: manifest.revision || null,
+        contentHash: manifest.contentHash?.value || null,
+        manifestSchemaVersion: manifest.schemaVersion || null,
+        manifestHash: {
+            algorithm: 'sha256-canonical-json-v1',
+            value: sha256Hex(canonicalizeHistoricalContractValue(manifest))
+        }
+    };
+}
+
+export function captureMonteCarloEngineProvenance(engineApi, manifest = HISTORICAL_DATA_MANIFEST) {
     let version = null;
     let config = null;
     try {
@@ -130,7 +147,8 @@ export function captureMonteCarloEngineProvenance(engineApi) {
             : {
                 algorithm: MONTE_CARLO_FINGERPRINT_ALGORITHM,
                 value: sha256Hex(canonicalizeHistoricalContractValue(config))
-            }
+            },
+        dataset: captureDatasetProvenance(manifest)
     });
 }
 
@@ -1,10 +1,11 @@
 /**
  * Module: Simulator Data
  * Purpose: Central repository for static simulation data.
- *          Includes historical market data (1925-2025), mortality tables,
- *          care grades/probabilities, and stress test presets.
+ *          Includes historical market data (1925-2025, optionally extended by
+ *          a user-local overlay), mortality tables, care grades/probabilities,
+ *          and stress test presets.
  * Usage: Imported by various simulator modules (historical, stress, etc.).
- * Dependencies: None (pure data)
+ * Dependencies: None (pure data; the overlay is read via Tauri when available)
  */
 "use strict";
 
@@ -69,8 +70,10 @@ function historicalSeriesManifest({
  * Ungeklaerte Herkunfts-, Varianten- und Lizenzangaben bleiben absichtlich
  * `unresolved`; der Hash wird vom DOM-freien Contract gegen die kanonische
  * Post-Normalisierungs-Projektion von HISTORICAL_DATA geprueft.
+ * HISTORICAL_DATA_MANIFEST weiter unten ist dieses Manifest oder das eines
+ * gueltigen Nutzer-Overlays bzw. eines fuer den Lauf gewaehlten Nutzerdatensatzes.
  */
-export const HISTORICAL_DATA_MANIFEST = deepFreezeDataMetadata({
+export const EMBEDDED_HISTORICAL_DATA_MANIFEST = deepFreezeDataMetadata({
   schemaVersion: 'HistoricalDataManifestV1',
   datasetId: 'ruhestandsapp-historical-data-v1',
   revision: '2026-07-18.1',
@@ -146,53 +149,6 @@ export const HISTORICAL_DATA_MANIFEST = deepFreezeDataMetadata({
   }
 });
 
-/**
- * Maschinenlesbare Metadaten zu Datenherkunft und Qualität der Simulationsreihen.
- * Detaillierte Quellenangaben stehen in docs/reference/DATA_SOURCES.md.
- */
-export const DATASET_META = Object.freeze({
-  historicalData: {
-    manifestId: HISTORICAL_DATA_MANIFEST.datasetId,
-    revision: HISTORICAL_DATA_MANIFEST.revision,
-    contentHash: HISTORICAL_DATA_MANIFEST.contentHash,
-    coverageYears: [1925, 2025],
-    estimatedYears: [ESTIMATED_HISTORY_MIN_YEAR, ESTIMATED_HISTORY_MAX_YEAR],
-    estimatedHistoryCutoffYear: ESTIMATED_HISTORY_CUTOFF_YEAR,
-    notes: [
-      'Years 1925-1949 are normalized to connect to the 1950 level.',
-      'Use the Monte Carlo setting "exclude estimated history" to drop years < 1950 from sampling.'
-    ],
-    series: {
-      msci_eur: {
-        label: 'MSCI World EUR-like index level',
-        variantStatus: 'undocumented',
-        sourceStatus: 'partially_estimated_pre_1950'
-      },
-      inflation_de: {
-        label: 'German CPI inflation (annual)',
-        sourceStatus: 'historical_series'
-      },
-      zinssatz_de: {
-        label: 'German short-rate proxy (annual)',
-        sourceStatus: 'historical_series'
-      },
-      lohn_de: {
-        label: 'German wage growth proxy (annual)',
-        sourceStatus: 'historical_series'
-      },
-      gold_eur_perf: {
-        label: 'Gold annual performance (EUR proxy)',
-        sourceStatus: 'partial_history_with_zero_fallbacks'
-      },
-      cape: {
-        label: 'CAPE/Shiller valuation proxy',
-        sourceStatus: 'historical_proxy'
-      }
-    },
-    documentation: 'docs/reference/DATA_SOURCES.md'
-  }
-});
-
 /**
  * Altersabhängige Eintrittswahrscheinlichkeiten für Pflegegrade 1–5.
  *
@@ -378,6 +334,219 @@ export const HISTORICAL_DATA = {
   }
 })();
 
+// --- USER-LOCAL DATASET OVERLAY ---
+
+export const HISTORICAL_DATASET_OVERLAY_SCHEMA_VERSION = 'HistoricalDatasetOverlayV1';
+const HISTORICAL_DATASET_OVERLAY_PARAM = 'historicalDatasetOverlay';
+const HISTORICAL_SERIES_IDS = Object.freeze(['msci_eur', 'inflation_de', 'zinssatz_de', 'lohn_de', 'gold_eur_perf', 'cape']);
+
+/**
+ * Jahresfortschreibungen des nativen Updaters (historical_dataset.rs) liegen
+ * als Overlay im App-Datenverzeichnis. Die nativen Engine-Hosts injizieren es
+ * als Global, Worker erhalten es ueber ihre URL (withHistoricalDatasetOverlay),
+ * das Fenster fragt den Tauri-Command ab. Ohne Tauri bleibt es beim
+ * eingebetteten Bestand.
+ */
+async function loadHistoricalDatasetOverlay() {
+  if (globalThis.__ruhestandHistoricalDatasetOverlay !== undefined) {
+    return globalThis.__ruhestandHistoricalDatasetOverlay;
+  }
+  if (typeof WorkerGlobalScope !== 'undefined') {
+    const param = new URLSearchParams(globalThis.location?.search || '').get(HISTORICAL_DATASET_OVERLAY_PARAM);
+    try {
+      return param ? JSON.parse(param) : null;
+    } catch {
+      return null;
+    }
+  }
+  const invoke = globalThis.window?.__TAURI__?.core?.invoke || globalThis.__TAURI__?.core?.invoke;
+  if (typeof invoke !== 'function') return null;
+  try {
+    return await invoke('load_historical_dataset_overlay');
+  } catch {
+    return null;
+  }
+}
+
+/**
+ * Strukturpruefung des Overlays: es muss auf genau diesem eingebetteten
+ * Bestand aufsetzen und die Folgejahre lueckenlos mit allen Reihen liefern.
+ * Manifest und Content-Hash prueft createHistoricalBacktestContractProvider
+ * wie beim eingebetteten Bestand. Ungueltige Overlays werden ignoriert.
+ */
+function readHistoricalDatasetOverlay(overlay) {
+  if (!overlay) return null;
+  const embeddedPeriod = EMBEDDED_HISTORICAL_DATA_MANIFEST.period;
+  const manifest = overlay.manifest;
+  const endYear = manifest?.period?.endYear;
+  const records = {};
+  let valid = overlay.schemaVersion === HISTORICAL_DATASET_OVERLAY_SCHEMA_VERSION
+    && overlay.baseContentHash === EMBEDDED_HISTORICAL_DATA_MANIFEST.contentHash.value
+    && manifest?.schemaVersion === EMBEDDED_HISTORICAL_DATA_MANIFEST.schemaVersion
+    && manifest.period?.startYear === embeddedPeriod.startYear
+    && Number.isInteger(endYear)
+    && endYear > embeddedPeriod.endYear
+    && Object.keys(overlay.records || {}).length === endYear - embeddedPeriod.endYear;
+  for (let year = embeddedPeriod.endYear + 1; valid && year <= endYear; year++) {
+    const record = overlay.records[year];
+    valid = HISTORICAL_SERIES_IDS.every(seriesId => Number.isFinite(record?.[seriesId]));
+    if (valid) {
+      records[year] = Object.fromEntries(HISTORICAL_SERIES_IDS.map(seriesId => [seriesId, record[seriesId]]));
+    }
+  }
+  if (!valid) {
+    console.warn('[simulator-data] Historisches Daten-Overlay passt nicht zum eingebetteten Bestand und wird ignoriert.');
+    return null;
+  }
+  return { overlay, manifest: JSON.parse(JSON.stringify(manifest)), records };
+}
+
+// --- USER-SUPPLIED DATASET ---
+
+export const USER_HISTORICAL_DATASET_SCHEMA_VERSION = 'UserHistoricalDatasetV1';
+const USER_HISTORICAL_DATASET_PARAM = 'historicalDataset';
+let userHistoricalDatasetError = null;
+
+/**
+ * Eigene Datensaetze des nativen Datensatz-Managers (user_datasets.rs)
+ * ersetzen den Bestand samt Overlay. Native Runner injizieren den Datensatz
+ * des Laufs als Global, Worker erhalten den des Fensters ueber ihre URL, das
+ * Fenster laedt den im Simulator gewaehlten (select_user_dataset), den der
+ * Command vorher erneut gegen den Contract prueft. Scheitert das, rechnet
+ * das Fenster mit dem eingebetteten Bestand und meldet den Fehler in
+ * DATASET_META.
+ */
+async function loadUserHistoricalDataset() {
+  if (globalThis.__ruhestandHistoricalDataset !== undefined) {
+    return globalThis.__ruhestandHistoricalDataset;
+  }
+  if (typeof WorkerGlobalScope !== 'undefined') {
+    const param = new URLSearchParams(globalThis.location?.search || '').get(USER_HISTORICAL_DATASET_PARAM);
+    return param ? JSON.parse(param) : null;
+  }
+  const invoke = globalThis.window?.__TAURI__?.core?.invoke || globalThis.__TAURI__?.core?.invoke;
+  if (typeof invoke !== 'function') return null;
+  try {
+    return await invoke('load_selected_user_dataset');
+  } catch (error) {
+    userHistoricalDatasetError = String(error?.message || error);
+    console.warn(`[simulator-data] Gewaehlter Nutzerdatensatz nicht ladbar: ${userHistoricalDatasetError}`);
+    return null;
+  }
+}
+
+/**
+ * Ein geladener, aber unlesbarer Datensatz bricht ab, statt still mit dem
+ * eingebetteten Bestand zu rechnen.
+ */
+function readUserHistoricalDataset(dataset) {
+  if (!dataset) return null;
+  const period = dataset.manifest?.period;
+  const records = {};
+  let valid = dataset.schemaVersion === USER_HISTORICAL_DATASET_SCHEMA_VERSION
+    && dataset.manifest?.schemaVersion === EMBEDDED_HISTORICAL_DATA_MANIFEST.schemaVersion
+    && Number.isInteger(period?.startYear)
+    && Number.isInteger(period?.endYear)
+    && Object.keys(dataset.records || {}).length === period.endYear - period.startYear + 1;
+  for (let year = period?.startYear; valid && year <= period.endYear; year++) {
+    const record = dataset.records[year];
+    valid = HISTORICAL_SERIES_IDS.every(seriesId => Number.isFinite(record?.[seriesId]));
+    if (valid) {
+      records[year] = Object.fromEntries(HISTORICAL_SERIES_IDS.map(seriesId => [seriesId, record[seriesId]]));
+    }
+  }
+  if (!valid) {
+    throw new Error(`[simulator-data] Historischer Nutzerdatensatz ${dataset.datasetId || ''} ist ungueltig.`);
+  }
+  return { dataset, manifest: JSON.parse(JSON.stringify(dataset.manifest)), records };
+}
+
+const userHistoricalDataset = readUserHistoricalDataset(await loadUserHistoricalDataset());
+const historicalDatasetOverlay = userHistoricalDataset
+  ? null
+  : readHistoricalDatasetOverlay(await loadHistoricalDatasetOverlay());
+if (userHistoricalDataset) {
+  for (const year of Object.keys(HISTORICAL_DATA)) {
+    delete HISTORICAL_DATA[year];
+  }
+  Object.assign(HISTORICAL_DATA, userHistoricalDataset.records);
+} else if (historicalDatasetOverlay) {
+  Object.assign(HISTORICAL_DATA, historicalDatasetOverlay.records);
+}
+
+export const HISTORICAL_DATA_MANIFEST = deepFreezeDataMetadata(
+  (userHistoricalDataset || historicalDatasetOverlay)?.manifest || EMBEDDED_HISTORICAL_DATA_MANIFEST
+);
+
+/**
+ * Worker-URL, ueber die ein Worker dasselbe Overlay bzw. denselben
+ * Nutzerdatensatz laedt wie das Fenster.
+ */
+export function withHistoricalDatasetOverlay(workerUrl) {
+  if (!historicalDatasetOverlay && !userHistoricalDataset) return workerUrl;
+  const url = new URL(workerUrl);
+  if (userHistoricalDataset) {
+    url.searchParams.set(USER_HISTORICAL_DATASET_PARAM, JSON.stringify(userHistoricalDataset.dataset));
+  } else {
+    url.searchParams.set(HISTORICAL_DATASET_OVERLAY_PARAM, JSON.stringify(historicalDatasetOverlay.overlay));
+  }
+  return url;
+}
+
+/**
+ * Maschinenlesbare Metadaten zu Datenherkunft und Qualität der Simulationsreihen.
+ * Detaillierte Quellenangaben stehen in docs/reference/DATA_SOURCES.md.
+ */
+export const DATASET_META = Object.freeze({
+  historicalData: {
+    manifestId: HISTORICAL_DATA_MANIFEST.datasetId,
+    revision: HISTORICAL_DATA_MANIFEST.revision,
+    contentHash: HISTORICAL_DATA_MANIFEST.contentHash,
+    coverageYears: [HISTORICAL_DATA_MANIFEST.period.startYear, HISTORICAL_DATA_MANIFEST.period.endYear],
+    overlayYears: historicalDatasetOverlay
+      ? [EMBEDDED_HISTORICAL_DATA_MANIFEST.period.endYear + 1, HISTORICAL_DATA_MANIFEST.period.endYear]
+      : null,
+    userDataset: userHistoricalDataset
+      ? { datasetId: userHistoricalDataset.dataset.datasetId, label: userHistoricalDataset.dataset.label }
+      : null,
+    userDatasetError: userHistoricalDatasetError,
+    estimatedYears: [ESTIMATED_HISTORY_MIN_YEAR, ESTIMATED_HISTORY_MAX_YEAR],
+    estimatedHistoryCutoffYear: ESTIMATED_HISTORY_CUTOFF_YEAR,
+    notes: [
+      'Years 1925-1949 are normalized to connect to the 1950 level.',
+      'Use the Monte Carlo setting "exclude estimated history" to drop years < 1950 from sampling.'
+    ],
+    series: {
+      msci_eur: {
+        label: 'MSCI World EUR-like index level',
+        variantStatus: 'undocumented',
+        sourceStatus: 'partially_estimated_pre_1950'
+      },
+      inflation_de: {
+        label: 'German CPI inflation (annual)',
+        sourceStatus: 'historical_series'
+      },
+      zinssatz_de: {
+        label: 'German short-rate proxy (annual)',
+        sourceStatus: 'historical_series'
+      },
+      lohn_de: {
+        label: 'German wage growth proxy (annual)',
+        sourceStatus: 'historical_series'
+      },
+      gold_eur_perf: {
+        label: 'Gold annual performance (EUR proxy)',
+        sourceStatus: 'partial_history_with_zero_fallbacks'
+      },
+      cape: {
+        label: 'CAPE/Shiller valuation proxy',
+        sourceStatus: 'historical_proxy'
+      }
+    },
+    documentation: 'docs/reference/DATA_SOURCES.md'
+  }
+});
+
 /**
  * Sterbetafeln für Männer und Frauen
  */
@@ -0,0 +1,66 @@
+"use strict";
+
+import { DATASET_META, EMBEDDED_HISTORICAL_DATA_MANIFEST } from './simulator-data.js';
+
+function tauriInvoke() {
+    const invoke = globalThis.window?.__TAURI__?.core?.invoke;
+    return typeof invoke === 'function' ? invoke : null;
+}
+
+function datasetOption(value, text) {
+    const option = document.createElement('option');
+    option.value = value;
+    option.textContent = text;
+    return option;
+}
+
+/**
+ * Auswahl des historischen Datensatzes (nur Desktop). Eigene Datensaetze aus
+ * user_datasets.rs ersetzen den eingebetteten Bestand samt Overlay fuer
+ * Monte-Carlo, Backtest, Sweep und Optimierung. simulator-data.js laedt die
+ * Auswahl beim Start, daher wird das Fenster nach einem Wechsel neu geladen.
+ */
+export async function initHistoricalDatasetPicker() {
+    const row = document.getElementById('historicalDatasetRow');
+    const select = document.getElementById('historicalDatasetSelect');
+    const status = document.getElementById('historicalDatasetStatus');
+    const invoke = tauriInvoke();
+    if (!row || !select || !invoke) return;
+
+    let datasets;
+    try {
+        datasets = await invoke('list_user_datasets');
+    } catch (error) {
+        console.warn('[simulator-dataset] Nutzerdatensaetze nicht lesbar:', error);
+        return;
+    }
+
+    const meta = DATASET_META.historicalData;
+    const embedded = EMBEDDED_HISTORICAL_DATA_MANIFEST.period;
+    const embeddedEnd = meta.overlayYears ? meta.overlayYears[1] : embedded.endYear;
+    select.replaceChildren(datasetOption('', `Eingebetteter Bestand (${embedded.startYear}-${embeddedEnd})`));
+    for (const dataset of datasets) {
+        select.appendChild(datasetOption(dataset.datasetId, `${dataset.label} (${dataset.startYear}-${dataset.endYear})`));
+    }
+    const active = meta.userDataset?.datasetId || '';
+    select.value = active;
+    if (status) {
+        status.textContent = meta.userDatasetError
+            ? `Gewählter Datensatz nicht ladbar, es gilt der eingebettete Bestand: ${meta.userDatasetError}`
+            : `Aktiv: ${meta.userDataset ? meta.userDataset.label : 'eingebetteter Bestand'} (${meta.coverageYears.join('-')})`;
+    }
+    row.hidden = false;
+    row.style.display = '';
+
+    select.addEventListener('change', async () => {
+        select.disabled = true;
+        try {
+            await invoke('select_user_dataset', { datasetId: select.value || null });
+            window.location.reload();
+        } catch (error) {
+            select.value = active;
+            select.disabled = false;
+            if (status) status.textContent = `Auswahl fehlgeschlagen: ${error?.message || error}`;
+        }
+    });
+}
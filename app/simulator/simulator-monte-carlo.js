@@ -24,6 +24,7 @@ import {
 } from './worker-job-runner.js';
 import { featureFlags } from '../shared/feature-flags.js';
 import { WorkerPool } from '../../workers/worker-pool.js';
+import { withHistoricalDatasetOverlay } from './simulator-data.js';
 import { formatSimulatorValidationError, validateSimulatorInputs } from './simulator-input-validation.js';
 import { EngineAPI } from '../../engine/index.mjs';
 import {
@@ -114,7 +115,7 @@ function getMonteCarloPool(workerCount) {
             globalMonteCarloPool.dispose();
         }
         globalMonteCarloPool = new WorkerPool({
-            workerUrl: new URL('../../workers/mc-worker.js', import.meta.url),
+            workerUrl: withHistoricalDatasetOverlay(new URL('../../workers/mc-worker.js', import.meta.url)),
             size: workerCount,
             type: 'module',
             telemetryName: 'MonteCarloPool',
@@ -20,6 +20,7 @@ import { displaySensitivityAnalysis, displayParetoFrontier } from './simulator-v
 import { deepClone, extractP2Invariants } from './simulator-sweep-utils.js';
 import { renderSweepHeatmapSVG } from './simulator-heatmap.js';
 import { WorkerPool } from '../../workers/worker-pool.js';
+import { withHistoricalDatasetOverlay } from './simulator-data.js';
 import { WorkerJobRunner } from './worker-job-runner.js';
 import { buildSweepInputs, runSweepChunk } from './sweep-runner.js';
 import { persistenceStorage } from '../shared/persistence-facade.js';
@@ -122,7 +123,7 @@ async function runSweepWithWorkers({
         ? desiredWorkers
         : Math.max(1, (navigator?.hardwareConcurrency || 2) - 1));
     const timeBudgetMs = workerConfig.timeBudgetMs ?? 200;
-    const workerUrl = new URL('../../workers/mc-worker.js', import.meta.url);
+    const workerUrl = withHistoricalDatasetOverlay(new URL('../../workers/mc-worker.js', import.meta.url));
 
     const pool = new WorkerPool({
         workerUrl,
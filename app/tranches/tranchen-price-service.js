@@ -240,7 +240,7 @@ function normalizeSearchResultSymbol(result) {
 export async function fetchProxySymbol(query, proxyBase = LOCAL_YAHOO_PROXY, nameHint, options = {}) {
     const url = `${proxyBase.replace(/\/$/, '')}/search?q=${encodeURIComponent(query)}`;
     const data = await fetchProxyJson(url, options);
-    const results = Array.isArray(data?.quotes) ? data.quotes : [];
+    const results = Array.isArray(data?.results) ? data.results : [];
     if (!results.length) return null;
     const q = String(query || '').trim().toUpperCase();
     const exact = results.find(result => normalizeSearchResultSymbol(result) === q);
@@ -249,7 +249,7 @@ export async function fetchProxySymbol(query, proxyBase = LOCAL_YAHOO_PROXY, nam
     if (nameHint) {
         const name = String(nameHint).trim().toUpperCase();
         const byName = results.find(result => {
-            const candidateName = String(result?.shortname || result?.longname || '').toUpperCase();
+            const candidateName = String(result?.name || '').toUpperCase();
             return candidateName.includes(name) && normalizeSearchResultSymbol(result);
         });
         if (byName) return normalizeSearchResultSymbol(byName);
@@ -270,7 +270,7 @@ export async function checkProxyHealth(statusEl, proxyBase = LOCAL_YAHOO_PROXY)
     try {
         const data = await fetchProxyJson(`${base}/search?q=VWCE`);
         const elapsed = Math.round(performance.now() - startedAt);
-        const count = Array.isArray(data?.quotes) ? data.quotes.length : 0;
+        const count = Array.isArray(data?.results) ? data.results.length : 0;
         const stamp = new Date().toLocaleString('de-DE');
         statusEl.textContent = `Proxy OK (${stamp}) - Antwort in ${elapsed} ms, Treffer: ${count}.`;
     } catch (error) {
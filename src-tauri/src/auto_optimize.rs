@@ -0,0 +1,733 @@
+//! Native auto-optimize: runs the LHS, quick filter, full evaluation,
+//! refinement and validation phases of auto_optimize.js with candidates
+//! spread over one embedded engine runtime per core. Every finished
+//! evaluation lands in an on-disk checkpoint, so a job restarted with the same
+//! id and request picks up where the previous process stopped.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+
+use crate::monte_carlo_export::{canonical_json, fingerprint};
+use crate::script_runtime::{
+  job_failure as failure, script_failure, JobFailure, ScriptHost, ScriptSource, SharedNativeJobs, SCRIPT_THREAD_STACK_BYTES,
+};
+
+pub(crate) const AUTO_OPTIMIZE_PROGRESS_EVENT: &str = "ruhestand://auto-optimize-progress";
+pub(crate) const AUTO_OPTIMIZE_CHAMPION_EVENT: &str = "ruhestand://auto-optimize-champion";
+
+const HOST_MODULE_NAME: &str = "native/auto-optimize-host.js";
+const HOST_MODULE_SOURCE: &str = include_str!("auto_optimize_host.js");
+const HOST_OBJECT: &str = "__ruhestandAutoOptimizeHost";
+const CHECKPOINT_SCHEMA_VERSION: &str = "AutoOptimizeCheckpointV1";
+const CHECKPOINT_DIR: &str = "auto-optimize";
+/// Evaluations finish every few hundred milliseconds on large machines; the
+/// checkpoint is rewritten at most this often and at the end of every phase.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Stage {
+  QuickFilter,
+  EvaluateLhs,
+  Refine,
+  Validate,
+  Current,
+  Done,
+}
+
+impl Stage {
+  /// Evaluations with the same runs and seeds are interchangeable, so full
+  /// evaluation and refinement share the train cache like `CandidateCache`.
+  fn cache_prefix(self) -> &'static str {
+    match self {
+      Stage::QuickFilter => "quick",
+      Stage::EvaluateLhs | Stage::Refine => "train",
+      Stage::Validate | Stage::Current | Stage::Done => "test",
+    }
+  }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AutoOptimizeProgress {
+  pub stage: Stage,
+  pub completed: usize,
+  pub total: usize,
+  /// Evaluations of this stage taken from the checkpoint.
+  pub resumed: usize,
+  pub elapsed_ms: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AutoOptimizeChampion {
+  pub stage: Stage,
+  pub candidate: Value,
+  pub obj_value: f64,
+  pub results: Value,
+  /// Only the final champion has passed the test seeds.
+  pub validated: bool,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum AutoOptimizeEvent {
+  Progress(AutoOptimizeProgress),
+  Champion(AutoOptimizeChampion),
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AutoOptimizeOptions {
+  /// Defaults to all available cores.
+  pub threads: Option<usize>,
+  /// Checkpoint file; `None` keeps everything in memory.
+  pub checkpoint: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Plan {
+  candidates: Vec<Value>,
+  train_seeds: Value,
+  test_seeds: Value,
+  quick_seeds: Value,
+  quick_runs: u32,
+  runs_per_candidate: u32,
+  quick_filter_top: usize,
+  refine_top: usize,
+  validate_top: usize,
+  current_config: Value,
+  optimization_context: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Score {
+  obj_value: f64,
+  feasible: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+  candidate: Value,
+  results: Value,
+  obj_value: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Checkpoint {
+  pub schema_version: String,
+  pub job_id: String,
+  pub request_fingerprint: String,
+  pub request: Value,
+  pub stage: Stage,
+  /// `<cache prefix>|<canonical candidate>` -> evaluation results, `null`
+  /// when the candidate failed the early constraint exit.
+  pub evaluations: BTreeMap<String, Value>,
+}
+
+struct CheckpointStore {
+  path: Option<PathBuf>,
+  checkpoint: Checkpoint,
+  /// Keys read from a previous process, counted once as resumed.
+  restored: HashSet<String>,
+  written: Instant,
+  dirty: bool,
+}
+
+impl CheckpointStore {
+  /// Reuses the evaluations of an existing checkpoint for the same request;
+  /// anything else at `path` is overwritten by the new run.
+  fn open(path: Option<PathBuf>, job_id: &str, request: &Value) -> Self {
+    let request_fingerprint = fingerprint(request);
+    let previous = path.as_deref().and_then(read_checkpoint).filter(|checkpoint| {
+      let matches = checkpoint.request_fingerprint == request_fingerprint;
+      if !matches {
+        log::warn!("Auto-Optimize-Checkpoint fuer {} gehoert zu einer anderen Anfrage und wird verworfen.", job_id);
+      }
+      matches
+    });
+    let checkpoint = Checkpoint {
+      schema_version: CHECKPOINT_SCHEMA_VERSION.to_string(),
+      job_id: job_id.to_string(),
+      request_fingerprint,
+      request: request.clone(),
+      stage: Stage::QuickFilter,
+      evaluations: previous.map(|checkpoint| checkpoint.evaluations).unwrap_or_default(),
+    };
+    let restored = checkpoint.evaluations.keys().cloned().collect();
+    CheckpointStore { path, checkpoint, restored, written: Instant::now(), dirty: false }
+  }
+
+  fn get(&self, key: &str) -> Option<&Value> {
+    self.checkpoint.evaluations.get(key)
+  }
+
+  fn take_restored(&mut self, key: &str) -> bool {
+    self.restored.remove(key)
+  }
+
+  fn insert(&mut self, key: String, results: Value) {
+    self.checkpoint.evaluations.insert(key, results);
+    self.dirty = true;
+  }
+
+  fn set_stage(&mut self, stage: Stage) {
+    self.checkpoint.stage = stage;
+    self.dirty = true;
+  }
+
+  fn flush(&mut self, force: bool) -> Result<(), JobFailure> {
+    let Some(path) = self.path.as_ref() else { return Ok(()) };
+    if !self.dirty || (!force && self.written.elapsed() < CHECKPOINT_INTERVAL) {
+      return Ok(());
+    }
+    let content = serde_json::to_string(&self.checkpoint).map_err(|e| failure("AUTO_OPT_CHECKPOINT_FAILED", e.to_string()))?;
+    crate::write_atomic(path, &content).map_err(|e| failure("AUTO_OPT_CHECKPOINT_FAILED", e.to_string()))?;
+    self.written = Instant::now();
+    self.dirty = false;
+    Ok(())
+  }
+
+  fn remove(&self) {
+    if let Some(path) = self.path.as_ref() {
+      let _ = fs::remove_file(path);
+    }
+  }
+}
+
+fn read_checkpoint(path: &Path) -> Option<Checkpoint> {
+  let raw = fs::read_to_string(path).ok()?;
+  serde_json::from_str::<Checkpoint>(&raw)
+    .ok()
+    .filter(|checkpoint| checkpoint.schema_version == CHECKPOINT_SCHEMA_VERSION)
+}
+
+fn open_host(source: &ScriptSource, request_json: &str, interrupt: Option<Arc<AtomicBool>>) -> Result<(ScriptHost, String), JobFailure> {
+  let host = ScriptHost::new(source.clone(), interrupt).map_err(|e| failure("AUTO_OPT_ENGINE_FAILED", e))?;
+  host
+    .evaluate_module(HOST_MODULE_NAME, HOST_MODULE_SOURCE)
+    .map_err(|e| script_failure("AUTO_OPT_ENGINE_FAILED", e))?;
+  let plan = call_host(&host, "prepare", (request_json.to_string(),))?;
+  Ok((host, plan))
+}
+
+fn call_host<A: for<'js> rquickjs::function::IntoArgs<'js>>(host: &ScriptHost, method: &str, args: A) -> Result<String, JobFailure> {
+  host.call_string(HOST_OBJECT, method, args).map_err(|e| {
+    let code = if e.contains("AUTO_OPT_REQUEST_INVALID") { "AUTO_OPT_REQUEST_INVALID" } else { "AUTO_OPT_ENGINE_FAILED" };
+    script_failure(code, e)
+  })
+}
+
+fn parse<T: serde::de::DeserializeOwned>(raw: &str) -> Result<T, JobFailure> {
+  serde_json::from_str(raw).map_err(|e| failure("AUTO_OPT_ENGINE_FAILED", e.to_string()))
+}
+
+struct Optimizer<'a> {
+  source: &'a ScriptSource,
+  request_json: String,
+  coordinator: ScriptHost,
+  threads: usize,
+  cancel: &'a Arc<AtomicBool>,
+  store: CheckpointStore,
+  started: Instant,
+  resumed: usize,
+  on_event: &'a (dyn Fn(AutoOptimizeEvent) + Sync),
+}
+
+fn report_progress(on_event: &(dyn Fn(AutoOptimizeEvent) + Sync), started: Instant, stage: Stage, completed: usize, total: usize, resumed: usize) {
+  on_event(AutoOptimizeEvent::Progress(AutoOptimizeProgress {
+    stage,
+    completed,
+    total,
+    resumed,
+    elapsed_ms: started.elapsed().as_millis() as u64,
+  }));
+}
+
+impl Optimizer<'_> {
+  fn progress(&self, stage: Stage, completed: usize, total: usize, resumed: usize) {
+    report_progress(self.on_event, self.started, stage, completed, total, resumed);
+  }
+
+  fn champion(&self, stage: Stage, entry: &Entry, validated: bool) {
+    (self.on_event)(AutoOptimizeEvent::Champion(AutoOptimizeChampion {
+      stage,
+      candidate: entry.candidate.clone(),
+      obj_value: entry.obj_value,
+      results: entry.results.clone(),
+      validated,
+    }));
+  }
+
+  fn score(&self, results: &Value) -> Result<Score, JobFailure> {
+    parse(&call_host(&self.coordinator, "score", (results.to_string(),))?)
+  }
+
+  /// Sorts like auto_optimize.js: by objective only, or with the
+  /// tie-breakers of `compareByObjective` when `with_tie_break` is set.
+  fn rank(&self, entries: Vec<Entry>, with_tie_break: bool) -> Result<Vec<Entry>, JobFailure> {
+    let entries = serde_json::to_string(&entries).map_err(|e| failure("AUTO_OPT_ENGINE_FAILED", e.to_string()))?;
+    parse(&call_host(&self.coordinator, "rank", (entries, with_tie_break))?)
+  }
+
+  /// Scores evaluated candidates; with `feasible_only` the constraints apply.
+  fn entries(&self, candidates: &[Value], evaluations: Vec<Option<Value>>, feasible_only: bool) -> Result<Vec<Entry>, JobFailure> {
+    let mut entries = Vec::new();
+    for (candidate, results) in candidates.iter().zip(evaluations) {
+      let Some(results) = results.filter(|results| !results.is_null()) else { continue };
+      let score = self.score(&results)?;
+      if !feasible_only || score.feasible {
+        entries.push(Entry { candidate: candidate.clone(), results, obj_value: score.obj_value });
+      }
+    }
+    Ok(entries)
+  }
+
+  /// Evaluates `candidates` in order, reusing cached and checkpointed
+  /// evaluations and spreading the rest over the worker runtimes. Repeated
+  /// candidates are evaluated once and keep their place in the result.
+  fn evaluate(
+    &mut self,
+    stage: Stage,
+    candidates: &[Value],
+    runs: u32,
+    seeds: &Value,
+    with_constraints: bool,
+  ) -> Result<Vec<Option<Value>>, JobFailure> {
+    self.store.set_stage(stage);
+    let keys: Vec<String> = candidates
+      .iter()
+      .map(|candidate| format!("{}|{}", stage.cache_prefix(), canonical_json(candidate)))
+      .collect();
+    let mut unique = HashSet::new();
+    let (mut pending, mut resumed) = (Vec::new(), 0);
+    for (index, key) in keys.iter().enumerate() {
+      if !unique.insert(key.as_str()) {
+        continue;
+      }
+      if self.store.get(key).is_none() {
+        pending.push(index);
+      } else if self.store.take_restored(key) {
+        resumed += 1;
+      }
+    }
+    let (total, done) = (unique.len(), unique.len() - pending.len());
+    self.resumed += resumed;
+    self.progress(stage, done, total, resumed);
+
+    let outcome = self.evaluate_pending(stage, candidates, &keys, &pending, runs, seeds, with_constraints, (done, total, resumed));
+    self.store.flush(true)?;
+    outcome?;
+    Ok(keys.iter().map(|key| self.store.get(key).cloned()).collect())
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  fn evaluate_pending(
+    &mut self,
+    stage: Stage,
+    candidates: &[Value],
+    keys: &[String],
+    pending: &[usize],
+    runs: u32,
+    seeds: &Value,
+    with_constraints: bool,
+    (done, total, resumed): (usize, usize, usize),
+  ) -> Result<(), JobFailure> {
+    if pending.is_empty() {
+      return Ok(());
+    }
+    let next = AtomicUsize::new(0);
+    let seeds_json = seeds.to_string();
+    let (sender, receiver) = mpsc::channel::<(usize, Result<String, JobFailure>)>();
+    let (source, request_json, cancel) = (self.source, &self.request_json, self.cancel);
+    let (on_event, started, store) = (self.on_event, self.started, &mut self.store);
+    let threads = self.threads.min(pending.len());
+
+    std::thread::scope(|scope| -> Result<(), JobFailure> {
+      for _ in 0..threads {
+        let sender = sender.clone();
+        let (next, seeds_json) = (&next, &seeds_json);
+        let cancel = cancel.clone();
+        std::thread::Builder::new()
+          .name("ruhestand-auto-optimize".to_string())
+          .stack_size(SCRIPT_THREAD_STACK_BYTES)
+          .spawn_scoped(scope, move || {
+            let host = match open_host(source, request_json, Some(cancel.clone())) {
+              Ok((host, _)) => host,
+              Err(error) => {
+                let _ = sender.send((usize::MAX, Err(error)));
+                return;
+              }
+            };
+            while !cancel.load(Ordering::Relaxed) {
+              let Some(&index) = pending.get(next.fetch_add(1, Ordering::Relaxed)) else { break };
+              let candidate = candidates[index].to_string();
+              let outcome = call_host(&host, "evaluate", (candidate, f64::from(runs), seeds_json.clone(), with_constraints));
+              let failed = outcome.is_err();
+              if sender.send((index, outcome)).is_err() || failed {
+                break;
+              }
+            }
+          })
+          .map_err(|e| failure("AUTO_OPT_ENGINE_FAILED", e.to_string()))?;
+      }
+      drop(sender);
+
+      let mut completed = done;
+      let mut first_error = None;
+      for (index, outcome) in receiver {
+        match outcome.and_then(|raw| parse::<Value>(&raw)) {
+          Ok(results) if first_error.is_none() => {
+            completed += 1;
+            store.insert(keys[index].clone(), results);
+            if let Err(error) = store.flush(false) {
+              cancel.store(true, Ordering::Relaxed);
+              first_error = Some(error);
+              continue;
+            }
+            report_progress(on_event, started, stage, completed, total, resumed);
+          }
+          Ok(_) => {}
+          Err(error) => {
+            // Errors after a cancel request are just interrupted evaluations.
+            if !cancel.swap(true, Ordering::Relaxed) {
+              first_error = Some(error);
+            }
+          }
+        }
+      }
+      match first_error {
+        Some(error) => Err(error),
+        None if completed < total => Err(failure("AUTO_OPT_NATIVE_CANCELLED", "Auto-Optimize wurde abgebrochen.")),
+        None => Ok(()),
+      }
+    })
+  }
+}
+
+fn metric(results: &Value, key: &str) -> f64 {
+  results[key].as_f64().unwrap_or(0.0)
+}
+
+/// Runs an auto-optimize request (base `inputs` plus the `runAutoOptimize`
+/// config) and returns the same champion document as auto_optimize.js.
+/// Candidate sets and parameter counts are only limited by the request.
+pub(crate) fn run_auto_optimize(
+  source: &ScriptSource,
+  job_id: &str,
+  request: &Value,
+  options: AutoOptimizeOptions,
+  cancel: &Arc<AtomicBool>,
+  on_event: &(dyn Fn(AutoOptimizeEvent) + Sync),
+) -> Result<Value, JobFailure> {
+  let request_json = request.to_string();
+  let (coordinator, plan) = open_host(source, &request_json, Some(cancel.clone()))?;
+  let plan: Plan = parse(&plan)?;
+  let available = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+  let mut optimizer = Optimizer {
+    source,
+    request_json,
+    coordinator,
+    threads: options.threads.unwrap_or(available).max(1),
+    cancel,
+    store: CheckpointStore::open(options.checkpoint, job_id, request),
+    started: Instant::now(),
+    resumed: 0,
+    on_event,
+  };
+
+  // Quick filter: few runs on two train seeds, no constraints.
+  let quick = optimizer.evaluate(Stage::QuickFilter, &plan.candidates, plan.quick_runs, &plan.quick_seeds, false)?;
+  let quick_filtered = optimizer.entries(&plan.candidates, quick, false)?;
+  if quick_filtered.is_empty() {
+    return Err(failure("AUTO_OPT_NO_CANDIDATES", "Quick-Filter: kein Kandidat lieferte ein gueltiges Ergebnis."));
+  }
+  let mut quick_filtered = optimizer.rank(quick_filtered, false)?;
+  quick_filtered.truncate(plan.quick_filter_top);
+  let shortlist: Vec<Value> = quick_filtered.into_iter().map(|entry| entry.candidate).collect();
+
+  // Full evaluation of the shortlist on all train seeds.
+  let full = optimizer.evaluate(Stage::EvaluateLhs, &shortlist, plan.runs_per_candidate, &plan.train_seeds, true)?;
+  let evaluated = optimizer.entries(&shortlist, full, true)?;
+  if evaluated.is_empty() {
+    return Err(failure("AUTO_OPT_NO_CANDIDATES", "Kein Kandidat erfuellt die Constraints nach voller Evaluation."));
+  }
+  let mut evaluated = optimizer.rank(evaluated, false)?;
+  optimizer.champion(Stage::EvaluateLhs, &evaluated[0], false);
+
+  // Refinement around the best candidates. Like auto_optimize.js, neighbours
+  // that were already on the shortlist come from the train cache and are
+  // ranked a second time.
+  let seeds: Vec<&Value> = evaluated.iter().take(plan.refine_top).map(|entry| &entry.candidate).collect();
+  let refine: Vec<Value> = parse(&call_host(&optimizer.coordinator, "neighbors", (Value::from(seeds.into_iter().cloned().collect::<Vec<_>>()).to_string(),))?)?;
+  let refined = optimizer.evaluate(Stage::Refine, &refine, plan.runs_per_candidate, &plan.train_seeds, true)?;
+  evaluated.extend(optimizer.entries(&refine, refined, true)?);
+  let mut ranked = optimizer.rank(evaluated, true)?;
+  let evaluated_count = ranked.len();
+  optimizer.champion(Stage::Refine, &ranked[0], false);
+  ranked.truncate(plan.validate_top);
+
+  // Validation on the test seeds; the champion has the best test objective.
+  let finalists: Vec<Value> = ranked.iter().map(|entry| entry.candidate.clone()).collect();
+  let tested = optimizer.evaluate(Stage::Validate, &finalists, plan.runs_per_candidate, &plan.test_seeds, false)?;
+  let validated = optimizer.entries(&finalists, tested, true)?;
+  if validated.is_empty() {
+    return Err(failure("AUTO_OPT_NO_CANDIDATES", "Kein Kandidat besteht die Validierung auf den Test-Seeds."));
+  }
+  let validated_count = validated.len();
+  let champion = optimizer.rank(validated, true)?.remove(0);
+  let train = ranked
+    .iter()
+    .find(|entry| entry.candidate == champion.candidate)
+    .map(|entry| entry.obj_value)
+    .unwrap_or(champion.obj_value);
+  let stability = (train / (champion.obj_value + 0.0001)).min(1.0);
+
+  let current_config = std::slice::from_ref(&plan.current_config);
+  let current = optimizer.evaluate(Stage::Current, current_config, plan.runs_per_candidate, &plan.test_seeds, false)?;
+  let current = current.into_iter().next().flatten().unwrap_or(Value::Null);
+  let test = &champion.results;
+  let delta = serde_json::json!({
+    "successRate": metric(test, "successProbFloor") - metric(&current, "successProbFloor"),
+    "drawdownP90": metric(test, "worst5Drawdown") - metric(&current, "worst5Drawdown"),
+    "endWealthP50": metric(test, "medianEndWealth") - metric(&current, "medianEndWealth"),
+    "timeShareWRgt45": metric(test, "timeShareWRgt45") - metric(&current, "timeShareWRgt45")
+  });
+
+  optimizer.champion(Stage::Validate, &champion, true);
+  optimizer.progress(Stage::Done, 1, 1, 0);
+  optimizer.store.remove();
+  Ok(serde_json::json!({
+    "championCfg": champion.candidate,
+    "metricsTest": champion.results,
+    "deltaVsCurrent": delta,
+    "stability": stability,
+    "optimizationContext": plan.optimization_context,
+    "candidateCounts": {
+      "sampled": plan.candidates.len(),
+      "shortlisted": shortlist.len(),
+      "refined": refine.len(),
+      "feasible": evaluated_count,
+      "validated": validated_count
+    },
+    "resumedEvaluations": optimizer.resumed
+  }))
+}
+
+/// Job ids become file names, so only a conservative character set passes.
+fn checkpoint_path(app: &tauri::AppHandle, job_id: &str) -> Result<PathBuf, String> {
+  let valid = !job_id.is_empty() && job_id.len() <= 128 && job_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+  if !valid {
+    return Err(failure("AUTO_OPT_REQUEST_INVALID", "Job-ID darf nur Buchstaben, Ziffern, '-' und '_' enthalten.").to_string());
+  }
+  Ok(checkpoint_dir(app)?.join(format!("{}.json", job_id)))
+}
+
+fn checkpoint_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+  Ok(app.path().app_data_dir().map_err(|e| e.to_string())?.join(CHECKPOINT_DIR))
+}
+
+#[tauri::command]
+pub async fn run_native_auto_optimize(
+  app: tauri::AppHandle,
+  state: tauri::State<'_, SharedNativeJobs>,
+  job_id: String,
+  request: Value,
+) -> Result<Value, String> {
+  let jobs = state.inner().clone();
+  let source = crate::script_runtime::app_source(&app);
+  let checkpoint = checkpoint_path(&app, &job_id)?;
+  tauri::async_runtime::spawn_blocking(move || {
+    let job = jobs.start(&job_id).map_err(|e| e.to_string())?;
+    let report = |event: AutoOptimizeEvent| {
+      let _ = match event {
+        AutoOptimizeEvent::Progress(progress) => {
+          app.emit(AUTO_OPTIMIZE_PROGRESS_EVENT, serde_json::json!({ "jobId": job_id, "progress": progress }))
+        }
+        AutoOptimizeEvent::Champion(champion) => {
+          app.emit(AUTO_OPTIMIZE_CHAMPION_EVENT, serde_json::json!({ "jobId": job_id, "champion": champion }))
+        }
+      };
+    };
+    let options = AutoOptimizeOptions { threads: None, checkpoint: Some(checkpoint) };
+    run_auto_optimize(&source, &job_id, &request, options, job.cancel_flag(), &report).map_err(|e| e.to_string())
+  })
+  .await
+  .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn cancel_native_auto_optimize(state: tauri::State<'_, SharedNativeJobs>, job_id: String) -> bool {
+  state.cancel(&job_id)
+}
+
+/// Interrupted optimisations, so the UI can offer to resume them after a
+/// restart by calling `run_native_auto_optimize` with the stored request.
+#[tauri::command]
+pub fn list_native_auto_optimize_checkpoints(app: tauri::AppHandle) -> Result<Vec<Value>, String> {
+  let Ok(entries) = fs::read_dir(checkpoint_dir(&app)?) else { return Ok(Vec::new()) };
+  let mut checkpoints: Vec<Value> = entries
+    .flatten()
+    .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+    .filter_map(|entry| read_checkpoint(&entry.path()))
+    .map(|checkpoint| {
+      serde_json::json!({
+        "jobId": checkpoint.job_id,
+        "stage": checkpoint.stage,
+        "evaluations": checkpoint.evaluations.len(),
+        "request": checkpoint.request
+      })
+    })
+    .collect();
+  checkpoints.sort_by(|a, b| a["jobId"].as_str().cmp(&b["jobId"].as_str()));
+  Ok(checkpoints)
+}
+
+#[tauri::command]
+pub fn discard_native_auto_optimize_checkpoint(app: tauri::AppHandle, job_id: String) -> Result<bool, String> {
+  let path = checkpoint_path(&app, &job_id)?;
+  if !path.exists() {
+    return Ok(false);
+  }
+  fs::remove_file(&path).map_err(|e| e.to_string())?;
+  Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::script_runtime::{checkout_root, directory_source};
+  use std::sync::Mutex;
+
+  fn request(params: Value) -> Value {
+    let inputs = &crate::monte_carlo::tests::golden().1["scenario"]["normalizedInputs"];
+    serde_json::json!({
+      "inputs": inputs,
+      "objective": { "metric": "EndWealth_P50", "direction": "max" },
+      "params": params,
+      "constraints": { "sr99": false, "noex": false, "ts45": false, "dd55": false },
+      "runsPerCandidate": 20,
+      "seedsTrain": 2,
+      "seedsTest": 1,
+      "maxDauer": 10,
+      "lhsSamples": 6,
+      "quickFilterTop": 3,
+      "refineTop": 1,
+      "validateTop": 2
+    })
+  }
+
+  fn two_params() -> Value {
+    serde_json::json!({
+      "targetEq": { "min": 40, "max": 80, "step": 5 },
+      "runwayMinM": { "min": 12, "max": 36, "step": 6 }
+    })
+  }
+
+  fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ruhestand-auto-optimize-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+  }
+
+  #[test]
+  fn interrupted_runs_resume_from_the_checkpoint() {
+    let source = directory_source(checkout_root());
+    let request = request(two_params());
+    let idle = Arc::new(AtomicBool::new(false));
+    let champions = Mutex::new(Vec::new());
+    let uninterrupted = run_auto_optimize(&source, "reference", &request, AutoOptimizeOptions::default(), &idle, &|event| {
+      if let AutoOptimizeEvent::Champion(champion) = event {
+        champions.lock().unwrap().push((champion.stage, champion.validated));
+      }
+    })
+    .unwrap();
+    assert_eq!(
+      champions.into_inner().unwrap(),
+      vec![(Stage::EvaluateLhs, false), (Stage::Refine, false), (Stage::Validate, true)]
+    );
+    let champion = &uninterrupted["championCfg"];
+    assert!(champion["targetEq"].as_f64().is_some_and(|value| (40.0..=80.0).contains(&value)));
+    assert!(champion["runwayMinM"].as_f64().is_some());
+    assert_eq!(uninterrupted["resumedEvaluations"], 0);
+
+    // Stop after the second full evaluation, as if the app had been closed.
+    let dir = scratch_dir("resume");
+    let path = dir.join("job.json");
+    let options = AutoOptimizeOptions { threads: Some(2), checkpoint: Some(path.clone()) };
+    let cancel = Arc::new(AtomicBool::new(false));
+    let error = run_auto_optimize(&source, "job", &request, options.clone(), &cancel, &|event| {
+      if let AutoOptimizeEvent::Progress(progress) = event {
+        if progress.stage == Stage::EvaluateLhs && progress.completed >= 2 {
+          cancel.store(true, Ordering::Relaxed);
+        }
+      }
+    })
+    .unwrap_err();
+    assert_eq!(error.code, "AUTO_OPT_NATIVE_CANCELLED");
+    let checkpoint = read_checkpoint(&path).unwrap();
+    assert_eq!(checkpoint.stage, Stage::EvaluateLhs);
+    assert!(checkpoint.evaluations.keys().filter(|key| key.starts_with("train|")).count() >= 2);
+
+    let resumed = run_auto_optimize(&source, "job", &request, options, &idle, &|_| {}).unwrap();
+    assert!(resumed["resumedEvaluations"].as_u64().unwrap() >= 8);
+    let mut expected = uninterrupted;
+    expected["resumedEvaluations"] = resumed["resumedEvaluations"].clone();
+    assert_eq!(canonical_json(&resumed), canonical_json(&expected));
+    assert!(!path.exists());
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  /// Same config and base inputs as tests/native-auto-optimize-golden.test.mjs,
+  /// which records the champion of runAutoOptimize under V8.
+  #[test]
+  fn picks_the_champion_of_the_js_optimizer() {
+    let mut golden: Value = serde_json::from_str(&fs::read_to_string(checkout_root().join("tests/fixtures/native-auto-optimize-golden-v1.json")).unwrap()).unwrap();
+    let mut request = golden["config"].take();
+    request["inputs"] = crate::monte_carlo::tests::golden().1["scenario"]["normalizedInputs"].take();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let result = run_auto_optimize(&directory_source(checkout_root()), "golden", &request, AutoOptimizeOptions::default(), &cancel, &|_| {}).unwrap();
+    assert_eq!(result["championCfg"], golden["result"]["championCfg"]);
+    let mut mismatches = Vec::new();
+    for key in ["metricsTest", "stability"] {
+      crate::monte_carlo::tests::collect_mismatches(key, &result[key], &golden["result"][key], &mut mismatches);
+    }
+    assert!(mismatches.is_empty(), "{:?}", mismatches);
+  }
+
+  #[test]
+  fn checkpoints_of_other_requests_are_discarded() {
+    let dir = scratch_dir("stale");
+    let path = dir.join("job.json");
+    let mut store = CheckpointStore::open(Some(path.clone()), "job", &serde_json::json!({ "lhsSamples": 6 }));
+    store.insert("quick|{}".to_string(), Value::Null);
+    store.flush(true).unwrap();
+    assert_eq!(CheckpointStore::open(Some(path.clone()), "job", &serde_json::json!({ "lhsSamples": 6 })).checkpoint.evaluations.len(), 1);
+    assert!(CheckpointStore::open(Some(path), "job", &serde_json::json!({ "lhsSamples": 7 })).checkpoint.evaluations.is_empty());
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn invalid_requests_fail_with_codes() {
+    let source = directory_source(checkout_root());
+    let cancel = Arc::new(AtomicBool::new(false));
+    let run = |request: &Value| run_auto_optimize(&source, "invalid", request, AutoOptimizeOptions::default(), &cancel, &|_| {});
+    let unknown = request(serde_json::json!({ "withdrawalRate": { "min": 3, "max": 5, "step": 1 } }));
+    assert_eq!(run(&unknown).unwrap_err().code, "AUTO_OPT_REQUEST_INVALID");
+    // Dynamic-flex parameters need dynamic flex in the base inputs.
+    let flex = request(serde_json::json!({ "horizonYears": { "min": 20, "max": 40, "step": 5 } }));
+    assert_eq!(run(&flex).unwrap_err().code, "AUTO_OPT_REQUEST_INVALID");
+    let mut objective = request(two_params());
+    objective["objective"]["metric"] = Value::from("Sharpe");
+    assert_eq!(run(&objective).unwrap_err().code, "AUTO_OPT_REQUEST_INVALID");
+  }
+}
@@ -0,0 +1,165 @@
+// Native auto-optimize host, evaluated inside the embedded QuickJS runtime of
+// the desktop build (see auto_optimize.rs). Sampling, candidate evaluation,
+// scoring and ranking use the same modules as auto_optimize.js; the Rust side
+// only schedules the phases, caches evaluations and writes checkpoints.
+
+import { prepareHistoricalDataOnce } from '../app/simulator/simulator-engine-helpers.js';
+import { EngineAPI } from '../engine/index.mjs';
+import { rng } from '../app/simulator/simulator-utils.js';
+import {
+    ALLOWED_PARAM_KEYS,
+    applyDynamicFlexMode,
+    buildCurrentConfig,
+    hasDynamicFlexOptimizerParams,
+    isValidCandidate
+} from '../app/simulator/auto-optimize-params.js';
+import { generateNeighborsReduced, latinHypercubeSample } from '../app/simulator/auto-optimize-sampling.js';
+import { evaluateCandidate } from '../app/simulator/auto-optimize-evaluate.js';
+import { runMonteCarloAutoOptimizeSerial } from '../app/simulator/auto-optimize-worker.js';
+import { compareByObjective } from '../app/simulator/auto-optimize-utils.js';
+import { checkConstraints, computeObjectiveWithSafety, getObjectiveValue } from '../app/simulator/auto-optimize-metrics.js';
+
+const CONSTRAINT_KEYS = ['sr99', 'noex', 'ts45', 'dd55'];
+
+let config = null;
+
+function invalid(message) {
+    return new Error(`AUTO_OPT_REQUEST_INVALID: ${message}`);
+}
+
+function positiveInteger(value, name, fallback) {
+    if (value === undefined || value === null) return fallback;
+    if (!Number.isInteger(value) || value < 1) throw invalid(`${name} muss eine positive ganze Zahl sein.`);
+    return value;
+}
+
+function requireConfig() {
+    if (!config) throw new Error('Native auto-optimize host used before prepare().');
+    return config;
+}
+
+function readRequest(request) {
+    if (!request || typeof request !== 'object' || !request.inputs || typeof request.inputs !== 'object') {
+        throw invalid('inputs fehlen.');
+    }
+    const params = request.params || {};
+    const keys = Object.keys(params);
+    if (keys.length === 0) throw invalid('Mindestens ein Parameter ist erforderlich.');
+    for (const key of keys) {
+        if (!ALLOWED_PARAM_KEYS.includes(key)) throw invalid(`Unbekannter Parameter ${key}.`);
+        const { min, max, step } = params[key] || {};
+        if (![min, max, step].every(Number.isFinite) || min > max || step <= 0) {
+            throw invalid(`Range fuer ${key} braucht min <= max und step > 0.`);
+        }
+    }
+    const objective = request.objective || {};
+    try {
+        getObjectiveValue({}, objective);
+    } catch (error) {
+        throw invalid(String(error?.message || error));
+    }
+    const constraints = {};
+    for (const key of CONSTRAINT_KEYS) constraints[key] = request.constraints?.[key] === true;
+    const maxDauer = positiveInteger(request.maxDauer, 'maxDauer', 35);
+    return {
+        params,
+        objective,
+        constraints,
+        maxDauer,
+        runsPerCandidate: positiveInteger(request.runsPerCandidate, 'runsPerCandidate', 2000),
+        seedsTrain: positiveInteger(request.seedsTrain, 'seedsTrain', 5),
+        seedsTest: positiveInteger(request.seedsTest, 'seedsTest', 3),
+        lhsSamples: positiveInteger(request.lhsSamples, 'lhsSamples', 100),
+        quickFilterTop: positiveInteger(request.quickFilterTop, 'quickFilterTop', 50),
+        refineTop: positiveInteger(request.refineTop, 'refineTop', 5),
+        validateTop: positiveInteger(request.validateTop, 'validateTop', 3)
+    };
+}
+
+// Mirrors the setup part of runAutoOptimize; the base inputs come from the
+// request because there is no simulator form to read them from.
+function prepare(requestJson) {
+    prepareHistoricalDataOnce();
+    const raw = JSON.parse(requestJson);
+    const request = readRequest(raw);
+    const { inputs: baseInputs, mode } = applyDynamicFlexMode(raw.inputs, raw.dynamicFlexMode);
+    const usesDynamicFlexParams = hasDynamicFlexOptimizerParams(request.params);
+    if (usesDynamicFlexParams && baseInputs.dynamicFlex !== true) {
+        throw invalid('Dynamic-Flex Parameter im Optimizer gewaehlt, aber Dynamic Flex ist nicht aktiv (Mode=force_on oder aktive Rahmendaten erforderlich).');
+    }
+    const safetyGuardsActive = raw.safetyGuards !== false && usesDynamicFlexParams;
+    const goldCap = baseInputs.goldAllokationProzent || 10;
+    config = { ...request, baseInputs, goldCap, safetyGuardsActive };
+
+    const rand = rng(42);
+    const candidates = latinHypercubeSample(request.params, request.lhsSamples, rand)
+        .map(sample => ({ ...sample }))
+        .filter(candidate => isValidCandidate(candidate, goldCap));
+    const trainSeeds = Array.from({ length: request.seedsTrain }, (_, i) => 42 + i);
+    return JSON.stringify({
+        candidates,
+        trainSeeds,
+        testSeeds: Array.from({ length: request.seedsTest }, (_, i) => 420 + i),
+        quickSeeds: trainSeeds.slice(0, 2),
+        quickRuns: Math.max(1, Math.min(200, Math.round(request.runsPerCandidate * 0.1))),
+        runsPerCandidate: request.runsPerCandidate,
+        quickFilterTop: request.quickFilterTop,
+        refineTop: request.refineTop,
+        validateTop: request.validateTop,
+        currentConfig: buildCurrentConfig(request.params, baseInputs),
+        optimizationContext: {
+            dynamicFlexMode: mode,
+            dynamicFlexActive: baseInputs.dynamicFlex === true,
+            safetyGuardsActive,
+            usesDynamicFlexParams
+        }
+    });
+}
+
+function runBatch(args) {
+    return runMonteCarloAutoOptimizeSerial({ ...args, engine: EngineAPI });
+}
+
+async function evaluate(candidateJson, runs, seedsJson, withConstraints) {
+    const { baseInputs, maxDauer, constraints } = requireConfig();
+    const results = await evaluateCandidate(
+        JSON.parse(candidateJson),
+        baseInputs,
+        runs,
+        maxDauer,
+        JSON.parse(seedsJson),
+        withConstraints ? constraints : null,
+        runBatch
+    );
+    return JSON.stringify(results ?? null);
+}
+
+function score(resultsJson) {
+    const { objective, constraints, safetyGuardsActive } = requireConfig();
+    const results = JSON.parse(resultsJson);
+    return JSON.stringify({
+        objValue: computeObjectiveWithSafety(results, objective, safetyGuardsActive),
+        feasible: checkConstraints(results, constraints)
+    });
+}
+
+// runAutoOptimize sorts the quick filter and the full evaluation by the
+// objective alone and uses the tie-breakers only after refinement and for
+// the champion; `withTieBreak` selects between the two comparators.
+function rank(entriesJson, withTieBreak) {
+    const compare = withTieBreak ? compareByObjective : (a, b) => b.objValue - a.objValue;
+    return JSON.stringify(JSON.parse(entriesJson).sort(compare));
+}
+
+function neighbors(candidatesJson) {
+    const { params, goldCap } = requireConfig();
+    const unique = new Set();
+    for (const candidate of JSON.parse(candidatesJson)) {
+        for (const neighbor of generateNeighborsReduced(candidate, params)) {
+            if (isValidCandidate(neighbor, goldCap)) unique.add(JSON.stringify(neighbor));
+        }
+    }
+    return JSON.stringify(Array.from(unique).map(entry => JSON.parse(entry)));
+}
+
+globalThis.__ruhestandAutoOptimizeHost = { prepare, evaluate, score, rank, neighbors };
@@ -0,0 +1,364 @@
+//! Native historical backtest: runs a `BacktestRequestV1` through the
+//! simulator's backtest runner on the embedded engine runtime and returns the
+//! same JSON/CSV exports as the webview download buttons. Rolling cohorts are
+//! spread over one runtime per core.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+use tauri::Emitter;
+
+use crate::script_runtime::{
+  job_failure as failure, script_failure, JobFailure, ScriptHost, ScriptSource, SharedNativeJobs, SCRIPT_THREAD_STACK_BYTES,
+};
+
+pub(crate) const BACKTEST_PROGRESS_EVENT: &str = "ruhestand://backtest-progress";
+
+const HOST_MODULE_NAME: &str = "native/backtest-host.js";
+const HOST_MODULE_SOURCE: &str = include_str!("backtest_host.js");
+const HOST_OBJECT: &str = "__ruhestandBacktestHost";
+/// Chunks per thread; cohorts of one batch have the same length, so a few
+/// suffice to balance the threads.
+const CHUNKS_PER_THREAD: usize = 4;
+/// `COHORT_OUTCOME_KINDS` in historical-backtest-cohorts.js.
+const COHORT_OUTCOME_KINDS: [&str; 5] = ["completed", "ruin", "incomplete", "technical_error", "cancelled"];
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct BacktestOutput {
+  /// `completed`, `ruin`, `incomplete` or `technical_error`.
+  pub outcome: Option<String>,
+  /// `HistoricalBacktestExportV1`, canonical JSON.
+  pub export: String,
+  /// `HistoricalBacktestCsvV1`.
+  pub csv: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BacktestProgress {
+  pub completed_cohorts: usize,
+  pub total_cohorts: usize,
+  pub elapsed_ms: u64,
+}
+
+fn open_host(source: &ScriptSource, interrupt: Option<Arc<AtomicBool>>) -> Result<ScriptHost, JobFailure> {
+  let host = ScriptHost::new(source.clone(), interrupt).map_err(|e| failure("BACKTEST_ENGINE_FAILED", e))?;
+  host
+    .evaluate_module(HOST_MODULE_NAME, HOST_MODULE_SOURCE)
+    .map_err(|e| script_failure("BACKTEST_ENGINE_FAILED", e))?;
+  Ok(host)
+}
+
+fn call_host<A: for<'js> rquickjs::function::IntoArgs<'js>>(host: &ScriptHost, method: &str, args: A) -> Result<String, JobFailure> {
+  host.call_string(HOST_OBJECT, method, args).map_err(|e| {
+    let code = if e.contains("BACKTEST_REQUEST_INVALID") { "BACKTEST_REQUEST_INVALID" } else { "BACKTEST_ENGINE_FAILED" };
+    script_failure(code, e)
+  })
+}
+
+/// `cohort_horizon_years` adds the rolling-cohort inventory to the export,
+/// as the "Kohorten" checkbox does in the simulator.
+pub(crate) fn run_backtest(source: &ScriptSource, request: &Value, cohort_horizon_years: Option<u32>) -> Result<BacktestOutput, JobFailure> {
+  let inventory = match cohort_horizon_years {
+    Some(horizon) => {
+      let cancel = Arc::new(AtomicBool::new(false));
+      let cohorts = run_backtest_cohorts(source, request, horizon, None, &cancel, &|_| {})?;
+      cohorts["inventory"].to_string()
+    }
+    None => String::new(),
+  };
+  let host = open_host(source, None)?;
+  let output = call_host(&host, "run", (request.to_string(), inventory))?;
+  serde_json::from_str(&output).map_err(|e| failure("BACKTEST_ENGINE_FAILED", e.to_string()))
+}
+
+/// Start years of the windows `runHistoricalBacktestCohorts` treats as
+/// eligible (inclusive end = start + horizon - 1 within the range).
+fn eligible_start_years(request: &Value, horizon: u32) -> Result<Vec<i64>, JobFailure> {
+  let (Some(start), Some(end)) = (request["startYear"].as_i64(), request["endYear"].as_i64()) else {
+    return Err(failure("BACKTEST_REQUEST_INVALID", "startYear/endYear muessen ganze Jahre in aufsteigender Reihenfolge sein."));
+  };
+  if start > end {
+    return Err(failure("BACKTEST_REQUEST_INVALID", "startYear/endYear muessen ganze Jahre in aufsteigender Reihenfolge sein."));
+  }
+  if horizon == 0 {
+    return Err(failure("BACKTEST_REQUEST_INVALID", "Der Kohortenhorizont muss mindestens ein Jahr betragen."));
+  }
+  Ok((start..=end).filter(|year| year + i64::from(horizon) - 1 <= end).collect())
+}
+
+/// JS numbers that happen to be integral serialize without a fraction.
+fn js_rate(value: f64) -> Value {
+  if value.fract() == 0.0 && value.abs() < 9_007_199_254_740_992.0 {
+    Value::from(value as i64)
+  } else {
+    Value::from(value)
+  }
+}
+
+/// `countOutcomes` in historical-backtest-cohorts.js.
+fn cohort_inventory(cohorts: &[Value], eligible: usize, excluded: &[Value]) -> Value {
+  let mut counts = [0usize; COHORT_OUTCOME_KINDS.len()];
+  let mut exclusion_reasons = serde_json::Map::new();
+  let mut count_reason = |code: &str| {
+    let entry = exclusion_reasons.entry(code.to_string()).or_insert(Value::from(0));
+    *entry = Value::from(entry.as_u64().unwrap_or(0) + 1);
+  };
+  for entry in excluded {
+    count_reason(entry["reason"]["code"].as_str().unwrap_or_default());
+  }
+  for cohort in cohorts {
+    let kind = cohort["outcome"]["kind"].as_str().unwrap_or_default();
+    if let Some(index) = COHORT_OUTCOME_KINDS.iter().position(|known| *known == kind) {
+      counts[index] += 1;
+    }
+    if let Some(reason) = cohort["exclusionReason"].as_str().filter(|reason| !reason.is_empty()) {
+      count_reason(reason);
+    }
+  }
+  let rate = |count: usize| if eligible > 0 { js_rate(count as f64 / eligible as f64 * 100.0) } else { Value::Null };
+  let [completed, ruin, incomplete, technical_error, cancelled] = counts;
+  serde_json::json!({
+    "candidate": eligible + excluded.len(),
+    "eligible": eligible,
+    "completed": completed,
+    "ruin": ruin,
+    "incomplete": incomplete,
+    "technicalError": technical_error,
+    "cancelled": cancelled,
+    "financiallyEvaluable": completed + ruin,
+    "excluded": excluded.len(),
+    "exclusionReasons": exclusion_reasons,
+    "rateDenominator": "all_eligible_cohorts",
+    "ratesPct": {
+      "completed": rate(completed),
+      "ruin": rate(ruin),
+      "incomplete": rate(incomplete),
+      "technicalError": rate(technical_error),
+      "cancelled": rate(cancelled)
+    }
+  })
+}
+
+/// Runs every eligible rolling cohort of the request's range and returns the
+/// same `HistoricalBacktestCohortsV1` document as `runHistoricalBacktestCohorts`.
+/// Cohorts are independent, so threads pull chunks of start years and the
+/// results are reassembled in start-year order.
+pub(crate) fn run_backtest_cohorts(
+  source: &ScriptSource,
+  request: &Value,
+  cohort_horizon_years: u32,
+  threads: Option<usize>,
+  cancel: &Arc<AtomicBool>,
+  on_progress: &(dyn Fn(BacktestProgress) + Sync),
+) -> Result<Value, JobFailure> {
+  let started = Instant::now();
+  let start_years = eligible_start_years(request, cohort_horizon_years)?;
+  let available = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+  let threads = threads.unwrap_or(available).max(1).min(start_years.len().max(1));
+  let chunk_len = start_years.len().div_ceil(threads * CHUNKS_PER_THREAD).max(1);
+  // An empty range still needs one call for the request frame and exclusions.
+  let chunks: Vec<&[i64]> = if start_years.is_empty() { vec![&[]] } else { start_years.chunks(chunk_len).collect() };
+  let request_json = request.to_string();
+  let horizon = f64::from(cohort_horizon_years);
+  let next_chunk = AtomicUsize::new(0);
+  let (sender, receiver) = mpsc::channel::<(usize, Result<String, JobFailure>)>();
+
+  let outputs = std::thread::scope(|scope| -> Result<Vec<Option<Value>>, JobFailure> {
+    for _ in 0..threads.min(chunks.len()) {
+      let sender = sender.clone();
+      let (chunks, next_chunk, request_json) = (&chunks, &next_chunk, &request_json);
+      let cancel = cancel.clone();
+      std::thread::Builder::new()
+        .name("ruhestand-backtest".to_string())
+        .stack_size(SCRIPT_THREAD_STACK_BYTES)
+        .spawn_scoped(scope, move || {
+          let host = match open_host(source, Some(cancel.clone())) {
+            Ok(host) => host,
+            Err(error) => {
+              let _ = sender.send((usize::MAX, Err(error)));
+              return;
+            }
+          };
+          while !cancel.load(Ordering::Relaxed) {
+            let index = next_chunk.fetch_add(1, Ordering::Relaxed);
+            let Some(years) = chunks.get(index) else { break };
+            let years_json = serde_json::to_string(years).unwrap_or_default();
+            let outcome = call_host(&host, "runCohorts", (request_json.clone(), horizon, years_json));
+            let failed = outcome.is_err();
+            if sender.send((index, outcome)).is_err() || failed {
+              break;
+            }
+          }
+        })
+        .map_err(|e| failure("BACKTEST_ENGINE_FAILED", e.to_string()))?;
+    }
+    drop(sender);
+
+    let mut outputs: Vec<Option<Value>> = vec![None; chunks.len()];
+    let mut completed_cohorts = 0;
+    let mut first_error = None;
+    for (index, outcome) in receiver {
+      let parsed = outcome.and_then(|raw| serde_json::from_str::<Value>(&raw).map_err(|e| failure("BACKTEST_ENGINE_FAILED", e.to_string())));
+      match parsed {
+        Ok(output) if first_error.is_none() => {
+          completed_cohorts += chunks[index].len();
+          outputs[index] = Some(output);
+          on_progress(BacktestProgress {
+            completed_cohorts,
+            total_cohorts: start_years.len(),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+          });
+        }
+        Ok(_) => {}
+        Err(error) => {
+          // Errors after a cancel request are just interrupted chunks.
+          if !cancel.swap(true, Ordering::Relaxed) {
+            first_error = Some(error);
+          }
+        }
+      }
+    }
+    match first_error {
+      Some(error) => Err(error),
+      None => Ok(outputs),
+    }
+  })?;
+
+  if outputs.iter().any(Option::is_none) {
+    return Err(failure("BACKTEST_NATIVE_CANCELLED", "Backtest wurde abgebrochen."));
+  }
+  let mut outputs = outputs.into_iter().flatten();
+  let mut document = outputs.next().unwrap_or(Value::Null);
+  let mut cohorts: Vec<Value> = document["cohorts"].as_array().cloned().unwrap_or_default();
+  for output in outputs {
+    cohorts.extend(output["cohorts"].as_array().cloned().unwrap_or_default());
+  }
+  cohorts.sort_by_key(|cohort| cohort["startYear"].as_i64());
+  let excluded = document["excluded"].as_array().cloned().unwrap_or_default();
+  document["inventory"] = cohort_inventory(&cohorts, start_years.len(), &excluded);
+  document["cohorts"] = Value::Array(cohorts);
+  Ok(document)
+}
+
+#[tauri::command]
+pub async fn run_native_backtest_cohorts(
+  app: tauri::AppHandle,
+  state: tauri::State<'_, SharedNativeJobs>,
+  job_id: String,
+  request: Value,
+  cohort_horizon_years: u32,
+  dataset_id: Option<String>,
+) -> Result<Value, String> {
+  let jobs = state.inner().clone();
+  tauri::async_runtime::spawn_blocking(move || {
+    // Loading a user dataset runs its contract check on the engine runtime.
+    let source = crate::user_datasets::run_source(&app, dataset_id.as_deref())?;
+    let job = jobs.start(&job_id).map_err(|e| e.to_string())?;
+    let report = |progress: BacktestProgress| {
+      let _ = app.emit(BACKTEST_PROGRESS_EVENT, serde_json::json!({ "jobId": job_id, "progress": progress }));
+    };
+    run_backtest_cohorts(&source, &request, cohort_horizon_years, None, job.cancel_flag(), &report).map_err(|e| e.to_string())
+  })
+  .await
+  .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn cancel_native_backtest(state: tauri::State<'_, SharedNativeJobs>, job_id: String) -> bool {
+  state.cancel(&job_id)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::monte_carlo_export::canonical_json;
+  use crate::script_runtime::{checkout_root, directory_source};
+  use std::sync::Mutex;
+
+  fn fixture(name: &str) -> Value {
+    let path = checkout_root().join("tests/fixtures").join(name);
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+  }
+
+  fn request(inputs: &Value, start_year: i64, end_year: i64) -> Value {
+    serde_json::json!({ "schemaVersion": "BacktestRequestV1", "startYear": start_year, "endYear": end_year, "inputs": inputs })
+  }
+
+  fn run(source: &ScriptSource, request: &Value, horizon: u32, threads: usize) -> Value {
+    let cancel = Arc::new(AtomicBool::new(false));
+    run_backtest_cohorts(source, request, horizon, Some(threads), &cancel, &|_| {}).unwrap()
+  }
+
+  #[test]
+  fn parallel_cohorts_match_the_serial_js_batch() {
+    let source = directory_source(checkout_root());
+    let inputs = &fixture("simulator-backtest-target-v1.json")["cases"][1]["inputs"];
+    // Runs past the end of the dataset, so the batch mixes every preflight outcome.
+    let request = request(inputs, 1995, 2030);
+    let progress = Mutex::new(Vec::new());
+    let cancel = Arc::new(AtomicBool::new(false));
+    let native = run_backtest_cohorts(&source, &request, 20, Some(3), &cancel, &|update| {
+      progress.lock().unwrap().push(update.completed_cohorts)
+    })
+    .unwrap();
+
+    let host = open_host(&source, None).unwrap();
+    let all_years: Vec<i64> = (1995..=2011).collect();
+    let serial: Value = serde_json::from_str(
+      &call_host(&host, "runCohorts", (request.to_string(), 20.0, serde_json::to_string(&all_years).unwrap())).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(canonical_json(&native), canonical_json(&serial));
+
+    let inventory = &native["inventory"];
+    assert_eq!(inventory["eligible"], 17);
+    assert_eq!(inventory["excluded"], 19);
+    assert!(inventory["completed"].as_u64().unwrap() > 0);
+    assert!(inventory["incomplete"].as_u64().unwrap() > 0);
+    assert_eq!(inventory["exclusionReasons"]["insufficient_horizon"], 19);
+    let progress = progress.into_inner().unwrap();
+    assert!(progress.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(progress.last(), Some(&17));
+  }
+
+  #[test]
+  fn cohorts_reproduce_the_backtest_baseline_outcomes() {
+    let source = directory_source(checkout_root());
+    let target = fixture("simulator-backtest-target-v1.json");
+    for case in fixture("simulator-backtest-baseline-v1.json")["cases"].as_array().unwrap() {
+      let id = case["id"].as_str().unwrap();
+      let (start, end) = (case["period"]["startYear"].as_i64().unwrap(), case["period"]["endYear"].as_i64().unwrap());
+      let horizon = (end - start + 1) as u32;
+      let document = run(&source, &request(&case["inputs"], start, end), horizon, 2);
+      let cohort = &document["cohorts"][0];
+      let expected_kind = case["outcomeObservation"].as_str().unwrap().trim_end_matches("_legacy");
+      assert_eq!(cohort["outcome"]["kind"], expected_kind, "{}", id);
+      assert_eq!(cohort["runResult"]["rows"].as_array().unwrap().len() as u64, case["expectedRowCount"].as_u64().unwrap(), "{}", id);
+      if let Some(target_case) = target["cases"].as_array().unwrap().iter().find(|target_case| target_case["id"] == id) {
+        // The fixture stores cents.
+        let summary = &cohort["runResult"]["summary"];
+        for (actual, expected) in [("totalWithdrawal", "totalWithdrawal"), ("endWealth", "summaryEndWealth")] {
+          let delta = summary[actual].as_f64().unwrap() - target_case["values"][expected].as_f64().unwrap();
+          assert!(delta.abs() < 0.01, "{} {}: {}", id, actual, delta);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn invalid_and_cancelled_cohort_runs_fail_with_codes() {
+    let source = directory_source(checkout_root());
+    let cancel = Arc::new(AtomicBool::new(false));
+    let reversed = request(&serde_json::json!({}), 2010, 2000);
+    assert_eq!(run_backtest_cohorts(&source, &reversed, 5, None, &cancel, &|_| {}).unwrap_err().code, "BACKTEST_REQUEST_INVALID");
+    let unversioned = serde_json::json!({ "startYear": 2000, "endYear": 2010, "inputs": {} });
+    assert_eq!(run_backtest_cohorts(&source, &unversioned, 5, None, &cancel, &|_| {}).unwrap_err().code, "BACKTEST_REQUEST_INVALID");
+
+    let cancel = Arc::new(AtomicBool::new(true));
+    let valid = request(&fixture("simulator-backtest-target-v1.json")["cases"][0]["inputs"], 2000, 2010);
+    assert_eq!(run_backtest_cohorts(&source, &valid, 5, None, &cancel, &|_| {}).unwrap_err().code, "BACKTEST_NATIVE_CANCELLED");
+  }
+}
@@ -0,0 +1,111 @@
+// Native backtest host, evaluated inside the embedded QuickJS runtime (see
+// backtest.rs). It wires the historical backtest runner to the same simulator
+// adapters as app/simulator/simulator-backtest.js, minus the DOM.
+
+import { BREAK_ON_RUIN } from '../app/simulator/simulator-data.js';
+import { initializePortfolio } from '../app/simulator/simulator-portfolio.js';
+import { simulateOneYear } from '../app/simulator/simulator-engine-wrapper.js';
+import { portfolioTotal } from '../app/simulator/simulator-results.js';
+import { computeAdjPctForYear } from '../app/simulator/simulator-main-helpers.js';
+import { resolveDynamicFlexRunnerHorizon } from '../app/simulator/dynamic-flex-runner-horizon.js';
+import { createHistoricalBacktestContractProvider } from '../app/simulator/historical-backtest-contract.js';
+import {
+    BACKTEST_REQUEST_SCHEMA_VERSION,
+    runHistoricalBacktest
+} from '../app/simulator/historical-backtest-runner.js';
+import { runHistoricalBacktestCohorts } from '../app/simulator/historical-backtest-cohorts.js';
+import {
+    captureHistoricalBacktestEngineProvenance,
+    serializeHistoricalBacktestCsv,
+    serializeHistoricalBacktestJson
+} from '../app/simulator/historical-backtest-export.js';
+import { EngineAPI } from '../engine/index.mjs';
+
+function requestError(message) {
+    return new Error(`BACKTEST_REQUEST_INVALID: ${message}`);
+}
+
+function readRequest(requestJson) {
+    const request = JSON.parse(requestJson);
+    if (!request || typeof request !== 'object' || Array.isArray(request)) {
+        throw requestError('Request muss ein Objekt sein.');
+    }
+    if (request.schemaVersion !== BACKTEST_REQUEST_SCHEMA_VERSION) {
+        throw requestError(`Unsupported schemaVersion ${String(request.schemaVersion)}.`);
+    }
+    if (!Number.isInteger(request.startYear) || !Number.isInteger(request.endYear) || request.startYear > request.endYear) {
+        throw requestError('startYear/endYear muessen ganze Jahre in aufsteigender Reihenfolge sein.');
+    }
+    if (!request.inputs || typeof request.inputs !== 'object') {
+        throw requestError('inputs fehlen.');
+    }
+    return request;
+}
+
+function breakOnRuinOf(request) {
+    return typeof request.breakOnRuin === 'boolean' ? request.breakOnRuin : BREAK_ON_RUIN;
+}
+
+function adapters(breakOnRuin) {
+    return {
+        historicalDataProvider: createHistoricalBacktestContractProvider(),
+        // The webview resolves `window.EngineAPI`; here the engine is passed explicitly.
+        simulateYear: (state, inputs, yearData, yearIndex) =>
+            simulateOneYear(state, inputs, yearData, yearIndex, null, 0, null, 1.0, EngineAPI),
+        initializePortfolio,
+        computeAdjustmentPct: computeAdjPctForYear,
+        resolveHorizon: resolveDynamicFlexRunnerHorizon,
+        totalPortfolio: portfolioTotal,
+        breakOnRuin
+    };
+}
+
+/**
+ * Returns `{ outcome, export, csv }` as produced by the webview download
+ * buttons. `cohortInventoryJson` is the inventory of a native cohort run.
+ */
+function run(requestJson, cohortInventoryJson) {
+    const request = readRequest(requestJson);
+    const period = { startYear: request.startYear, endYear: request.endYear };
+    const result = runHistoricalBacktest({
+        inputs: request.inputs,
+        period,
+        ...adapters(breakOnRuinOf(request)),
+        engineProvenance: captureHistoricalBacktestEngineProvenance(EngineAPI)
+    });
+    const cohortInventory = cohortInventoryJson && ['completed', 'ruin'].includes(result.outcome?.kind)
+        ? JSON.parse(cohortInventoryJson)
+        : null;
+    return JSON.stringify({
+        outcome: result.outcome?.kind || null,
+        export: serializeHistoricalBacktestJson(result, { cohortInventory }),
+        csv: serializeHistoricalBacktestCsv(result)
+    });
+}
+
+/**
+ * Runs the rolling cohorts starting in `startYearsJson` out of the full
+ * preflighted batch. Every thread preflights the same batch, so incomplete
+ * and technical classifications match the serial run exactly.
+ */
+function runCohorts(requestJson, cohortHorizonYears, startYearsJson) {
+    const request = readRequest(requestJson);
+    const assigned = new Set(JSON.parse(startYearsJson));
+    const skipped = new Error('COHORT_NOT_ASSIGNED');
+    const batch = runHistoricalBacktestCohorts({
+        inputs: request.inputs,
+        range: { startYear: request.startYear, endYear: request.endYear },
+        cohortHorizonYears,
+        ...adapters(breakOnRuinOf(request)),
+        runSinglePath: args => {
+            if (!assigned.has(args.period.startYear)) throw skipped;
+            return runHistoricalBacktest(args);
+        }
+    });
+    return JSON.stringify({
+        ...batch,
+        cohorts: batch.cohorts.filter(cohort => assigned.has(cohort.startYear))
+    });
+}
+
+globalThis.__ruhestandBacktestHost = { run, runCohorts };
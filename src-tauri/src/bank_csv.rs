@@ -0,0 +1,592 @@
+//! CSV exports of German banks. Each bank gets a profile naming its columns;
+//! the profile is detected from the header row (after any preamble lines),
+//! the encoding from the bytes (UTF-8, otherwise Windows-1252). Rows that do
+//! not parse are reported per line like `rejectedRows` of
+//! `ExpenseCsvImportError` in app/balance/balance-expenses-csv.js, so the
+//! caller can show them instead of importing half a file.
+
+use serde::{Deserialize, Serialize};
+
+use crate::bank_import::amount_cents;
+use crate::script_runtime::{job_failure as failure, JobFailure};
+
+/// Preamble lines searched for the header row.
+const HEADER_SEARCH_LINES: usize = 40;
+
+/// Who the other side of a booking is.
+enum Counterparty {
+  Column(&'static str),
+  /// Separate payer and payee columns (DKB).
+  ByDirection { debit: &'static str, credit: &'static str },
+  /// Embedded as "Auftraggeber: ..." / "Empfaenger: ..." in the text (comdirect).
+  FromText,
+}
+
+/// Column names are compared normalized: lower case, umlauts transliterated,
+/// everything but letters and digits dropped ("Betrag (€)" -> "betrag").
+struct CsvProfile {
+  id: &'static str,
+  label: &'static str,
+  fingerprint: &'static [&'static str],
+  date: &'static str,
+  amount: &'static str,
+  counterparty: Counterparty,
+  text: &'static [&'static str],
+  currency: Option<&'static str>,
+  /// Column whose value marks a booking as still pending ("vorgemerkt").
+  status: Option<&'static str>,
+  account: Option<&'static str>,
+  /// First cells (normalized) of the lines that close the booking list, such
+  /// as comdirect's balance line; nothing after them is read.
+  footer: &'static [&'static str],
+}
+
+static PROFILES: [CsvProfile; 6] = [
+  CsvProfile {
+    id: "dkb",
+    label: "DKB",
+    fingerprint: &["buchungsdatum", "wertstellung", "zahlungspflichtiger", "zahlungsempfaengerin", "verwendungszweck", "betrag"],
+    date: "buchungsdatum",
+    amount: "betrag",
+    counterparty: Counterparty::ByDirection { debit: "zahlungsempfaengerin", credit: "zahlungspflichtiger" },
+    text: &["umsatztyp", "verwendungszweck"],
+    currency: None,
+    status: Some("status"),
+    account: None,
+    footer: &[],
+  },
+  CsvProfile {
+    id: "dkb_legacy",
+    label: "DKB (bis 2023)",
+    fingerprint: &["buchungstag", "wertstellung", "buchungstext", "auftraggeberbeguenstigter", "verwendungszweck", "betrageur"],
+    date: "buchungstag",
+    amount: "betrageur",
+    counterparty: Counterparty::Column("auftraggeberbeguenstigter"),
+    text: &["buchungstext", "verwendungszweck"],
+    currency: None,
+    status: None,
+    account: None,
+    footer: &[],
+  },
+  CsvProfile {
+    id: "ing",
+    label: "ING",
+    fingerprint: &["buchung", "auftraggeberempfaenger", "buchungstext", "verwendungszweck", "betrag", "waehrung"],
+    date: "buchung",
+    amount: "betrag",
+    counterparty: Counterparty::Column("auftraggeberempfaenger"),
+    text: &["buchungstext", "verwendungszweck"],
+    currency: Some("waehrung"),
+    status: None,
+    account: None,
+    footer: &[],
+  },
+  CsvProfile {
+    id: "comdirect",
+    label: "comdirect",
+    fingerprint: &["buchungstag", "wertstellungvaluta", "vorgang", "buchungstext", "umsatzineur"],
+    date: "buchungstag",
+    amount: "umsatzineur",
+    counterparty: Counterparty::FromText,
+    text: &["vorgang", "buchungstext"],
+    currency: None,
+    status: None,
+    account: None,
+    footer: &["alterkontostand"],
+  },
+  CsvProfile {
+    id: "sparkasse",
+    label: "Sparkasse (CSV-CAMT)",
+    fingerprint: &["auftragskonto", "buchungstag", "valutadatum", "buchungstext", "verwendungszweck", "beguenstigterzahlungspflichtiger", "betrag"],
+    date: "buchungstag",
+    amount: "betrag",
+    counterparty: Counterparty::Column("beguenstigterzahlungspflichtiger"),
+    text: &["buchungstext", "verwendungszweck"],
+    currency: Some("waehrung"),
+    status: Some("info"),
+    account: Some("auftragskonto"),
+    footer: &[],
+  },
+  CsvProfile {
+    id: "volksbank",
+    label: "Volksbank / Raiffeisenbank",
+    fingerprint: &["ibanauftragskonto", "buchungstag", "valutadatum", "namezahlungsbeteiligter", "verwendungszweck", "betrag"],
+    date: "buchungstag",
+    amount: "betrag",
+    counterparty: Counterparty::Column("namezahlungsbeteiligter"),
+    text: &["buchungstext", "verwendungszweck"],
+    currency: Some("waehrung"),
+    status: None,
+    account: Some("ibanauftragskonto"),
+    footer: &[],
+  },
+];
+
+/// Windows-1252 characters for 0x80..=0x9F (the rest equals Latin-1).
+const CP1252_HIGH: [char; 32] = [
+  '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}', '\u{02C6}', '\u{2030}',
+  '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}', '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}',
+  '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}', '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}',
+  '\u{017E}', '\u{0178}',
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub(crate) enum TextEncoding {
+  #[serde(rename = "utf-8")]
+  Utf8,
+  #[serde(rename = "windows-1252")]
+  Windows1252,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CsvPreviewRequest {
+  pub name: String,
+  /// Raw file bytes; preferred, because only they reveal the encoding.
+  pub bytes: Option<Vec<u8>>,
+  pub content: Option<String>,
+  /// Skips detection, e.g. when the user picked the bank by hand.
+  pub profile_id: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CsvProfileInfo {
+  pub id: String,
+  pub label: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CsvRow {
+  pub line_number: usize,
+  /// `YYYY-MM-DD`.
+  pub booking_date: String,
+  /// Signed amount: negative for debits.
+  pub amount: f64,
+  pub currency: String,
+  pub counterparty: String,
+  pub text: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CsvLineError {
+  pub line_number: usize,
+  pub code: &'static str,
+  pub reason: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CsvSummary {
+  pub total_rows: usize,
+  pub imported_rows: usize,
+  pub rejected_rows: usize,
+  pub pending_rows: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CsvPreview {
+  pub file: String,
+  pub profile: CsvProfileInfo,
+  pub encoding: TextEncoding,
+  pub delimiter: String,
+  pub header_line: usize,
+  pub account: String,
+  pub rows: Vec<CsvRow>,
+  pub errors: Vec<CsvLineError>,
+  pub summary: CsvSummary,
+}
+
+/// UTF-8 (with or without BOM) when the bytes are valid UTF-8, otherwise
+/// Windows-1252, which the older bank exports use.
+pub(crate) fn decode(bytes: &[u8]) -> (String, TextEncoding) {
+  let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+  match std::str::from_utf8(bytes) {
+    Ok(text) => (text.to_string(), TextEncoding::Utf8),
+    Err(_) => (
+      bytes
+        .iter()
+        .map(|&byte| match byte {
+          0x80..=0x9F => CP1252_HIGH[usize::from(byte - 0x80)],
+          _ => char::from(byte),
+        })
+        .collect(),
+      TextEncoding::Windows1252,
+    ),
+  }
+}
+
+pub(crate) fn normalize_header(cell: &str) -> String {
+  let mut out = String::new();
+  for c in cell.trim().chars().flat_map(char::to_lowercase) {
+    match c {
+      'ä' => out.push_str("ae"),
+      'ö' => out.push_str("oe"),
+      'ü' => out.push_str("ue"),
+      'ß' => out.push_str("ss"),
+      c if c.is_ascii_alphanumeric() => out.push(c),
+      _ => {}
+    }
+  }
+  out
+}
+
+/// Same rules as `splitCsvLine`: double quotes group, `""` escapes a quote.
+pub(crate) fn split_line(line: &str, delimiter: char) -> Result<Vec<String>, String> {
+  let mut cells = Vec::new();
+  let mut current = String::new();
+  let mut in_quotes = false;
+  let mut chars = line.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c == '"' {
+      if in_quotes && chars.peek() == Some(&'"') {
+        current.push('"');
+        chars.next();
+      } else {
+        in_quotes = !in_quotes;
+      }
+    } else if c == delimiter && !in_quotes {
+      cells.push(std::mem::take(&mut current));
+    } else {
+      current.push(c);
+    }
+  }
+  if in_quotes {
+    return Err("Die CSV-Zeile enthaelt ein nicht geschlossenes Anfuehrungszeichen.".to_string());
+  }
+  cells.push(current);
+  Ok(cells)
+}
+
+/// Same candidates and tie-breaking as `detectCsvDelimiter`.
+pub(crate) fn detect_delimiter(line: &str) -> char {
+  let mut best: (char, Option<usize>) = (';', None);
+  for delimiter in [';', '\t', ','] {
+    let mut count = 0;
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+      if c == '"' {
+        if in_quotes && chars.peek() == Some(&'"') {
+          chars.next();
+        } else {
+          in_quotes = !in_quotes;
+        }
+      } else if c == delimiter && !in_quotes {
+        count += 1;
+      }
+    }
+    if !best.1.is_some_and(|most| count <= most) {
+      best = (delimiter, Some(count));
+    }
+  }
+  best.0
+}
+
+fn find_profile(id: &str) -> Option<&'static CsvProfile> {
+  PROFILES.iter().find(|profile| profile.id == id)
+}
+
+pub(crate) fn profiles() -> Vec<CsvProfileInfo> {
+  PROFILES.iter().map(|profile| CsvProfileInfo { id: profile.id.to_string(), label: profile.label.to_string() }).collect()
+}
+
+/// Header row of a known profile: `(line index, delimiter, profile)`.
+fn detect_header(lines: &[&str], forced: Option<&'static CsvProfile>) -> Option<(usize, char, &'static CsvProfile)> {
+  lines.iter().take(HEADER_SEARCH_LINES).enumerate().find_map(|(index, line)| {
+    let delimiter = detect_delimiter(line);
+    let header: Vec<String> = split_line(line, delimiter).ok()?.iter().map(|cell| normalize_header(cell)).collect();
+    let matches = |profile: &CsvProfile| profile.fingerprint.iter().all(|column| header.iter().any(|cell| cell == column));
+    match forced {
+      Some(profile) => matches(profile).then_some((index, delimiter, profile)),
+      None => PROFILES.iter().find(|profile| matches(profile)).map(|profile| (index, delimiter, profile)),
+    }
+  })
+}
+
+/// Recognizes the file as a bank CSV without parsing the rows.
+pub(crate) fn looks_like_bank_csv(content: &str) -> bool {
+  let lines: Vec<&str> = content.lines().collect();
+  detect_header(&lines, None).is_some()
+}
+
+pub(crate) fn days_in_month(year: i32, month: u32) -> u32 {
+  match month {
+    2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+    2 => 28,
+    4 | 6 | 9 | 11 => 30,
+    _ => 31,
+  }
+}
+
+/// `dd.mm.yyyy`, `dd.mm.yy` or ISO dates; impossible days such as 31.02.
+/// are rejected.
+pub(crate) fn parse_date(raw: &str) -> Option<String> {
+  let raw = raw.trim();
+  let (year, month, day) = if raw.len() == 10 && raw.as_bytes()[4] == b'-' {
+    (raw.get(..4)?.parse::<i32>().ok()?, raw.get(5..7)?.parse::<u32>().ok()?, raw.get(8..)?.parse::<u32>().ok()?)
+  } else {
+    let mut parts = raw.split('.');
+    let day = parts.next()?.parse::<u32>().ok()?;
+    let month = parts.next()?.parse::<u32>().ok()?;
+    let year_part = parts.next()?;
+    if parts.next().is_some() {
+      return None;
+    }
+    let year = match year_part.len() {
+      2 => 2000 + year_part.parse::<i32>().ok()?,
+      4 => year_part.parse::<i32>().ok()?,
+      _ => return None,
+    };
+    (year, month, day)
+  };
+  ((1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month)).then(|| format!("{:04}-{:02}-{:02}", year, month, day))
+}
+
+/// German amounts ("-1.234,56", "1234,56 €", "+12,00"); plain dot decimals
+/// are accepted when no comma occurs.
+fn parse_amount(raw: &str) -> Option<i64> {
+  let mut value: String = raw.chars().filter(|c| !c.is_whitespace() && *c != '€').collect();
+  if let Some(stripped) = value.strip_suffix("EUR") {
+    value = stripped.to_string();
+  }
+  let (negative, digits) = match value.strip_prefix('-') {
+    Some(rest) => (true, rest.to_string()),
+    None => (false, value.strip_prefix('+').unwrap_or(&value).to_string()),
+  };
+  let digits = if digits.contains(',') { digits.replace('.', "") } else { digits };
+  let cents = amount_cents(&digits)?;
+  Some(if negative { -cents } else { cents })
+}
+
+/// IBAN in the preamble ("IBAN;DE..." at ING, "Girokonto";"DE..." at DKB).
+fn preamble_account(lines: &[&str], delimiter: char) -> Option<String> {
+  lines.iter().find_map(|line| {
+    split_line(line, delimiter).ok()?.into_iter().map(|cell| cell.replace(' ', "")).find(|cell| {
+      cell.len() >= 15
+        && cell.len() <= 34
+        && cell.chars().take(2).all(|c| c.is_ascii_uppercase())
+        && cell.chars().skip(2).take(2).all(|c| c.is_ascii_digit())
+        && cell.chars().all(|c| c.is_ascii_alphanumeric())
+    })
+  })
+}
+
+/// Name after "Auftraggeber:" or "Empfaenger:" up to the next comdirect key.
+fn counterparty_from_text(text: &str) -> String {
+  let start = ["Auftraggeber:", "Empfänger:", "Empfaenger:"]
+    .iter()
+    .find_map(|key| text.find(key).map(|index| index + key.len()));
+  let Some(start) = start else { return String::new() };
+  let rest = &text[start..];
+  let end = ["Kto/IBAN:", "Buchungstext:", "Ref."].iter().filter_map(|key| rest.find(key)).min().unwrap_or(rest.len());
+  rest[..end].trim().to_string()
+}
+
+pub(crate) fn parse_csv(name: &str, content: &str, encoding: TextEncoding, profile_id: Option<&str>) -> Result<CsvPreview, JobFailure> {
+  let forced = match profile_id {
+    Some(id) => Some(find_profile(id).ok_or_else(|| failure("BANK_CSV_PROFILE_UNKNOWN", format!("Unbekanntes CSV-Profil {}.", id)))?),
+    None => None,
+  };
+  let lines: Vec<&str> = content.lines().collect();
+  let (header_index, delimiter, profile) = detect_header(&lines, forced).ok_or_else(|| {
+    let known: Vec<&str> = PROFILES.iter().map(|profile| profile.label).collect();
+    failure(
+      "BANK_CSV_PROFILE_UNKNOWN",
+      format!("{}: Kopfzeile passt zu keinem Bankprofil ({}).", name, known.join(", ")),
+    )
+  })?;
+  let header: Vec<String> = split_line(lines[header_index], delimiter)
+    .unwrap_or_default()
+    .iter()
+    .map(|cell| normalize_header(cell))
+    .collect();
+  let column = |name: &str| header.iter().position(|cell| cell == name);
+  let amount_index = column(profile.amount).unwrap_or_else(|| unreachable!("fingerprint contains the amount column"));
+  let date_index = column(profile.date).unwrap_or_else(|| unreachable!("fingerprint contains the date column"));
+  // ING lists a currency after the balance and one after the amount.
+  let currency_index = profile
+    .currency
+    .and_then(|name| header.iter().skip(amount_index).position(|cell| cell == name).map(|offset| offset + amount_index).or_else(|| column(name)));
+  let status_index = profile.status.and_then(column);
+  let text_indices: Vec<usize> = profile.text.iter().filter_map(|name| column(name)).collect();
+  let preamble = preamble_account(&lines[..header_index], delimiter).unwrap_or_default();
+
+  let mut preview = CsvPreview {
+    file: name.to_string(),
+    profile: CsvProfileInfo { id: profile.id.to_string(), label: profile.label.to_string() },
+    encoding,
+    delimiter: delimiter.to_string(),
+    header_line: header_index + 1,
+    account: preamble.clone(),
+    rows: Vec::new(),
+    errors: Vec::new(),
+    summary: CsvSummary { total_rows: 0, imported_rows: 0, rejected_rows: 0, pending_rows: 0 },
+  };
+  let mut errors = Vec::new();
+  for (index, line) in lines.iter().enumerate().skip(header_index + 1) {
+    if line.trim().is_empty() {
+      continue;
+    }
+    let line_number = index + 1;
+    let split = split_line(line, delimiter);
+    if split.as_ref().is_ok_and(|cells| profile.footer.contains(&normalize_header(&cells[0]).as_str())) {
+      break;
+    }
+    preview.summary.total_rows += 1;
+    let mut reject = |code: &'static str, reason: String| errors.push(CsvLineError { line_number, code, reason });
+    let cells = match split {
+      Ok(cells) => cells,
+      Err(reason) => {
+        reject("bank_csv_unclosed_quote", reason);
+        continue;
+      }
+    };
+    // Trailing delimiters are common; missing columns are not.
+    if cells.len() < header.len() && cells.len() <= amount_index.max(date_index) {
+      reject("bank_csv_column_count", format!("Spaltenanzahl {} statt {}", cells.len(), header.len()));
+      continue;
+    }
+    let cell = |index: usize| cells.get(index).map(|cell| cell.trim()).unwrap_or_default();
+    let raw_date = cell(date_index);
+    let pending = status_index.is_some_and(|index| cell(index).to_lowercase().contains("vorgemerkt"))
+      || raw_date.eq_ignore_ascii_case("offen");
+    if pending {
+      preview.summary.pending_rows += 1;
+      continue;
+    }
+    let Some(booking_date) = parse_date(raw_date) else {
+      reject("bank_csv_date_invalid", format!("Datum \"{}\" ist ungueltig", raw_date));
+      continue;
+    };
+    let Some(cents) = parse_amount(cell(amount_index)) else {
+      reject("bank_csv_amount_invalid", format!("Betrag \"{}\" ist ungueltig", cell(amount_index)));
+      continue;
+    };
+    let text = text_indices.iter().map(|&index| cell(index)).filter(|part| !part.is_empty()).collect::<Vec<_>>().join(" ");
+    let counterparty = match profile.counterparty {
+      Counterparty::Column(name) => column(name).map(|index| cell(index).to_string()).unwrap_or_default(),
+      Counterparty::ByDirection { debit, credit } => {
+        column(if cents < 0 { debit } else { credit }).map(|index| cell(index).to_string()).unwrap_or_default()
+      }
+      Counterparty::FromText => counterparty_from_text(&text),
+    };
+    if preview.account.is_empty() {
+      if let Some(index) = profile.account.and_then(column) {
+        preview.account = cell(index).to_string();
+      }
+    }
+    preview.rows.push(CsvRow {
+      line_number,
+      booking_date,
+      amount: cents as f64 / 100.0,
+      currency: currency_index.map(cell).filter(|code| !code.is_empty()).unwrap_or("EUR").to_string(),
+      counterparty,
+      text,
+    });
+  }
+  preview.errors = errors;
+  preview.summary.imported_rows = preview.rows.len();
+  preview.summary.rejected_rows = preview.errors.len();
+  Ok(preview)
+}
+
+pub(crate) fn preview(request: &CsvPreviewRequest) -> Result<CsvPreview, JobFailure> {
+  let (content, encoding) = match (&request.bytes, &request.content) {
+    (Some(bytes), _) => decode(bytes),
+    (None, Some(content)) => (content.clone(), TextEncoding::Utf8),
+    (None, None) => return Err(failure("BANK_CSV_INVALID", "bytes oder content ist erforderlich.")),
+  };
+  parse_csv(&request.name, &content, encoding, request.profile_id.as_deref())
+}
+
+#[tauri::command]
+pub fn list_bank_csv_profiles() -> Vec<CsvProfileInfo> {
+  profiles()
+}
+
+#[tauri::command]
+pub fn preview_bank_csv(request: CsvPreviewRequest) -> Result<CsvPreview, String> {
+  preview(&request).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const DKB: &str = "\"Girokonto\";\"DE02 1203 0000 0000 2020 51\"\n\"Zeitraum:\";\"01.01.2024 - 31.01.2024\"\n\"Kontostand vom 31.01.2024:\";\"1.234,56 €\"\n\"\"\n\"Buchungsdatum\";\"Wertstellung\";\"Status\";\"Zahlungspflichtige*r\";\"Zahlungsempfänger*in\";\"Verwendungszweck\";\"Umsatztyp\";\"IBAN\";\"Betrag (€)\";\"Gläubiger-ID\";\"Mandatsreferenz\";\"Kundenreferenz\"\n\"31.01.24\";\"31.01.24\";\"Gebucht\";\"Max Muster\";\"REWE Markt\";\"Einkauf\";\"Ausgang\";\"DE11\";\"-1.054,20 €\";\"\";\"\";\"\"\n\"30.01.24\";\"30.01.24\";\"Gebucht\";\"Rentenversicherung\";\"Max Muster\";\"Rente\";\"Eingang\";\"DE22\";\"1.800,00 €\";\"\";\"\";\"\"\n\"01.02.24\";\"01.02.24\";\"Vorgemerkt\";\"Max Muster\";\"Stadtwerke\";\"Strom\";\"Ausgang\";\"DE33\";\"-80,00 €\";\"\";\"\";\"\"\n\"3x.01.24\";\"31.01.24\";\"Gebucht\";\"Max Muster\";\"Kiosk\";\"\";\"Ausgang\";\"\";\"-2,00 €\";\"\";\"\";\"\"\n";
+
+  #[test]
+  fn detects_windows_1252_ing_export_with_preamble() {
+    let text = "Umsatzanzeige;Datei erstellt am: 01.02.2024 10:00\n\nIBAN;DE02 5001 0517 0000 0000 00\nKontoname;Girokonto\nBank;ING\n\nBuchung;Wertstellungsdatum;Auftraggeber/Empfänger;Buchungstext;Verwendungszweck;Saldo;Währung;Betrag;Währung\n15.01.2024;15.01.2024;Bäckerei Müller;Lastschrift;Brötchen;1.000,00;EUR;-4,50;EUR\n";
+    let bytes: Vec<u8> = text.chars().map(|c| u32::from(c) as u8).collect();
+    let preview = preview(&CsvPreviewRequest { name: "ing.csv".into(), bytes: Some(bytes), content: None, profile_id: None }).unwrap();
+    assert_eq!((preview.profile.id.as_str(), preview.encoding, preview.header_line), ("ing", TextEncoding::Windows1252, 7));
+    assert_eq!(preview.account, "DE02500105170000000000");
+    let row = &preview.rows[0];
+    assert_eq!((row.booking_date.as_str(), row.amount, row.currency.as_str()), ("2024-01-15", -4.5, "EUR"));
+    assert_eq!((row.counterparty.as_str(), row.text.as_str()), ("Bäckerei Müller", "Lastschrift Brötchen"));
+  }
+
+  #[test]
+  fn dkb_rows_pending_and_line_errors_are_reported() {
+    let preview = preview(&CsvPreviewRequest { name: "dkb.csv".into(), bytes: Some(DKB.as_bytes().to_vec()), content: None, profile_id: None }).unwrap();
+    assert_eq!((preview.profile.id.as_str(), preview.encoding), ("dkb", TextEncoding::Utf8));
+    assert_eq!(preview.rows.len(), 2);
+    assert_eq!((preview.rows[0].amount, preview.rows[0].counterparty.as_str()), (-1054.2, "REWE Markt"));
+    assert_eq!(preview.rows[1].counterparty, "Rentenversicherung");
+    assert_eq!(preview.errors, vec![CsvLineError { line_number: 9, code: "bank_csv_date_invalid", reason: "Datum \"3x.01.24\" ist ungueltig".into() }]);
+    assert_eq!((preview.summary.total_rows, preview.summary.pending_rows, preview.summary.rejected_rows), (4, 1, 1));
+  }
+
+  #[test]
+  fn comdirect_sparkasse_and_volksbank_profiles() {
+    let comdirect = ";\n\"Umsätze Girokonto\";\"Zeitraum: 30 Tage\";\n\"Buchungstag\";\"Wertstellung (Valuta)\";\"Vorgang\";\"Buchungstext\";\"Umsatz in EUR\";\n\"offen\";\"--\";\"Lastschrift\";\"Empfänger: Stadtwerke\";\"-80,00\";\n\"15.01.2024\";\"15.01.2024\";\"Lastschrift / Belastung\";\"Auftraggeber: REWE Buchungstext: Einkauf Ref. 123\";\"-12,34\";\n";
+    let preview = parse_csv("c.csv", comdirect, TextEncoding::Utf8, None).unwrap();
+    assert_eq!(preview.profile.id, "comdirect");
+    assert_eq!((preview.rows.len(), preview.summary.pending_rows), (1, 1));
+    assert_eq!(preview.rows[0].counterparty, "REWE");
+
+    // Layout of a real export: balance line before the header, the old
+    // balance after the last booking, and further account blocks below.
+    let export = ";\n\"Umsätze Girokonto\";\"Zeitraum: 30 Tage\";\n\"Neuer Kontostand\";\"1.234,56 EUR\";\n\n\
+      \"Buchungstag\";\"Wertstellung (Valuta)\";\"Vorgang\";\"Buchungstext\";\"Umsatz in EUR\";\n\
+      \"16.01.2024\";\"16.01.2024\";\"Übertrag / Überweisung\";\"Auftraggeber: Deutsche Rentenversicherung Buchungstext: Rente 01/2024\";\"1.800,00\";\n\
+      \"15.01.2024\";\"15.01.2024\";\"Lastschrift / Belastung\";\"Empfänger: Stadtwerke Kto/IBAN: DE99 Buchungstext: Strom\";\"-80,00\";\n\
+      \"Alter Kontostand\";\"-485,44 EUR\";\n\n\
+      \"Umsätze Visa-Karte (Kreditkarte)\";\"Zeitraum: 30 Tage\";\n\
+      \"Buchungstag\";\"Umsatztag\";\"Vorgang\";\"Referenz\";\"Buchungstext\";\"Umsatz in EUR\";\n\
+      \"14.01.2024\";\"12.01.2024\";\"Visa-Umsatz\";\"123\";\"Tankstelle\";\"-60,00\";\n";
+    let preview = parse_csv("umsaetze.csv", export, TextEncoding::Utf8, None).unwrap();
+    assert!(preview.errors.is_empty(), "{:?}", preview.errors);
+    assert_eq!((preview.header_line, preview.summary.total_rows, preview.rows.len()), (5, 2, 2));
+    assert_eq!((preview.rows[0].amount, preview.rows[0].counterparty.as_str()), (1800.0, "Deutsche Rentenversicherung"));
+    assert_eq!(preview.rows[1].counterparty, "Stadtwerke");
+
+    let sparkasse = "\"Auftragskonto\";\"Buchungstag\";\"Valutadatum\";\"Buchungstext\";\"Verwendungszweck\";\"Glaeubiger ID\";\"Mandatsreferenz\";\"Kundenreferenz (End-to-End)\";\"Sammlerreferenz\";\"Lastschrift Ursprungsbetrag\";\"Auslagenersatz Ruecklastschrift\";\"Beguenstigter/Zahlungspflichtiger\";\"Kontonummer/IBAN\";\"BIC (SWIFT-Code)\";\"Betrag\";\"Waehrung\";\"Info\"\n\"DE0012\";\"02.01.24\";\"02.01.24\";\"KARTENZAHLUNG\";\"Tankstelle\";\"\";\"\";\"\";\"\";\"\";\"\";\"ARAL\";\"DE99\";\"BIC\";\"-60,10\";\"EUR\";\"Umsatz gebucht\"\n";
+    let preview = parse_csv("s.csv", sparkasse, TextEncoding::Utf8, None).unwrap();
+    assert_eq!((preview.profile.id.as_str(), preview.account.as_str()), ("sparkasse", "DE0012"));
+    assert_eq!((preview.rows[0].counterparty.as_str(), preview.rows[0].amount), ("ARAL", -60.1));
+
+    let volksbank = "Bezeichnung Auftragskonto;IBAN Auftragskonto;BIC Auftragskonto;Bankname Auftragskonto;Buchungstag;Valutadatum;Name Zahlungsbeteiligter;IBAN Zahlungsbeteiligter;BIC (SWIFT-Code) Zahlungsbeteiligter;Buchungstext;Verwendungszweck;Betrag;Waehrung;Saldo nach Buchung;Bemerkung;Kategorie;Steuerrelevant;Glaeubiger ID;Mandatsreferenz\nGiro;DE77;GENO;VR Bank;03.01.2024;03.01.2024;Apotheke;DE55;BIC;Kartenzahlung;Medikamente;-19,99;EUR;900,00;;;;;\n";
+    let preview = parse_csv("v.csv", volksbank, TextEncoding::Utf8, None).unwrap();
+    assert_eq!((preview.profile.id.as_str(), preview.account.as_str()), ("volksbank", "DE77"));
+    assert_eq!(preview.rows[0].text, "Kartenzahlung Medikamente");
+
+    assert_eq!(parse_csv("x.csv", "Kategorie;Betrag\nEssen;10", TextEncoding::Utf8, None).unwrap_err().code, "BANK_CSV_PROFILE_UNKNOWN");
+    assert_eq!(parse_csv("x.csv", volksbank, TextEncoding::Utf8, Some("ing")).unwrap_err().code, "BANK_CSV_PROFILE_UNKNOWN");
+  }
+
+  #[test]
+  fn impossible_dates_are_rejected() {
+    assert_eq!(parse_date("29.02.2024").as_deref(), Some("2024-02-29"));
+    assert_eq!(parse_date("31.12.24").as_deref(), Some("2024-12-31"));
+    assert_eq!(parse_date("2000-02-29").as_deref(), Some("2000-02-29"));
+    for raw in ["31.02.2024", "29.02.2023", "2100-02-29", "31.04.2024", "00.01.2024", "32.01.2024", "2024-1\u{e4}-1", "2024-\u{e4}\u{e4}1"] {
+      assert_eq!(parse_date(raw), None, "{}", raw);
+    }
+  }
+}
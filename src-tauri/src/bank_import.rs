@@ -0,0 +1,879 @@
+//! Bank statement import for the expense check. Parses CAMT.053 XML and MT940
+//! statements into bookings, drops bookings repeated by overlapping
+//! statements, assigns expense categories through user rules and sums them
+//! per month in the `years[year].months[month].profiles[profileId]` shape of
+//! `balance_expenses_v1` (app/balance/balance-expenses-storage.js).
+//!
+//! Debits count as positive spending. Credits only count when a rule matches
+//! them (refunds) or when they reverse an earlier debit (returned direct
+//! debits, CAMT `RvslInd` or MT940 `RC`), and then reduce the category; other
+//! credits are income and are skipped.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::bank_csv;
+use crate::script_runtime::{job_failure as failure, JobFailure};
+
+const DEFAULT_CATEGORY: &str = "Sonstiges";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StatementFile {
+  pub name: String,
+  #[serde(default)]
+  pub content: String,
+  /// Raw bytes instead of `content`; decoded as UTF-8 or Windows-1252.
+  pub bytes: Option<Vec<u8>>,
+  /// CSV profile chosen by hand instead of detected.
+  pub profile_id: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum RuleField {
+  #[default]
+  Any,
+  Text,
+  Counterparty,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum RuleDirection {
+  #[default]
+  Any,
+  Debit,
+  Credit,
+}
+
+/// First matching rule wins. `contains` is compared case-insensitively and
+/// without whitespace, since banks wrap booking texts at arbitrary places.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CategoryRule {
+  pub category: String,
+  pub contains: String,
+  #[serde(default)]
+  pub field: RuleField,
+  #[serde(default)]
+  pub direction: RuleDirection,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BankImportRequest {
+  pub files: Vec<StatementFile>,
+  /// Expense profile the totals are booked to.
+  pub profile_id: String,
+  #[serde(default)]
+  pub rules: Vec<CategoryRule>,
+  /// Category for debits no rule matches; defaults to "Sonstiges".
+  pub default_category: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum StatementFormat {
+  Camt053,
+  Mt940,
+  Csv,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Booking {
+  pub account: String,
+  /// `YYYY-MM-DD`.
+  pub booking_date: String,
+  /// Signed amount: negative for debits.
+  pub amount: f64,
+  pub currency: String,
+  pub counterparty: String,
+  pub text: String,
+  pub reference: Option<String>,
+  pub file: String,
+  pub category: Option<String>,
+  pub duplicate: bool,
+  /// Reverses an earlier booking; the sign is that of the reversal itself.
+  pub reversal: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StatementSummary {
+  pub file: String,
+  pub format: StatementFormat,
+  /// CSV profile id, e.g. "dkb".
+  pub profile: Option<String>,
+  pub accounts: Vec<String>,
+  pub bookings: usize,
+  pub duplicates: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MonthTotals {
+  pub categories: BTreeMap<String, f64>,
+  pub booking_count: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MonthProfiles {
+  pub profiles: BTreeMap<String, MonthTotals>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct YearTotals {
+  pub months: BTreeMap<String, MonthProfiles>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BankImportResult {
+  pub years: BTreeMap<String, YearTotals>,
+  pub bookings: Vec<Booking>,
+  pub statements: Vec<StatementSummary>,
+  pub duplicate_count: usize,
+  pub uncategorized_count: usize,
+  pub ignored_credit_count: usize,
+  /// Bookings in other currencies than EUR stay out of the totals.
+  pub foreign_currency_count: usize,
+}
+
+/// A booking as read from a statement, amounts in cents.
+#[derive(Clone, Debug)]
+struct RawBooking {
+  account: String,
+  booking_date: String,
+  amount_cents: i64,
+  currency: String,
+  counterparty: String,
+  text: String,
+  reference: Option<String>,
+  reversal: bool,
+}
+
+fn parse_failed(file: &str, message: impl std::fmt::Display) -> JobFailure {
+  failure("BANK_IMPORT_PARSE_FAILED", format!("{}: {}", file, message))
+}
+
+/// Amount with either decimal separator ("12.34" in CAMT, "12,34" in MT940).
+pub(crate) fn amount_cents(raw: &str) -> Option<i64> {
+  let raw = raw.trim().replace(',', ".");
+  let (whole, fraction) = raw.split_once('.').unwrap_or((&raw, ""));
+  if whole.is_empty() && fraction.is_empty() {
+    return None;
+  }
+  if !whole.chars().all(|c| c.is_ascii_digit()) || !fraction.chars().all(|c| c.is_ascii_digit()) || fraction.len() > 2 {
+    return None;
+  }
+  let whole: i64 = if whole.is_empty() { 0 } else { whole.parse().ok()? };
+  let fraction: i64 = format!("{:0<2}", fraction).parse().ok()?;
+  whole.checked_mul(100)?.checked_add(fraction)
+}
+
+/// `YYYY-MM-DD` naming a real day.
+fn valid_date(date: &str) -> bool {
+  bank_csv::parse_date(date).as_deref() == Some(date)
+}
+
+/// Upper case without whitespace and with umlauts spelled out ("Baeckerei"
+/// matches "Bäckerei"), used for rule matching and duplicates.
+fn squeeze(value: &str) -> String {
+  let mut out = String::with_capacity(value.len());
+  for c in value.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_uppercase) {
+    match c {
+      'Ä' => out.push_str("AE"),
+      'Ö' => out.push_str("OE"),
+      'Ü' => out.push_str("UE"),
+      'ß' | 'ẞ' => out.push_str("SS"),
+      c => out.push(c),
+    }
+  }
+  out
+}
+
+// ---------------------------------------------------------------------------
+// CAMT.053
+
+type XmlNode<'a, 'input> = roxmltree::Node<'a, 'input>;
+
+fn child<'a, 'input>(node: XmlNode<'a, 'input>, name: &str) -> Option<XmlNode<'a, 'input>> {
+  node.children().find(|child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn children_named<'a, 'input: 'a>(node: XmlNode<'a, 'input>, name: &'a str) -> impl Iterator<Item = XmlNode<'a, 'input>> + 'a {
+  node.children().filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn at<'a, 'input>(node: XmlNode<'a, 'input>, path: &[&str]) -> Option<XmlNode<'a, 'input>> {
+  path.iter().try_fold(node, |node, name| child(node, name))
+}
+
+fn text_at<'a>(node: XmlNode<'a, '_>, path: &[&str]) -> Option<&'a str> {
+  at(node, path).and_then(|node| node.text()).map(str::trim).filter(|text| !text.is_empty())
+}
+
+/// Date of a `BookgDt`/`ValDt` element (`Dt` or the date part of `DtTm`).
+fn camt_date(node: Option<XmlNode>) -> Option<String> {
+  let node = node?;
+  let raw = text_at(node, &["Dt"]).or_else(|| text_at(node, &["DtTm"]))?;
+  raw.get(..10).filter(|date| valid_date(date)).map(str::to_string)
+}
+
+/// Name of a related party; camt.053.001.08 wraps it in `Pty`.
+fn party_name(parties: Option<XmlNode>, role: &str) -> Option<String> {
+  let party = child(parties?, role)?;
+  text_at(party, &["Nm"]).or_else(|| text_at(party, &["Pty", "Nm"])).map(str::to_string)
+}
+
+/// Signed cents and currency of an `Amt` element; `debit` is the
+/// `CdtDbtInd` that applies to it. A reversal entry carries the direction of
+/// the reversal itself (a returned direct debit is `CRDT`), so `RvslInd`
+/// never flips the sign.
+fn camt_amount(file: &str, amount: XmlNode, debit: bool) -> Result<(i64, String), JobFailure> {
+  let raw = amount.text().unwrap_or_default();
+  let cents = amount_cents(raw).ok_or_else(|| parse_failed(file, format!("Betrag {} ist ungueltig.", raw.trim())))?;
+  let currency = amount.attribute("Ccy").unwrap_or("EUR").to_string();
+  Ok((if debit { -cents } else { cents }, currency))
+}
+
+fn camt_debit(file: &str, indicator: Option<&str>) -> Result<bool, JobFailure> {
+  match indicator {
+    Some("DBIT") => Ok(true),
+    Some("CRDT") => Ok(false),
+    other => Err(parse_failed(file, format!("CdtDbtInd {:?} ist ungueltig.", other.unwrap_or("")))),
+  }
+}
+
+/// Text, counterparty and reference of one `TxDtls`.
+fn camt_details(entry: XmlNode, details: Option<XmlNode>, debit: bool) -> (String, String, Option<String>) {
+  let parties = details.and_then(|details| child(details, "RltdPties"));
+  let counterparty = if debit { party_name(parties, "Cdtr") } else { party_name(parties, "Dbtr") };
+  let mut texts: Vec<String> = Vec::new();
+  if let Some(info) = text_at(entry, &["AddtlNtryInf"]) {
+    texts.push(info.to_string());
+  }
+  if let Some(details) = details {
+    if let Some(remittance) = child(details, "RmtInf") {
+      texts.extend(children_named(remittance, "Ustrd").filter_map(|line| line.text()).map(|line| line.trim().to_string()));
+    }
+    if let Some(info) = text_at(details, &["AddtlTxInf"]) {
+      texts.push(info.to_string());
+    }
+  }
+  let reference = details
+    .and_then(|details| text_at(details, &["Refs", "EndToEndId"]))
+    .filter(|id| *id != "NOTPROVIDED")
+    .or_else(|| text_at(entry, &["AcctSvcrRef"]))
+    .map(str::to_string);
+  (counterparty.unwrap_or_default(), texts.join(" "), reference)
+}
+
+/// Bookings of one `Ntry`. A batch booking (several `TxDtls`) is split into
+/// its transactions when each carries its own amount (`Amt` or
+/// `AmtDtls/TxAmt/Amt`); the parts must add up to the entry amount.
+fn parse_camt_entry(file: &str, account: &str, entry: XmlNode) -> Result<Vec<RawBooking>, JobFailure> {
+  let amount = child(entry, "Amt").ok_or_else(|| parse_failed(file, "Buchung ohne Amt."))?;
+  let debit = camt_debit(file, text_at(entry, &["CdtDbtInd"]))?;
+  let reversal = text_at(entry, &["RvslInd"]) == Some("true");
+  let (cents, currency) = camt_amount(file, amount, debit)?;
+  let booking_date = camt_date(child(entry, "BookgDt"))
+    .or_else(|| camt_date(child(entry, "ValDt")))
+    .ok_or_else(|| parse_failed(file, "Buchung ohne Buchungsdatum."))?;
+  let transactions: Vec<XmlNode> = children_named(entry, "NtryDtls").flat_map(|details| children_named(details, "TxDtls")).collect();
+  let booking = |details: Option<XmlNode>, debit: bool, amount_cents: i64, currency: String| {
+    let (counterparty, text, reference) = camt_details(entry, details, debit);
+    RawBooking {
+      account: account.to_string(),
+      booking_date: booking_date.clone(),
+      amount_cents,
+      currency,
+      counterparty,
+      text,
+      reference,
+      reversal,
+    }
+  };
+  let split_amounts: Vec<XmlNode> =
+    transactions.iter().filter_map(|details| child(*details, "Amt").or_else(|| at(*details, &["AmtDtls", "TxAmt", "Amt"]))).collect();
+  if transactions.len() < 2 || split_amounts.len() != transactions.len() {
+    return Ok(vec![booking(transactions.first().copied(), debit, cents, currency)]);
+  }
+  let mut bookings = Vec::with_capacity(transactions.len());
+  for (details, amount) in transactions.into_iter().zip(split_amounts) {
+    let debit = match text_at(details, &["CdtDbtInd"]) {
+      Some(indicator) => camt_debit(file, Some(indicator))?,
+      None => debit,
+    };
+    let (cents, currency) = camt_amount(file, amount, debit)?;
+    bookings.push(booking(Some(details), debit, cents, currency));
+  }
+  if bookings.iter().all(|part| part.currency == currency) && bookings.iter().map(|part| part.amount_cents).sum::<i64>() != cents {
+    return Err(parse_failed(file, format!("Einzelbetraege der Sammelbuchung vom {} ergeben nicht den Buchungsbetrag.", booking_date)));
+  }
+  Ok(bookings)
+}
+
+fn parse_camt(file: &str, content: &str) -> Result<Vec<RawBooking>, JobFailure> {
+  let document = roxmltree::Document::parse(content.trim_start_matches('\u{feff}')).map_err(|error| parse_failed(file, error))?;
+  let statements: Vec<XmlNode> = document.descendants().filter(|node| node.is_element() && node.tag_name().name() == "Stmt").collect();
+  if statements.is_empty() {
+    return Err(parse_failed(file, "Keine CAMT.053-Kontoauszuege (Stmt) gefunden."));
+  }
+  let mut bookings = Vec::new();
+  for statement in statements {
+    let account = text_at(statement, &["Acct", "Id", "IBAN"]).or_else(|| text_at(statement, &["Acct", "Id", "Othr", "Id"])).unwrap_or_default();
+    for entry in children_named(statement, "Ntry") {
+      let status = text_at(entry, &["Sts"]).or_else(|| text_at(entry, &["Sts", "Cd"]));
+      if status.is_some_and(|status| status != "BOOK") {
+        continue;
+      }
+      bookings.extend(parse_camt_entry(file, account, entry)?);
+    }
+  }
+  Ok(bookings)
+}
+
+// ---------------------------------------------------------------------------
+// MT940
+
+/// Splits the statement into `(tag, value)` fields; continuation lines are
+/// appended without a separator (`:86:` wraps at fixed widths).
+fn mt940_fields(content: &str) -> Vec<(String, String)> {
+  let mut fields: Vec<(String, String)> = Vec::new();
+  for line in content.lines().map(|line| line.trim_end_matches('\r')) {
+    let tag = line
+      .strip_prefix(':')
+      .and_then(|rest| rest.find(':').map(|end| (&rest[..end], &rest[end + 1..])))
+      .filter(|(tag, _)| (2..=3).contains(&tag.len()) && tag.starts_with(|c: char| c.is_ascii_digit()));
+    match tag {
+      Some((tag, value)) => fields.push((tag.to_string(), value.to_string())),
+      None if line.starts_with('{') || line.trim() == "-" || line.trim().is_empty() => {}
+      None => {
+        if let Some((_, value)) = fields.last_mut() {
+          value.push_str(line);
+        }
+      }
+    }
+  }
+  fields
+}
+
+struct Mt940Line {
+  booking_date: String,
+  amount_cents: i64,
+  reference: Option<String>,
+  /// `RC`/`RD`: reversal of a credit (booked as debit) or of a debit.
+  reversal: bool,
+}
+
+/// `:61:` = value date YYMMDD, optional entry date MMDD, C/D/RC/RD, optional
+/// funds code, amount, transaction type and references.
+fn parse_mt940_line(value: &str) -> Result<Mt940Line, String> {
+  let digits = |text: &str| !text.is_empty() && text.chars().all(|c| c.is_ascii_digit());
+  let value_date = value.get(..6).filter(|date| digits(date)).ok_or("Valutadatum fehlt.")?;
+  let year = 2000 + value_date[..2].parse::<i32>().map_err(|e| e.to_string())?;
+  let value_month: u32 = value_date[2..4].parse().map_err(|e: std::num::ParseIntError| e.to_string())?;
+  let mut rest = &value[6..];
+  let mut date = format!("{:04}-{}-{}", year, &value_date[2..4], &value_date[4..6]);
+  if let Some(entry) = rest.get(..4).filter(|entry| digits(entry)) {
+    let entry_month: u32 = entry[..2].parse().map_err(|e: std::num::ParseIntError| e.to_string())?;
+    let entry_year = match (value_month, entry_month) {
+      (1, 12) => year - 1,
+      (12, 1) => year + 1,
+      _ => year,
+    };
+    date = format!("{:04}-{}-{}", entry_year, &entry[..2], &entry[2..]);
+    rest = &rest[4..];
+  }
+  if !valid_date(&date) {
+    return Err(format!("Datum {} ist ungueltig.", date));
+  }
+  let reversal = rest.starts_with('R');
+  let (debit, after_mark) = if let Some(after) = rest.strip_prefix("RC") {
+    (true, after)
+  } else if let Some(after) = rest.strip_prefix("RD") {
+    (false, after)
+  } else if let Some(after) = rest.strip_prefix('C') {
+    (false, after)
+  } else if let Some(after) = rest.strip_prefix('D') {
+    (true, after)
+  } else {
+    return Err("Soll/Haben-Kennung fehlt.".to_string());
+  };
+  rest = after_mark;
+  if rest.starts_with(|c: char| c.is_ascii_alphabetic()) {
+    rest = &rest[1..];
+  }
+  let amount_end = rest.find(|c: char| !c.is_ascii_digit() && c != ',').unwrap_or(rest.len());
+  let cents = amount_cents(&rest[..amount_end]).ok_or("Betrag ist ungueltig.")?;
+  let tail = &rest[amount_end..];
+  let reference = tail
+    .get(4..)
+    .map(|refs| refs.split("//").next().unwrap_or_default().trim())
+    .filter(|reference| !reference.is_empty() && *reference != "NONREF")
+    .map(str::to_string);
+  Ok(Mt940Line { booking_date: date, amount_cents: if debit { -cents } else { cents }, reference, reversal })
+}
+
+/// `:86:` with German `?NN` subfields: ?00 booking text, ?20-?29 and ?60-?63
+/// purpose, ?32/?33 counterparty. Unstructured content is kept as text.
+fn parse_mt940_details(value: &str) -> (String, String) {
+  if value.get(3..4) != Some("?") {
+    return (String::new(), value.trim().to_string());
+  }
+  let (mut booking_text, mut purpose, mut name) = (String::new(), String::new(), String::new());
+  for field in value[4..].split('?') {
+    let (Some(code), Some(content)) = (field.get(..2), field.get(2..)) else { continue };
+    match code {
+      "00" => booking_text.push_str(content),
+      "20" | "21" | "22" | "23" | "24" | "25" | "26" | "27" | "28" | "29" | "60" | "61" | "62" | "63" => {
+        purpose.push_str(content)
+      }
+      "32" | "33" => name.push_str(content),
+      _ => {}
+    }
+  }
+  let text = [booking_text.trim(), purpose.trim()].into_iter().filter(|part| !part.is_empty()).collect::<Vec<_>>().join(" ");
+  (name.trim().to_string(), text)
+}
+
+fn parse_mt940(file: &str, content: &str) -> Result<Vec<RawBooking>, JobFailure> {
+  let mut bookings: Vec<RawBooking> = Vec::new();
+  let (mut account, mut currency) = (String::new(), "EUR".to_string());
+  let mut seen_statement = false;
+  for (tag, value) in mt940_fields(content) {
+    match tag.as_str() {
+      "20" => seen_statement = true,
+      "25" => account = value.trim().to_string(),
+      "60F" | "60M" => {
+        if let Some(code) = value.get(7..10) {
+          currency = code.to_string();
+        }
+      }
+      "61" => {
+        let line = parse_mt940_line(&value).map_err(|message| parse_failed(file, format!(":61:{} - {}", value, message)))?;
+        bookings.push(RawBooking {
+          account: account.clone(),
+          booking_date: line.booking_date,
+          amount_cents: line.amount_cents,
+          currency: currency.clone(),
+          counterparty: String::new(),
+          text: String::new(),
+          reference: line.reference,
+          reversal: line.reversal,
+        });
+      }
+      "86" => {
+        if let Some(booking) = bookings.last_mut() {
+          let (counterparty, text) = parse_mt940_details(&value);
+          booking.counterparty = counterparty;
+          booking.text = text;
+        }
+      }
+      _ => {}
+    }
+  }
+  if !seen_statement {
+    return Err(parse_failed(file, "Kein MT940-Auszug (:20:) gefunden."));
+  }
+  Ok(bookings)
+}
+
+fn detect_format(file: &StatementFile, content: &str) -> Result<StatementFormat, JobFailure> {
+  let head = content.trim_start_matches('\u{feff}').trim_start();
+  if head.starts_with('<') {
+    Ok(StatementFormat::Camt053)
+  } else if content.contains(":20:") && content.contains(":25:") {
+    Ok(StatementFormat::Mt940)
+  } else if file.profile_id.is_some() || bank_csv::looks_like_bank_csv(content) {
+    Ok(StatementFormat::Csv)
+  } else {
+    Err(failure(
+      "BANK_IMPORT_FORMAT_UNKNOWN",
+      format!("{}: weder CAMT.053 (XML), MT940 noch eine bekannte Bank-CSV erkannt.", file.name),
+    ))
+  }
+}
+
+/// CSV rows as bookings; like the category CSV import, a file with invalid
+/// rows is rejected as a whole (`preview_bank_csv` shows the lines).
+fn parse_csv(file: &StatementFile, content: &str, encoding: bank_csv::TextEncoding) -> Result<(Vec<RawBooking>, String), JobFailure> {
+  let preview = bank_csv::parse_csv(&file.name, content, encoding, file.profile_id.as_deref())?;
+  if !preview.errors.is_empty() {
+    let lines: Vec<String> = preview.errors.iter().take(5).map(|error| format!("Zeile {}: {}", error.line_number, error.reason)).collect();
+    let remainder = if preview.errors.len() > 5 { format!("; weitere {}", preview.errors.len() - 5) } else { String::new() };
+    return Err(parse_failed(
+      &file.name,
+      format!("{} von {} Datenzeile(n) ungueltig ({}{}).", preview.errors.len(), preview.summary.total_rows, lines.join("; "), remainder),
+    ));
+  }
+  let bookings = preview
+    .rows
+    .into_iter()
+    .map(|row| RawBooking {
+      account: preview.account.clone(),
+      booking_date: row.booking_date,
+      amount_cents: (row.amount * 100.0).round() as i64,
+      currency: row.currency,
+      counterparty: row.counterparty,
+      text: row.text,
+      reference: None,
+      reversal: false,
+    })
+    .collect();
+  Ok((bookings, preview.profile.id))
+}
+
+// ---------------------------------------------------------------------------
+// Categories and totals
+
+fn rule_matches(rule: &CategoryRule, booking: &RawBooking) -> bool {
+  let direction = match rule.direction {
+    RuleDirection::Any => true,
+    RuleDirection::Debit => booking.amount_cents < 0,
+    RuleDirection::Credit => booking.amount_cents > 0,
+  };
+  let needle = squeeze(&rule.contains);
+  let found = |haystack: &str| squeeze(haystack).contains(&needle);
+  direction
+    && match rule.field {
+      RuleField::Any => found(&booking.counterparty) || found(&booking.text),
+      RuleField::Text => found(&booking.text),
+      RuleField::Counterparty => found(&booking.counterparty),
+    }
+}
+
+fn fingerprint(booking: &RawBooking) -> String {
+  format!(
+    "{}|{}|{}|{}|{}",
+    squeeze(&booking.account),
+    booking.booking_date,
+    booking.amount_cents,
+    squeeze(&booking.counterparty),
+    squeeze(&booking.text)
+  )
+}
+
+fn validate(request: &BankImportRequest) -> Result<(), JobFailure> {
+  let invalid = |message: &str| failure("BANK_IMPORT_INVALID", message);
+  if request.files.is_empty() {
+    return Err(invalid("Mindestens eine Auszugsdatei ist erforderlich."));
+  }
+  if request.profile_id.trim().is_empty() {
+    return Err(invalid("profileId ist erforderlich."));
+  }
+  if request.rules.iter().any(|rule| rule.category.trim().is_empty() || squeeze(&rule.contains).is_empty()) {
+    return Err(invalid("Jede Regel braucht eine Kategorie und einen Suchtext."));
+  }
+  if request.default_category.as_deref().is_some_and(|category| category.trim().is_empty()) {
+    return Err(invalid("defaultCategory darf nicht leer sein."));
+  }
+  Ok(())
+}
+
+pub(crate) fn import_statements(request: &BankImportRequest) -> Result<BankImportResult, JobFailure> {
+  validate(request)?;
+  let default_category = request.default_category.as_deref().unwrap_or(DEFAULT_CATEGORY).trim();
+  let mut result = BankImportResult {
+    years: BTreeMap::new(),
+    bookings: Vec::new(),
+    statements: Vec::new(),
+    duplicate_count: 0,
+    uncategorized_count: 0,
+    ignored_credit_count: 0,
+    foreign_currency_count: 0,
+  };
+  let mut month_cents: BTreeMap<(String, String), BTreeMap<String, i64>> = BTreeMap::new();
+  // Occurrences already taken per fingerprint: identical bookings within one
+  // statement are real, the same ones in an overlapping statement are not.
+  let mut taken: HashMap<String, usize> = HashMap::new();
+
+  for file in &request.files {
+    let (content, encoding) = match &file.bytes {
+      Some(bytes) => bank_csv::decode(bytes),
+      None => (file.content.clone(), bank_csv::TextEncoding::Utf8),
+    };
+    let format = detect_format(file, &content)?;
+    let (raw, profile) = match format {
+      StatementFormat::Camt053 => (parse_camt(&file.name, &content)?, None),
+      StatementFormat::Mt940 => (parse_mt940(&file.name, &content)?, None),
+      StatementFormat::Csv => {
+        let (raw, profile) = parse_csv(file, &content, encoding)?;
+        (raw, Some(profile))
+      }
+    };
+    let mut summary = StatementSummary {
+      file: file.name.clone(),
+      format,
+      profile,
+      accounts: Vec::new(),
+      bookings: raw.len(),
+      duplicates: 0,
+    };
+    let mut local: HashMap<String, usize> = HashMap::new();
+    for booking in raw {
+      if !summary.accounts.contains(&booking.account) {
+        summary.accounts.push(booking.account.clone());
+      }
+      let key = fingerprint(&booking);
+      let occurrence = local.entry(key.clone()).or_default();
+      *occurrence += 1;
+      let already = taken.entry(key).or_default();
+      let duplicate = *occurrence <= *already;
+      if duplicate {
+        summary.duplicates += 1;
+      } else {
+        *already = *occurrence;
+      }
+
+      let rule = request.rules.iter().find(|rule| rule_matches(rule, &booking));
+      let category = match rule {
+        Some(rule) => Some(rule.category.trim().to_string()),
+        None if booking.amount_cents < 0 || booking.reversal => Some(default_category.to_string()),
+        None => None,
+      };
+      if !duplicate {
+        if booking.currency != "EUR" {
+          result.foreign_currency_count += 1;
+        } else if let Some(category) = &category {
+          if rule.is_none() {
+            result.uncategorized_count += 1;
+          }
+          let month = (booking.booking_date[..4].to_string(), booking.booking_date[5..7].trim_start_matches('0').to_string());
+          *month_cents.entry(month).or_default().entry(category.clone()).or_default() -= booking.amount_cents;
+        } else {
+          result.ignored_credit_count += 1;
+        }
+      }
+      result.bookings.push(Booking {
+        account: booking.account,
+        booking_date: booking.booking_date,
+        amount: booking.amount_cents as f64 / 100.0,
+        currency: booking.currency,
+        counterparty: booking.counterparty,
+        text: booking.text,
+        reference: booking.reference,
+        file: file.name.clone(),
+        category,
+        duplicate,
+        reversal: booking.reversal,
+      });
+    }
+    result.duplicate_count += summary.duplicates;
+    result.statements.push(summary);
+  }
+
+  for ((year, month), categories) in month_cents {
+    let booking_count = result
+      .bookings
+      .iter()
+      .filter(|booking| !booking.duplicate && booking.currency == "EUR" && booking.category.is_some())
+      .filter(|booking| booking.booking_date[..4] == year && booking.booking_date[5..7].trim_start_matches('0') == month)
+      .count();
+    let totals = MonthTotals {
+      categories: categories.into_iter().map(|(category, cents)| (category, cents as f64 / 100.0)).collect(),
+      booking_count,
+    };
+    let month = result.years.entry(year).or_default().months.entry(month).or_default();
+    month.profiles.insert(request.profile_id.clone(), totals);
+  }
+  Ok(result)
+}
+
+#[tauri::command]
+pub fn import_bank_statements(request: BankImportRequest) -> Result<BankImportResult, String> {
+  import_statements(&request).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  const CAMT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02">
+  <BkToCstmrStmt>
+    <Stmt>
+      <Acct><Id><IBAN>DE02120300000000202051</IBAN></Id></Acct>
+      <Ntry>
+        <Amt Ccy="EUR">54.20</Amt><CdtDbtInd>DBIT</CdtDbtInd><Sts>BOOK</Sts>
+        <BookgDt><Dt>2024-01-30</Dt></BookgDt>
+        <AddtlNtryInf>KARTENZAHLUNG</AddtlNtryInf>
+        <NtryDtls><TxDtls>
+          <RltdPties><Cdtr><Nm>REWE Markt GmbH</Nm></Cdtr></RltdPties>
+          <RmtInf><Ustrd>Einkauf &amp; Pfand</Ustrd></RmtInf>
+        </TxDtls></NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">12.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><Sts>BOOK</Sts>
+        <BookgDt><Dt>2024-01-31</Dt></BookgDt>
+        <NtryDtls><TxDtls>
+          <RltdPties><Dbtr><Nm>REWE Markt GmbH</Nm></Dbtr></RltdPties>
+          <RmtInf><Ustrd>Erstattung</Ustrd></RmtInf>
+        </TxDtls></NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">1800.00</Amt><CdtDbtInd>CRDT</CdtDbtInd><Sts>BOOK</Sts>
+        <BookgDt><Dt>2024-01-31</Dt></BookgDt>
+        <NtryDtls><TxDtls><RmtInf><Ustrd>RENTE 01/2024</Ustrd></RmtInf></TxDtls></NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">99.00</Amt><CdtDbtInd>DBIT</CdtDbtInd><Sts>PDNG</Sts>
+        <BookgDt><Dt>2024-02-01</Dt></BookgDt>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">30.00</Amt><CdtDbtInd>DBIT</CdtDbtInd><Sts><Cd>BOOK</Cd></Sts>
+        <BookgDt><DtTm>2024-02-02T10:00:00</DtTm></BookgDt>
+        <NtryDtls><TxDtls><RltdPties><Cdtr><Pty><Nm>Stadtwerke</Nm></Pty></Cdtr></RltdPties></TxDtls></NtryDtls>
+      </Ntry>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>"#;
+
+  const MT940_JAN: &str = "{1:F01BANKDEFFXXXX0000000000}\r\n:20:STARTUMSE\r\n:25:12030000/0000202051\r\n:28C:00001/001\r\n:60F:C240101EUR1000,00\r\n:61:2401150115DR42,50NDDTNONREF\r\n:86:105?00BASISLASTSCHRIFT?20SVWZ+Beitrag Januar?21 Hausratversicherung?32ALLIANZ VERSICHERUNG\r\n:61:2401160116DR5,00NMSCNONREF\r\n:86:106?00KARTENZAHLUNG?20Baecker?32BAECKEREI\r\n:61:2401160116DR5,00NMSCNONREF\r\n:86:106?00KARTENZAHLUNG?20Baecker?32BAECKEREI\r\n:62F:C240131EUR947,50\r\n-";
+
+  const MT940_OVERLAP: &str = ":20:STARTUMSE\n:25:12030000/0000202051\n:60F:C240116EUR957,50\n:61:2401160116DR5,00NMSCNONREF\n:86:106?00KARTENZAHLUNG?20Bae\ncker?32BAECKEREI\n:61:2401310201DR20,00NMSCNONREF\n:86:Barauszahlung Automat\n:62F:C240201EUR932,50\n-";
+
+  fn request(files: &[(&str, &str)]) -> BankImportRequest {
+    serde_json::from_value(json!({
+      "files": files.iter().map(|(name, content)| json!({ "name": name, "content": content })).collect::<Vec<_>>(),
+      "profileId": "p1",
+      "rules": [
+        { "category": "Lebensmittel", "contains": "rewe" },
+        { "category": "Lebensmittel", "contains": "baeckerei", "field": "counterparty", "direction": "debit" },
+        { "category": "Versicherungen", "contains": "hausratversicherung", "field": "text" }
+      ]
+    }))
+    .unwrap()
+  }
+
+  fn categories(result: &BankImportResult, year: &str, month: &str) -> BTreeMap<String, f64> {
+    result.years[year].months[month].profiles["p1"].categories.clone()
+  }
+
+  #[test]
+  fn camt_bookings_are_categorized_per_month() {
+    let result = import_statements(&request(&[("januar.xml", CAMT)])).unwrap();
+    assert_eq!(result.statements[0].accounts, vec!["DE02120300000000202051".to_string()]);
+    assert_eq!(result.bookings.len(), 4, "pending entries are skipped");
+    assert_eq!(result.bookings[0].text, "KARTENZAHLUNG Einkauf & Pfand");
+    // The refund from REWE lowers the category; the pension is income.
+    assert_eq!(categories(&result, "2024", "1"), BTreeMap::from([("Lebensmittel".to_string(), 42.2)]));
+    assert_eq!(categories(&result, "2024", "2"), BTreeMap::from([("Sonstiges".to_string(), 30.0)]));
+    assert_eq!(result.bookings[3].counterparty, "Stadtwerke");
+    assert_eq!((result.ignored_credit_count, result.uncategorized_count), (1, 1));
+  }
+
+  #[test]
+  fn batch_bookings_are_split_per_transaction() {
+    let batch = r#"<Document><BkToCstmrStmt><Stmt>
+      <Acct><Id><IBAN>DE02120300000000202051</IBAN></Id></Acct>
+      <Ntry>
+        <Amt Ccy="EUR">75.50</Amt><CdtDbtInd>DBIT</CdtDbtInd><Sts>BOOK</Sts>
+        <BookgDt><Dt>2024-03-01</Dt></BookgDt>
+        <NtryDtls>
+          <Btch><NbOfTxs>2</NbOfTxs></Btch>
+          <TxDtls><AmtDtls><TxAmt><Amt Ccy="EUR">25.50</Amt></TxAmt></AmtDtls>
+            <RltdPties><Cdtr><Nm>REWE Markt GmbH</Nm></Cdtr></RltdPties></TxDtls>
+          <TxDtls><Amt Ccy="EUR">50.00</Amt>
+            <RmtInf><Ustrd>Hausratversicherung</Ustrd></RmtInf></TxDtls>
+        </NtryDtls>
+      </Ntry>
+    </Stmt></BkToCstmrStmt></Document>"#;
+    let result = import_statements(&request(&[("sammler.xml", batch)])).unwrap();
+    assert_eq!(result.bookings.iter().map(|booking| booking.amount).collect::<Vec<_>>(), vec![-25.5, -50.0]);
+    assert_eq!(
+      categories(&result, "2024", "3"),
+      BTreeMap::from([("Lebensmittel".to_string(), 25.5), ("Versicherungen".to_string(), 50.0)])
+    );
+    let value = serde_json::to_value(&result).unwrap();
+    assert_eq!(value["years"]["2024"]["months"]["3"]["profiles"]["p1"]["bookingCount"], 2);
+
+    let wrong_sum = batch.replace("50.00", "40.00");
+    let wrong_sum = request(&[("sammler.xml", wrong_sum.as_str())]);
+    assert_eq!(import_statements(&wrong_sum).unwrap_err().code, "BANK_IMPORT_PARSE_FAILED");
+  }
+
+  #[test]
+  fn reversal_entries_keep_their_own_direction() {
+    let entry = |amount: &str, indicator: &str, reversal: bool, day: &str, text: &str| {
+      format!(
+        "<Ntry><Amt Ccy=\"EUR\">{}</Amt><CdtDbtInd>{}</CdtDbtInd>{}<Sts>BOOK</Sts><BookgDt><Dt>2024-04-{}</Dt></BookgDt>\
+         <NtryDtls><TxDtls><RmtInf><Ustrd>{}</Ustrd></RmtInf></TxDtls></NtryDtls></Ntry>",
+        amount,
+        indicator,
+        if reversal { "<RvslInd>true</RvslInd>" } else { "" },
+        day,
+        text
+      )
+    };
+    let statement = format!(
+      "<Document><BkToCstmrStmt><Stmt><Acct><Id><IBAN>DE02120300000000202051</IBAN></Id></Acct>{}{}{}{}</Stmt></BkToCstmrStmt></Document>",
+      entry("42.50", "DBIT", false, "02", "Hausratversicherung April"),
+      // Returned direct debit and reversed card payment: credits.
+      entry("42.50", "CRDT", true, "09", "Ruecklastschrift Hausratversicherung April"),
+      entry("80.00", "DBIT", false, "10", "Kartenzahlung Elektromarkt"),
+      entry("80.00", "CRDT", true, "11", "Storno Kartenzahlung Elektromarkt")
+    );
+    let result = import_statements(&request(&[("april.xml", statement.as_str())])).unwrap();
+    assert_eq!(result.bookings.iter().map(|booking| booking.amount).collect::<Vec<_>>(), vec![-42.5, 42.5, -80.0, 80.0]);
+    assert_eq!(result.bookings.iter().map(|booking| booking.reversal).collect::<Vec<_>>(), vec![false, true, false, true]);
+    assert_eq!(result.bookings[3].category.as_deref(), Some(DEFAULT_CATEGORY));
+    assert!(categories(&result, "2024", "4").values().all(|total| *total == 0.0), "{:?}", categories(&result, "2024", "4"));
+    assert_eq!(result.ignored_credit_count, 0);
+  }
+
+  #[test]
+  fn mt940_overlaps_count_once_and_same_day_repeats_stay() {
+    let result = import_statements(&request(&[("jan.sta", MT940_JAN), ("overlap.sta", MT940_OVERLAP)])).unwrap();
+    assert_eq!(result.bookings[0].counterparty, "ALLIANZ VERSICHERUNG");
+    assert_eq!(result.bookings[0].text, "BASISLASTSCHRIFT SVWZ+Beitrag Januar Hausratversicherung");
+    // Two bakery purchases in January; the overlapping file repeats one of them.
+    assert_eq!(result.duplicate_count, 1);
+    assert_eq!(result.statements[1].duplicates, 1);
+    let january = categories(&result, "2024", "1");
+    assert_eq!(january["Lebensmittel"], 10.0);
+    assert_eq!(january["Versicherungen"], 42.5);
+    // Value date 31 Jan, booked 1 Feb.
+    assert_eq!(categories(&result, "2024", "2")["Sonstiges"], 20.0);
+    assert_eq!(result.years["2024"].months["1"].profiles["p1"].booking_count, 3);
+  }
+
+  #[test]
+  fn bank_csv_files_go_through_the_same_rules() {
+    let csv = "Buchung;Wertstellungsdatum;Auftraggeber/Empf\u{e4}nger;Buchungstext;Verwendungszweck;Saldo;W\u{e4}hrung;Betrag;W\u{e4}hrung\n05.03.2024;05.03.2024;B\u{e4}ckerei;Lastschrift;Br\u{f6}tchen;1.000,00;EUR;-3,80;EUR\n";
+    let bytes: Vec<u8> = csv.chars().map(|c| u32::from(c) as u8).collect();
+    let mut import = request(&[]);
+    import.files.push(StatementFile { name: "ing.csv".into(), content: String::new(), bytes: Some(bytes), profile_id: None });
+    let result = import_statements(&import).unwrap();
+    assert_eq!((result.statements[0].format, result.statements[0].profile.as_deref()), (StatementFormat::Csv, Some("ing")));
+    assert_eq!(categories(&result, "2024", "3")["Lebensmittel"], 3.8);
+
+    let broken = csv.replace("05.03.2024;05", "5.3.;05");
+    let broken = request(&[("ing.csv", broken.as_str())]);
+    let error = import_statements(&broken).unwrap_err();
+    assert_eq!(error.code, "BANK_IMPORT_PARSE_FAILED");
+    assert!(error.message.contains("Zeile 2"), "{}", error.message);
+  }
+
+  #[test]
+  fn broken_or_unknown_files_are_rejected() {
+    let unknown = request(&[("notes.txt", "Kategorie;Betrag")]);
+    assert_eq!(import_statements(&unknown).unwrap_err().code, "BANK_IMPORT_FORMAT_UNKNOWN");
+    let broken = request(&[("kaputt.xml", "<Document><Stmt><Ntry></Stmt></Document>")]);
+    assert_eq!(import_statements(&broken).unwrap_err().code, "BANK_IMPORT_PARSE_FAILED");
+    let bad_amount = request(&[("bad.sta", ":20:X\n:25:1/2\n:61:2401150115DRN\n")]);
+    assert_eq!(import_statements(&bad_amount).unwrap_err().code, "BANK_IMPORT_PARSE_FAILED");
+    let mut empty_rule = request(&[("januar.xml", CAMT)]);
+    empty_rule.rules[0].contains = " ".to_string();
+    assert_eq!(import_statements(&empty_rule).unwrap_err().code, "BANK_IMPORT_INVALID");
+    let mut no_profile = request(&[("januar.xml", CAMT)]);
+    no_profile.profile_id = " ".to_string();
+    assert_eq!(import_statements(&no_profile).unwrap_err().code, "BANK_IMPORT_INVALID");
+  }
+}
@@ -0,0 +1,5 @@
+//! Headless Monte-Carlo/backtest runner; see `app_lib::run_cli`.
+
+fn main() {
+  std::process::exit(app_lib::run_cli(std::env::args().skip(1).collect()));
+}
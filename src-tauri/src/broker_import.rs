@@ -0,0 +1,1190 @@
+//! Broker transaction import for the tranche manager. Reads broker CSV
+//! exports (a profile per broker, or a user-defined column mapping), replays
+//! buys, sells, splits and fee lines per ISIN into FIFO lots with cost basis,
+//! and diffs those lots against the profile's current tranches. Transactions
+//! repeated by overlapping export files count once; only EUR lines are
+//! accepted, since the tranches keep their cost basis in EUR. Nothing is
+//! written: like the preview step of app/tranches/tranche-reconciliation.js
+//! the result only proposes the new tranche list for the user to confirm.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::bank_csv::{decode, detect_delimiter, normalize_header, parse_date, split_line};
+use crate::script_runtime::{job_failure as failure, JobFailure};
+
+/// Same tolerance as tranche-reconciliation.js.
+const SHARE_EPSILON: f64 = 1e-10;
+const PRICE_EPSILON: f64 = 1e-6;
+const HEADER_SEARCH_LINES: usize = 40;
+
+const BUY_WORDS: &[&str] = &["kauf", "buy", "sparplan", "savingsplan", "sparplanausfuehrung", "einlieferung"];
+const SELL_WORDS: &[&str] = &["verkauf", "sell", "auslieferung"];
+const SPLIT_WORDS: &[&str] = &["split", "aktiensplit", "stocksplit"];
+const FEE_WORDS: &[&str] = &["gebuehr", "gebuehren", "fee", "fees"];
+
+/// Columns of a broker export, as normalized header names (see
+/// `normalize_header` in bank_csv.rs).
+struct BrokerProfile {
+  id: &'static str,
+  label: &'static str,
+  fingerprint: &'static [&'static str],
+  date: &'static str,
+  /// Time of day, when the export has its own column for it.
+  time: Option<&'static str>,
+  kind: &'static str,
+  isin: &'static str,
+  name: Option<&'static str>,
+  currency: Option<&'static str>,
+  shares: Option<&'static str>,
+  price: Option<&'static str>,
+  amount: Option<&'static str>,
+  fee: Option<&'static str>,
+  /// Status column and the values that mean "executed"; other rows are skipped.
+  status: Option<(&'static str, &'static [&'static str])>,
+  /// Whether `amount` already contains the fees (cash-flow style exports).
+  amount_includes_fees: bool,
+}
+
+static PROFILES: [BrokerProfile; 3] = [
+  BrokerProfile {
+    id: "scalable",
+    label: "Scalable Capital",
+    fingerprint: &["date", "status", "type", "isin", "shares", "price", "amount", "fee"],
+    date: "date",
+    time: Some("time"),
+    kind: "type",
+    isin: "isin",
+    name: Some("description"),
+    currency: Some("currency"),
+    shares: Some("shares"),
+    price: Some("price"),
+    amount: Some("amount"),
+    fee: Some("fee"),
+    status: Some(("status", &["executed"])),
+    amount_includes_fees: false,
+  },
+  BrokerProfile {
+    id: "trade_republic",
+    label: "Trade Republic (pytr-Export)",
+    fingerprint: &["date", "type", "value", "note", "isin", "shares", "fees"],
+    date: "date",
+    time: None,
+    kind: "type",
+    isin: "isin",
+    name: Some("note"),
+    currency: None,
+    shares: Some("shares"),
+    price: None,
+    amount: Some("value"),
+    fee: Some("fees"),
+    status: None,
+    amount_includes_fees: true,
+  },
+  BrokerProfile {
+    id: "portfolio_performance",
+    label: "Portfolio Performance (Export)",
+    fingerprint: &["datum", "typ", "wert", "stueck", "isin", "wertpapiername"],
+    date: "datum",
+    time: None,
+    kind: "typ",
+    isin: "isin",
+    name: Some("wertpapiername"),
+    currency: Some("buchungswaehrung"),
+    shares: Some("stueck"),
+    price: None,
+    amount: Some("wert"),
+    fee: Some("gebuehren"),
+    status: None,
+    amount_includes_fees: true,
+  },
+];
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MappingColumns {
+  pub date: String,
+  pub time: Option<String>,
+  #[serde(rename = "type")]
+  pub kind: String,
+  pub isin: String,
+  pub name: Option<String>,
+  pub currency: Option<String>,
+  pub shares: Option<String>,
+  pub price: Option<String>,
+  pub amount: Option<String>,
+  pub fee: Option<String>,
+  /// Split ratio such as "4:1" or "4".
+  pub ratio: Option<String>,
+}
+
+/// Transaction type values on top of the built-in words.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MappingKinds {
+  #[serde(default)]
+  pub buy: Vec<String>,
+  #[serde(default)]
+  pub sell: Vec<String>,
+  #[serde(default)]
+  pub split: Vec<String>,
+  #[serde(default)]
+  pub fee: Vec<String>,
+}
+
+/// Column mapping for exports without a profile; names as in the header row.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GenericMapping {
+  pub columns: MappingColumns,
+  #[serde(default)]
+  pub kinds: MappingKinds,
+  #[serde(default)]
+  pub amount_includes_fees: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BrokerFile {
+  pub name: String,
+  #[serde(default)]
+  pub content: String,
+  pub bytes: Option<Vec<u8>>,
+  pub profile_id: Option<String>,
+  pub mapping: Option<GenericMapping>,
+}
+
+/// Classification for ISINs that have no tranche yet.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TrancheDefaults {
+  pub category: String,
+  #[serde(rename = "type")]
+  pub kind: String,
+  pub tqf: f64,
+}
+
+impl Default for TrancheDefaults {
+  fn default() -> Self {
+    TrancheDefaults { category: "equity".to_string(), kind: "aktien_neu".to_string(), tqf: 0.3 }
+  }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BrokerImportRequest {
+  pub files: Vec<BrokerFile>,
+  /// Persisted tranche records of the open profile.
+  #[serde(default)]
+  pub current_tranches: Vec<Value>,
+  /// Depot the exports belong to, as in the tranches' `depotId` or
+  /// `sourceProfileId`. Tranches of other depots are kept untouched.
+  #[serde(default)]
+  pub depot_id: Option<String>,
+  #[serde(default)]
+  pub defaults: TrancheDefaults,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum TxKind {
+  Buy,
+  Sell,
+  Split,
+  Fee,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BrokerTransaction {
+  pub file: String,
+  pub line_number: usize,
+  pub date: String,
+  /// `HH:MM:SS` from a time column or the date cell, if the export has one.
+  pub time: Option<String>,
+  pub kind: TxKind,
+  pub isin: String,
+  pub name: String,
+  pub shares: f64,
+  /// Trade value without fees.
+  pub gross: f64,
+  pub fee: f64,
+  pub ratio: Option<f64>,
+  /// Already imported from an earlier, overlapping file; not replayed.
+  pub duplicate: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BrokerLineError {
+  pub file: String,
+  pub line_number: usize,
+  pub code: &'static str,
+  pub reason: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BrokerFileSummary {
+  pub file: String,
+  pub profile: String,
+  pub header_line: usize,
+  pub transactions: usize,
+  pub ignored_rows: usize,
+  pub duplicates: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImportedLot {
+  pub tranche_id: String,
+  pub name: String,
+  pub isin: String,
+  pub shares: f64,
+  pub purchase_price: f64,
+  pub purchase_date: String,
+  pub cost_basis: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RealizedSale {
+  pub isin: String,
+  pub date: String,
+  pub shares: f64,
+  pub net_proceeds: f64,
+  pub cost_basis: f64,
+  pub realized_gain: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum DiffAction {
+  Add,
+  Update,
+  Remove,
+  Unchanged,
+  /// Tranche of an instrument the import does not cover.
+  Keep,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FieldChange {
+  pub field: &'static str,
+  pub before: Value,
+  pub after: Value,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DiffEntry {
+  pub action: DiffAction,
+  pub isin: Option<String>,
+  pub tranche_id: Option<String>,
+  pub current: Option<Value>,
+  pub proposed: Option<Value>,
+  pub changes: Vec<FieldChange>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DiffSummary {
+  pub add: usize,
+  pub update: usize,
+  pub remove: usize,
+  pub unchanged: usize,
+  pub keep: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BrokerImportPreview {
+  pub files: Vec<BrokerFileSummary>,
+  pub transactions: Vec<BrokerTransaction>,
+  pub duplicate_count: usize,
+  pub errors: Vec<BrokerLineError>,
+  pub imported_lots: Vec<ImportedLot>,
+  pub realized: Vec<RealizedSale>,
+  pub diff: Vec<DiffEntry>,
+  pub summary: DiffSummary,
+  /// Current tranches with the diff applied, for the confirm step.
+  pub proposed_tranches: Vec<Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BrokerProfileInfo {
+  pub id: String,
+  pub label: String,
+}
+
+/// A profile or a generic mapping with resolved column indices.
+struct Layout {
+  label: String,
+  date: usize,
+  time: Option<usize>,
+  kind: usize,
+  isin: usize,
+  name: Option<usize>,
+  currency: Option<usize>,
+  shares: Option<usize>,
+  price: Option<usize>,
+  amount: Option<usize>,
+  fee: Option<usize>,
+  ratio: Option<usize>,
+  status: Option<(usize, Vec<String>)>,
+  amount_includes_fees: bool,
+  kinds: [(TxKind, Vec<String>); 4],
+}
+
+fn invalid(message: impl Into<String>) -> JobFailure {
+  failure("BROKER_IMPORT_INVALID", message)
+}
+
+fn default_kinds(extra: &MappingKinds) -> [(TxKind, Vec<String>); 4] {
+  let words = |builtin: &[&str], extra: &[String]| -> Vec<String> {
+    builtin.iter().map(|word| word.to_string()).chain(extra.iter().map(|word| normalize_header(word))).collect()
+  };
+  [
+    (TxKind::Buy, words(BUY_WORDS, &extra.buy)),
+    (TxKind::Sell, words(SELL_WORDS, &extra.sell)),
+    (TxKind::Split, words(SPLIT_WORDS, &extra.split)),
+    (TxKind::Fee, words(FEE_WORDS, &extra.fee)),
+  ]
+}
+
+fn profile_layout(profile: &BrokerProfile, header: &[String]) -> Option<Layout> {
+  let column = |name: &str| header.iter().position(|cell| cell == name);
+  if !profile.fingerprint.iter().all(|name| column(name).is_some()) {
+    return None;
+  }
+  Some(Layout {
+    label: profile.id.to_string(),
+    date: column(profile.date)?,
+    time: profile.time.and_then(column),
+    kind: column(profile.kind)?,
+    isin: column(profile.isin)?,
+    name: profile.name.and_then(column),
+    currency: profile.currency.and_then(column),
+    shares: profile.shares.and_then(column),
+    price: profile.price.and_then(column),
+    amount: profile.amount.and_then(column),
+    fee: profile.fee.and_then(column),
+    ratio: None,
+    status: profile
+      .status
+      .and_then(|(name, values)| column(name).map(|index| (index, values.iter().map(|value| value.to_string()).collect()))),
+    amount_includes_fees: profile.amount_includes_fees,
+    kinds: default_kinds(&MappingKinds::default()),
+  })
+}
+
+fn mapping_layout(mapping: &GenericMapping, header: &[String]) -> Option<Layout> {
+  let column = |name: &str| header.iter().position(|cell| *cell == normalize_header(name));
+  let optional = |name: &Option<String>| -> Option<Option<usize>> {
+    match name {
+      Some(name) => column(name).map(Some),
+      None => Some(None),
+    }
+  };
+  let columns = &mapping.columns;
+  Some(Layout {
+    label: "mapping".to_string(),
+    date: column(&columns.date)?,
+    time: optional(&columns.time)?,
+    kind: column(&columns.kind)?,
+    isin: column(&columns.isin)?,
+    name: optional(&columns.name)?,
+    currency: optional(&columns.currency)?,
+    shares: optional(&columns.shares)?,
+    price: optional(&columns.price)?,
+    amount: optional(&columns.amount)?,
+    fee: optional(&columns.fee)?,
+    ratio: optional(&columns.ratio)?,
+    status: None,
+    amount_includes_fees: mapping.amount_includes_fees,
+    kinds: default_kinds(&mapping.kinds),
+  })
+}
+
+/// Header row and layout, from the mapping, the chosen profile or detection.
+fn find_layout(file: &BrokerFile, lines: &[&str]) -> Result<(usize, char, Layout), JobFailure> {
+  let forced = match &file.profile_id {
+    Some(id) => Some(
+      PROFILES
+        .iter()
+        .find(|profile| profile.id == id)
+        .ok_or_else(|| failure("BROKER_IMPORT_FORMAT_UNKNOWN", format!("Unbekanntes Broker-Profil {}.", id)))?,
+    ),
+    None => None,
+  };
+  let found = lines.iter().take(HEADER_SEARCH_LINES).enumerate().find_map(|(index, line)| {
+    let delimiter = detect_delimiter(line);
+    let header: Vec<String> = split_line(line, delimiter).ok()?.iter().map(|cell| normalize_header(cell)).collect();
+    let layout = match (&file.mapping, forced) {
+      (Some(mapping), _) => mapping_layout(mapping, &header),
+      (None, Some(profile)) => profile_layout(profile, &header),
+      (None, None) => PROFILES.iter().find_map(|profile| profile_layout(profile, &header)),
+    }?;
+    Some((index, delimiter, layout))
+  });
+  found.ok_or_else(|| {
+    let known: Vec<&str> = PROFILES.iter().map(|profile| profile.label).collect();
+    failure(
+      "BROKER_IMPORT_FORMAT_UNKNOWN",
+      format!("{}: Kopfzeile passt weder zu einem Broker-Profil ({}) noch zum Mapping.", file.name, known.join(", ")),
+    )
+  })
+}
+
+/// Decimal with comma or point; with both, the last one is the separator.
+pub(crate) fn parse_decimal(raw: &str) -> Option<f64> {
+  let mut value: String = raw.chars().filter(|c| !c.is_whitespace() && *c != '€').collect();
+  if let Some(stripped) = value.strip_suffix("EUR") {
+    value = stripped.to_string();
+  }
+  if value.is_empty() {
+    return None;
+  }
+  let normalized = match (value.rfind(','), value.rfind('.')) {
+    (Some(comma), Some(point)) if comma > point => value.replace('.', "").replace(',', "."),
+    (Some(_), Some(_)) => value.replace(',', ""),
+    (Some(_), None) => value.replace(',', "."),
+    _ => value,
+  };
+  normalized.parse::<f64>().ok().filter(|number| number.is_finite())
+}
+
+/// "4:1" -> 4, "1:4" -> 0.25, plain numbers as they are.
+fn parse_ratio(raw: &str) -> Option<f64> {
+  let ratio = match raw.split_once(':').or_else(|| raw.split_once('/')) {
+    Some((new, old)) => parse_decimal(new)? / parse_decimal(old)?,
+    None => parse_decimal(raw)?,
+  };
+  (ratio.is_finite() && ratio > 0.0).then_some(ratio)
+}
+
+/// Date and, if present, time of `2024-01-15T10:00`, `15.01.2024 10:00` and
+/// plain dates.
+fn parse_row_date(raw: &str) -> Option<(String, Option<String>)> {
+  let (date, time) = raw.trim().split_once(|c: char| c == 'T' || c.is_whitespace()).unwrap_or((raw.trim(), ""));
+  let time = match time.trim() {
+    "" => None,
+    time => Some(parse_time(time)?),
+  };
+  Some((parse_date(date)?, time))
+}
+
+/// `9:05`, `09:05:30` or `09:05:30.000Z` as `09:05:30`.
+fn parse_time(raw: &str) -> Option<String> {
+  let clock = raw.trim().split(|c: char| c != ':' && !c.is_ascii_digit()).next()?;
+  let mut parts = clock.split(':').map(|part| part.parse::<u32>().ok().filter(|_| (1..=2).contains(&part.len())));
+  let (hour, minute, second) = (parts.next()??, parts.next()??, parts.next().unwrap_or(Some(0))?);
+  (parts.next().is_none() && hour < 24 && minute < 60 && second < 60).then(|| format!("{:02}:{:02}:{:02}", hour, minute, second))
+}
+
+fn valid_isin(isin: &str) -> bool {
+  // ASCII first, so the byte slices below stay on char boundaries.
+  isin.len() == 12
+    && isin.is_ascii()
+    && isin[..2].chars().all(|c| c.is_ascii_uppercase())
+    && isin.chars().all(|c| c.is_ascii_alphanumeric())
+    && isin[11..].chars().all(|c| c.is_ascii_digit())
+}
+
+fn parse_file(
+  file: &BrokerFile,
+  transactions: &mut Vec<BrokerTransaction>,
+  errors: &mut Vec<BrokerLineError>,
+) -> Result<BrokerFileSummary, JobFailure> {
+  let content = match &file.bytes {
+    Some(bytes) => decode(bytes).0,
+    None => file.content.clone(),
+  };
+  let lines: Vec<&str> = content.lines().collect();
+  let (header_index, delimiter, layout) = find_layout(file, &lines)?;
+  let mut summary = BrokerFileSummary {
+    file: file.name.clone(),
+    profile: layout.label.clone(),
+    header_line: header_index + 1,
+    transactions: 0,
+    ignored_rows: 0,
+    duplicates: 0,
+  };
+  for (index, line) in lines.iter().enumerate().skip(header_index + 1) {
+    if line.trim().is_empty() {
+      continue;
+    }
+    let line_number = index + 1;
+    let mut reject = |code: &'static str, reason: String| {
+      errors.push(BrokerLineError { file: file.name.clone(), line_number, code, reason });
+    };
+    let cells = match split_line(line, delimiter) {
+      Ok(cells) => cells,
+      Err(reason) => {
+        reject("broker_csv_unclosed_quote", reason);
+        continue;
+      }
+    };
+    let cell = |index: usize| cells.get(index).map(|cell| cell.trim()).unwrap_or_default();
+    let optional_cell = |index: Option<usize>| index.map(cell).filter(|value| !value.is_empty());
+    if let Some((index, executed)) = &layout.status {
+      if !executed.contains(&normalize_header(cell(*index))) {
+        summary.ignored_rows += 1;
+        continue;
+      }
+    }
+    let kind_word = normalize_header(cell(layout.kind));
+    let Some(kind) = layout.kinds.iter().find(|(_, words)| words.contains(&kind_word)).map(|(kind, _)| *kind) else {
+      // Dividends, deposits, interest and the like do not touch tranches.
+      summary.ignored_rows += 1;
+      continue;
+    };
+    let Some((date, date_time)) = parse_row_date(cell(layout.date)) else {
+      reject("broker_csv_date_invalid", format!("Datum \"{}\" ist ungueltig", cell(layout.date)));
+      continue;
+    };
+    let time = match optional_cell(layout.time) {
+      Some(raw) => match parse_time(raw) {
+        Some(time) => Some(time),
+        None => {
+          reject("broker_csv_time_invalid", format!("Uhrzeit \"{}\" ist ungueltig", raw));
+          continue;
+        }
+      },
+      None => date_time,
+    };
+    if let Some(currency) = optional_cell(layout.currency).filter(|currency| !currency.eq_ignore_ascii_case("EUR")) {
+      reject("broker_csv_currency_unsupported", format!("Waehrung \"{}\" wird nicht unterstuetzt, nur EUR", currency));
+      continue;
+    }
+    let isin: String = cell(layout.isin).chars().filter(|c| !c.is_whitespace()).collect::<String>().to_uppercase();
+    if isin.is_empty() && kind == TxKind::Fee {
+      // Account fees without an instrument are not part of any cost basis.
+      summary.ignored_rows += 1;
+      continue;
+    }
+    if !valid_isin(&isin) {
+      reject("broker_csv_isin_invalid", format!("ISIN \"{}\" ist ungueltig", cell(layout.isin)));
+      continue;
+    }
+    let number = |index: Option<usize>| optional_cell(index).map(|raw| parse_decimal(raw).ok_or(raw));
+    let (shares, price, amount, fee) = match (number(layout.shares), number(layout.price), number(layout.amount), number(layout.fee)) {
+      (Some(Err(raw)), ..) | (_, Some(Err(raw)), ..) | (_, _, Some(Err(raw)), _) | (.., Some(Err(raw))) => {
+        reject("broker_csv_number_invalid", format!("Zahl \"{}\" ist ungueltig", raw));
+        continue;
+      }
+      (shares, price, amount, fee) => (
+        shares.and_then(Result::ok).map(f64::abs),
+        price.and_then(Result::ok).map(f64::abs),
+        amount.and_then(Result::ok).map(f64::abs),
+        fee.and_then(Result::ok).map(f64::abs).unwrap_or(0.0),
+      ),
+    };
+    let ratio = match optional_cell(layout.ratio) {
+      Some(raw) => match parse_ratio(raw) {
+        Some(ratio) => Some(ratio),
+        None => {
+          reject("broker_csv_ratio_invalid", format!("Split-Verhaeltnis \"{}\" ist ungueltig", raw));
+          continue;
+        }
+      },
+      None => None,
+    };
+    let shares = shares.unwrap_or(0.0);
+    let gross = match kind {
+      TxKind::Buy | TxKind::Sell => {
+        if shares <= SHARE_EPSILON {
+          reject("broker_csv_shares_invalid", "Stueckzahl fehlt oder ist 0".to_string());
+          continue;
+        }
+        match (amount, price) {
+          (Some(amount), _) if layout.amount_includes_fees && kind == TxKind::Buy => amount - fee,
+          (Some(amount), _) if layout.amount_includes_fees => amount + fee,
+          (Some(amount), _) => amount,
+          (None, Some(price)) => shares * price,
+          (None, None) => {
+            reject("broker_csv_amount_missing", "Betrag oder Kurs fehlt".to_string());
+            continue;
+          }
+        }
+      }
+      TxKind::Split => {
+        if ratio.is_none() && shares <= SHARE_EPSILON {
+          reject("broker_csv_ratio_invalid", "Split ohne Verhaeltnis oder zusaetzliche Stueck".to_string());
+          continue;
+        }
+        0.0
+      }
+      TxKind::Fee => amount.unwrap_or(fee),
+    };
+    let fee = if kind == TxKind::Fee { 0.0 } else { fee };
+    transactions.push(BrokerTransaction {
+      file: file.name.clone(),
+      line_number,
+      date,
+      time,
+      kind,
+      isin,
+      name: optional_cell(layout.name).unwrap_or_default().to_string(),
+      shares,
+      gross,
+      fee,
+      ratio,
+      duplicate: false,
+    });
+    summary.transactions += 1;
+  }
+  Ok(summary)
+}
+
+struct OpenLot {
+  tranche_id: String,
+  name: String,
+  date: String,
+  shares: f64,
+  cost: f64,
+}
+
+/// Replay order within a day when the export has no time: splits take effect
+/// before trading, fees follow their buy and sells come last.
+fn kind_rank(kind: TxKind) -> u8 {
+  match kind {
+    TxKind::Split => 0,
+    TxKind::Buy => 1,
+    TxKind::Fee => 2,
+    TxKind::Sell => 3,
+  }
+}
+
+fn fingerprint(tx: &BrokerTransaction) -> String {
+  format!(
+    "{}|{}|{:?}|{}|{}|{}|{}|{:?}",
+    tx.date,
+    tx.time.as_deref().unwrap_or_default(),
+    tx.kind,
+    tx.isin,
+    tx.shares,
+    tx.gross,
+    tx.fee,
+    tx.ratio
+  )
+}
+
+/// Marks transactions an earlier file already contained. Like the bank
+/// statement import, identical lines within one file are real repeats
+/// (two savings plan executions), the same ones in another file are not.
+fn mark_duplicates(transactions: &mut [BrokerTransaction], taken: &mut HashMap<String, usize>) -> usize {
+  let mut local: HashMap<String, usize> = HashMap::new();
+  let mut duplicates = 0;
+  for tx in transactions {
+    let key = fingerprint(tx);
+    let occurrence = local.entry(key.clone()).or_default();
+    *occurrence += 1;
+    let already = taken.entry(key).or_default();
+    if *occurrence <= *already {
+      tx.duplicate = true;
+      duplicates += 1;
+    } else {
+      *already = *occurrence;
+    }
+  }
+  duplicates
+}
+
+/// Replays the transactions in date, time and `kind_rank` order into FIFO lots
+/// per ISIN. Newest-first exports thus replay like oldest-first ones.
+fn replay(
+  transactions: &[BrokerTransaction],
+  errors: &mut Vec<BrokerLineError>,
+) -> (BTreeMap<String, Vec<OpenLot>>, Vec<RealizedSale>) {
+  let mut order: Vec<&BrokerTransaction> = transactions.iter().filter(|tx| !tx.duplicate).collect();
+  order.sort_by(|a, b| (&a.date, &a.time, kind_rank(a.kind)).cmp(&(&b.date, &b.time, kind_rank(b.kind))));
+  let mut lots: BTreeMap<String, Vec<OpenLot>> = BTreeMap::new();
+  let mut realized = Vec::new();
+  let mut ids: BTreeMap<String, usize> = BTreeMap::new();
+  for tx in order {
+    let reject = |errors: &mut Vec<BrokerLineError>, code: &'static str, reason: String| {
+      errors.push(BrokerLineError { file: tx.file.clone(), line_number: tx.line_number, code, reason });
+    };
+    let open = lots.entry(tx.isin.clone()).or_default();
+    let held: f64 = open.iter().map(|lot| lot.shares).sum();
+    match tx.kind {
+      TxKind::Buy => {
+        let base = format!("import-{}-{}", tx.isin, tx.date);
+        let counter = ids.entry(base.clone()).or_default();
+        *counter += 1;
+        let tranche_id = if *counter == 1 { base } else { format!("{}-{}", base, counter) };
+        open.push(OpenLot { tranche_id, name: tx.name.clone(), date: tx.date.clone(), shares: tx.shares, cost: tx.gross + tx.fee });
+      }
+      TxKind::Fee => match open.iter_mut().rev().find(|lot| lot.date == tx.date) {
+        Some(lot) => lot.cost += tx.gross,
+        None => reject(errors, "broker_fee_unassigned", format!("Gebuehr ohne Kauf von {} am {}", tx.isin, tx.date)),
+      },
+      TxKind::Sell => {
+        if tx.shares > held + SHARE_EPSILON {
+          reject(errors, "broker_sell_exceeds_holdings", format!("Verkauf von {} Stueck {}, Bestand nur {}", tx.shares, tx.isin, held));
+          continue;
+        }
+        let mut remaining = tx.shares;
+        let mut cost = 0.0;
+        for lot in open.iter_mut() {
+          if remaining <= SHARE_EPSILON {
+            break;
+          }
+          let taken = remaining.min(lot.shares);
+          let portion = lot.cost * taken / lot.shares;
+          cost += portion;
+          lot.cost -= portion;
+          lot.shares -= taken;
+          remaining -= taken;
+        }
+        open.retain(|lot| lot.shares > SHARE_EPSILON);
+        let net = tx.gross - tx.fee;
+        realized.push(RealizedSale {
+          isin: tx.isin.clone(),
+          date: tx.date.clone(),
+          shares: tx.shares,
+          net_proceeds: net,
+          cost_basis: cost,
+          realized_gain: net - cost,
+        });
+      }
+      TxKind::Split => {
+        if held <= SHARE_EPSILON {
+          reject(errors, "broker_split_without_holdings", format!("Split von {} ohne Bestand", tx.isin));
+          continue;
+        }
+        let ratio = tx.ratio.unwrap_or((held + tx.shares) / held);
+        for lot in open.iter_mut() {
+          lot.shares *= ratio;
+        }
+      }
+    }
+  }
+  (lots, realized)
+}
+
+fn text_field<'a>(tranche: &'a Value, field: &str) -> Option<&'a str> {
+  tranche.get(field).and_then(Value::as_str).map(str::trim).filter(|text| !text.is_empty())
+}
+
+fn tranche_isin(tranche: &Value) -> Option<String> {
+  text_field(tranche, "isin").map(|isin| isin.replace(char::is_whitespace, "").to_uppercase())
+}
+
+fn tranche_id(tranche: &Value) -> Option<String> {
+  text_field(tranche, "trancheId").or_else(|| text_field(tranche, "id")).map(str::to_string)
+}
+
+/// `depotId`, or `sourceProfileId` for tranches merged from a profile group.
+fn tranche_depot(tranche: &Value) -> Option<&str> {
+  text_field(tranche, "depotId").or_else(|| text_field(tranche, "sourceProfileId"))
+}
+
+/// Untagged tranches belong to every import; tagged ones only to their depot.
+fn in_depot(tranche: &Value, depot: Option<&str>) -> bool {
+  match (tranche_depot(tranche), depot) {
+    (Some(own), Some(depot)) => own == depot,
+    (Some(_), None) => false,
+    (None, _) => true,
+  }
+}
+
+fn changes(current: &Value, lot: &ImportedLot) -> Vec<FieldChange> {
+  let mut out = Vec::new();
+  let number = |field: &str| current.get(field).and_then(Value::as_f64);
+  if !number("shares").is_some_and(|shares| (shares - lot.shares).abs() <= SHARE_EPSILON) {
+    out.push(FieldChange { field: "shares", before: current.get("shares").cloned().unwrap_or(Value::Null), after: json!(lot.shares) });
+  }
+  if !number("purchasePrice").is_some_and(|price| (price - lot.purchase_price).abs() <= PRICE_EPSILON) {
+    out.push(FieldChange {
+      field: "purchasePrice",
+      before: current.get("purchasePrice").cloned().unwrap_or(Value::Null),
+      after: json!(lot.purchase_price),
+    });
+  }
+  if text_field(current, "purchaseDate") != Some(lot.purchase_date.as_str()) {
+    out.push(FieldChange {
+      field: "purchaseDate",
+      before: current.get("purchaseDate").cloned().unwrap_or(Value::Null),
+      after: json!(lot.purchase_date),
+    });
+  }
+  out
+}
+
+fn apply(current: &Value, changes: &[FieldChange]) -> Value {
+  let mut proposed = current.clone();
+  if let Some(object) = proposed.as_object_mut() {
+    for change in changes {
+      object.insert(change.field.to_string(), change.after.clone());
+    }
+  }
+  proposed
+}
+
+/// New tranche record; classification and current price come from a sibling
+/// tranche of the same ISIN when the profile has one.
+fn new_tranche(lot: &ImportedLot, sibling: Option<&Value>, defaults: &TrancheDefaults) -> Value {
+  let mut record = Map::new();
+  record.insert("schemaVersion".into(), json!(1));
+  record.insert("trancheId".into(), json!(lot.tranche_id));
+  let name = sibling.and_then(|tranche| text_field(tranche, "name")).unwrap_or(&lot.name);
+  record.insert("name".into(), json!(if name.is_empty() { lot.isin.as_str() } else { name }));
+  record.insert("isin".into(), json!(lot.isin));
+  record.insert("shares".into(), json!(lot.shares));
+  record.insert("purchasePrice".into(), json!(lot.purchase_price));
+  let current_price = sibling.and_then(|tranche| tranche.get("currentPrice")).and_then(Value::as_f64).unwrap_or(lot.purchase_price);
+  record.insert("currentPrice".into(), json!(current_price));
+  record.insert("purchaseDate".into(), json!(lot.purchase_date));
+  for (field, fallback) in [("category", json!(defaults.category)), ("type", json!(defaults.kind)), ("tqf", json!(defaults.tqf))] {
+    let value = sibling.and_then(|tranche| tranche.get(field)).cloned().unwrap_or(fallback);
+    record.insert(field.into(), value);
+  }
+  if let Some(ticker) = sibling.and_then(|tranche| tranche.get("ticker")) {
+    record.insert("ticker".into(), ticker.clone());
+  }
+  Value::Object(record)
+}
+
+/// `covered` lists every ISIN with transactions in the import, including
+/// instruments that were sold out completely. Only tranches of `depot` are
+/// matched or removed.
+fn diff(
+  current: &[Value],
+  imported: &[ImportedLot],
+  covered: &[&str],
+  depot: Option<&str>,
+  defaults: &TrancheDefaults,
+) -> (Vec<DiffEntry>, Vec<Value>) {
+  let mut matched_lot = vec![false; imported.len()];
+  let mut pairs: Vec<Option<usize>> = vec![None; current.len()];
+  let scoped: Vec<bool> = current.iter().map(|tranche| in_depot(tranche, depot)).collect();
+  // Same ISIN and purchase date first, then undated tranches in order.
+  for pass in 0..2 {
+    for (index, tranche) in current.iter().enumerate() {
+      if pairs[index].is_some() || !scoped[index] {
+        continue;
+      }
+      let Some(isin) = tranche_isin(tranche) else { continue };
+      let date = text_field(tranche, "purchaseDate");
+      let found = imported.iter().enumerate().position(|(lot_index, lot)| {
+        !matched_lot[lot_index] && lot.isin == isin && if pass == 0 { date == Some(lot.purchase_date.as_str()) } else { date.is_none() }
+      });
+      if let Some(lot_index) = found {
+        matched_lot[lot_index] = true;
+        pairs[index] = Some(lot_index);
+      }
+    }
+  }
+
+  let mut entries = Vec::new();
+  let mut proposed = Vec::new();
+  for ((tranche, pair), scoped) in current.iter().zip(&pairs).zip(&scoped) {
+    let isin = tranche_isin(tranche);
+    let base = DiffEntry {
+      action: DiffAction::Keep,
+      isin: isin.clone(),
+      tranche_id: tranche_id(tranche),
+      current: Some(tranche.clone()),
+      proposed: Some(tranche.clone()),
+      changes: Vec::new(),
+    };
+    let entry = match pair {
+      Some(lot_index) => {
+        let changes = changes(tranche, &imported[*lot_index]);
+        let next = apply(tranche, &changes);
+        let action = if changes.is_empty() { DiffAction::Unchanged } else { DiffAction::Update };
+        DiffEntry { action, proposed: Some(next), changes, ..base }
+      }
+      // Covered by the import but without an open lot: sold or duplicated.
+      None if *scoped && isin.as_deref().is_some_and(|isin| covered.contains(&isin)) => {
+        DiffEntry { action: DiffAction::Remove, proposed: None, ..base }
+      }
+      None => base,
+    };
+    if let Some(next) = &entry.proposed {
+      proposed.push(next.clone());
+    }
+    entries.push(entry);
+  }
+  for (lot_index, lot) in imported.iter().enumerate() {
+    if matched_lot[lot_index] {
+      continue;
+    }
+    let sibling = current.iter().find(|tranche| tranche_isin(tranche).as_deref() == Some(lot.isin.as_str()));
+    let mut record = new_tranche(lot, sibling, defaults);
+    if let (Some(depot), Some(object)) = (depot, record.as_object_mut()) {
+      object.insert("depotId".into(), json!(depot));
+    }
+    proposed.push(record.clone());
+    entries.push(DiffEntry {
+      action: DiffAction::Add,
+      isin: Some(lot.isin.clone()),
+      tranche_id: Some(lot.tranche_id.clone()),
+      current: None,
+      proposed: Some(record),
+      changes: Vec::new(),
+    });
+  }
+  (entries, proposed)
+}
+
+pub(crate) fn preview_import(request: &BrokerImportRequest) -> Result<BrokerImportPreview, JobFailure> {
+  if request.files.is_empty() {
+    return Err(invalid("Mindestens eine Exportdatei ist erforderlich."));
+  }
+  if let Some(index) = request.current_tranches.iter().position(|tranche| !tranche.is_object()) {
+    return Err(invalid(format!("currentTranches[{}] ist kein Objekt.", index)));
+  }
+  if !(0.0..=1.0).contains(&request.defaults.tqf) {
+    return Err(invalid("defaults.tqf muss zwischen 0 und 1 liegen."));
+  }
+  let depot = request.depot_id.as_deref().map(str::trim).filter(|id| !id.is_empty());
+  if depot.is_none() && request.current_tranches.iter().any(|tranche| tranche_depot(tranche).is_some()) {
+    return Err(invalid("depotId ist erforderlich, wenn die Tranchen Depots zugeordnet sind."));
+  }
+  let mut transactions = Vec::new();
+  let mut errors = Vec::new();
+  let mut files = Vec::new();
+  let mut taken: HashMap<String, usize> = HashMap::new();
+  for file in &request.files {
+    let start = transactions.len();
+    let mut summary = parse_file(file, &mut transactions, &mut errors)?;
+    summary.duplicates = mark_duplicates(&mut transactions[start..], &mut taken);
+    files.push(summary);
+  }
+  let duplicate_count = files.iter().map(|file| file.duplicates).sum();
+  let (lots, realized) = replay(&transactions, &mut errors);
+  let imported_lots: Vec<ImportedLot> = lots
+    .into_iter()
+    .flat_map(|(isin, open)| {
+      open.into_iter().map(move |lot| ImportedLot {
+        tranche_id: lot.tranche_id,
+        name: lot.name,
+        isin: isin.clone(),
+        shares: lot.shares,
+        purchase_price: lot.cost / lot.shares,
+        purchase_date: lot.date,
+        cost_basis: lot.cost,
+      })
+    })
+    .collect();
+  let mut covered: Vec<&str> = transactions.iter().map(|tx| tx.isin.as_str()).collect();
+  covered.sort_unstable();
+  covered.dedup();
+  let (diff, proposed_tranches) = diff(&request.current_tranches, &imported_lots, &covered, depot, &request.defaults);
+  let mut summary = DiffSummary::default();
+  for entry in &diff {
+    *match entry.action {
+      DiffAction::Add => &mut summary.add,
+      DiffAction::Update => &mut summary.update,
+      DiffAction::Remove => &mut summary.remove,
+      DiffAction::Unchanged => &mut summary.unchanged,
+      DiffAction::Keep => &mut summary.keep,
+    } += 1;
+  }
+  Ok(BrokerImportPreview { files, transactions, duplicate_count, errors, imported_lots, realized, diff, summary, proposed_tranches })
+}
+
+#[tauri::command]
+pub fn list_broker_import_profiles() -> Vec<BrokerProfileInfo> {
+  PROFILES.iter().map(|profile| BrokerProfileInfo { id: profile.id.to_string(), label: profile.label.to_string() }).collect()
+}
+
+#[tauri::command]
+pub fn preview_broker_import(request: BrokerImportRequest) -> Result<BrokerImportPreview, String> {
+  preview_import(&request).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn file(name: &str, content: &str) -> BrokerFile {
+    BrokerFile { name: name.into(), content: content.into(), ..BrokerFile::default() }
+  }
+
+  #[test]
+  fn scalable_export_replays_fifo_and_diffs_against_profile() {
+    let csv = "date;time;status;reference;description;assetType;type;isin;shares;price;amount;fee;tax;currency\n\
+      2023-01-10;09:00:00;Executed;R1;iShares Core MSCI World;Security;Buy;IE00B4L5Y983;10;70,00;-700,00;1,00;0;EUR\n\
+      2023-06-01;09:00:00;Executed;R2;iShares Core MSCI World;Security;Savings plan;IE00B4L5Y983;5;80,00;-400,00;0;0;EUR\n\
+      2023-07-01;09:00:00;Cancelled;R3;iShares Core MSCI World;Security;Buy;IE00B4L5Y983;99;80,00;-7920,00;0;0;EUR\n\
+      2024-02-01;09:00:00;Executed;R4;iShares Core MSCI World;Security;Sell;IE00B4L5Y983;12;90,00;1080,00;1,00;0;EUR\n\
+      2024-03-01;09:00:00;Executed;R5;iShares Core MSCI World;Security;Distribution;IE00B4L5Y983;;;12,00;0;0;EUR\n";
+    let current = vec![
+      json!({"schemaVersion": 1, "trancheId": "t1", "name": "World", "isin": "IE00B4L5Y983", "shares": 10, "purchasePrice": 70.1, "purchaseDate": "2023-01-10", "category": "equity", "type": "aktien_neu", "tqf": 0.3}),
+      json!({"schemaVersion": 1, "trancheId": "t2", "name": "World", "isin": "IE00B4L5Y983", "shares": 5, "purchasePrice": 80, "purchaseDate": "2023-06-01", "category": "equity", "type": "aktien_neu", "tqf": 0.3}),
+      json!({"schemaVersion": 1, "trancheId": "gold", "name": "Gold", "shares": 1, "purchasePrice": 1800, "category": "gold", "type": "gold", "tqf": 0}),
+    ];
+    let request = BrokerImportRequest { files: vec![file("scalable.csv", csv)], current_tranches: current, ..BrokerImportRequest::default() };
+    let preview = preview_import(&request).unwrap();
+    assert_eq!(preview.files[0].profile, "scalable");
+    assert_eq!((preview.files[0].transactions, preview.files[0].ignored_rows), (3, 2));
+    assert!(preview.errors.is_empty());
+
+    assert_eq!(preview.imported_lots.len(), 1);
+    let lot = &preview.imported_lots[0];
+    assert_eq!((lot.purchase_date.as_str(), lot.shares, lot.cost_basis), ("2023-06-01", 3.0, 240.0));
+    let sale = &preview.realized[0];
+    assert!((sale.cost_basis - 861.0).abs() < 1e-9 && (sale.realized_gain - 218.0).abs() < 1e-9);
+
+    let actions: Vec<DiffAction> = preview.diff.iter().map(|entry| entry.action).collect();
+    assert_eq!(actions, vec![DiffAction::Remove, DiffAction::Update, DiffAction::Keep]);
+    assert_eq!(preview.diff[1].changes.len(), 1);
+    assert_eq!(preview.diff[1].changes[0].field, "shares");
+    assert_eq!(preview.summary, DiffSummary { add: 0, update: 1, remove: 1, unchanged: 0, keep: 1 });
+    let ids: Vec<&str> = preview.proposed_tranches.iter().map(|t| t["trancheId"].as_str().unwrap()).collect();
+    assert_eq!(ids, vec!["t2", "gold"]);
+    assert_eq!(preview.proposed_tranches[0]["shares"], json!(3.0));
+  }
+
+  #[test]
+  fn diff_touches_only_tranches_of_the_imported_depot() {
+    let csv = "date;time;status;reference;description;assetType;type;isin;shares;price;amount;fee;tax;currency\n\
+      2023-01-10;09:00:00;Executed;R1;World;Security;Buy;IE00B4L5Y983;10;70,00;-700,00;0;0;EUR\n\
+      2023-03-01;09:00:00;Executed;R2;World;Security;Buy;IE00B4L5Y983;4;75,00;-300,00;0;0;EUR\n\
+      2023-06-01;09:00:00;Executed;R3;World;Security;Sell;IE00B4L5Y983;4;80,00;320,00;0;0;EUR\n";
+    let current = vec![
+      json!({"trancheId": "scalable", "depotId": "scalable", "isin": "IE00B4L5Y983", "shares": 8, "purchasePrice": 70, "purchaseDate": "2023-01-10"}),
+      json!({"trancheId": "tr-1", "depotId": "tr", "isin": "IE00B4L5Y983", "shares": 10, "purchasePrice": 70, "purchaseDate": "2023-01-10"}),
+      json!({"trancheId": "tr-2", "sourceProfileId": "tr", "isin": "IE00B4L5Y983", "shares": 3, "purchasePrice": 60, "purchaseDate": "2022-05-01"}),
+    ];
+    let mut request = BrokerImportRequest { files: vec![file("scalable.csv", csv)], current_tranches: current, ..BrokerImportRequest::default() };
+    assert_eq!(preview_import(&request).unwrap_err().code, "BROKER_IMPORT_INVALID");
+
+    request.depot_id = Some("scalable".into());
+    let preview = preview_import(&request).unwrap();
+    let actions: Vec<(Option<&str>, DiffAction)> = preview.diff.iter().map(|entry| (entry.tranche_id.as_deref(), entry.action)).collect();
+    assert_eq!(
+      actions,
+      vec![
+        (Some("scalable"), DiffAction::Update),
+        (Some("tr-1"), DiffAction::Keep),
+        (Some("tr-2"), DiffAction::Keep),
+        (Some("import-IE00B4L5Y983-2023-03-01"), DiffAction::Add),
+      ]
+    );
+    assert_eq!(preview.diff[0].changes[0].field, "shares");
+    let added = &preview.proposed_tranches[3];
+    assert_eq!((added["depotId"].as_str(), added["purchaseDate"].as_str()), (Some("scalable"), Some("2023-03-01")));
+  }
+
+  #[test]
+  fn non_ascii_isin_cells_fail_validation() {
+    assert!(valid_isin("IE00B4L5Y983"));
+    assert!(!valid_isin("A\u{c4}123456789") && !valid_isin("\u{c4}E00B4L5Y98"));
+    let csv = "date;time;status;reference;description;assetType;type;isin;shares;price;amount;fee;tax;currency\n\
+      2023-01-10;09:00:00;Executed;R1;World;Security;Buy;A\u{c4}123456789;10;70,00;-700,00;0;0;EUR\n";
+    let request = BrokerImportRequest { files: vec![file("scalable.csv", csv)], ..BrokerImportRequest::default() };
+    let preview = preview_import(&request).unwrap();
+    let codes: Vec<(usize, &str)> = preview.errors.iter().map(|error| (error.line_number, error.code)).collect();
+    assert_eq!(codes, vec![(2, "broker_csv_isin_invalid")]);
+  }
+
+  #[test]
+  fn newest_first_overlapping_exports_replay_once_in_eur() {
+    let header = "date;time;status;reference;description;assetType;type;isin;shares;price;amount;fee;tax;currency\n";
+    let newest_first = format!(
+      "{}{}{}{}{}",
+      header,
+      "2024-02-01;15:00:00;Executed;R4;World;Security;Sell;IE00B4L5Y983;4;90,00;360,00;1,00;0;EUR\n",
+      "2024-02-01;09:30:00;Executed;R3;World;Security;Fee;IE00B4L5Y983;;;-2,00;0;0;EUR\n",
+      "2024-02-01;09:30:00;Executed;R2;World;Security;Buy;IE00B4L5Y983;10;80,00;-800,00;0;0;EUR\n",
+      "2024-01-15;10:00:00;Executed;R1;Apple;Security;Buy;US0378331005;1;150,00;-150,00;0;0;USD\n"
+    );
+    let overlap = format!(
+      "{}{}{}",
+      header,
+      "2024-02-01;09:30:00;Executed;R2;World;Security;Buy;IE00B4L5Y983;10;80,00;-800,00;0;0;EUR\n",
+      "2024-03-01;9:00;Executed;R5;World;Security;Buy;IE00B4L5Y983;1;95,00;-95,00;0;0;EUR\n"
+    );
+    let request = BrokerImportRequest {
+      files: vec![file("neu.csv", &newest_first), file("alt.csv", &overlap)],
+      ..BrokerImportRequest::default()
+    };
+    let preview = preview_import(&request).unwrap();
+    let codes: Vec<(&str, usize, &str)> = preview.errors.iter().map(|e| (e.file.as_str(), e.line_number, e.code)).collect();
+    assert_eq!(codes, vec![("neu.csv", 5, "broker_csv_currency_unsupported")]);
+    assert_eq!((preview.duplicate_count, preview.files[1].duplicates), (1, 1));
+    assert!(preview.transactions[3].duplicate);
+    assert_eq!(preview.transactions[4].time.as_deref(), Some("09:00:00"));
+
+    let lots: Vec<(f64, f64)> = preview.imported_lots.iter().map(|lot| (lot.shares, lot.cost_basis)).collect();
+    assert_eq!(lots.len(), 2);
+    assert!((lots[0].0 - 6.0).abs() < 1e-9 && (lots[0].1 - 481.2).abs() < 1e-9, "{:?}", lots);
+    assert_eq!(lots[1], (1.0, 95.0));
+    assert!((preview.realized[0].realized_gain - (359.0 - 320.8)).abs() < 1e-9);
+  }
+
+  #[test]
+  fn generic_mapping_handles_splits_fees_and_line_errors() {
+    let csv = "Depotumsaetze\n\nTag;Art;Wertpapier;Name;Anzahl;Kurs;Gebühr;Verhältnis\n\
+      02.01.2020;Kauf;DE0007164600;SAP;10;100,00;0;\n\
+      02.01.2020;Provision;DE0007164600;SAP;;;4,90;\n\
+      01.01.2021;Split;DE0007164600;SAP;;;;2:1\n\
+      01.02.2021;Verkauf;DE0007164600;SAP;50;60,00;0;\n\
+      3x.02.2021;Kauf;DE0007164600;SAP;1;60,00;0;\n";
+    let mapping = GenericMapping {
+      columns: MappingColumns {
+        date: "Tag".into(),
+        kind: "Art".into(),
+        isin: "Wertpapier".into(),
+        name: Some("Name".into()),
+        shares: Some("Anzahl".into()),
+        price: Some("Kurs".into()),
+        fee: Some("Gebühr".into()),
+        ratio: Some("Verhältnis".into()),
+        ..MappingColumns::default()
+      },
+      kinds: MappingKinds { fee: vec!["Provision".into()], ..MappingKinds::default() },
+      amount_includes_fees: false,
+    };
+    let request = BrokerImportRequest {
+      files: vec![BrokerFile { mapping: Some(mapping), ..file("depot.csv", csv) }],
+      ..BrokerImportRequest::default()
+    };
+    let preview = preview_import(&request).unwrap();
+    assert_eq!((preview.files[0].profile.as_str(), preview.files[0].header_line), ("mapping", 3));
+    let codes: Vec<(usize, &str)> = preview.errors.iter().map(|e| (e.line_number, e.code)).collect();
+    assert_eq!(codes, vec![(8, "broker_csv_date_invalid"), (7, "broker_sell_exceeds_holdings")]);
+
+    let lot = &preview.imported_lots[0];
+    assert_eq!((lot.shares, lot.cost_basis), (20.0, 1004.9));
+    assert!((lot.purchase_price - 50.245).abs() < 1e-9);
+    assert_eq!(preview.summary.add, 1);
+    let added = &preview.proposed_tranches[0];
+    assert_eq!((added["trancheId"].clone(), added["type"].clone(), added["tqf"].clone()), (json!("import-DE0007164600-2020-01-02"), json!("aktien_neu"), json!(0.3)));
+    assert_eq!((added["schemaVersion"].clone(), added["name"].clone()), (json!(1), json!("SAP")));
+  }
+
+  #[test]
+  fn portfolio_performance_windows_1252_and_unknown_formats() {
+    let text = "Datum;Typ;Wert;Buchungswährung;Gebühren;Steuern;Stück;ISIN;WKN;Ticker-Symbol;Wertpapiername\n\
+      2022-03-15T00:00;Kauf;1.001,50;EUR;1,50;;10;US0378331005;865985;AAPL;Apple Inc.\n";
+    let bytes: Vec<u8> = text.chars().map(|c| u32::from(c) as u8).collect();
+    let current = vec![json!({"trancheId": "a1", "name": "Apple", "isin": "US0378331005", "shares": 10, "purchasePrice": 100.15, "purchaseDate": "2022-03-15"})];
+    let request = BrokerImportRequest {
+      files: vec![BrokerFile { bytes: Some(bytes), ..file("pp.csv", "") }],
+      current_tranches: current,
+      ..BrokerImportRequest::default()
+    };
+    let preview = preview_import(&request).unwrap();
+    assert_eq!(preview.files[0].profile, "portfolio_performance");
+    assert_eq!(preview.transactions[0].gross, 1000.0);
+    assert_eq!(preview.summary.unchanged, 1);
+
+    let unknown = BrokerImportRequest { files: vec![file("x.csv", "Kategorie;Betrag\nEssen;10\n")], ..BrokerImportRequest::default() };
+    assert_eq!(preview_import(&unknown).unwrap_err().code, "BROKER_IMPORT_FORMAT_UNKNOWN");
+    let forced = BrokerImportRequest {
+      files: vec![BrokerFile { profile_id: Some("degiro".into()), ..file("x.csv", "a;b\n") }],
+      ..BrokerImportRequest::default()
+    };
+    assert_eq!(preview_import(&forced).unwrap_err().code, "BROKER_IMPORT_FORMAT_UNKNOWN");
+    assert_eq!(preview_import(&BrokerImportRequest::default()).unwrap_err().code, "BROKER_IMPORT_INVALID");
+  }
+}
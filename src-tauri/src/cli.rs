@@ -0,0 +1,433 @@
+//! `ruhestand-cli`: headless Monte-Carlo and backtest runs for scripted
+//! batches (cron, CI). Uses the same native runners as the desktop app and
+//! writes the same export documents as the simulator's download buttons.
+
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+
+use crate::backtest::run_backtest;
+use crate::monte_carlo::{export_monte_carlo, run_monte_carlo, MonteCarloProgress, NativeRunOptions};
+use crate::monte_carlo_export::{diff_exports, kpi_rows, verify_export, verify_export_value, ExportVerification};
+use crate::script_runtime::{directory_source, ScriptSource, ENGINE_SOURCES_ENV};
+use crate::user_datasets::{check_dataset, dataset_source, read_dataset};
+
+const USAGE: &str = "\
+Verwendung:
+  ruhestand-cli monte-carlo <request.json> --out <export.json> [--csv <kpis.csv>] [--threads <n>] [--sources <dir>] [--dataset <datensatz.json>]
+  ruhestand-cli backtest <request.json> --out <export.json> [--csv <rows.csv>] [--cohorts <jahre>] [--sources <dir>] [--dataset <datensatz.json>]
+  ruhestand-cli verify <export.json> [--json]
+  ruhestand-cli diff <alt.json> <neu.json> [--tolerance <relativ>] [--out <diff.json>]
+
+<request.json> ist ein MonteCarloRunRequestV1 bzw. BacktestRequestV1 oder ein
+frueherer Export, dessen `request` erneut ausgefuehrt wird. --sources zeigt auf
+ein Verzeichnis mit `app/` und `engine/` (Repository oder dist); ohne Angabe
+gelten RUHESTAND_ENGINE_SOURCES und danach das aktuelle Verzeichnis.
+--dataset rechnet mit einem importierten Nutzerdatensatz
+(UserHistoricalDatasetV1 aus historical_datasets/ im App-Datenverzeichnis)
+statt mit dem eingebetteten historischen Bestand.
+verify prueft einen MonteCarloExportV1 (Schema, Fingerprints, Datenschutz),
+diff vergleicht die KPIs zweier Exporte.
+
+Exit-Codes: 0 Lauf abgeschlossen bzw. Export gueltig/KPIs gleich, 1 Lauf
+fehlgeschlagen, technischer Fehler, ungueltiger Export oder KPI-Abweichung,
+2 Aufruf- oder Dateifehler.";
+
+const MONTE_CARLO_EXPORT_SCHEMA_ID: &str = "de.ruhestandsapp.monte-carlo.run";
+const BACKTEST_EXPORT_SCHEMA_ID: &str = "de.ruhestandsapp.historical-backtest.raw";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CliCommand {
+  MonteCarlo,
+  Backtest,
+  Verify,
+  Diff,
+}
+
+#[derive(Debug, PartialEq)]
+struct CliOptions {
+  command: CliCommand,
+  inputs: Vec<PathBuf>,
+  out: Option<PathBuf>,
+  csv: Option<PathBuf>,
+  threads: Option<usize>,
+  cohorts: Option<u32>,
+  sources: Option<PathBuf>,
+  dataset: Option<PathBuf>,
+  json: bool,
+  tolerance: f64,
+}
+
+impl CliOptions {
+  fn input(&self) -> &Path {
+    &self.inputs[0]
+  }
+
+  fn runs_engine(&self) -> bool {
+    matches!(self.command, CliCommand::MonteCarlo | CliCommand::Backtest)
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<CliOptions, String> {
+  let command = match args.first().map(String::as_str) {
+    Some("monte-carlo") => CliCommand::MonteCarlo,
+    Some("backtest") => CliCommand::Backtest,
+    Some("verify") => CliCommand::Verify,
+    Some("diff") => CliCommand::Diff,
+    Some(other) => return Err(format!("Unbekannter Befehl: {}", other)),
+    None => return Err("Befehl fehlt.".to_string()),
+  };
+  let mut options = CliOptions {
+    command,
+    inputs: Vec::new(),
+    out: None,
+    csv: None,
+    threads: None,
+    cohorts: None,
+    sources: None,
+    dataset: None,
+    json: false,
+    tolerance: 0.0,
+  };
+  let runs_engine = options.runs_engine();
+  let expected_inputs = if command == CliCommand::Diff { 2 } else { 1 };
+  let mut rest = args[1..].iter();
+  while let Some(arg) = rest.next() {
+    let mut value = |flag: &str| rest.next().cloned().ok_or_else(|| format!("{} erwartet einen Wert.", flag));
+    match arg.as_str() {
+      "--out" if command != CliCommand::Verify => options.out = Some(PathBuf::from(value("--out")?)),
+      "--csv" if runs_engine => options.csv = Some(PathBuf::from(value("--csv")?)),
+      "--sources" if runs_engine => options.sources = Some(PathBuf::from(value("--sources")?)),
+      "--dataset" if runs_engine => options.dataset = Some(PathBuf::from(value("--dataset")?)),
+      "--threads" if command == CliCommand::MonteCarlo => {
+        let raw = value("--threads")?;
+        options.threads = Some(raw.parse().ok().filter(|n| *n > 0).ok_or_else(|| format!("Ungueltige Threadzahl: {}", raw))?);
+      }
+      "--cohorts" if command == CliCommand::Backtest => {
+        let raw = value("--cohorts")?;
+        options.cohorts = Some(raw.parse().ok().filter(|n| *n > 0).ok_or_else(|| format!("Ungueltiger Kohortenhorizont: {}", raw))?);
+      }
+      "--json" if command == CliCommand::Verify => options.json = true,
+      "--tolerance" if command == CliCommand::Diff => {
+        let raw = value("--tolerance")?;
+        options.tolerance = raw
+          .parse::<f64>()
+          .ok()
+          .filter(|t| t.is_finite() && *t >= 0.0)
+          .ok_or_else(|| format!("Ungueltige Toleranz: {}", raw))?;
+      }
+      flag if flag.starts_with("--") => return Err(format!("Unbekannte Option: {}", flag)),
+      path if options.inputs.len() < expected_inputs => options.inputs.push(PathBuf::from(path)),
+      extra => return Err(format!("Unerwartetes Argument: {}", extra)),
+    }
+  }
+  if options.inputs.len() < expected_inputs {
+    return Err(if command == CliCommand::Diff { "diff erwartet zwei Exporte." } else { "Eingabedatei fehlt." }.to_string());
+  }
+  if runs_engine && options.out.is_none() {
+    return Err("--out fehlt.".to_string());
+  }
+  Ok(options)
+}
+
+fn resolve_sources(explicit: Option<&Path>, env_dir: Option<String>, cwd: &Path) -> Result<ScriptSource, String> {
+  let root = explicit
+    .map(Path::to_path_buf)
+    .or_else(|| env_dir.filter(|dir| !dir.trim().is_empty()).map(PathBuf::from))
+    .unwrap_or_else(|| cwd.to_path_buf());
+  if !root.join("engine").join("index.mjs").is_file() {
+    return Err(format!(
+      "Keine Engine-Quellen in {} gefunden (erwartet engine/index.mjs); --sources angeben.",
+      root.display()
+    ));
+  }
+  Ok(directory_source(root))
+}
+
+/// `source` with the user dataset at `path`, checked against the contract.
+fn with_dataset(source: ScriptSource, path: &Path) -> Result<ScriptSource, String> {
+  let dataset = read_dataset(path).ok_or_else(|| format!("{} ist kein UserHistoricalDatasetV1.", path.display()))?;
+  check_dataset(&source, &dataset).map_err(|e| e.to_string())?;
+  Ok(dataset_source(source, &dataset))
+}
+
+/// Accepts a bare request or a previous export wrapping one.
+fn read_request(path: &Path, export_schema_id: &str) -> Result<Value, String> {
+  let raw = fs::read_to_string(path).map_err(|e| format!("{} konnte nicht gelesen werden: {}", path.display(), e))?;
+  let document: Value = serde_json::from_str(&raw).map_err(|e| format!("{} ist kein gueltiges JSON: {}", path.display(), e))?;
+  if document.get("schemaId").and_then(Value::as_str) == Some(export_schema_id) {
+    return document.get("request").cloned().ok_or_else(|| format!("{} enthaelt keinen request.", path.display()));
+  }
+  Ok(document)
+}
+
+fn write_output(path: &Path, content: &str) -> Result<(), String> {
+  crate::write_atomic(path, content).map_err(|e| format!("{} konnte nicht geschrieben werden: {}", path.display(), e))
+}
+
+fn csv_cell(value: &str) -> String {
+  if value.contains([',', '"', '\n']) {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value.to_string()
+  }
+}
+
+fn leaf_text(value: &Value) -> String {
+  match value {
+    Value::String(text) => text.clone(),
+    Value::Null => String::new(),
+    other => other.to_string(),
+  }
+}
+
+/// One `metric,value` row per KPI leaf, so nightly summaries diff line by line.
+fn monte_carlo_kpi_csv(result: &Value) -> String {
+  let mut csv = String::from("metric,value\n");
+  for (metric, value) in kpi_rows(result) {
+    csv.push_str(&format!("{},{}\n", csv_cell(&metric), csv_cell(&leaf_text(&value))));
+  }
+  csv
+}
+
+fn run_monte_carlo_command(options: &CliOptions, source: &ScriptSource, out: &Path) -> Result<i32, String> {
+  let request = read_request(options.input(), MONTE_CARLO_EXPORT_SCHEMA_ID)?;
+  let cancel = Arc::new(AtomicBool::new(false));
+  let last_decile = Mutex::new(0);
+  let report = |progress: MonteCarloProgress| {
+    let decile = progress.completed_runs * 10 / progress.total_runs.max(1);
+    let mut last = last_decile.lock().unwrap_or_else(|e| e.into_inner());
+    if decile > *last {
+      *last = decile;
+      eprintln!("{:>3}% ({}/{} Runs, {} ms)", decile * 10, progress.completed_runs, progress.total_runs, progress.elapsed_ms);
+    }
+  };
+  let run_options = NativeRunOptions { threads: options.threads, chunk_runs: None };
+  let result = run_monte_carlo(source, &request, run_options, &cancel, &report).map_err(|e| e.to_string())?;
+  let export = export_monte_carlo(source, &request, &result).map_err(|e| e.to_string())?;
+  write_output(out, &export)?;
+  if let Some(csv_path) = &options.csv {
+    write_output(csv_path, &monte_carlo_kpi_csv(&result))?;
+  }
+  let status = result["batchStatus"].as_str().unwrap_or("unknown");
+  println!("monte-carlo {} -> {} ({})", status, out.display(), options.input().display());
+  Ok(if status == "completed" { 0 } else { 1 })
+}
+
+fn run_backtest_command(options: &CliOptions, source: &ScriptSource, out: &Path) -> Result<i32, String> {
+  let request = read_request(options.input(), BACKTEST_EXPORT_SCHEMA_ID)?;
+  let output = run_backtest(source, &request, options.cohorts).map_err(|e| e.to_string())?;
+  write_output(out, &output.export)?;
+  if let Some(csv_path) = &options.csv {
+    write_output(csv_path, &output.csv)?;
+  }
+  let outcome = output.outcome.as_deref().unwrap_or("unknown");
+  println!("backtest {} -> {} ({})", outcome, out.display(), options.input().display());
+  Ok(if matches!(outcome, "completed" | "ruin") { 0 } else { 1 })
+}
+
+fn read_text(path: &Path) -> Result<String, String> {
+  fs::read_to_string(path).map_err(|e| format!("{} konnte nicht gelesen werden: {}", path.display(), e))
+}
+
+fn print_issues(label: &str, verification: &ExportVerification) {
+  for issue in &verification.issues {
+    eprintln!("{}{} {}: {}", label, issue.code, issue.path, issue.message);
+  }
+}
+
+fn run_verify_command(options: &CliOptions) -> Result<i32, String> {
+  let verification = verify_export(&read_text(options.input())?);
+  if options.json {
+    println!("{}", serde_json::to_string_pretty(&verification).map_err(|e| e.to_string())?);
+  } else if verification.valid {
+    println!("gueltig: {} ({})", verification.run_id.as_deref().unwrap_or("-"), options.input().display());
+    for field in &verification.unknown_fields {
+      println!("  ignoriertes Feld {}", field);
+    }
+  } else {
+    println!("ungueltig: {} Befund(e) ({})", verification.issues.len(), options.input().display());
+    print_issues("  ", &verification);
+  }
+  Ok(if verification.valid { 0 } else { 1 })
+}
+
+fn run_diff_command(options: &CliOptions) -> Result<i32, String> {
+  let mut documents = Vec::new();
+  let mut verifications = Vec::new();
+  for path in &options.inputs {
+    let document: Value = serde_json::from_str(&read_text(path)?)
+      .map_err(|e| format!("{} ist kein gueltiges JSON: {}", path.display(), e))?;
+    let verification = verify_export_value(&document);
+    print_issues(&format!("{}: ", path.display()), &verification);
+    verifications.push(verification);
+    documents.push(document);
+  }
+  let diff = diff_exports(&documents[0], &documents[1], options.tolerance);
+  if let Some(out) = &options.out {
+    let report = serde_json::json!({ "left": verifications[0], "right": verifications[1], "diff": diff });
+    write_output(out, &serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?)?;
+  }
+  println!(
+    "{} von {} Kennzahlen abweichend{}",
+    diff.changed.len(),
+    diff.compared,
+    if diff.same_request { "" } else { " (unterschiedliche Requests)" }
+  );
+  for delta in &diff.changed {
+    let relative = delta.relative_delta.map(|r| format!(", {:+.4}%", r * 100.0)).unwrap_or_default();
+    println!("  {}: {} -> {}{}", delta.path, leaf_text(&delta.left), leaf_text(&delta.right), relative);
+  }
+  let all_valid = verifications.iter().all(|verification| verification.valid);
+  Ok(if all_valid && diff.changed.is_empty() { 0 } else { 1 })
+}
+
+/// Entry point of the `ruhestand-cli` binary; returns the process exit code.
+pub fn run_cli(args: Vec<String>) -> i32 {
+  if args.is_empty() || args.iter().any(|arg| arg == "--help" || arg == "-h") {
+    println!("{}", USAGE);
+    return if args.is_empty() { 2 } else { 0 };
+  }
+  let options = match parse_args(&args) {
+    Ok(options) => options,
+    Err(message) => {
+      eprintln!("{}\n\n{}", message, USAGE);
+      return 2;
+    }
+  };
+  let outcome = match (options.command, options.out.as_deref()) {
+    (CliCommand::Verify, _) => run_verify_command(&options),
+    (CliCommand::Diff, _) => run_diff_command(&options),
+    (command, Some(out)) => {
+      let cwd = std::env::current_dir().unwrap_or_default();
+      let source = resolve_sources(options.sources.as_deref(), std::env::var(ENGINE_SOURCES_ENV).ok(), &cwd);
+      let source = match source.and_then(|source| match &options.dataset {
+        Some(path) => with_dataset(source, path),
+        None => Ok(source),
+      }) {
+        Ok(source) => source,
+        Err(message) => {
+          eprintln!("{}", message);
+          return 2;
+        }
+      };
+      if command == CliCommand::MonteCarlo {
+        run_monte_carlo_command(&options, &source, out)
+      } else {
+        run_backtest_command(&options, &source, out)
+      }
+    }
+    (_, None) => Err("--out fehlt.".to_string()),
+  };
+  outcome.unwrap_or_else(|message| {
+    eprintln!("{}", message);
+    1
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::script_runtime::checkout_root;
+
+  fn args(raw: &[&str]) -> Vec<String> {
+    raw.iter().map(|arg| arg.to_string()).collect()
+  }
+
+  #[test]
+  fn arguments_are_parsed_per_command() {
+    let options = parse_args(&args(&["monte-carlo", "req.json", "--out", "out.json", "--threads", "4"])).unwrap();
+    assert_eq!(options.command, CliCommand::MonteCarlo);
+    assert_eq!(options.input(), Path::new("req.json"));
+    assert_eq!(options.threads, Some(4));
+
+    let options = parse_args(&args(&["backtest", "--cohorts", "30", "req.json", "--out", "o.json", "--csv", "o.csv"])).unwrap();
+    assert_eq!(options.cohorts, Some(30));
+    assert_eq!(options.csv, Some(PathBuf::from("o.csv")));
+    let options = parse_args(&args(&["backtest", "req.json", "--out", "o.json", "--dataset", "user-0123456789ab.json"])).unwrap();
+    assert_eq!(options.dataset, Some(PathBuf::from("user-0123456789ab.json")));
+    assert!(parse_args(&args(&["verify", "a.json", "--dataset", "d.json"])).is_err());
+
+    assert!(parse_args(&args(&["backtest", "req.json", "--out", "o.json", "--threads", "2"])).is_err());
+    assert!(parse_args(&args(&["monte-carlo", "req.json"])).unwrap_err().contains("--out"));
+    assert!(parse_args(&args(&["monte-carlo", "req.json", "--out", "o", "--threads", "0"])).is_err());
+    assert!(parse_args(&args(&["sweep", "req.json"])).is_err());
+
+    let options = parse_args(&args(&["diff", "a.json", "b.json", "--tolerance", "1e-9"])).unwrap();
+    assert_eq!(options.inputs, vec![PathBuf::from("a.json"), PathBuf::from("b.json")]);
+    assert_eq!(options.tolerance, 1e-9);
+    assert!(parse_args(&args(&["diff", "a.json"])).is_err());
+    assert!(parse_args(&args(&["verify", "a.json", "--json"])).unwrap().json);
+    assert!(parse_args(&args(&["verify", "a.json", "--out", "x"])).is_err());
+  }
+
+  #[test]
+  fn requests_are_unwrapped_from_exports_and_kpis_flattened() {
+    let dir = std::env::temp_dir().join(format!("ruhestand-cli-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("export.json");
+    fs::write(&path, r#"{"schemaId":"de.ruhestandsapp.monte-carlo.run","request":{"schemaVersion":"MonteCarloRunRequestV1"}}"#).unwrap();
+    assert_eq!(read_request(&path, MONTE_CARLO_EXPORT_SCHEMA_ID).unwrap()["schemaVersion"], "MonteCarloRunRequestV1");
+    assert_eq!(read_request(&path, BACKTEST_EXPORT_SCHEMA_ID).unwrap()["schemaId"], MONTE_CARLO_EXPORT_SCHEMA_ID);
+    assert!(resolve_sources(None, None, &dir).is_err());
+    let _ = fs::remove_dir_all(&dir);
+
+    let result = serde_json::json!({
+      "batchStatus": "completed",
+      "kpis": { "successRate": 0.91, "label": "a,b", "missing": null },
+      "diagnostics": { "ignored": 1 }
+    });
+    assert_eq!(
+      monte_carlo_kpi_csv(&result),
+      "metric,value\nbatchStatus,completed\nkpis.label,\"a,b\"\nkpis.missing,\nkpis.successRate,0.91\n"
+    );
+  }
+
+  #[test]
+  fn backtest_command_writes_export_and_csv() {
+    let fixture: Value = serde_json::from_str(
+      &fs::read_to_string(checkout_root().join("tests/fixtures/simulator-backtest-target-v1.json")).unwrap(),
+    )
+    .unwrap();
+    let case = &fixture["cases"][0];
+    let request = serde_json::json!({
+      "schemaVersion": "BacktestRequestV1",
+      "startYear": case["period"]["startYear"],
+      "endYear": case["period"]["endYear"],
+      "inputs": case["inputs"]
+    });
+    let dir = std::env::temp_dir().join(format!("ruhestand-cli-backtest-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let request_path = dir.join("request.json");
+    fs::write(&request_path, request.to_string()).unwrap();
+    let (out, csv) = (dir.join("out/export.json"), dir.join("out/rows.csv"));
+    let code = run_cli(args(&[
+      "backtest",
+      request_path.to_str().unwrap(),
+      "--out",
+      out.to_str().unwrap(),
+      "--csv",
+      csv.to_str().unwrap(),
+      "--sources",
+      checkout_root().to_str().unwrap(),
+    ]));
+    assert_eq!(code, 0);
+    let export: Value = serde_json::from_str(&fs::read_to_string(&out).unwrap()).unwrap();
+    assert_eq!(export["schemaId"], BACKTEST_EXPORT_SCHEMA_ID);
+    assert_eq!(export["result"]["outcome"]["kind"], "completed");
+    let summary = &export["result"]["summary"];
+    assert_eq!(summary["totalWithdrawal"], case["values"]["totalWithdrawal"]);
+    let end_wealth = summary["endWealth"].as_f64().unwrap();
+    assert!((end_wealth - case["values"]["summaryEndWealth"].as_f64().unwrap()).abs() < 0.01);
+    assert_eq!(fs::read_to_string(&csv).unwrap().lines().count(), 1 + case["expectedRowCount"].as_u64().unwrap() as usize);
+
+    // Re-running the export reproduces the same fingerprint.
+    let rerun = dir.join("rerun.json");
+    assert_eq!(run_cli(args(&["backtest", out.to_str().unwrap(), "--out", rerun.to_str().unwrap(), "--sources", checkout_root().to_str().unwrap()])), 0);
+    let rerun: Value = serde_json::from_str(&fs::read_to_string(&rerun).unwrap()).unwrap();
+    assert_eq!(rerun["fingerprint"], export["fingerprint"]);
+    let _ = fs::remove_dir_all(&dir);
+  }
+}
@@ -0,0 +1,433 @@
+//! Live valuation of the active profile's depot. Every tranche with a ticker
+//! is priced through the same Yahoo quote chain as the `/quote` route (one
+//! request per symbol); tranches without ticker or with a failed quote keep
+//! their stored `currentPrice` and are flagged. Tax-if-sold figures are the
+//! cent-exact settlement of selling the tranche, the asset class or the whole
+//! depot alone in the current year, including Pauschbetrag and loss carry.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::network::SharedNetworkState;
+use crate::script_runtime::{job_failure as failure, JobFailure};
+use crate::tax::{settle_tax_year_exact, RawAggregate, Settlement, SettlementInput, TaxStateInput};
+
+/// Quotes older than this are used but flagged; weekends stay below it.
+const DEFAULT_STALE_AFTER_HOURS: f64 = 72.0;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ValuationTranche {
+  pub tranche_id: Option<String>,
+  pub name: Option<String>,
+  pub isin: Option<String>,
+  pub ticker: Option<String>,
+  pub shares: f64,
+  pub purchase_price: f64,
+  /// Last stored price, used when no live quote is available.
+  pub current_price: Option<f64>,
+  pub category: Option<String>,
+  pub tqf: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ValuationRequest {
+  pub tranches: Vec<ValuationTranche>,
+  pub sparer_pauschbetrag: Option<f64>,
+  pub kirchensteuer_satz: Option<f64>,
+  pub tax_state_prev: Option<TaxStateInput>,
+  pub stale_after_hours: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum PriceStatus {
+  Live,
+  /// Live quote older than `staleAfterHours`.
+  Stale,
+  /// Quote failed; the stored `currentPrice` is used.
+  Fallback,
+  /// No ticker; the stored `currentPrice` is used.
+  Manual,
+  /// No quote and no stored price; excluded from all totals.
+  Missing,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct QuoteError {
+  pub code: &'static str,
+  pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PriceInfo {
+  pub symbol: Option<String>,
+  pub price: Option<f64>,
+  /// UTC Unix seconds of the quote; `None` for stored prices.
+  pub as_of: Option<u64>,
+  pub source: &'static str,
+  pub status: PriceStatus,
+  pub error: Option<QuoteError>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TrancheValuation {
+  pub tranche_id: Option<String>,
+  pub name: Option<String>,
+  pub isin: Option<String>,
+  pub category: String,
+  pub shares: f64,
+  pub price: PriceInfo,
+  pub cost_basis: f64,
+  pub market_value: Option<f64>,
+  pub unrealized_gain: Option<f64>,
+  /// Gain after Teilfreistellung.
+  pub taxable_gain: Option<f64>,
+  pub tax_if_sold: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ValuationSum {
+  pub category: String,
+  pub tranche_count: usize,
+  pub market_value: f64,
+  pub cost_basis: f64,
+  pub unrealized_gain: f64,
+  pub taxable_gain: f64,
+  pub tax_if_sold: f64,
+  /// Tranches valued with a stale, stored or missing price.
+  pub flagged: usize,
+  pub missing: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DepotValuation {
+  pub valued_at: u64,
+  pub tranches: Vec<TrancheValuation>,
+  pub asset_classes: Vec<ValuationSum>,
+  pub totals: ValuationSum,
+  pub tax_raw_aggregate: RawAggregate,
+  /// Settlement of selling the whole depot.
+  pub settlement: Settlement,
+}
+
+fn invalid(message: impl Into<String>) -> JobFailure {
+  failure("VALUATION_INPUT_INVALID", message)
+}
+
+fn cents(value: f64) -> i64 {
+  (value * 100.0).round() as i64
+}
+
+fn euros(cents: i64) -> f64 {
+  cents as f64 / 100.0
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+  value.as_deref().map(str::trim).filter(|text| !text.is_empty())
+}
+
+fn validate(request: &ValuationRequest) -> Result<(), JobFailure> {
+  for (index, tranche) in request.tranches.iter().enumerate() {
+    if !tranche.shares.is_finite() || tranche.shares < 0.0 {
+      return Err(invalid(format!("tranches[{}].shares muss eine nicht negative Zahl sein.", index)));
+    }
+    if !tranche.purchase_price.is_finite() || tranche.purchase_price < 0.0 {
+      return Err(invalid(format!("tranches[{}].purchasePrice muss eine nicht negative Zahl sein.", index)));
+    }
+    if !tranche.tqf.unwrap_or(0.0).is_finite() || !(0.0..=1.0).contains(&tranche.tqf.unwrap_or(0.0)) {
+      return Err(invalid(format!("tranches[{}].tqf muss zwischen 0 und 1 liegen.", index)));
+    }
+  }
+  if request.stale_after_hours.is_some_and(|hours| !hours.is_finite() || hours <= 0.0) {
+    return Err(invalid("staleAfterHours muss positiv sein."));
+  }
+  Ok(())
+}
+
+fn stored_price(tranche: &ValuationTranche) -> Option<f64> {
+  tranche.current_price.filter(|price| price.is_finite() && *price > 0.0)
+}
+
+fn price_info(
+  tranche: &ValuationTranche,
+  quotes: &HashMap<String, Result<Value, JobFailure>>,
+  now_seconds: u64,
+  stale_after_seconds: u64,
+) -> PriceInfo {
+  let symbol = non_empty(&tranche.ticker).map(str::to_ascii_uppercase);
+  let stored = |status: PriceStatus, error: Option<QuoteError>| PriceInfo {
+    symbol: symbol.clone(),
+    price: stored_price(tranche),
+    as_of: None,
+    source: "profile",
+    status: if stored_price(tranche).is_some() { status } else { PriceStatus::Missing },
+    error,
+  };
+  let Some(quote) = symbol.as_ref().and_then(|symbol| quotes.get(symbol)) else {
+    return stored(PriceStatus::Manual, None);
+  };
+  match quote {
+    Ok(quote) => {
+      let as_of = quote.get("asOf").and_then(Value::as_u64).unwrap_or(0);
+      let source = match quote.get("source").and_then(Value::as_str) {
+        Some("yahoo-quote") => "yahoo-quote",
+        _ => "yahoo-chart",
+      };
+      let stale = now_seconds.saturating_sub(as_of) > stale_after_seconds;
+      PriceInfo {
+        symbol: symbol.clone(),
+        price: quote.get("price").and_then(Value::as_f64),
+        as_of: Some(as_of),
+        source,
+        status: if stale { PriceStatus::Stale } else { PriceStatus::Live },
+        error: None,
+      }
+    }
+    Err(error) => stored(PriceStatus::Fallback, Some(QuoteError { code: error.code, message: error.message.clone() })),
+  }
+}
+
+/// Gains in cents: (realized, after Teilfreistellung).
+fn settle(request: &ValuationRequest, gain: i64, taxable: i64) -> Result<(Settlement, RawAggregate), JobFailure> {
+  let raw = RawAggregate {
+    sum_realized_gain_signed: Some(euros(gain)),
+    sum_taxable_after_tqf_signed: Some(euros(taxable)),
+    sum_stock_taxable_signed: None,
+  };
+  let settlement = settle_tax_year_exact(&SettlementInput {
+    tax_state_prev: request.tax_state_prev.clone(),
+    raw_aggregate: Some(raw.clone()),
+    sparer_pauschbetrag: request.sparer_pauschbetrag,
+    kirchensteuer_satz: request.kirchensteuer_satz,
+    ..SettlementInput::default()
+  })?;
+  Ok((settlement, raw))
+}
+
+#[derive(Default)]
+struct Accumulator {
+  count: usize,
+  value: i64,
+  cost: i64,
+  gain: i64,
+  taxable: i64,
+  flagged: usize,
+  missing: usize,
+}
+
+impl Accumulator {
+  fn finish(&self, request: &ValuationRequest, category: String) -> Result<(ValuationSum, Settlement, RawAggregate), JobFailure> {
+    let (settlement, raw) = settle(request, self.gain, self.taxable)?;
+    let sum = ValuationSum {
+      category,
+      tranche_count: self.count,
+      market_value: euros(self.value),
+      cost_basis: euros(self.cost),
+      unrealized_gain: euros(self.gain),
+      taxable_gain: euros(self.taxable),
+      tax_if_sold: settlement.tax_due,
+      flagged: self.flagged,
+      missing: self.missing,
+    };
+    Ok((sum, settlement, raw))
+  }
+}
+
+/// Values the depot with `fetch` resolving one Yahoo symbol to a normalized quote.
+pub(crate) fn value_depot_with<F>(request: &ValuationRequest, now_seconds: u64, mut fetch: F) -> Result<DepotValuation, JobFailure>
+where
+  F: FnMut(&str) -> Result<Value, JobFailure>,
+{
+  validate(request)?;
+  // Validates Pauschbetrag, church tax and loss carry before any request.
+  settle(request, 0, 0)?;
+  let stale_after_seconds = (request.stale_after_hours.unwrap_or(DEFAULT_STALE_AFTER_HOURS) * 3600.0) as u64;
+
+  let mut quotes: HashMap<String, Result<Value, JobFailure>> = HashMap::new();
+  for tranche in &request.tranches {
+    if let Some(symbol) = non_empty(&tranche.ticker).map(str::to_ascii_uppercase) {
+      if let Entry::Vacant(entry) = quotes.entry(symbol) {
+        let quote = fetch(entry.key());
+        entry.insert(quote);
+      }
+    }
+  }
+
+  let mut tranches = Vec::new();
+  let mut classes: BTreeMap<String, Accumulator> = BTreeMap::new();
+  let mut depot = Accumulator::default();
+  for tranche in &request.tranches {
+    let price = price_info(tranche, &quotes, now_seconds, stale_after_seconds);
+    let category = non_empty(&tranche.category).unwrap_or("unknown").to_string();
+    let cost = cents(tranche.shares * tranche.purchase_price);
+    let class = classes.entry(category.clone()).or_default();
+    let flagged = price.status != PriceStatus::Live;
+    let mut valuation = TrancheValuation {
+      tranche_id: tranche.tranche_id.clone(),
+      name: tranche.name.clone(),
+      isin: tranche.isin.clone(),
+      category,
+      shares: tranche.shares,
+      price,
+      cost_basis: euros(cost),
+      market_value: None,
+      unrealized_gain: None,
+      taxable_gain: None,
+      tax_if_sold: None,
+    };
+    for acc in [&mut *class, &mut depot] {
+      acc.count += 1;
+      acc.flagged += usize::from(flagged);
+      acc.missing += usize::from(valuation.price.status == PriceStatus::Missing);
+    }
+    if let Some(price) = valuation.price.price {
+      let value = cents(tranche.shares * price);
+      let gain = value - cost;
+      let taxable = cents(euros(gain) * (1.0 - tranche.tqf.unwrap_or(0.0)));
+      let (settlement, _) = settle(request, gain, taxable)?;
+      valuation.market_value = Some(euros(value));
+      valuation.unrealized_gain = Some(euros(gain));
+      valuation.taxable_gain = Some(euros(taxable));
+      valuation.tax_if_sold = Some(settlement.tax_due);
+      for acc in [&mut *class, &mut depot] {
+        acc.value += value;
+        acc.cost += cost;
+        acc.gain += gain;
+        acc.taxable += taxable;
+      }
+    }
+    tranches.push(valuation);
+  }
+
+  let mut asset_classes = Vec::new();
+  for (category, acc) in &classes {
+    asset_classes.push(acc.finish(request, category.clone())?.0);
+  }
+  let (totals, settlement, tax_raw_aggregate) = depot.finish(request, "depot".to_string())?;
+  Ok(DepotValuation { valued_at: now_seconds, tranches, asset_classes, totals, tax_raw_aggregate, settlement })
+}
+
+#[tauri::command]
+pub async fn value_depot(
+  state: tauri::State<'_, SharedNetworkState>,
+  request: ValuationRequest,
+) -> Result<DepotValuation, String> {
+  let network = state.inner().clone();
+  tauri::async_runtime::spawn_blocking(move || {
+    value_depot_with(&request, network.now_seconds(), |symbol| {
+      crate::fetch_quote(&network, symbol).map_err(|error| failure(error.code, error.message))
+    })
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  const NOW: u64 = 1_700_000_000;
+
+  fn tranche(value: Value) -> ValuationTranche {
+    let mut tranche = ValuationTranche { shares: value["shares"].as_f64().unwrap(), ..ValuationTranche::default() };
+    tranche.tranche_id = value["trancheId"].as_str().map(str::to_string);
+    tranche.ticker = value["ticker"].as_str().map(str::to_string);
+    tranche.purchase_price = value["purchasePrice"].as_f64().unwrap();
+    tranche.current_price = value["currentPrice"].as_f64();
+    tranche.category = value["category"].as_str().map(str::to_string);
+    tranche.tqf = value["tqf"].as_f64();
+    tranche
+  }
+
+  fn quote(symbol: &str, price: f64, as_of: u64) -> Value {
+    json!({ "symbol": symbol, "price": price, "currency": "EUR", "asOf": as_of, "source": "yahoo-chart" })
+  }
+
+  #[test]
+  fn prices_each_symbol_once_and_flags_fallbacks() {
+    let request = ValuationRequest {
+      tranches: vec![
+        tranche(json!({"trancheId": "a", "ticker": "vwce.de", "shares": 10, "purchasePrice": 100, "category": "equity", "tqf": 0.3})),
+        tranche(json!({"trancheId": "b", "ticker": "VWCE.DE", "shares": 5, "purchasePrice": 120, "category": "equity", "tqf": 0.3})),
+        tranche(json!({"trancheId": "c", "shares": 2, "purchasePrice": 1500, "currentPrice": 2000, "category": "gold"})),
+        tranche(json!({"trancheId": "d", "ticker": "XEON.DE", "shares": 3, "purchasePrice": 140, "currentPrice": 145, "category": "money_market"})),
+        tranche(json!({"trancheId": "e", "ticker": "GONE.DE", "shares": 1, "purchasePrice": 50, "category": "equity"})),
+      ],
+      ..ValuationRequest::default()
+    };
+    let mut calls = Vec::new();
+    let valuation = value_depot_with(&request, NOW, |symbol| {
+      calls.push(symbol.to_string());
+      match symbol {
+        "VWCE.DE" => Ok(quote(symbol, 110.0, NOW - 600)),
+        _ => Err(failure("PROVIDER_TIMEOUT", "query1.finance.yahoo.com: Timeout nach 4000 ms.")),
+      }
+    })
+    .unwrap();
+    assert_eq!(calls, vec!["VWCE.DE", "XEON.DE", "GONE.DE"]);
+
+    let statuses: Vec<PriceStatus> = valuation.tranches.iter().map(|t| t.price.status).collect();
+    assert_eq!(
+      statuses,
+      vec![PriceStatus::Live, PriceStatus::Live, PriceStatus::Manual, PriceStatus::Fallback, PriceStatus::Missing]
+    );
+    assert_eq!((valuation.tranches[0].price.as_of, valuation.tranches[0].price.source), (Some(NOW - 600), "yahoo-chart"));
+    assert_eq!(valuation.tranches[3].price.error.as_ref().unwrap().code, "PROVIDER_TIMEOUT");
+    assert_eq!(valuation.tranches[4].market_value, None);
+    assert_eq!(valuation.tranches[1].unrealized_gain, Some(-50.0));
+
+    // 1100 + 550 + 4000 + 435; the missing tranche counts but has no value.
+    assert_eq!(valuation.totals.market_value, 6085.0);
+    assert_eq!((valuation.totals.tranche_count, valuation.totals.flagged, valuation.totals.missing), (5, 3, 1));
+    let equity = valuation.asset_classes.iter().find(|class| class.category == "equity").unwrap();
+    assert_eq!((equity.market_value, equity.unrealized_gain, equity.taxable_gain), (1650.0, 50.0, 35.0));
+  }
+
+  #[test]
+  fn tax_if_sold_uses_pauschbetrag_per_scope_and_flags_stale_quotes() {
+    let request = ValuationRequest {
+      tranches: vec![
+        tranche(json!({"trancheId": "a", "ticker": "AAA.DE", "shares": 10, "purchasePrice": 100, "category": "equity", "tqf": 0.3})),
+        tranche(json!({"trancheId": "b", "ticker": "BBB.DE", "shares": 10, "purchasePrice": 100, "category": "equity", "tqf": 0.3})),
+      ],
+      sparer_pauschbetrag: Some(1000.0),
+      ..ValuationRequest::default()
+    };
+    let valuation = value_depot_with(&request, NOW, |symbol| {
+      let as_of = if symbol == "BBB.DE" { NOW - 4 * 86_400 } else { NOW - 60 };
+      Ok(quote(symbol, 200.0, as_of))
+    })
+    .unwrap();
+    let first = &valuation.tranches[0];
+    assert_eq!((first.unrealized_gain, first.taxable_gain, first.tax_if_sold), (Some(1000.0), Some(700.0), Some(0.0)));
+    assert_eq!(valuation.tranches[1].price.status, PriceStatus::Stale);
+    // 1400 taxable minus 1000 Pauschbetrag at 26.375 %.
+    assert_eq!(valuation.totals.tax_if_sold, 105.5);
+    assert_eq!(valuation.settlement.details.spb_used_this_year, 1000.0);
+    assert_eq!(valuation.tax_raw_aggregate.sum_taxable_after_tqf_signed, Some(1400.0));
+  }
+
+  #[test]
+  fn rejects_invalid_input_before_fetching() {
+    let mut request = ValuationRequest {
+      tranches: vec![tranche(json!({"ticker": "AAA.DE", "shares": -1, "purchasePrice": 100}))],
+      ..ValuationRequest::default()
+    };
+    let fetch = |_: &str| -> Result<Value, JobFailure> { panic!("no quote expected") };
+    assert_eq!(value_depot_with(&request, NOW, fetch).unwrap_err().code, "VALUATION_INPUT_INVALID");
+    request.tranches[0].shares = 1.0;
+    request.kirchensteuer_satz = Some(2.0);
+    assert_eq!(value_depot_with(&request, NOW, fetch).unwrap_err().code, "TAX_INPUT_INVALID");
+  }
+}
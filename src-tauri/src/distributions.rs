@@ -0,0 +1,393 @@
+//! Fund distributions and dividends from the Yahoo chart events. Events pass
+//! the same symbol and currency rules as `normalize_provider_quote` (EUR
+//! only) and are cached per symbol in the app data dir together with the
+//! date range they cover, so repeated and offline queries need no download.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::network::{NetworkState, SharedNetworkState};
+use crate::quote_history::{civil_from_days, parse_day};
+use crate::script_runtime::job_failure as failure;
+use crate::{quote_failure, QuoteFailure};
+
+pub(crate) const DISTRIBUTION_CACHE_FILENAME: &str = "ruhestand_suite_distributions.json";
+const SECONDS_PER_DAY: u64 = 86_400;
+/// Ranges reaching into the last day are fetched again after this age.
+const RECENT_CACHE_MAX_AGE_SECONDS: u64 = 24 * 60 * 60;
+const DEFAULT_RANGE_DAYS: i64 = 10 * 365;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DistributionEvent {
+  /// Ex-date as `YYYY-MM-DD` (UTC).
+  pub ex_date: String,
+  pub amount: f64,
+  pub currency: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheEntry {
+  covered_from: i64,
+  covered_to: i64,
+  fetched_at: u64,
+  events: Vec<DistributionEvent>,
+}
+
+/// Symbol -> covered range and events, optionally mirrored to a JSON file.
+pub(crate) struct DistributionCache {
+  entries: Mutex<BTreeMap<String, CacheEntry>>,
+  path: Option<PathBuf>,
+}
+
+impl DistributionCache {
+  pub(crate) fn in_memory() -> Self {
+    DistributionCache { entries: Mutex::new(BTreeMap::new()), path: None }
+  }
+
+  pub(crate) fn load(path: PathBuf) -> Self {
+    let entries = fs::read_to_string(&path)
+      .ok()
+      .and_then(|raw| serde_json::from_str::<BTreeMap<String, CacheEntry>>(&raw).ok())
+      .unwrap_or_default();
+    DistributionCache { entries: Mutex::new(entries), path: Some(path) }
+  }
+
+  fn get(&self, symbol: &str) -> Option<CacheEntry> {
+    self.entries.lock().unwrap_or_else(|e| e.into_inner()).get(symbol).cloned()
+  }
+
+  fn insert(&self, symbol: String, entry: CacheEntry) {
+    let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+    entries.insert(symbol, entry);
+    let Some(path) = &self.path else { return };
+    let Ok(content) = serde_json::to_string(&*entries) else { return };
+    let _ = crate::write_atomic(path, &content);
+  }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DistributionRequest {
+  pub symbol: String,
+  /// Inclusive UTC days as `YYYY-MM-DD`; default the last ten years.
+  pub from: Option<String>,
+  pub to: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DistributionHistory {
+  pub symbol: String,
+  pub from: String,
+  pub to: String,
+  pub currency: String,
+  pub events: Vec<DistributionEvent>,
+  pub source: &'static str,
+  pub fetched_at: u64,
+  pub cached: bool,
+  /// Served from the cache because the provider could not be reached.
+  pub stale: bool,
+}
+
+fn invalid_query(message: impl Into<String>) -> QuoteFailure {
+  quote_failure("INVALID_DISTRIBUTION_QUERY", message, 400)
+}
+
+fn day_param(raw: Option<&str>, field: &str) -> Result<Option<i64>, QuoteFailure> {
+  match raw.map(str::trim).filter(|text| !text.is_empty()) {
+    Some(text) => parse_day(text).map(Some).ok_or_else(|| invalid_query(format!("{} muss ein Datum YYYY-MM-DD sein.", field))),
+    None => Ok(None),
+  }
+}
+
+/// First chart result, after the symbol and EUR rules of the quote contract.
+pub(crate) fn checked_chart_result<'a>(requested: &str, data: &'a Value) -> Result<&'a Value, QuoteFailure> {
+  let result = data.pointer("/chart/result/0").filter(|result| result.is_object()).ok_or_else(|| {
+    quote_failure("SYMBOL_NOT_FOUND", "Yahoo: Symbol nicht gefunden.", 404)
+  })?;
+  let meta = result.get("meta").unwrap_or(&Value::Null);
+  let response_symbol = meta
+    .get("symbol")
+    .and_then(Value::as_str)
+    .and_then(|symbol| crate::normalize_yahoo_symbol(symbol).ok())
+    .ok_or_else(|| quote_failure("INVALID_RESPONSE", "Yahoo-Antwort enthaelt kein gueltiges Symbol.", 422))?;
+  if response_symbol != requested {
+    return Err(quote_failure(
+      "SYMBOL_MISMATCH",
+      format!("Antwortsymbol {} entspricht nicht der Anfrage {}.", response_symbol, requested),
+      422,
+    ));
+  }
+  let currency = meta.get("currency").and_then(Value::as_str).unwrap_or("").trim().to_ascii_uppercase();
+  if currency.is_empty() {
+    return Err(quote_failure("CURRENCY_MISSING", "Yahoo-Antwort enthaelt keine eindeutige Waehrung.", 422));
+  }
+  if currency != "EUR" {
+    return Err(quote_failure("UNSUPPORTED_CURRENCY", format!("Waehrung {} wird nicht unterstuetzt.", currency), 422));
+  }
+  Ok(result)
+}
+
+/// Dividend events of one chart answer, checked like a quote.
+pub(crate) fn parse_distribution_events(requested: &str, data: &Value) -> Result<Vec<DistributionEvent>, QuoteFailure> {
+  let result = checked_chart_result(requested, data)?;
+  let mut events = Vec::new();
+  if let Some(dividends) = result.pointer("/events/dividends").and_then(Value::as_object) {
+    for dividend in dividends.values() {
+      let amount = dividend.get("amount").and_then(Value::as_f64).filter(|amount| amount.is_finite() && *amount > 0.0);
+      let date = dividend.get("date").and_then(Value::as_u64).filter(|date| *date > 0);
+      let (Some(amount), Some(date)) = (amount, date) else {
+        return Err(quote_failure("INVALID_RESPONSE", "Ausschuettung ohne positiven Betrag oder Ex-Tag.", 422));
+      };
+      events.push(DistributionEvent { ex_date: civil_from_days((date / SECONDS_PER_DAY) as i64), amount, currency: "EUR".to_string() });
+    }
+  }
+  events.sort_by(|a, b| a.ex_date.cmp(&b.ex_date));
+  Ok(events)
+}
+
+fn within(events: &[DistributionEvent], from: i64, to: i64) -> Vec<DistributionEvent> {
+  events
+    .iter()
+    .filter(|event| parse_day(&event.ex_date).is_some_and(|day| day >= from && day <= to))
+    .cloned()
+    .collect()
+}
+
+/// Serves from the cache when it covers the range, otherwise calls `fetch`
+/// with the normalized symbol and the chart period (`period1`, `period2` in
+/// Unix seconds) covering the request and the cached range together.
+pub(crate) fn load_distributions<F>(
+  cache: &DistributionCache,
+  raw_symbol: &str,
+  from: Option<&str>,
+  to: Option<&str>,
+  now_seconds: u64,
+  fetch: F,
+) -> Result<DistributionHistory, QuoteFailure>
+where
+  F: FnOnce(&str, u64, u64) -> Result<Value, QuoteFailure>,
+{
+  let symbol = crate::normalize_yahoo_symbol(raw_symbol)?;
+  let today = (now_seconds / SECONDS_PER_DAY) as i64;
+  let to = day_param(to, "to")?.unwrap_or(today);
+  let from = day_param(from, "from")?.unwrap_or(to - DEFAULT_RANGE_DAYS);
+  if from > to {
+    return Err(invalid_query("from liegt nach to."));
+  }
+  if from < 0 {
+    return Err(invalid_query("from liegt vor 1970-01-01."));
+  }
+  let history = |entry: &CacheEntry, cached: bool, stale: bool| DistributionHistory {
+    symbol: symbol.clone(),
+    from: civil_from_days(from),
+    to: civil_from_days(to),
+    currency: "EUR".to_string(),
+    events: within(&entry.events, from, to),
+    source: "yahoo-chart",
+    fetched_at: entry.fetched_at,
+    cached,
+    stale,
+  };
+
+  let cached = cache.get(&symbol);
+  if let Some(entry) = &cached {
+    let covers = entry.covered_from <= from && entry.covered_to >= to;
+    let settled = (to as u64 + 1) * SECONDS_PER_DAY <= entry.fetched_at
+      || now_seconds.saturating_sub(entry.fetched_at) <= RECENT_CACHE_MAX_AGE_SECONDS;
+    if covers && settled {
+      return Ok(history(entry, true, false));
+    }
+  }
+
+  let (fetch_from, fetch_to) = match &cached {
+    Some(entry) => (from.min(entry.covered_from), to.max(entry.covered_to)),
+    None => (from, to),
+  };
+  let period1 = fetch_from as u64 * SECONDS_PER_DAY;
+  let period2 = (fetch_to as u64 + 1) * SECONDS_PER_DAY;
+  let events = match fetch(&symbol, period1, period2).and_then(|data| parse_distribution_events(&symbol, &data)) {
+    Ok(events) => events,
+    Err(error) => {
+      return match cached {
+        Some(entry) if crate::is_recoverable(&error) => Ok(history(&entry, true, true)),
+        _ => Err(error),
+      };
+    }
+  };
+  let entry = CacheEntry { covered_from: fetch_from, covered_to: fetch_to, fetched_at: now_seconds, events };
+  let result = history(&entry, false, false);
+  cache.insert(symbol.clone(), entry);
+  Ok(result)
+}
+
+/// Chart request with `query`, primary host first, fallback host on provider
+/// errors.
+pub(crate) fn fetch_chart(network: &NetworkState, symbol: &str, query: &str) -> Result<Value, QuoteFailure> {
+  let endpoints = network.yahoo_endpoints();
+  let encoded = urlencoding::encode(symbol);
+  let mut last_error = quote_failure("SYMBOL_NOT_FOUND", "Yahoo: Symbol nicht gefunden.", 404);
+  for base in [&endpoints.primary, &endpoints.fallback] {
+    let url = format!(
+      "{}/v8/finance/chart/{}?{}&lang=en-US&region=US&corsDomain=finance.yahoo.com",
+      base, encoded, query
+    );
+    match crate::fetch_json(network, &url) {
+      Ok(data) => return Ok(data),
+      Err(error) => {
+        let stop = crate::should_stop_quote_fallback(&error);
+        last_error = error;
+        if stop {
+          break;
+        }
+      }
+    }
+  }
+  Err(last_error)
+}
+
+pub(crate) fn fetch_distributions(
+  network: &NetworkState,
+  symbol: &str,
+  from: Option<&str>,
+  to: Option<&str>,
+) -> Result<DistributionHistory, QuoteFailure> {
+  let replay_cache = DistributionCache::in_memory();
+  let cache = if network.is_replaying() { &replay_cache } else { network.distribution_cache() };
+  load_distributions(cache, symbol, from, to, network.now_seconds(), |symbol, period1, period2| {
+    fetch_chart(network, symbol, &format!("period1={}&period2={}&interval=1d&events=div", period1, period2))
+  })
+}
+
+#[tauri::command]
+pub async fn get_distributions(
+  state: tauri::State<'_, SharedNetworkState>,
+  request: DistributionRequest,
+) -> Result<DistributionHistory, String> {
+  let network = state.inner().clone();
+  tauri::async_runtime::spawn_blocking(move || {
+    fetch_distributions(&network, &request.symbol, request.from.as_deref(), request.to.as_deref())
+      .map_err(|error| failure(error.code, error.message))
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mock_upstream::{MockResponse, MockUpstream, ProxyUnderTest};
+  use serde_json::json;
+
+  const NOW: u64 = 1_700_000_000;
+
+  fn chart_with_dividends(symbol: &str, currency: &str) -> Value {
+    json!({ "chart": { "result": [{
+      "meta": { "symbol": symbol, "currency": currency },
+      "events": { "dividends": {
+        "1686787200": { "amount": 0.2, "date": 1686787200 + 32_400 },
+        "1679270400": { "amount": 0.15, "date": 1679270400 },
+        "1695254400": { "amount": 0.25, "date": 1695254400 }
+      }}
+    }]}})
+  }
+
+  #[test]
+  fn normalizes_dividend_events_under_the_quote_currency_rules() {
+    let events = parse_distribution_events("VWCE.DE", &chart_with_dividends("vwce.de", "eur")).unwrap();
+    let days: Vec<(&str, f64)> = events.iter().map(|event| (event.ex_date.as_str(), event.amount)).collect();
+    assert_eq!(days, vec![("2023-03-20", 0.15), ("2023-06-15", 0.2), ("2023-09-21", 0.25)]);
+    assert!(events.iter().all(|event| event.currency == "EUR"));
+
+    let no_events = json!({ "chart": { "result": [{ "meta": { "symbol": "SAP.DE", "currency": "EUR" } }] } });
+    assert!(parse_distribution_events("SAP.DE", &no_events).unwrap().is_empty());
+    let code = |data: Value| parse_distribution_events("VWCE.DE", &data).unwrap_err().code;
+    assert_eq!(code(chart_with_dividends("VWRL.L", "EUR")), "SYMBOL_MISMATCH");
+    assert_eq!(code(chart_with_dividends("VWCE.DE", "USD")), "UNSUPPORTED_CURRENCY");
+    assert_eq!(code(chart_with_dividends("VWCE.DE", " ")), "CURRENCY_MISSING");
+    assert_eq!(code(json!({ "chart": { "result": [] } })), "SYMBOL_NOT_FOUND");
+    let broken = json!({ "chart": { "result": [{
+      "meta": { "symbol": "VWCE.DE", "currency": "EUR" },
+      "events": { "dividends": { "1": { "amount": -1.0, "date": 1 } } }
+    }]}});
+    assert_eq!(code(broken), "INVALID_RESPONSE");
+  }
+
+  #[test]
+  fn caches_covered_ranges_and_serves_stale_events_when_offline() {
+    let dir = std::env::temp_dir().join(format!("ruhestand-distributions-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(DISTRIBUTION_CACHE_FILENAME);
+    let _ = fs::remove_file(&path);
+    let cache = DistributionCache::load(path.clone());
+
+    let mut periods = Vec::new();
+    let first = load_distributions(&cache, "vwce.de", Some("2023-01-01"), Some("2023-06-30"), NOW, |symbol, p1, p2| {
+      periods.push((symbol.to_string(), p1, p2));
+      Ok(chart_with_dividends("VWCE.DE", "EUR"))
+    })
+    .unwrap();
+    assert_eq!(periods, vec![("VWCE.DE".to_string(), 1_672_531_200, 1_688_169_600)]);
+    assert_eq!((first.cached, first.events.len(), first.from.as_str()), (false, 2, "2023-01-01"));
+
+    // A sub-range of a past, covered range needs no download, even after a restart.
+    let reloaded = DistributionCache::load(path.clone());
+    let inner = load_distributions(&reloaded, "VWCE.DE", Some("2023-03-01"), Some("2023-03-31"), NOW + 30 * 86_400, |_, _, _| {
+      panic!("covered range must come from the cache")
+    })
+    .unwrap();
+    assert_eq!((inner.cached, inner.events.len(), inner.fetched_at), (true, 1, NOW));
+
+    // A wider range refetches the union; without network the cached part is served as stale.
+    let mut widened = None;
+    let offline = load_distributions(&reloaded, "VWCE.DE", Some("2022-01-01"), Some("2023-06-30"), NOW, |_, p1, p2| {
+      widened = Some((p1, p2));
+      Err(quote_failure("OFFLINE_MODE", "Offline", 503))
+    })
+    .unwrap();
+    assert_eq!(widened, Some((1_640_995_200, 1_688_169_600)));
+    assert_eq!((offline.cached, offline.stale, offline.events.len()), (true, true, 2));
+    let mismatch = load_distributions(&reloaded, "VWCE.DE", Some("2022-01-01"), None, NOW, |_, _, _| {
+      Ok(chart_with_dividends("VWCE.DE", "USD"))
+    });
+    assert_eq!(mismatch.unwrap_err().code, "UNSUPPORTED_CURRENCY");
+
+    for (from, to) in [(Some("2023-02-30"), None), (Some("2023-06-01"), Some("2023-01-01")), (None, Some("morgen"))] {
+      let error = load_distributions(&reloaded, "VWCE.DE", from, to, NOW, |_, _, _| panic!("invalid query")).unwrap_err();
+      assert_eq!(error.code, "INVALID_DISTRIBUTION_QUERY");
+    }
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn proxy_route_falls_back_to_second_host_and_answers_from_cache() {
+    let upstream = MockUpstream::start(vec![
+      ("/query1/v8/finance/chart/VWCE.DE", MockResponse::raw(503, "{}")),
+      ("/query2/v8/finance/chart/VWCE.DE", MockResponse::json(200, chart_with_dividends("VWCE.DE", "EUR"))),
+    ]);
+    let proxy = ProxyUnderTest::start(&upstream);
+
+    let (status, body) = proxy.get("/distributions?symbol=vwce.de&from=2023-01-01&to=2023-12-31");
+    assert_eq!(status, 200);
+    assert_eq!(body["symbol"], "VWCE.DE");
+    assert_eq!(body["events"][0], json!({ "exDate": "2023-03-20", "amount": 0.15, "currency": "EUR" }));
+    assert_eq!((body["events"].as_array().unwrap().len(), &body["cached"]), (3, &json!(false)));
+
+    let (status, body) = proxy.get("/distributions?symbol=VWCE.DE&from=2023-06-01&to=2023-06-30");
+    assert_eq!((status, &body["cached"], body["events"].as_array().unwrap().len()), (200, &json!(true), 1));
+    assert_eq!(
+      upstream.hits(),
+      vec!["/query1/v8/finance/chart/VWCE.DE".to_string(), "/query2/v8/finance/chart/VWCE.DE".to_string()]
+    );
+
+    let (status, body) = proxy.get("/distributions?from=2023-01-01");
+    assert_eq!((status, body["code"].as_str()), (400, Some("INVALID_SYMBOL")));
+  }
+}
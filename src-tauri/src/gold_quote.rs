@@ -0,0 +1,217 @@
+//! Gold in EUR for the gold bucket of the Balance app. Spot gold is only
+//! quoted in USD (COMEX future `GC=F`), so it is converted with `EURUSD=X`;
+//! both inputs pass the quote contract of `normalize_provider_quote` with USD
+//! as expected currency. Exchange-traded gold (Xetra-Gold, EUWAX Gold) is an
+//! ordinary EUR quote converted to grams via the gold entitlement per unit.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::network::{NetworkState, SharedNetworkState};
+use crate::script_runtime::job_failure as failure;
+use crate::{quote_failure, QuoteFailure};
+
+pub(crate) const GRAMS_PER_TROY_OUNCE: f64 = 31.103_476_8;
+const SPOT_SYMBOL: &str = "GC=F";
+const FX_SYMBOL: &str = "EURUSD=X";
+
+/// EUR-listed gold securities with physical delivery claim per unit.
+const GOLD_SECURITIES: &[(&str, &str, f64)] = &[
+  ("4GLD.DE", "Xetra-Gold", 1.0),
+  ("EWG2.DE", "EUWAX Gold II", 1.0),
+];
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GoldQuoteRequest {
+  /// Empty or `spot` for spot gold, otherwise a symbol from the gold
+  /// securities table.
+  pub instrument: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FxConversion {
+  pub symbol: String,
+  /// USD per EUR.
+  pub rate: f64,
+  pub as_of: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GoldQuote {
+  pub instrument: String,
+  pub name: String,
+  pub currency: String,
+  pub price_per_ounce: f64,
+  pub price_per_gram: f64,
+  /// Price of one security unit; `null` for spot gold.
+  pub price_per_unit: Option<f64>,
+  pub grams_per_unit: Option<f64>,
+  /// Older timestamp of all inputs.
+  pub as_of: u64,
+  pub source: String,
+  pub fx: Option<FxConversion>,
+}
+
+fn quote_field(quote: &Value, field: &str) -> f64 {
+  quote.get(field).and_then(Value::as_f64).unwrap_or(0.0)
+}
+
+fn quote_time(quote: &Value) -> u64 {
+  quote.get("asOf").and_then(Value::as_u64).unwrap_or(0)
+}
+
+fn quote_source(quote: &Value) -> &str {
+  quote.get("source").and_then(Value::as_str).unwrap_or("")
+}
+
+/// `fetch(symbol, currency)` returns a quote that already passed the quote
+/// contract for `currency`.
+pub(crate) fn gold_quote_with<F>(instrument: Option<&str>, mut fetch: F) -> Result<GoldQuote, QuoteFailure>
+where
+  F: FnMut(&str, &str) -> Result<Value, QuoteFailure>,
+{
+  let instrument = instrument.map(str::trim).filter(|value| !value.is_empty()).unwrap_or("spot");
+  if instrument.eq_ignore_ascii_case("spot") {
+    let spot = fetch(SPOT_SYMBOL, "USD")?;
+    let fx = fetch(FX_SYMBOL, "USD")?;
+    let rate = quote_field(&fx, "price");
+    let price_per_ounce = quote_field(&spot, "price") / rate;
+    return Ok(GoldQuote {
+      instrument: "spot".to_string(),
+      name: "Gold (Spot)".to_string(),
+      currency: "EUR".to_string(),
+      price_per_ounce,
+      price_per_gram: price_per_ounce / GRAMS_PER_TROY_OUNCE,
+      price_per_unit: None,
+      grams_per_unit: None,
+      as_of: quote_time(&spot).min(quote_time(&fx)),
+      source: quote_source(&spot).to_string(),
+      fx: Some(FxConversion { symbol: FX_SYMBOL.to_string(), rate, as_of: quote_time(&fx) }),
+    });
+  }
+
+  let symbol = crate::normalize_yahoo_symbol(instrument)?;
+  let (_, name, grams_per_unit) = GOLD_SECURITIES
+    .iter()
+    .find(|(known, _, _)| *known == symbol)
+    .ok_or_else(|| quote_failure(
+      "UNSUPPORTED_GOLD_INSTRUMENT",
+      format!("{} ist kein unterstuetztes Gold-Wertpapier (spot, 4GLD.DE, EWG2.DE).", symbol),
+      400,
+    ))?;
+  let quote = fetch(&symbol, "EUR")?;
+  let price_per_unit = quote_field(&quote, "price");
+  let price_per_gram = price_per_unit / grams_per_unit;
+  Ok(GoldQuote {
+    instrument: symbol,
+    name: name.to_string(),
+    currency: "EUR".to_string(),
+    price_per_ounce: price_per_gram * GRAMS_PER_TROY_OUNCE,
+    price_per_gram,
+    price_per_unit: Some(price_per_unit),
+    grams_per_unit: Some(*grams_per_unit),
+    as_of: quote_time(&quote),
+    source: quote_source(&quote).to_string(),
+    fx: None,
+  })
+}
+
+pub(crate) fn fetch_gold_quote(network: &NetworkState, instrument: Option<&str>) -> Result<GoldQuote, QuoteFailure> {
+  gold_quote_with(instrument, |symbol, currency| crate::fetch_quote_in(network, symbol, currency))
+}
+
+#[tauri::command]
+pub async fn get_gold_quote(
+  state: tauri::State<'_, SharedNetworkState>,
+  request: GoldQuoteRequest,
+) -> Result<GoldQuote, String> {
+  let network = state.inner().clone();
+  tauri::async_runtime::spawn_blocking(move || {
+    fetch_gold_quote(&network, request.instrument.as_deref()).map_err(|error| failure(error.code, error.message))
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mock_upstream::{MockResponse, MockUpstream, ProxyUnderTest};
+  use serde_json::json;
+
+  fn quote(symbol: &str, price: f64, currency: &str, as_of: u64) -> Value {
+    json!({ "symbol": symbol, "price": price, "currency": currency, "asOf": as_of, "source": "yahoo-chart" })
+  }
+
+  #[test]
+  fn converts_usd_spot_gold_with_the_eurusd_rate() {
+    let mut requested = Vec::new();
+    let gold = gold_quote_with(None, |symbol, currency| {
+      requested.push(format!("{}:{}", symbol, currency));
+      Ok(match symbol {
+        "GC=F" => quote("GC=F", 2_160.0, "USD", 1_700_000_100),
+        _ => quote("EURUSD=X", 1.08, "USD", 1_700_000_000),
+      })
+    })
+    .unwrap();
+    assert_eq!(requested, vec!["GC=F:USD", "EURUSD=X:USD"]);
+    assert!((gold.price_per_ounce - 2_000.0).abs() < 1e-9);
+    assert!((gold.price_per_gram - 64.301_493).abs() < 1e-5);
+    assert_eq!((gold.instrument.as_str(), gold.currency.as_str(), gold.as_of), ("spot", "EUR", 1_700_000_000));
+    assert_eq!(gold.fx, Some(FxConversion { symbol: "EURUSD=X".into(), rate: 1.08, as_of: 1_700_000_000 }));
+
+    let error = gold_quote_with(Some("spot"), |symbol, _| match symbol {
+      "GC=F" => Ok(quote("GC=F", 2_160.0, "USD", 1_700_000_000)),
+      _ => Err(quote_failure("QUOTE_STALE", "alt", 422)),
+    })
+    .unwrap_err();
+    assert_eq!(error.code, "QUOTE_STALE");
+  }
+
+  #[test]
+  fn values_exchange_traded_gold_per_gram() {
+    let gold = gold_quote_with(Some(" 4gld.de "), |symbol, currency| {
+      assert_eq!(currency, "EUR");
+      Ok(quote(symbol, 66.5, currency, 1_700_000_000))
+    })
+    .unwrap();
+    assert_eq!((gold.instrument.as_str(), gold.name.as_str()), ("4GLD.DE", "Xetra-Gold"));
+    assert_eq!((gold.price_per_unit, gold.grams_per_unit, gold.price_per_gram), (Some(66.5), Some(1.0), 66.5));
+    assert!((gold.price_per_ounce - 66.5 * GRAMS_PER_TROY_OUNCE).abs() < 1e-9);
+    assert!(gold.fx.is_none());
+
+    let unknown = gold_quote_with(Some("VWCE.DE"), |_, _| panic!("no fetch for unknown instruments")).unwrap_err();
+    assert_eq!(unknown.code, "UNSUPPORTED_GOLD_INSTRUMENT");
+  }
+
+  #[test]
+  fn proxy_gold_route_validates_both_inputs() {
+    let now = crate::unix_now_seconds();
+    let chart = |symbol: &str, price: f64, currency: &str| json!({
+      "chart": { "result": [ { "meta": {
+        "symbol": symbol, "regularMarketPrice": price, "currency": currency, "regularMarketTime": now - 60
+      } } ] }
+    });
+    let upstream = MockUpstream::start(vec![
+      ("/query1/v8/finance/chart/GC%3DF", MockResponse::json(200, chart("GC=F", 2_700.0, "USD"))),
+      ("/query1/v8/finance/chart/EURUSD%3DX", MockResponse::json(200, chart("EURUSD=X", 1.125, "USD"))),
+      ("/query1/v8/finance/chart/EWG2.DE", MockResponse::json(200, chart("EWG2.DE", 70.0, "USD"))),
+    ]);
+    let proxy = ProxyUnderTest::start(&upstream);
+
+    let (status, body) = proxy.get("/gold");
+    assert_eq!(status, 200);
+    assert_eq!((body["pricePerOunce"].as_f64(), &body["fx"]["rate"]), (Some(2_400.0), &json!(1.125)));
+    assert_eq!(body["asOf"], now - 60);
+
+    // An EUR security quoted in USD violates the contract and is not converted.
+    let (status, body) = proxy.get("/gold?instrument=EWG2.DE");
+    assert_eq!((status, body["code"].as_str()), (422, Some("UNSUPPORTED_CURRENCY")));
+    let (status, body) = proxy.get("/gold?instrument=SAP.DE");
+    assert_eq!((status, body["code"].as_str()), (400, Some("UNSUPPORTED_GOLD_INSTRUMENT")));
+  }
+}
@@ -0,0 +1,636 @@
+//! Annual extension of the simulator's historical dataset. The embedded
+//! 1925-2025 series in simulator-data.js stay untouched; new years go into a
+//! user-local overlay in the app data dir that simulator-data.js merges at
+//! load time and that `script_runtime::app_source` injects into native engine
+//! runs. Values come from a user CSV and, for the fields it leaves open, from
+//! native sources: the gold return from year-end closes of a EUR gold ETC
+//! (Yahoo chart) and the short rate as annual average of the ECB deposit
+//! rate. The index level, German CPI inflation (Destatis VPI), CAPE and wage
+//! growth have no matching source the app can reach and come from the CSV;
+//! an ETF return stands in for the index only on request, and the overlay
+//! manifest then records the changed variant. Before anything is written the
+//! merged dataset runs through `createHistoricalBacktestContractProvider` on
+//! the embedded engine runtime, i.e. manifest, content hash and every
+//! `HistoricalYearRecordV1` are checked exactly as the backtest checks them.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::Manager;
+
+use crate::bank_csv::{detect_delimiter, normalize_header, split_line};
+use crate::broker_import::parse_decimal;
+use crate::distributions::{checked_chart_result, fetch_chart};
+use crate::network::{NetworkState, SharedNetworkState};
+use crate::quote_history::{civil_from_days, parse_day};
+use crate::reference_rates::{annual_average_with, RateSeries};
+use crate::script_runtime::{job_failure as failure, script_failure, JobFailure, ScriptHost, ScriptSource, SCRIPT_THREAD_STACK_BYTES};
+use crate::{quote_failure, QuoteFailure};
+
+pub(crate) const OVERLAY_FILENAME: &str = "ruhestand_suite_historical_overlay.json";
+/// `HISTORICAL_DATASET_OVERLAY_SCHEMA_VERSION` in simulator-data.js.
+const OVERLAY_SCHEMA_VERSION: &str = "HistoricalDatasetOverlayV1";
+const OVERLAY_GLOBAL: &str = "__ruhestandHistoricalDatasetOverlay";
+pub(crate) const SIMULATOR_DATA_MODULE: &str = "app/simulator/simulator-data.js";
+
+const HOST_MODULE_NAME: &str = "native/historical-dataset-host.js";
+const HOST_MODULE_SOURCE: &str = include_str!("historical_dataset_host.js");
+const HOST_OBJECT: &str = "__ruhestandHistoricalDatasetHost";
+
+const SECONDS_PER_DAY: u64 = 86_400;
+/// Xetra-Gold, physically backed and without running costs.
+const DEFAULT_GOLD_SYMBOL: &str = "4GLD.DE";
+/// Host error codes that reach the caller unchanged.
+const HOST_ERROR_CODES: [&str; 3] = ["HISTORICAL_UPDATE_INVALID", "HISTORICAL_DATASET_INVALID", "USER_DATASET_INVALID"];
+
+pub(crate) const SERIES_IDS: [&str; 6] = ["msci_eur", "inflation_de", "zinssatz_de", "lohn_de", "gold_eur_perf", "cape"];
+/// Index return in percent; chained onto the previous `msci_eur` level.
+pub(crate) const MSCI_RETURN: &str = "msci_return";
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HistoricalDatasetUpdateRequest {
+  /// Year to complete; default the last calendar year, or only the CSV years
+  /// when a CSV is given.
+  pub year: Option<i32>,
+  /// Header `jahr` plus any of `msci_return`, `msci_eur`, `inflation_de`,
+  /// `zinssatz_de`, `lohn_de`, `gold_eur_perf`, `cape`; CSV values win over
+  /// fetched ones.
+  pub csv: Option<String>,
+  /// Take everything from the CSV, fetch nothing.
+  #[serde(default)]
+  pub csv_only: bool,
+  /// EUR-listed ETF whose net total return stands in for the index in years
+  /// without CSV value; unset, the index must come from the CSV. The embedded
+  /// levels are most likely a price index, so the overlay manifest records
+  /// the changed variant for these years.
+  pub index_symbol: Option<String>,
+  pub gold_symbol: Option<String>,
+  /// Write the overlay; otherwise only the validated preview is returned.
+  #[serde(default)]
+  pub apply: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct YearInput {
+  pub year: i32,
+  /// Series id -> value in the unit of simulator-data.js.
+  pub values: BTreeMap<String, f64>,
+  pub msci_return: Option<f64>,
+  /// Series id -> origin, kept as provenance in the overlay.
+  pub sources: BTreeMap<String, String>,
+  /// Series id -> how this year's value differs from the embedded variant;
+  /// recorded in the overlay manifest.
+  #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+  pub notes: BTreeMap<String, String>,
+}
+
+impl YearInput {
+  fn new(year: i32) -> Self {
+    YearInput { year, ..Default::default() }
+  }
+
+  fn set(&mut self, series: &str, value: f64, source: &str) {
+    if series == MSCI_RETURN {
+      self.msci_return = Some(value);
+      self.sources.insert("msci_eur".to_string(), source.to_string());
+    } else {
+      self.values.insert(series.to_string(), value);
+      self.sources.insert(series.to_string(), source.to_string());
+    }
+  }
+
+  fn has(&self, series: &str) -> bool {
+    self.values.contains_key(series) || (series == "msci_eur" && self.msci_return.is_some())
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HistoricalDatasetUpdate {
+  pub years: Vec<i32>,
+  /// `HistoricalDatasetOverlayV1` with manifest, records and provenance.
+  pub overlay: Value,
+  pub written: bool,
+}
+
+fn invalid_update(message: impl Into<String>) -> QuoteFailure {
+  quote_failure("HISTORICAL_UPDATE_INVALID", message, 400)
+}
+
+fn source_incomplete(message: impl Into<String>) -> QuoteFailure {
+  quote_failure("HISTORICAL_SOURCE_INCOMPLETE", message, 404)
+}
+
+fn round2(value: f64) -> f64 {
+  (value * 100.0).round() / 100.0
+}
+
+fn year_of_day(day: i64) -> i32 {
+  civil_from_days(day)[..4].parse().unwrap_or(0)
+}
+
+/// One `YearInput` per CSV row.
+pub(crate) fn parse_dataset_csv(content: &str) -> Result<BTreeMap<i32, YearInput>, QuoteFailure> {
+  let mut rows = BTreeMap::new();
+  let mut lines = content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+  let Some((_, header_line)) = lines.next() else { return Ok(rows) };
+  let delimiter = detect_delimiter(header_line);
+  let header: Vec<String> = split_line(header_line, delimiter)
+    .map_err(invalid_update)?
+    .iter()
+    .map(|cell| normalize_header(cell))
+    .collect();
+  let year_column = header
+    .iter()
+    .position(|cell| cell == "jahr" || cell == "year")
+    .ok_or_else(|| invalid_update("CSV ohne Spalte jahr."))?;
+  let columns: Vec<(usize, &str)> = header
+    .iter()
+    .enumerate()
+    .filter_map(|(index, cell)| {
+      SERIES_IDS
+        .iter()
+        .chain(std::iter::once(&MSCI_RETURN))
+        .find(|series| normalize_header(series) == *cell)
+        .map(|series| (index, *series))
+    })
+    .collect();
+  if columns.is_empty() {
+    return Err(invalid_update(format!("CSV ohne Datenspalte ({}, {}).", MSCI_RETURN, SERIES_IDS.join(", "))));
+  }
+  for (index, line) in lines {
+    let row = index + 1;
+    let cells = split_line(line, delimiter).map_err(|e| invalid_update(format!("Zeile {}: {}", row, e)))?;
+    let year = cells
+      .get(year_column)
+      .and_then(|cell| cell.trim().parse::<i32>().ok())
+      .ok_or_else(|| invalid_update(format!("Zeile {}: Jahr fehlt.", row)))?;
+    if rows.contains_key(&year) {
+      return Err(invalid_update(format!("Zeile {}: Jahr {} doppelt.", row, year)));
+    }
+    let mut input = YearInput::new(year);
+    for &(column, series) in &columns {
+      let raw = cells.get(column).map(|cell| cell.trim()).unwrap_or("");
+      if raw.is_empty() {
+        continue;
+      }
+      let value = parse_decimal(raw).ok_or_else(|| invalid_update(format!("Zeile {}: {} ist keine Zahl.", row, series)))?;
+      input.set(series, value, "csv");
+    }
+    rows.insert(year, input);
+  }
+  Ok(rows)
+}
+
+/// Return of `year` in percent between the last closes of the two Decembers;
+/// adjusted closes where Yahoo has them, so distributions count.
+pub(crate) fn annual_return(symbol: &str, year: i32, data: &Value) -> Result<f64, QuoteFailure> {
+  let result = checked_chart_result(symbol, data)?;
+  let timestamps = result.get("timestamp").and_then(Value::as_array).map(Vec::as_slice).unwrap_or(&[]);
+  let closes = result
+    .pointer("/indicators/adjclose/0/adjclose")
+    .or_else(|| result.pointer("/indicators/quote/0/close"))
+    .and_then(Value::as_array)
+    .map(Vec::as_slice)
+    .unwrap_or(&[]);
+  let year_end = |target: i32| {
+    let december = format!("{:04}-12-01", target);
+    let next_year = format!("{:04}-01-01", target + 1);
+    timestamps
+      .iter()
+      .zip(closes)
+      .filter_map(|(time, close)| {
+        let day = civil_from_days((time.as_u64()? / SECONDS_PER_DAY) as i64);
+        let close = close.as_f64().filter(|close| close.is_finite() && *close > 0.0)?;
+        (day >= december && day < next_year).then_some(close)
+      })
+      .next_back()
+  };
+  match (year_end(year - 1), year_end(year)) {
+    (Some(start), Some(end)) => Ok(round2((end / start - 1.0) * 100.0)),
+    _ => Err(source_incomplete(format!("{}: keine Dezember-Schlusskurse {} und {}.", symbol, year - 1, year))),
+  }
+}
+
+/// CSV rows plus the requested year, completed from the native sources.
+/// `chart(symbol, query)` answers a Yahoo chart request, `fetch(url)` any
+/// other JSON source.
+pub(crate) fn collect_year_inputs<F, C>(
+  request: &HistoricalDatasetUpdateRequest,
+  now_seconds: u64,
+  mut fetch: F,
+  mut chart: C,
+) -> Result<Vec<YearInput>, QuoteFailure>
+where
+  F: FnMut(&str) -> Result<Value, QuoteFailure>,
+  C: FnMut(&str, &str) -> Result<Value, QuoteFailure>,
+{
+  let current_year = year_of_day((now_seconds / SECONDS_PER_DAY) as i64);
+  let mut inputs = parse_dataset_csv(request.csv.as_deref().unwrap_or(""))?;
+  let year = request.year.or_else(|| inputs.is_empty().then_some(current_year - 1));
+  if let Some(year) = year {
+    inputs.entry(year).or_insert_with(|| YearInput::new(year));
+  }
+  if let Some(open) = inputs.keys().find(|year| **year >= current_year) {
+    return Err(quote_failure(
+      "HISTORICAL_YEAR_INCOMPLETE",
+      format!("{} ist noch nicht abgeschlossen; nur vergangene Jahre koennen ergaenzt werden.", open),
+      400,
+    ));
+  }
+
+  if !request.csv_only {
+    let index_symbol = request
+      .index_symbol
+      .as_deref()
+      .filter(|symbol| !symbol.trim().is_empty())
+      .map(crate::normalize_yahoo_symbol)
+      .transpose()?;
+    let gold_symbol = crate::normalize_yahoo_symbol(request.gold_symbol.as_deref().unwrap_or(DEFAULT_GOLD_SYMBOL))?;
+    for input in inputs.values_mut() {
+      let year = input.year;
+      let decembers = || {
+        let start = parse_day(&format!("{:04}-12-01", year - 1)).unwrap_or(0) as u64 * SECONDS_PER_DAY;
+        let end = parse_day(&format!("{:04}-01-01", year + 1)).unwrap_or(0) as u64 * SECONDS_PER_DAY;
+        format!("period1={}&period2={}&interval=1d", start, end)
+      };
+      if let Some(index_symbol) = index_symbol.as_deref().filter(|_| !input.has("msci_eur")) {
+        let value = annual_return(index_symbol, year, &chart(index_symbol, &decembers())?)?;
+        input.set(MSCI_RETURN, value, &format!("yahoo:{}", index_symbol));
+        input.notes.insert(
+          "msci_eur".to_string(),
+          format!("net total return of the ETF {} (adjusted year-end closes, fund costs deducted) chained onto the previous level", index_symbol),
+        );
+      }
+      if !input.has("gold_eur_perf") {
+        let value = annual_return(&gold_symbol, year, &chart(&gold_symbol, &decembers())?)?;
+        input.set("gold_eur_perf", value, &format!("yahoo:{}", gold_symbol));
+        input.notes.insert("gold_eur_perf".to_string(), format!("return of the gold ETC {} between year-end closes", gold_symbol));
+      }
+      if !input.has("zinssatz_de") {
+        let value = annual_average_with(RateSeries::DepositRate, year, &mut fetch)?;
+        input.set("zinssatz_de", value, &format!("ecb:ecbDepositRate@{}", year));
+        input.notes.insert("zinssatz_de".to_string(), "calendar-day average of the ECB deposit facility rate".to_string());
+      }
+    }
+  }
+
+  for input in inputs.values() {
+    let missing: Vec<&str> = SERIES_IDS.iter().copied().filter(|series| !input.has(series)).collect();
+    if !missing.is_empty() {
+      return Err(quote_failure(
+        "HISTORICAL_UPDATE_INCOMPLETE",
+        format!("{}: es fehlen {} (als CSV-Spalten angeben).", input.year, missing.join(", ")),
+        422,
+      ));
+    }
+  }
+  Ok(inputs.into_values().collect())
+}
+
+/// The overlay file, if it carries the overlay schema. Whether it fits the
+/// embedded dataset is decided by simulator-data.js.
+pub(crate) fn read_overlay(path: &Path) -> Option<Value> {
+  fs::read_to_string(path)
+    .ok()
+    .and_then(|raw| serde_json::from_str::<Value>(&raw).ok())
+    .filter(|overlay| overlay["schemaVersion"] == OVERLAY_SCHEMA_VERSION)
+}
+
+fn write_overlay(path: &Path, overlay: &Value) -> Result<(), JobFailure> {
+  let content = serde_json::to_string_pretty(overlay).map_err(|e| failure("HISTORICAL_UPDATE_FAILED", e.to_string()))?;
+  crate::write_atomic(path, &content).map_err(|e| failure("HISTORICAL_UPDATE_FAILED", e.to_string()))
+}
+
+/// `source` with the overlay at `path` injected into simulator-data.js, so
+/// native engine runs see the same dataset as the simulator window.
+pub(crate) fn overlay_source(source: ScriptSource, path: PathBuf) -> ScriptSource {
+  Arc::new(move |name: &str| {
+    let code = source(name)?;
+    match read_overlay(&path).filter(|_| name == SIMULATOR_DATA_MODULE) {
+      Some(overlay) => Some(format!("globalThis.{} = {};{}", OVERLAY_GLOBAL, overlay, code)),
+      None => Some(code),
+    }
+  })
+}
+
+/// Answer of a dataset host method: the result, or the code and message of a
+/// rejected input.
+#[derive(Deserialize)]
+struct HostReply {
+  ok: bool,
+  #[serde(default)]
+  value: Value,
+  #[serde(default)]
+  code: String,
+  #[serde(default)]
+  message: String,
+}
+
+/// Calls `method` of the dataset host on a fresh runtime over `source`; the
+/// host's contract errors keep their code, anything else becomes `failed`.
+pub(crate) fn call_dataset_host(
+  source: &ScriptSource,
+  method: &'static str,
+  request: String,
+  failed: &'static str,
+) -> Result<Value, JobFailure> {
+  let source = source.clone();
+  let output = std::thread::Builder::new()
+    .name("ruhestand-dataset".to_string())
+    .stack_size(SCRIPT_THREAD_STACK_BYTES)
+    .spawn(move || -> Result<String, JobFailure> {
+      let host = ScriptHost::new(source, None).map_err(|e| failure(failed, e))?;
+      host.evaluate_module(HOST_MODULE_NAME, HOST_MODULE_SOURCE).map_err(|e| script_failure(failed, e))?;
+      host.call_string(HOST_OBJECT, method, (request,)).map_err(|e| script_failure(failed, e))
+    })
+    .map_err(|e| failure(failed, e.to_string()))?
+    .join()
+    .map_err(|_| failure(failed, "Datenbestand-Host abgebrochen."))??;
+  let reply: HostReply = serde_json::from_str(&output).map_err(|e| failure(failed, e.to_string()))?;
+  if reply.ok {
+    return Ok(reply.value);
+  }
+  let code = HOST_ERROR_CODES.into_iter().find(|code| *code == reply.code).unwrap_or(failed);
+  Err(failure(code, reply.message))
+}
+
+/// Merges `inputs` into `existing` (or a fresh overlay) and validates the
+/// result on `source`, which must be the plain bundled sources.
+pub(crate) fn build_overlay(
+  source: &ScriptSource,
+  existing: Option<&Value>,
+  inputs: &[YearInput],
+  created_at: &str,
+) -> Result<Value, JobFailure> {
+  let request = serde_json::json!({ "existing": existing, "years": inputs, "createdAt": created_at }).to_string();
+  call_dataset_host(source, "prepare", request, "HISTORICAL_UPDATE_FAILED")
+}
+
+pub(crate) fn update_historical_dataset_with(
+  network: &NetworkState,
+  source: &ScriptSource,
+  overlay_path: &Path,
+  request: &HistoricalDatasetUpdateRequest,
+) -> Result<HistoricalDatasetUpdate, JobFailure> {
+  let inputs = collect_year_inputs(
+    request,
+    network.now_seconds(),
+    |url| crate::fetch_json(network, url),
+    |symbol, query| fetch_chart(network, symbol, query),
+  )
+  .map_err(|error| failure(error.code, error.message))?;
+  let created_at = civil_from_days((network.now_seconds() / SECONDS_PER_DAY) as i64);
+  let overlay = build_overlay(source, read_overlay(overlay_path).as_ref(), &inputs, &created_at)?;
+  if request.apply {
+    write_overlay(overlay_path, &overlay)?;
+  }
+  Ok(HistoricalDatasetUpdate { years: inputs.iter().map(|input| input.year).collect(), overlay, written: request.apply })
+}
+
+fn overlay_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+  Ok(app.path().app_data_dir().map_err(|e| e.to_string())?.join(OVERLAY_FILENAME))
+}
+
+#[tauri::command]
+pub async fn update_historical_dataset(
+  app: tauri::AppHandle,
+  state: tauri::State<'_, SharedNetworkState>,
+  request: HistoricalDatasetUpdateRequest,
+) -> Result<HistoricalDatasetUpdate, String> {
+  let network = state.inner().clone();
+  let source = crate::script_runtime::bundled_source(&app);
+  let path = overlay_path(&app)?;
+  tauri::async_runtime::spawn_blocking(move || update_historical_dataset_with(&network, &source, &path, &request))
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn load_historical_dataset_overlay(app: tauri::AppHandle) -> Result<Option<Value>, String> {
+  Ok(read_overlay(&overlay_path(&app)?))
+}
+
+/// Back to the embedded dataset; `false` if there was no overlay.
+#[tauri::command]
+pub fn remove_historical_dataset_overlay(app: tauri::AppHandle) -> Result<bool, String> {
+  let path = overlay_path(&app)?;
+  match fs::remove_file(&path) {
+    Ok(()) => Ok(true),
+    Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(false),
+    Err(error) => Err(error.to_string()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::script_runtime::{checkout_root, directory_source};
+  use serde_json::json;
+
+  /// 2027-03-01 12:00 UTC.
+  const NOW: u64 = 1_803_902_400;
+
+  fn day_seconds(day: &str) -> u64 {
+    parse_day(day).unwrap() as u64 * SECONDS_PER_DAY + 9 * 3_600
+  }
+
+  fn chart(symbol: &str, closes: &[(&str, Option<f64>)]) -> Value {
+    let timestamps: Vec<u64> = closes.iter().map(|(day, _)| day_seconds(day)).collect();
+    let values: Vec<Value> = closes.iter().map(|(_, close)| json!(close)).collect();
+    json!({ "chart": { "result": [ {
+      "meta": { "symbol": symbol, "currency": "EUR" },
+      "timestamp": timestamps,
+      "indicators": { "quote": [ { "close": values.clone() } ], "adjclose": [ { "adjclose": values } ] }
+    } ] } })
+  }
+
+  /// Deposit rate of 2 % from before 2026 on, cut to 1.5 % on 2026-07-01.
+  fn source(url: &str) -> Result<Value, QuoteFailure> {
+    assert!(url.contains("/FM/B.U2.EUR.4F.KR.DFR.LEV?") && url.ends_with("&endPeriod=2026-12-31"), "{}", url);
+    let days = ["2025-12-30", "2026-07-01", "2026-12-31"];
+    Ok(json!({
+      "dataSets": [{ "series": { "0:0:0:0:0:0:0": { "observations": { "0": [2.0], "1": [1.5], "2": [1.5] } } } }],
+      "structure": { "dimensions": { "observation": [{ "id": "TIME_PERIOD", "values": days.map(|day| json!({ "id": day })) }] } }
+    }))
+  }
+
+  fn chart_source(symbol: &str, query: &str) -> Result<Value, QuoteFailure> {
+    assert!(query.contains("period1=1764547200&period2=1798761600&interval=1d"), "{}", query);
+    let (start, end) = if symbol == "EUNL.DE" { (100.0, 108.0) } else { (80.0, 92.0) };
+    Ok(chart(symbol, &[
+      ("2025-12-30", Some(start - 1.0)),
+      ("2025-12-31", Some(start)),
+      ("2026-06-30", Some(1.0)),
+      ("2026-12-30", Some(end)),
+      ("2026-12-31", None),
+    ]))
+  }
+
+  #[test]
+  fn completes_csv_rows_from_the_native_sources() {
+    let mut fetched = 0;
+    let request = HistoricalDatasetUpdateRequest {
+      csv: Some("Jahr;MSCI_Return;Lohn_DE;CAPE;Inflation_DE\n2026;6,5;3,1;35,2;2,3\n".to_string()),
+      ..Default::default()
+    };
+    let fetch = |url: &str| {
+      fetched += 1;
+      source(url)
+    };
+    let inputs = collect_year_inputs(&request, NOW, fetch, chart_source).unwrap();
+    assert_eq!(inputs.len(), 1);
+    let input = &inputs[0];
+    assert_eq!((input.year, input.msci_return), (2026, Some(6.5)));
+    assert_eq!(input.values["gold_eur_perf"], 15.0);
+    // 181 days at 2 % and 184 days at 1.5 %.
+    assert_eq!((input.values["inflation_de"], input.values["zinssatz_de"]), (2.3, 1.75));
+    assert_eq!((input.values["lohn_de"], input.values["cape"]), (3.1, 35.2));
+    assert_eq!((input.sources["msci_eur"].as_str(), input.sources["cape"].as_str()), ("csv", "csv"));
+    assert_eq!(input.sources["zinssatz_de"], "ecb:ecbDepositRate@2026");
+    assert_eq!(input.notes.keys().collect::<Vec<_>>(), ["gold_eur_perf", "zinssatz_de"]);
+    assert_eq!(fetched, 1);
+
+    // The index, inflation, CAPE and wages have no matching native source.
+    let error = collect_year_inputs(&HistoricalDatasetUpdateRequest::default(), NOW, source, chart_source).unwrap_err();
+    assert_eq!(error.code, "HISTORICAL_UPDATE_INCOMPLETE");
+    assert!(error.message.starts_with("2026: es fehlen msci_eur, inflation_de, lohn_de, cape"), "{}", error.message);
+    // An ETF stands in for the index only on request, with the variant noted.
+    let proxy = HistoricalDatasetUpdateRequest { index_symbol: Some("eunl.de".to_string()), ..request };
+    let input = &collect_year_inputs(&proxy, NOW, source, chart_source).unwrap()[0];
+    assert_eq!((input.msci_return, input.sources["msci_eur"].as_str()), (Some(6.5), "csv"));
+    let csv = "Jahr;Lohn_DE;CAPE;Inflation_DE\n2026;3,1;35,2;2,3\n".to_string();
+    let proxy = HistoricalDatasetUpdateRequest { csv: Some(csv), ..proxy };
+    let input = &collect_year_inputs(&proxy, NOW, source, chart_source).unwrap()[0];
+    assert_eq!((input.msci_return, input.sources["msci_eur"].as_str()), (Some(8.0), "yahoo:EUNL.DE"));
+    assert!(input.notes["msci_eur"].starts_with("net total return of the ETF EUNL.DE"), "{}", input.notes["msci_eur"]);
+
+    let open_year = HistoricalDatasetUpdateRequest { year: Some(2027), csv_only: true, ..Default::default() };
+    let error = collect_year_inputs(&open_year, NOW, |_| unreachable!(), |_, _| unreachable!()).unwrap_err();
+    assert_eq!(error.code, "HISTORICAL_YEAR_INCOMPLETE");
+    let duplicate = HistoricalDatasetUpdateRequest { csv: Some("jahr,cape\n2026,30\n2026,31".to_string()), ..Default::default() };
+    let error = collect_year_inputs(&duplicate, NOW, |_| unreachable!(), |_, _| unreachable!()).unwrap_err();
+    assert_eq!((error.code, error.message.as_str()), ("HISTORICAL_UPDATE_INVALID", "Zeile 3: Jahr 2026 doppelt."));
+  }
+
+  fn full_year(year: i32, msci_return: f64, cape: f64) -> YearInput {
+    let mut input = YearInput::new(year);
+    input.set(MSCI_RETURN, msci_return, "csv");
+    for (series, value) in [("inflation_de", 2.1), ("zinssatz_de", 2.0), ("lohn_de", 3.1), ("gold_eur_perf", 15.0), ("cape", cape)] {
+      input.set(series, value, "csv");
+    }
+    input
+  }
+
+  const PROBE_MODULE: &str = r#"
+import { DATASET_META, HISTORICAL_DATA, HISTORICAL_DATA_MANIFEST } from '../app/simulator/simulator-data.js';
+import { createHistoricalBacktestContractProvider } from '../app/simulator/historical-backtest-contract.js';
+globalThis.__probe = {
+    run: () => {
+        const provider = createHistoricalBacktestContractProvider();
+        return JSON.stringify({
+            coverage: DATASET_META.historicalData.coverageYears,
+            revision: HISTORICAL_DATA_MANIFEST.revision,
+            msci: HISTORICAL_DATA[2026]?.msci_eur ?? null,
+            recordYear: provider.getRecord(2026)?.year ?? null
+        });
+    }
+};
+"#;
+
+  fn probe(source: ScriptSource) -> Value {
+    std::thread::Builder::new()
+      .stack_size(SCRIPT_THREAD_STACK_BYTES)
+      .spawn(move || {
+        let host = ScriptHost::new(source, None).unwrap();
+        host.evaluate_module("native/probe.js", PROBE_MODULE).unwrap();
+        serde_json::from_str(&host.call_string("__probe", "run", ()).unwrap()).unwrap()
+      })
+      .unwrap()
+      .join()
+      .unwrap()
+  }
+
+  #[test]
+  fn validated_overlay_extends_the_dataset_for_native_runs() {
+    let source = directory_source(checkout_root());
+    let overlay = build_overlay(&source, None, &[full_year(2026, 8.0, 35.2)], "2027-03-01").unwrap();
+    assert_eq!(overlay["schemaVersion"], OVERLAY_SCHEMA_VERSION);
+    assert_eq!(overlay["records"]["2026"]["msci_eur"], json!(2844.5));
+    assert_eq!(overlay["manifest"]["period"]["endYear"], 2026);
+    assert_eq!(overlay["manifest"]["series"]["cape"]["period"]["endYear"], 2026);
+    assert_eq!(overlay["manifest"]["revision"], "2026-07-18.1+user.2027-03-01");
+    assert_ne!(overlay["manifest"]["contentHash"]["value"], overlay["baseContentHash"]);
+    assert_eq!(overlay["provenance"]["2026"]["cape"], "csv");
+    assert_eq!(overlay["manifest"]["series"]["msci_eur"]["variant"]["status"], "unresolved");
+
+    // Years from a source of another variant are recorded in the manifest.
+    let mut proxied = full_year(2026, 8.0, 35.2);
+    proxied.notes.insert("msci_eur".to_string(), "net total return of the ETF EUNL.DE".to_string());
+    let noted = build_overlay(&source, None, &[proxied], "2027-03-01").unwrap();
+    let msci = &noted["manifest"]["series"]["msci_eur"];
+    assert_eq!(msci["variant"], json!({
+      "status": "known",
+      "value": "Mixed: 1925-2025 embedded (unresolved); 2026: net total return of the ETF EUNL.DE."
+    }));
+    assert!(msci["transformation"]["value"].as_str().unwrap().ends_with(" Variant change in 2026: net total return of the ETF EUNL.DE."));
+    assert_eq!(noted["notes"]["2026"]["msci_eur"], "net total return of the ETF EUNL.DE");
+    assert_eq!(noted["manifest"]["series"]["cape"]["variant"]["status"], "unresolved");
+
+    let dir = std::env::temp_dir().join(format!("ruhestand-dataset-{}", std::process::id()));
+    let path = dir.join(OVERLAY_FILENAME);
+    write_overlay(&path, &overlay).unwrap();
+    assert_eq!(read_overlay(&path).as_ref(), Some(&overlay));
+    let embedded = probe(source.clone());
+    assert_eq!((embedded["coverage"].clone(), embedded["msci"].clone()), (json!([1925, 2025]), Value::Null));
+    let extended = probe(overlay_source(source.clone(), path.clone()));
+    assert_eq!(extended["coverage"], json!([1925, 2026]));
+    assert_eq!((extended["msci"].clone(), extended["recordYear"].clone()), (json!(2844.5), json!(2026)));
+
+    // A second run extends the stored overlay.
+    let next = build_overlay(&source, read_overlay(&path).as_ref(), &[full_year(2027, -10.0, 30.0)], "2028-02-01").unwrap();
+    assert_eq!(next["manifest"]["period"]["endYear"], 2027);
+    assert_eq!(next["records"]["2027"]["msci_eur"], json!(2560.05));
+    assert_eq!(next["records"]["2026"], overlay["records"]["2026"]);
+    // A year with successors is only updated together with them, so the
+    // index stays chained.
+    let alone = build_overlay(&source, Some(&next), &[full_year(2026, 10.0, 35.2)], "2028-03-01").unwrap_err();
+    assert_eq!((alone.code, alone.message.as_str()), (
+      "HISTORICAL_UPDATE_INVALID",
+      "2026 hat bereits Folgejahre bis 2027; sie muessen mit aktualisiert werden (2027 fehlt)."
+    ));
+    let rechained =
+      build_overlay(&source, Some(&next), &[full_year(2026, 10.0, 35.2), full_year(2027, -10.0, 30.0)], "2028-03-01").unwrap();
+    assert_eq!((rechained["records"]["2026"]["msci_eur"].clone(), rechained["records"]["2027"]["msci_eur"].clone()), (json!(2897.18), json!(2607.46)));
+
+    // A tampered overlay is ignored by simulator-data.js.
+    let mut tampered = overlay.clone();
+    tampered["baseContentHash"] = json!("0".repeat(64));
+    write_overlay(&path, &tampered).unwrap();
+    assert_eq!(probe(overlay_source(source, path))["coverage"], json!([1925, 2025]));
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn rejects_gaps_and_contract_violations_before_writing() {
+    let source = directory_source(checkout_root());
+    let gap = build_overlay(&source, None, &[full_year(2027, 5.0, 30.0)], "2028-02-01").unwrap_err();
+    assert_eq!(gap.code, "HISTORICAL_UPDATE_INVALID");
+    assert!(gap.message.contains("2027 schliesst nicht an 2025 an"), "{}", gap.message);
+    let embedded = build_overlay(&source, None, &[full_year(2025, 5.0, 30.0)], "2028-02-01").unwrap_err();
+    assert_eq!(embedded.code, "HISTORICAL_UPDATE_INVALID");
+    let contract = build_overlay(&source, None, &[full_year(2026, 5.0, 0.0)], "2028-02-01").unwrap_err();
+    assert_eq!(contract.code, "HISTORICAL_DATASET_INVALID");
+    assert!(contract.message.starts_with("HISTORICAL_INDEX_LEVEL_INVALID: "), "{}", contract.message);
+
+    let data = chart("EUNL.DE", &[("2026-12-30", Some(100.0))]);
+    assert_eq!(annual_return("EUNL.DE", 2026, &data).unwrap_err().code, "HISTORICAL_SOURCE_INCOMPLETE");
+    assert_eq!(annual_return("VWCE.DE", 2026, &data).unwrap_err().code, "SYMBOL_MISMATCH");
+  }
+}
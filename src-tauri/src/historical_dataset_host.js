@@ -0,0 +1,343 @@
+// Native historical dataset host, evaluated inside the embedded QuickJS
+// runtime (see historical_dataset.rs and user_datasets.rs). It extends the
+// embedded dataset with new years or builds a complete user dataset, and
+// validates the result with the same contract provider the backtest uses, so
+// nothing is written that the simulator would reject. Every method answers
+// JSON `{ ok: true, value }` or, for rejected input, `{ ok: false, code,
+// message }`; anything else it throws is a host failure.
+
+import {
+    EMBEDDED_HISTORICAL_DATA_MANIFEST,
+    HISTORICAL_DATA,
+    HISTORICAL_DATASET_OVERLAY_SCHEMA_VERSION,
+    USER_HISTORICAL_DATASET_SCHEMA_VERSION
+} from '../app/simulator/simulator-data.js';
+import {
+    HistoricalDataContractError,
+    computeHistoricalDatasetHash,
+    createHistoricalBacktestContractProvider
+} from '../app/simulator/historical-backtest-contract.js';
+
+const SERIES_IDS = ['msci_eur', 'inflation_de', 'zinssatz_de', 'lohn_de', 'gold_eur_perf', 'cape'];
+
+class HostError extends Error {
+    constructor(code, message) {
+        super(message);
+        this.code = code;
+    }
+}
+
+function updateError(message) {
+    return new HostError('HISTORICAL_UPDATE_INVALID', message);
+}
+
+function importError(message) {
+    return new HostError('USER_DATASET_INVALID', message);
+}
+
+function hostMethod(method) {
+    return requestJson => {
+        try {
+            return JSON.stringify({ ok: true, value: method(JSON.parse(requestJson)) });
+        } catch (error) {
+            if (error instanceof HostError) {
+                return JSON.stringify({ ok: false, code: error.code, message: error.message });
+            }
+            throw error;
+        }
+    };
+}
+
+function cloneJson(value) {
+    return JSON.parse(JSON.stringify(value));
+}
+
+function roundLevel(value) {
+    return Math.round(value * 100) / 100;
+}
+
+// Years of an earlier overlay stay unless they are updated again; an overlay
+// built on another embedded revision is dropped.
+function baseDataset(existing) {
+    const records = cloneJson(HISTORICAL_DATA);
+    const provenance = {};
+    const notes = {};
+    if (existing?.baseContentHash === EMBEDDED_HISTORICAL_DATA_MANIFEST.contentHash.value) {
+        Object.assign(records, cloneJson(existing.records || {}));
+        Object.assign(provenance, cloneJson(existing.provenance || {}));
+        Object.assign(notes, cloneJson(existing.notes || {}));
+    }
+    return { records, provenance, notes };
+}
+
+function yearsLabel(years) {
+    return segmentsOf(years)
+        .map(({ startYear, endYear }) => (startYear === endYear ? `${startYear}` : `${startYear}-${endYear}`))
+        .join(', ');
+}
+
+// Overlay years whose value differs from the embedded variant (an ETF return
+// for a price index, an annual average for an unspecified rate) change the
+// series variant; manifest variant and transformation say which years.
+function overlayManifest(endYear, contentHash, createdAt, notes) {
+    const embedded = EMBEDDED_HISTORICAL_DATA_MANIFEST;
+    const embeddedEnd = embedded.period.endYear;
+    const manifest = cloneJson(embedded);
+    const revision = `${embedded.revision}+user.${createdAt}`;
+    const overlayNote = ` Years ${embeddedEnd + 1}-${endYear} come from the user-local overlay; see its provenance.`;
+    manifest.revision = revision;
+    manifest.period.endYear = endYear;
+    manifest.contentHash.value = contentHash;
+    for (const [seriesId, series] of Object.entries(manifest.series)) {
+        series.period.endYear = endYear;
+        series.revision = revision;
+        series.transformation.value += overlayNote;
+        const yearsByNote = new Map();
+        for (let year = embeddedEnd + 1; year <= endYear; year++) {
+            const note = notes[year]?.[seriesId];
+            if (note) yearsByNote.set(note, [...(yearsByNote.get(note) || []), year]);
+        }
+        if (yearsByNote.size === 0) continue;
+        const changes = [...yearsByNote].map(([note, years]) => `${yearsLabel(years)}: ${note}`).join('; ');
+        const embeddedVariant = series.variant.status === 'known' ? series.variant.value : series.variant.status;
+        series.variant = {
+            status: 'known',
+            value: `Mixed: ${embedded.period.startYear}-${embeddedEnd} embedded (${embeddedVariant}); ${changes}.`
+        };
+        series.transformation.value += ` Variant change in ${changes}.`;
+    }
+    return manifest;
+}
+
+function validate(records, manifest) {
+    try {
+        return createHistoricalBacktestContractProvider({ records, manifest });
+    } catch (error) {
+        if (error instanceof HistoricalDataContractError) {
+            throw new HostError('HISTORICAL_DATASET_INVALID', `${error.code}: ${error.message}`);
+        }
+        throw error;
+    }
+}
+
+// Stored years stay stable: a year that already has successors can only be
+// updated together with all of them, so msci_eur is chained again from the
+// new level instead of leaving a jump.
+function prepare(request) {
+    const embeddedEnd = EMBEDDED_HISTORICAL_DATA_MANIFEST.period.endYear;
+    const entries = (Array.isArray(request.years) ? request.years : []).slice().sort((a, b) => a.year - b.year);
+    if (entries.length === 0) {
+        throw updateError('Keine Jahre angegeben.');
+    }
+    const { records, provenance, notes } = baseDataset(request.existing);
+    const storedEnd = Math.max(...Object.keys(records).map(Number));
+    const updated = new Set(entries.map(entry => entry.year));
+    let endYear = storedEnd;
+    for (const entry of entries) {
+        const year = entry.year;
+        if (!Number.isInteger(year) || year <= embeddedEnd) {
+            throw updateError(`${year} liegt im eingebetteten Datenbestand (bis ${embeddedEnd}).`);
+        }
+        if (year > endYear + 1) {
+            throw updateError(`${year} schliesst nicht an ${endYear} an.`);
+        }
+        for (let later = year + 1; later <= storedEnd; later++) {
+            if (!updated.has(later)) {
+                throw updateError(`${year} hat bereits Folgejahre bis ${storedEnd}; sie muessen mit aktualisiert werden (${later} fehlt).`);
+            }
+        }
+        const values = { ...entry.values };
+        if (!Number.isFinite(values.msci_eur) && Number.isFinite(entry.msciReturn)) {
+            values.msci_eur = roundLevel(records[year - 1].msci_eur * (1 + entry.msciReturn / 100));
+        }
+        const record = {};
+        for (const seriesId of SERIES_IDS) {
+            if (!Number.isFinite(values[seriesId])) {
+                throw updateError(`${year}: ${seriesId} fehlt.`);
+            }
+            record[seriesId] = values[seriesId];
+        }
+        records[year] = record;
+        provenance[year] = { ...entry.sources };
+        notes[year] = { ...entry.notes };
+        endYear = Math.max(endYear, year);
+    }
+
+    const manifest = overlayManifest(endYear, computeHistoricalDatasetHash(records), request.createdAt, notes);
+    validate(records, manifest);
+    const overlayRecords = {};
+    const overlayProvenance = {};
+    const overlayNotes = {};
+    for (let year = embeddedEnd + 1; year <= endYear; year++) {
+        overlayRecords[year] = records[year];
+        overlayProvenance[year] = provenance[year] || {};
+        if (Object.keys(notes[year] || {}).length > 0) overlayNotes[year] = notes[year];
+    }
+    return {
+        schemaVersion: HISTORICAL_DATASET_OVERLAY_SCHEMA_VERSION,
+        baseRevision: EMBEDDED_HISTORICAL_DATA_MANIFEST.revision,
+        baseContentHash: EMBEDDED_HISTORICAL_DATA_MANIFEST.contentHash.value,
+        createdAt: request.createdAt,
+        manifest,
+        records: overlayRecords,
+        provenance: overlayProvenance,
+        notes: overlayNotes
+    };
+}
+
+const UNRESOLVED = Object.freeze({ status: 'unresolved', value: null });
+
+function known(value) {
+    return typeof value === 'string' && value.trim() !== '' ? { status: 'known', value: value.trim() } : null;
+}
+
+function segmentsOf(years) {
+    const segments = [];
+    for (const year of years) {
+        const last = segments[segments.length - 1];
+        if (last && last.endYear === year - 1) {
+            last.endYear = year;
+        } else {
+            segments.push({ startYear: year, endYear: year });
+        }
+    }
+    return segments;
+}
+
+function importedTransformation(seriesId, unit, chained) {
+    if (chained) return 'Index levels chained from imported annual returns in percent, base 100 before the first year.';
+    const target = EMBEDDED_HISTORICAL_DATA_MANIFEST.series[seriesId].unit;
+    return unit && unit !== target
+        ? `Imported as ${unit}, converted to ${target}.`
+        : 'Identity projection from imported annual values.';
+}
+
+// Series metadata of a user dataset: units, frequency and missingness rules
+// follow the embedded manifest, everything describing the data itself comes
+// from the import or stays unresolved.
+function userSeriesManifest(seriesId, request, period, quality, chained) {
+    const template = EMBEDDED_HISTORICAL_DATA_MANIFEST.series[seriesId];
+    const meta = request.series?.[seriesId] || {};
+    return {
+        id: seriesId,
+        label: meta.label?.trim() || `${request.label}: ${seriesId}`,
+        unit: template.unit,
+        variant: known(meta.variant) || { ...UNRESOLVED },
+        currency: known(meta.currency)
+            || (template.currency.status === 'not_applicable' ? cloneJson(template.currency) : { ...UNRESOLVED }),
+        region: known(meta.region) || { ...UNRESOLVED },
+        frequency: cloneJson(template.frequency),
+        period: { ...period },
+        source: known(meta.source) || known(`User import: ${request.source?.fileName || request.label}`),
+        license: known(meta.license) || { ...UNRESOLVED },
+        transformation: known(importedTransformation(seriesId, request.units?.[seriesId], chained)),
+        estimatedSegments: segmentsOf(quality.estimated[seriesId]),
+        missingness: {
+            required: true,
+            rule: template.missingness.rule,
+            fallbackZeroSegments: segmentsOf(quality.fallbackZero[seriesId]),
+            zeroValuePolicy: template.missingness.zeroValuePolicy
+        },
+        revision: request.importedAt
+    };
+}
+
+// Builds a complete dataset from imported rows. Years must be contiguous and
+// cover at least the backtest lookback plus one year; a row's quality marks
+// all its series as estimated, or its zero values as fallback zeros.
+function importDataset(request) {
+    const lookbackYears = EMBEDDED_HISTORICAL_DATA_MANIFEST.lookback.backtestYears;
+    const rows = (Array.isArray(request.rows) ? request.rows : []).slice().sort((a, b) => a.year - b.year);
+    if (rows.length <= lookbackYears) {
+        throw importError(`Mindestens ${lookbackYears + 1} Jahre noetig (${lookbackYears} Jahre Vorlauf fuer den Backtest).`);
+    }
+    const records = {};
+    const quality = {
+        estimated: Object.fromEntries(SERIES_IDS.map(seriesId => [seriesId, []])),
+        fallbackZero: Object.fromEntries(SERIES_IDS.map(seriesId => [seriesId, []]))
+    };
+    let chained = false;
+    let previous = null;
+    for (const row of rows) {
+        const year = row.year;
+        if (!Number.isInteger(year)) {
+            throw importError(`Ungueltiges Jahr ${year}.`);
+        }
+        if (previous && year !== previous.year + 1) {
+            throw importError(`${year} schliesst nicht an ${previous.year} an.`);
+        }
+        const values = { ...row.values };
+        if (!Number.isFinite(values.msci_eur) && Number.isFinite(row.msciReturn)) {
+            const level = previous ? previous.record.msci_eur : 100;
+            values.msci_eur = Math.round(level * (1 + row.msciReturn / 100) * 10000) / 10000;
+            chained = true;
+        }
+        const record = {};
+        for (const seriesId of SERIES_IDS) {
+            if (!Number.isFinite(values[seriesId])) {
+                throw importError(`${year}: ${seriesId} fehlt.`);
+            }
+            record[seriesId] = values[seriesId];
+        }
+        if (row.quality === 'estimated') {
+            SERIES_IDS.forEach(seriesId => quality.estimated[seriesId].push(year));
+        } else if (row.quality === 'fallback_zero') {
+            const zeros = SERIES_IDS.filter(seriesId => record[seriesId] === 0);
+            if (zeros.length === 0) {
+                throw importError(`${year}: fallback_zero ohne Nullwert.`);
+            }
+            zeros.forEach(seriesId => quality.fallbackZero[seriesId].push(year));
+        } else if (row.quality && row.quality !== 'present') {
+            throw importError(`${year}: Qualitaetsstatus ${row.quality} nicht importierbar.`);
+        }
+        records[year] = record;
+        previous = { year, record };
+    }
+
+    const contentHash = computeHistoricalDatasetHash(records);
+    const period = { startYear: rows[0].year, endYear: previous.year };
+    const embedded = EMBEDDED_HISTORICAL_DATA_MANIFEST;
+    const manifest = {
+        schemaVersion: embedded.schemaVersion,
+        datasetId: `user-${contentHash.slice(0, 12)}`,
+        revision: request.importedAt,
+        period,
+        lookback: cloneJson(embedded.lookback),
+        contentHash: { algorithm: embedded.contentHash.algorithm, value: contentHash },
+        documentation: embedded.documentation,
+        series: Object.fromEntries(SERIES_IDS.map(seriesId => [
+            seriesId,
+            userSeriesManifest(seriesId, request, period, quality, chained && seriesId === 'msci_eur')
+        ]))
+    };
+    validate(records, manifest);
+    return {
+        schemaVersion: USER_HISTORICAL_DATASET_SCHEMA_VERSION,
+        datasetId: manifest.datasetId,
+        label: request.label,
+        importedAt: request.importedAt,
+        fingerprint: cloneJson(manifest.contentHash),
+        source: request.source || {},
+        manifest,
+        records
+    };
+}
+
+// Re-validates a stored user dataset before a run uses it.
+function checkDataset(document) {
+    if (document?.schemaVersion !== USER_HISTORICAL_DATASET_SCHEMA_VERSION) {
+        throw importError(`Kein ${USER_HISTORICAL_DATASET_SCHEMA_VERSION}.`);
+    }
+    if (document.datasetId !== document.manifest?.datasetId
+        || document.fingerprint?.value !== document.manifest?.contentHash?.value) {
+        throw importError(`${document.datasetId}: Kennung oder Fingerprint passt nicht zum Manifest.`);
+    }
+    const provider = validate(document.records, document.manifest);
+    return { datasetId: provider.datasetId, revision: provider.revision, contentHash: provider.contentHash };
+}
+
+globalThis.__ruhestandHistoricalDatasetHost = {
+    prepare: hostMethod(prepare),
+    importDataset: hostMethod(importDataset),
+    checkDataset: hostMethod(checkDataset)
+};
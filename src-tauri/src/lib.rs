@@ -1,14 +1,45 @@
 #![cfg_attr(mobile, tauri::mobile_entry_point)]
 
+mod auto_optimize;
+mod backtest;
+mod bank_csv;
+mod bank_import;
+mod broker_import;
+mod depot_valuation;
+mod cli;
+mod distributions;
+mod gold_quote;
+mod historical_dataset;
+mod monte_carlo;
+mod monte_carlo_export;
+mod network;
+mod network_audit;
+mod quote_history;
+mod reference_rates;
+mod sale_planner;
+mod script_runtime;
+mod symbol_resolver;
+mod tax;
+mod upstream_fixtures;
+mod user_datasets;
+mod vorabpauschale;
+#[cfg(test)]
+mod mock_upstream;
+
+pub use cli::run_cli;
+
 use log::LevelFilter;
+use network::{NetworkState, SharedNetworkState};
+use network_audit::UpstreamObservation;
+use upstream_fixtures::{UpstreamFixture, UpstreamFixtureMode};
 use serde::Deserialize;
 use serde_json::json;
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tauri::{Emitter, Manager};
 use tiny_http::{Header, Method, Response, Server, StatusCode};
 
@@ -17,6 +48,9 @@ const SNAPSHOT_STATE_FILENAME: &str = "ruhestand_suite_snapshots.json";
 const QUOTE_MAX_AGE_SECONDS: u64 = 7 * 24 * 60 * 60;
 const QUOTE_FUTURE_TOLERANCE_SECONDS: u64 = 5 * 60;
 const UPSTREAM_TIMEOUT_SECONDS: u64 = 4;
+const SEARCH_QUERY_MAX_CHARS: usize = 64;
+const SEARCH_DEFAULT_LIMIT: usize = 10;
+const SEARCH_MAX_LIMIT: usize = 50;
 
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -60,6 +94,19 @@ fn load_app_state(app: tauri::AppHandle, target: Option<StateTarget>) -> Result<
   fs::read_to_string(&file_path).map_err(|e| e.to_string())
 }
 
+/// Writes `content` to `<file>.tmp` and renames it over `path`, so readers
+/// never see a half-written file. Missing parent directories are created.
+pub(crate) fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+  if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+    fs::create_dir_all(parent)?;
+  }
+  let mut tmp_path = path.as_os_str().to_owned();
+  tmp_path.push(".tmp");
+  let tmp_path = PathBuf::from(tmp_path);
+  fs::write(&tmp_path, content)?;
+  fs::rename(&tmp_path, path)
+}
+
 #[tauri::command]
 fn save_app_state(app: tauri::AppHandle, content: String, target: Option<StateTarget>) -> Result<(), String> {
   let file_path = app_state_path(&app, target)?;
@@ -211,10 +258,100 @@ fn normalize_yahoo_symbol(symbol: &str) -> Result<String, QuoteFailure> {
   Ok(normalized)
 }
 
-fn fetch_json(url: &str) -> Result<serde_json::Value, QuoteFailure> {
-  let client = reqwest::blocking::Client::builder()
+fn offline_mode_failure() -> QuoteFailure {
+  quote_failure("OFFLINE_MODE", "Offline-Modus aktiv: keine Verbindung zu externen Diensten.", 503)
+}
+
+fn unix_now_seconds() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|duration| duration.as_secs())
+    .unwrap_or(0)
+}
+
+/// Single choke point for native upstream calls: serves replay fixtures,
+/// enforces offline mode and appends every live attempt to the audit log.
+fn fetch_json(network: &NetworkState, url: &str) -> Result<serde_json::Value, QuoteFailure> {
+  let fixtures = network.active_fixtures();
+  let fixture_dir = fixtures.directory.as_deref();
+  if fixtures.mode == UpstreamFixtureMode::Replay {
+    return replay_fixture(fixture_dir, url);
+  }
+  let record_dir = fixture_dir.filter(|_| fixtures.mode == UpstreamFixtureMode::Record);
+
+  let started = Instant::now();
+  let mut observation = UpstreamObservation::default();
+  let result = if network.is_offline() {
+    Err(offline_mode_failure())
+  } else {
+    fetch_json_uncounted(network, url, &mut observation, record_dir)
+  };
+  network.audit().record(
+    url,
+    unix_now_seconds(),
+    observation,
+    result.as_ref().err().map(|error| error.code),
+    started.elapsed(),
+  );
+  result
+}
+
+fn configure_upstream_client(builder: reqwest::blocking::ClientBuilder) -> reqwest::blocking::ClientBuilder {
+  builder
     .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64)")
     .timeout(Duration::from_secs(UPSTREAM_TIMEOUT_SECONDS))
+}
+
+fn replay_fixture(fixture_dir: Option<&std::path::Path>, url: &str) -> Result<serde_json::Value, QuoteFailure> {
+  let fixture = fixture_dir
+    .and_then(|dir| upstream_fixtures::read_fixture(dir, url))
+    .ok_or_else(|| quote_failure(
+      "FIXTURE_MISSING",
+      format!("Keine aufgezeichnete Antwort fuer {}.", upstream_fixtures::fixture_file_name(url)),
+      404,
+    ))?;
+  if !(200..300).contains(&fixture.status) {
+    return Err(upstream_status_failure(url, fixture.status));
+  }
+  parse_upstream_json(url, &fixture.body)
+}
+
+/// Host named in upstream error messages; Yahoo, ECB and Bundesbank all
+/// share `fetch_json`.
+fn upstream_host(url: &str) -> String {
+  reqwest::Url::parse(url).ok().and_then(|parsed| parsed.host_str().map(str::to_string)).unwrap_or_else(|| url.to_string())
+}
+
+fn upstream_status_failure(url: &str, status: u16) -> QuoteFailure {
+  let host = upstream_host(url);
+  match status {
+    404 => quote_failure("SYMBOL_NOT_FOUND", format!("{}: nicht gefunden (HTTP 404).", host), 404),
+    429 => quote_failure("PROVIDER_RATE_LIMITED", format!("{}: Abruflimit erreicht.", host), 429),
+    500..=599 => quote_failure("PROVIDER_UNAVAILABLE", format!("{}: HTTP {}.", host, status), 502),
+    _ => quote_failure("INVALID_RESPONSE", format!("{}: HTTP {}.", host, status), 502),
+  }
+}
+
+fn parse_upstream_json(url: &str, text: &str) -> Result<serde_json::Value, QuoteFailure> {
+  serde_json::from_str(text).map_err(|_| quote_failure(
+    "INVALID_RESPONSE",
+    format!("Ungueltiges JSON von {}: {}", upstream_host(url), text.chars().take(200).collect::<String>()),
+    502,
+  ))
+}
+
+fn fetch_json_uncounted(
+  network: &NetworkState,
+  url: &str,
+  observation: &mut UpstreamObservation,
+  record_dir: Option<&std::path::Path>,
+) -> Result<serde_json::Value, QuoteFailure> {
+  let builder = network::upstream_client_builder(&network.upstream_settings()).map_err(|error| quote_failure(
+    "NETWORK_CONFIG_INVALID",
+    format!("Netzwerkeinstellung ungueltig ({}): {}", error.step, error.message),
+    502,
+  ))?;
+  let client = configure_upstream_client(builder)
     .build()
     .map_err(|e| quote_failure("PROVIDER_UNAVAILABLE", e.to_string(), 502))?;
 
@@ -225,29 +362,39 @@ fn fetch_json(url: &str) -> Result<serde_json::Value, QuoteFailure> {
     .send()
     .map_err(|error| {
       if error.is_timeout() {
-        quote_failure("PROVIDER_TIMEOUT", "Yahoo-Timeout nach 4000 ms.", 504)
+        quote_failure(
+          "PROVIDER_TIMEOUT",
+          format!("{}: Timeout nach {} ms.", upstream_host(url), UPSTREAM_TIMEOUT_SECONDS * 1000),
+          504,
+        )
       } else {
         quote_failure("PROVIDER_UNAVAILABLE", error.to_string(), 502)
       }
     })?;
 
   let status = resp.status().as_u16();
+  observation.status = Some(status);
+  let text = resp.text();
+  if let Ok(body) = &text {
+    observation.bytes = body.len() as u64;
+    if let Some(dir) = record_dir {
+      let fixture = UpstreamFixture {
+        url: url.to_string(),
+        status,
+        body: body.clone(),
+        recorded_at: unix_now_seconds(),
+      };
+      if let Err(error) = upstream_fixtures::write_fixture(dir, &fixture) {
+        log::warn!("Upstream-Fixture konnte nicht gespeichert werden: {}", error);
+      }
+    }
+  }
   if !(200..300).contains(&status) {
-    return Err(match status {
-      404 => quote_failure("SYMBOL_NOT_FOUND", "Yahoo: Symbol nicht gefunden.", 404),
-      429 => quote_failure("PROVIDER_RATE_LIMITED", "Yahoo: Abruflimit erreicht.", 429),
-      500..=599 => quote_failure("PROVIDER_UNAVAILABLE", format!("Yahoo HTTP {}.", status), 502),
-      _ => quote_failure("INVALID_RESPONSE", format!("Yahoo HTTP {}.", status), 502),
-    });
+    return Err(upstream_status_failure(url, status));
   }
 
-  let text = resp.text()
-    .map_err(|e| quote_failure("PROVIDER_UNAVAILABLE", e.to_string(), 502))?;
-  serde_json::from_str(&text).map_err(|_| quote_failure(
-    "INVALID_RESPONSE",
-    format!("Ungueltiges JSON von Yahoo: {}", &text[..text.len().min(200)]),
-    502,
-  ))
+  let text = text.map_err(|e| quote_failure("PROVIDER_UNAVAILABLE", e.to_string(), 502))?;
+  parse_upstream_json(url, &text)
 }
 
 fn pick_chart_candidate(data: &serde_json::Value) -> serde_json::Value {
@@ -293,10 +440,13 @@ fn pick_quote_candidate(data: &serde_json::Value) -> serde_json::Value {
   })
 }
 
+/// Quote contract. Quotes are EUR; only conversion inputs such as USD gold
+/// and FX rates are validated against another `expected_currency`.
 fn normalize_provider_quote(
   requested_symbol: &str,
   candidate: &serde_json::Value,
   now_seconds: u64,
+  expected_currency: &str,
 ) -> Result<serde_json::Value, QuoteFailure> {
   let requested = normalize_yahoo_symbol(requested_symbol)?;
   let raw_response_symbol = candidate.get("symbol").and_then(|value| value.as_str()).unwrap_or("");
@@ -327,7 +477,7 @@ fn normalize_provider_quote(
       "CURRENCY_MISSING", "Yahoo-Antwort enthaelt keine eindeutige Waehrung.", 422
     ));
   }
-  if currency != "EUR" {
+  if currency != expected_currency {
     return Err(quote_failure(
       "UNSUPPORTED_CURRENCY", format!("Waehrung {} wird nicht unterstuetzt.", currency), 422
     ));
@@ -371,48 +521,61 @@ fn normalize_provider_quote(
   }))
 }
 
+type CandidatePicker = fn(&serde_json::Value) -> serde_json::Value;
+
 fn should_stop_quote_fallback(error: &QuoteFailure) -> bool {
-  matches!(error.code, "UNSUPPORTED_CURRENCY" | "SYMBOL_MISMATCH" | "QUOTE_STALE" | "QUOTE_FROM_FUTURE")
+  matches!(
+    error.code,
+    "UNSUPPORTED_CURRENCY" | "SYMBOL_MISMATCH" | "QUOTE_STALE" | "QUOTE_FROM_FUTURE" | "OFFLINE_MODE"
+  )
 }
 
-fn handle_quote(request: tiny_http::Request, raw_symbol: &str) {
-  let symbol = match normalize_yahoo_symbol(raw_symbol) {
-    Ok(symbol) => symbol,
-    Err(error) => {
-      send_quote_error(request, &error);
-      return;
-    }
-  };
+/// Provider trouble after which a cached value may stand in; contract
+/// violations stay visible.
+pub(crate) fn is_recoverable(error: &QuoteFailure) -> bool {
+  matches!(error.code, "OFFLINE_MODE" | "PROVIDER_TIMEOUT" | "PROVIDER_UNAVAILABLE" | "PROVIDER_RATE_LIMITED")
+}
+
+/// Walks the Yahoo fallback chain and returns the first quote that passes the
+/// quote contract. Shared by the `/quote` route and native valuation; every
+/// live quote is appended to the local quote history.
+fn fetch_quote(network: &NetworkState, raw_symbol: &str) -> Result<serde_json::Value, QuoteFailure> {
+  fetch_quote_in(network, raw_symbol, "EUR")
+}
+
+fn fetch_quote_in(network: &NetworkState, raw_symbol: &str, currency: &str) -> Result<serde_json::Value, QuoteFailure> {
+  let symbol = normalize_yahoo_symbol(raw_symbol)?;
   let encoded_symbol = urlencoding::encode(&symbol);
-  let attempts: Vec<(String, fn(&serde_json::Value) -> serde_json::Value)> = vec![
+  let endpoints = network.yahoo_endpoints();
+  let attempts: Vec<(String, CandidatePicker)> = vec![
     (
-      format!("https://query1.finance.yahoo.com/v8/finance/chart/{}?interval=1d&range=1d&lang=en-US&region=US&corsDomain=finance.yahoo.com", encoded_symbol),
+      format!("{}/v8/finance/chart/{}?interval=1d&range=1d&lang=en-US&region=US&corsDomain=finance.yahoo.com", endpoints.primary, encoded_symbol),
       pick_chart_candidate,
     ),
     (
-      format!("https://query2.finance.yahoo.com/v8/finance/chart/{}?interval=1d&range=1d&lang=en-US&region=US&corsDomain=finance.yahoo.com", encoded_symbol),
+      format!("{}/v8/finance/chart/{}?interval=1d&range=1d&lang=en-US&region=US&corsDomain=finance.yahoo.com", endpoints.fallback, encoded_symbol),
       pick_chart_candidate,
     ),
     (
-      format!("https://query1.finance.yahoo.com/v7/finance/quote?symbols={}", encoded_symbol),
+      format!("{}/v7/finance/quote?symbols={}", endpoints.primary, encoded_symbol),
       pick_quote_candidate,
     ),
     (
-      format!("https://query2.finance.yahoo.com/v7/finance/quote?symbols={}", encoded_symbol),
+      format!("{}/v7/finance/quote?symbols={}", endpoints.fallback, encoded_symbol),
       pick_quote_candidate,
     ),
   ];
-  let now_seconds = SystemTime::now()
-    .duration_since(UNIX_EPOCH)
-    .map(|duration| duration.as_secs())
-    .unwrap_or(0);
+  let now_seconds = network.now_seconds();
   let mut last_error = quote_failure("SYMBOL_NOT_FOUND", "Yahoo: Symbol nicht gefunden.", 404);
 
   for (url, pick_candidate) in attempts {
-    match fetch_json(&url).and_then(|data| normalize_provider_quote(&symbol, &pick_candidate(&data), now_seconds)) {
+    match fetch_json(network, &url).and_then(|data| normalize_provider_quote(&symbol, &pick_candidate(&data), now_seconds, currency)) {
       Ok(quote) => {
-        send_json(request, 200, quote);
-        return;
+        // Replayed fixtures are not observations and stay out of the history.
+        if !network.is_replaying() {
+          network.quote_history().record(&quote);
+        }
+        return Ok(quote);
       }
       Err(error) => {
         let stop = should_stop_quote_fallback(&error);
@@ -421,29 +584,161 @@ fn handle_quote(request: tiny_http::Request, raw_symbol: &str) {
       }
     }
   }
-  send_quote_error(request, &last_error);
+  Err(last_error)
 }
 
-fn handle_search(request: tiny_http::Request, query: &str) {
-  let url = format!("https://query1.finance.yahoo.com/v1/finance/search?q={}", urlencoding::encode(query));
-  match fetch_json(&url) {
-    Ok(data) => send_json(request, 200, data),
+fn handle_quote(network: &NetworkState, request: tiny_http::Request, raw_symbol: &str) {
+  match fetch_quote(network, raw_symbol) {
+    Ok(quote) => send_json(request, 200, quote),
     Err(error) => send_quote_error(request, &error),
   }
 }
 
-fn handle_chart(request: tiny_http::Request, symbol: &str, period1: &str, period2: &str, interval: &str) {
+fn invalid_search_query(message: impl Into<String>) -> QuoteFailure {
+  quote_failure("INVALID_SEARCH_QUERY", message, 400)
+}
+
+/// Trimmed search term: 1 to 64 characters, letters, digits, blanks and the
+/// punctuation found in fund names and tickers.
+fn normalize_search_query(raw: &str) -> Result<String, QuoteFailure> {
+  let query = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+  let length = query.chars().count();
+  if length == 0 || length > SEARCH_QUERY_MAX_CHARS {
+    return Err(invalid_search_query(format!("Suchbegriff muss 1 bis {} Zeichen lang sein.", SEARCH_QUERY_MAX_CHARS)));
+  }
+  if let Some(character) = query
+    .chars()
+    .find(|character| !(character.is_alphanumeric() || matches!(character, ' ' | '.' | '-' | '&' | '\'' | '^' | '=' | '/' | '+' | ',')))
+  {
+    return Err(invalid_search_query(format!("Suchbegriff enthaelt ungueltiges Zeichen '{}'.", character)));
+  }
+  Ok(query)
+}
+
+fn parse_search_limit(raw: Option<&str>) -> Result<usize, QuoteFailure> {
+  match raw {
+    None => Ok(SEARCH_DEFAULT_LIMIT),
+    Some(raw) => raw
+      .trim()
+      .parse::<usize>()
+      .ok()
+      .filter(|limit| (1..=SEARCH_MAX_LIMIT).contains(limit))
+      .ok_or_else(|| invalid_search_query(format!("limit muss zwischen 1 und {} liegen.", SEARCH_MAX_LIMIT))),
+  }
+}
+
+/// Tradable Yahoo quote types and their names in the `/search` contract.
+fn search_instrument_type(quote_type: &str) -> Option<&'static str> {
+  match quote_type {
+    "EQUITY" => Some("equity"),
+    "ETF" => Some("etf"),
+    "MUTUALFUND" => Some("fund"),
+    "MONEYMARKET" => Some("money_market"),
+    _ => None,
+  }
+}
+
+/// A tradable quote of a Yahoo search answer.
+pub(crate) struct SearchQuote {
+  pub symbol: String,
+  pub name: String,
+  pub exchange: Option<String>,
+  pub exchange_name: Option<String>,
+  /// Yahoo `quoteType`, e.g. "ETF".
+  pub quote_type: String,
+  pub instrument_type: &'static str,
+  /// Upper-cased; Yahoo leaves it out for many listings.
+  pub currency: Option<String>,
+}
+
+/// Tradable quotes of a Yahoo search answer (no news, indices, currencies or
+/// futures), unique by symbol, in Yahoo's order. Shared by `/search` and the
+/// ISIN/WKN resolver.
+pub(crate) fn parse_search_quotes(data: &serde_json::Value) -> Vec<SearchQuote> {
+  let text = |quote: &serde_json::Value, fields: &[&str]| -> Option<String> {
+    fields.iter().find_map(|field| {
+      quote.get(*field).and_then(|value| value.as_str()).map(str::trim).filter(|value| !value.is_empty()).map(str::to_string)
+    })
+  };
+  let mut quotes: Vec<SearchQuote> = Vec::new();
+  for quote in data.get("quotes").and_then(|value| value.as_array()).into_iter().flatten() {
+    if quote.get("isYahooFinance").and_then(|value| value.as_bool()) == Some(false) {
+      continue;
+    }
+    let Some(quote_type) = quote.get("quoteType").and_then(|value| value.as_str()) else { continue };
+    let Some(instrument_type) = search_instrument_type(quote_type) else { continue };
+    let Some(symbol) = quote.get("symbol").and_then(|value| value.as_str()).and_then(|raw| normalize_yahoo_symbol(raw).ok()) else {
+      continue;
+    };
+    if quotes.iter().any(|known| known.symbol == symbol) {
+      continue;
+    }
+    let exchange = text(quote, &["exchange"]);
+    quotes.push(SearchQuote {
+      name: text(quote, &["longname", "shortname"]).unwrap_or_else(|| symbol.clone()),
+      symbol,
+      exchange_name: text(quote, &["exchDisp"]).or_else(|| exchange.clone()),
+      exchange,
+      quote_type: quote_type.to_string(),
+      instrument_type,
+      currency: text(quote, &["currency"]).map(|currency| currency.to_ascii_uppercase()),
+    });
+  }
+  quotes
+}
+
+/// Stable `/search` shape. The ISIN is known when the query itself is one.
+fn normalize_search_results(query: &str, data: &serde_json::Value, limit: usize) -> serde_json::Value {
+  let isin = symbol_resolver::classify_identifier(query)
+    .ok()
+    .filter(|(_, kind)| *kind == symbol_resolver::IdentifierKind::Isin)
+    .map(|(isin, _)| isin);
+  let results: Vec<serde_json::Value> = parse_search_quotes(data)
+    .into_iter()
+    .take(limit)
+    .map(|quote| {
+      json!({
+        "symbol": quote.symbol,
+        "name": quote.name,
+        "exchange": quote.exchange,
+        "exchangeName": quote.exchange_name,
+        "type": quote.instrument_type,
+        "currency": quote.currency,
+        "isin": isin,
+      })
+    })
+    .collect();
+  json!({ "query": query, "results": results })
+}
+
+fn handle_search(network: &NetworkState, request: tiny_http::Request, raw_query: &str, raw_limit: Option<&str>) {
+  let (query, limit) = match normalize_search_query(raw_query).and_then(|query| Ok((query, parse_search_limit(raw_limit)?))) {
+    Ok(parsed) => parsed,
+    Err(error) => {
+      send_quote_error(request, &error);
+      return;
+    }
+  };
+  let url = format!("{}/v1/finance/search?q={}", network.yahoo_endpoints().primary, urlencoding::encode(&query));
+  match fetch_json(network, &url) {
+    Ok(data) => send_json(request, 200, normalize_search_results(&query, &data, limit)),
+    Err(error) => send_quote_error(request, &error),
+  }
+}
+
+fn handle_chart(network: &NetworkState, request: tiny_http::Request, symbol: &str, period1: &str, period2: &str, interval: &str) {
   let url = format!(
-    "https://query1.finance.yahoo.com/v8/finance/chart/{}?period1={}&period2={}&interval={}&lang=en-US&region=US&corsDomain=finance.yahoo.com",
+    "{}/v8/finance/chart/{}?period1={}&period2={}&interval={}&lang=en-US&region=US&corsDomain=finance.yahoo.com",
+    network.yahoo_endpoints().primary,
     urlencoding::encode(symbol), urlencoding::encode(period1), urlencoding::encode(period2), urlencoding::encode(interval)
   );
-  match fetch_json(&url) {
+  match fetch_json(network, &url) {
     Ok(data) => send_json(request, 200, data),
     Err(error) => send_quote_error(request, &error),
   }
 }
 
-fn start_yahoo_proxy() {
+fn start_yahoo_proxy(network: SharedNetworkState) {
   let server = match Server::http("127.0.0.1:8787") {
     Ok(server) => server,
     Err(err) => {
@@ -451,7 +746,11 @@ fn start_yahoo_proxy() {
       return;
     }
   };
+  serve_yahoo_proxy(&server, &network);
+}
 
+/// Request loop of the local proxy; returns once the server is unblocked.
+fn serve_yahoo_proxy(server: &Server, network: &NetworkState) {
   for request in server.incoming_requests() {
     if request.method() == &Method::Options {
       let origin = get_request_origin(&request);
@@ -471,17 +770,24 @@ fn start_yahoo_proxy() {
     let query = parts.next().unwrap_or("");
     let params = parse_query(query);
 
+    // `/distributions` answers from its local cache while offline; fetch_json
+    // still blocks every download.
+    if network.is_offline() && !network.is_replaying() && path != "/distributions" {
+      send_quote_error(request, &offline_mode_failure());
+      continue;
+    }
+
     match path {
       "/quote" => {
         if let Some(symbol) = params.get("symbol") {
-          handle_quote(request, symbol);
+          handle_quote(network, request, symbol);
         } else {
           send_quote_error(request, &quote_failure("INVALID_SYMBOL", "Yahoo-Symbol fehlt.", 400));
         }
       }
       "/search" => {
         if let Some(q) = params.get("q") {
-          handle_search(request, q);
+          handle_search(network, request, q, params.get("limit").map(String::as_str));
         } else {
           send_quote_error(request, &quote_failure("INVALID_SEARCH_QUERY", "Suchbegriff fehlt.", 400));
         }
@@ -492,11 +798,27 @@ fn start_yahoo_proxy() {
         let period2 = params.get("period2");
         let interval = params.get("interval").map(String::as_str).unwrap_or("1d");
         if let (Some(symbol), Some(period1), Some(period2)) = (symbol, period1, period2) {
-          handle_chart(request, symbol, period1, period2, interval);
+          handle_chart(network, request, symbol, period1, period2, interval);
         } else {
           send_quote_error(request, &quote_failure("INVALID_CHART_QUERY", "Chart-Parameter fehlen.", 400));
         }
       }
+      "/gold" => match gold_quote::fetch_gold_quote(network, params.get("instrument").map(String::as_str)) {
+        Ok(quote) => send_json(request, 200, serde_json::to_value(quote).unwrap_or_default()),
+        Err(error) => send_quote_error(request, &error),
+      },
+      "/distributions" => {
+        if let Some(symbol) = params.get("symbol") {
+          let from = params.get("from").map(String::as_str);
+          let to = params.get("to").map(String::as_str);
+          match distributions::fetch_distributions(network, symbol, from, to) {
+            Ok(history) => send_json(request, 200, serde_json::to_value(history).unwrap_or_default()),
+            Err(error) => send_quote_error(request, &error),
+          }
+        } else {
+          send_quote_error(request, &quote_failure("INVALID_SYMBOL", "Yahoo-Symbol fehlt.", 400));
+        }
+      }
       _ => send_quote_error(request, &quote_failure("NOT_FOUND", "Route nicht gefunden.", 404)),
     }
   }
@@ -509,11 +831,53 @@ pub fn run() {
       allow_close: Mutex::new(false),
       close_pending: Mutex::new(false),
     })
+    .manage(script_runtime::SharedNativeJobs::default())
     .invoke_handler(tauri::generate_handler![
       load_app_state,
       save_app_state,
       quarantine_app_state,
-      confirm_app_close
+      confirm_app_close,
+      network::get_offline_mode,
+      network::set_offline_mode,
+      network::get_upstream_connection,
+      network::set_upstream_connection,
+      network::test_connectivity,
+      network::get_upstream_fixtures,
+      network::set_upstream_fixtures,
+      network::list_network_audit,
+      network::clear_network_audit,
+      monte_carlo::run_native_monte_carlo,
+      monte_carlo::cancel_native_monte_carlo,
+      backtest::run_native_backtest_cohorts,
+      backtest::cancel_native_backtest,
+      auto_optimize::run_native_auto_optimize,
+      auto_optimize::cancel_native_auto_optimize,
+      auto_optimize::list_native_auto_optimize_checkpoints,
+      auto_optimize::discard_native_auto_optimize_checkpoint,
+      tax::settle_tax_year,
+      vorabpauschale::calculate_vorabpauschale,
+      sale_planner::plan_tax_optimal_sale,
+      bank_import::import_bank_statements,
+      bank_csv::list_bank_csv_profiles,
+      bank_csv::preview_bank_csv,
+      broker_import::list_broker_import_profiles,
+      broker_import::preview_broker_import,
+      depot_valuation::value_depot,
+      quote_history::get_quote_history,
+      quote_history::get_depot_value_history,
+      symbol_resolver::resolve_instrument,
+      distributions::get_distributions,
+      gold_quote::get_gold_quote,
+      reference_rates::get_reference_rates,
+      reference_rates::get_basiszins,
+      historical_dataset::update_historical_dataset,
+      historical_dataset::load_historical_dataset_overlay,
+      historical_dataset::remove_historical_dataset_overlay,
+      user_datasets::import_user_dataset,
+      user_datasets::list_user_datasets,
+      user_datasets::remove_user_dataset,
+      user_datasets::select_user_dataset,
+      user_datasets::load_selected_user_dataset
     ])
     .setup(|app| {
       if cfg!(debug_assertions) {
@@ -524,7 +888,15 @@ pub fn run() {
         )?;
       }
 
-      thread::spawn(start_yahoo_proxy);
+      let network: SharedNetworkState = Arc::new(match app.path().app_data_dir() {
+        Ok(app_dir) => {
+          let _ = fs::create_dir_all(&app_dir);
+          NetworkState::load(&app_dir)
+        }
+        Err(_) => NetworkState::in_memory(Default::default()),
+      });
+      app.manage(network.clone());
+      thread::spawn(move || start_yahoo_proxy(network));
       Ok(())
     })
     .on_window_event(|window, event| {
@@ -611,7 +983,7 @@ mod tests {
         ]
       }
     });
-    let normalized_quote = normalize_provider_quote("vwce.de", &pick_quote_candidate(&quote), now).unwrap();
+    let normalized_quote = normalize_provider_quote("vwce.de", &pick_quote_candidate(&quote), now, "EUR").unwrap();
     assert_eq!(normalized_quote.get("symbol").and_then(|value| value.as_str()), Some("VWCE.DE"));
     assert_eq!(normalized_quote.get("price").and_then(|value| value.as_f64()), Some(123.45));
     assert_eq!(normalized_quote.get("currency").and_then(|value| value.as_str()), Some("EUR"));
@@ -653,7 +1025,7 @@ mod tests {
         "asOf": now - 60,
         "source": "yahoo-chart"
       });
-      let error = normalize_provider_quote("VWRL.L", &candidate, now).unwrap_err();
+      let error = normalize_provider_quote("VWRL.L", &candidate, now, "EUR").unwrap_err();
       assert_eq!(error.code, "UNSUPPORTED_CURRENCY");
       assert!(error.message.contains(currency));
     }
@@ -662,7 +1034,7 @@ mod tests {
       "symbol": "VWCE.DE", "price": 100.0, "currency": "EUR", "source": "yahoo-chart"
     });
     assert_eq!(
-      normalize_provider_quote("VWCE.DE", &missing_time, now).unwrap_err().code,
+      normalize_provider_quote("VWCE.DE", &missing_time, now, "EUR").unwrap_err().code,
       "AS_OF_MISSING"
     );
 
@@ -674,11 +1046,281 @@ mod tests {
       "source": "yahoo-chart"
     });
     assert_eq!(
-      normalize_provider_quote("VWCE.DE", &stale, now).unwrap_err().code,
+      normalize_provider_quote("VWCE.DE", &stale, now, "EUR").unwrap_err().code,
       "QUOTE_STALE"
     );
   }
 
+  #[test]
+  fn offline_mode_blocks_fetches_before_any_network_access() {
+    let network = NetworkState::in_memory(network::NetworkSettings { offline_mode: true, ..Default::default() });
+    let error = fetch_json(&network, "https://query1.finance.yahoo.com/v7/finance/quote?symbols=VWCE.DE").unwrap_err();
+    assert_eq!(error.code, "OFFLINE_MODE");
+    assert_eq!(error.status, 503);
+    assert!(should_stop_quote_fallback(&error));
+
+    let entries = network.audit().entries();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].host, "query1.finance.yahoo.com");
+    assert_eq!(entries[0].path, "/v7/finance/quote");
+    assert_eq!(entries[0].code.as_deref(), Some("OFFLINE_MODE"));
+    assert_eq!(entries[0].bytes, 0);
+  }
+
+  #[test]
+  fn offline_proxy_serves_cached_distributions() {
+    use std::io::{Read, Write};
+    let network = Arc::new(NetworkState::in_memory(network::NetworkSettings { offline_mode: true, ..Default::default() }));
+    let chart = json!({ "chart": { "result": [{
+      "meta": { "symbol": "VWCE.DE", "currency": "EUR" },
+      "events": { "dividends": { "1679270400": { "amount": 0.15, "date": 1679270400 } } }
+    }]}});
+    distributions::load_distributions(network.distribution_cache(), "VWCE.DE", Some("2023-01-01"), Some("2023-06-30"), network.now_seconds(), |_, _, _| {
+      Ok(chart)
+    })
+    .unwrap();
+
+    let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+    let port = server.server_addr().to_ip().unwrap().port();
+    let proxy = {
+      let (server, network) = (server.clone(), network.clone());
+      thread::spawn(move || serve_yahoo_proxy(&server, &network))
+    };
+    let get = |path: &str| {
+      let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+      write!(stream, "GET {} HTTP/1.1\r\nHost: 127.0.0.1\r\nConnection: close\r\n\r\n", path).unwrap();
+      let mut answer = String::new();
+      stream.read_to_string(&mut answer).unwrap();
+      let (head, body) = answer.split_once("\r\n\r\n").unwrap();
+      (head.split(' ').nth(1).unwrap().to_string(), serde_json::from_str::<serde_json::Value>(body).unwrap())
+    };
+
+    let (status, body) = get("/distributions?symbol=VWCE.DE&from=2023-03-01&to=2023-03-31");
+    assert_eq!((status.as_str(), &body["cached"], &body["stale"]), ("200", &json!(true), &json!(false)));
+    assert_eq!(body["events"].as_array().unwrap().len(), 1);
+    let (status, body) = get("/distributions?symbol=VWCE.DE&from=2022-01-01&to=2023-06-30");
+    assert_eq!((status.as_str(), &body["stale"]), ("200", &json!(true)));
+    let (status, body) = get("/distributions?symbol=SAP.DE");
+    assert_eq!((status.as_str(), &body["code"]), ("503", &json!("OFFLINE_MODE")));
+    let (status, _) = get("/quote?symbol=VWCE.DE");
+    assert_eq!(status, "503");
+
+    server.unblock();
+    proxy.join().unwrap();
+  }
+
+  #[test]
+  fn replay_mode_serves_fixtures_offline_and_maps_recorded_errors() {
+    let dir = std::env::temp_dir().join(format!("ruhestand-replay-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let quote_url = "https://query1.finance.yahoo.com/v7/finance/quote?symbols=VWCE.DE";
+    let limited_url = "https://query2.finance.yahoo.com/v7/finance/quote?symbols=VWCE.DE";
+    upstream_fixtures::write_fixture(&dir, &UpstreamFixture {
+      url: quote_url.to_string(),
+      status: 200,
+      body: json!({ "quoteResponse": { "result": [] } }).to_string(),
+      recorded_at: 1_800_000_000,
+    }).unwrap();
+    upstream_fixtures::write_fixture(&dir, &UpstreamFixture {
+      url: limited_url.to_string(),
+      status: 429,
+      body: String::new(),
+      recorded_at: 1_800_000_100,
+    }).unwrap();
+
+    let network = NetworkState::in_memory(network::NetworkSettings {
+      offline_mode: true,
+      fixtures: upstream_fixtures::UpstreamFixtureSettings {
+        mode: UpstreamFixtureMode::Replay,
+        directory: Some(dir.to_string_lossy().to_string()),
+      },
+      ..Default::default()
+    });
+    assert!(fetch_json(&network, quote_url).unwrap().pointer("/quoteResponse/result").is_some());
+    let limited = fetch_json(&network, limited_url).unwrap_err();
+    assert_eq!((limited.code, limited.message.as_str()), ("PROVIDER_RATE_LIMITED", "query2.finance.yahoo.com: Abruflimit erreicht."));
+    assert_eq!(fetch_json(&network, "https://query1.finance.yahoo.com/v1/finance/search?q=x").unwrap_err().code, "FIXTURE_MISSING");
+    assert_eq!(network.now_seconds(), 1_800_000_100);
+    assert!(network.audit().entries().is_empty());
+
+    // The clock is read once per fixture configuration, not per quote.
+    upstream_fixtures::write_fixture(&dir, &UpstreamFixture {
+      url: format!("{}&later=1", quote_url),
+      status: 200,
+      body: "{}".to_string(),
+      recorded_at: 1_800_000_500,
+    }).unwrap();
+    assert_eq!(network.now_seconds(), 1_800_000_100);
+    network.update(|_| {}).unwrap();
+    assert_eq!(network.now_seconds(), 1_800_000_500);
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  fn mock_chart(symbol: &str, currency: &str, as_of: u64) -> serde_json::Value {
+    json!({
+      "chart": { "result": [ { "meta": {
+        "symbol": symbol, "regularMarketPrice": 101.5, "currency": currency, "regularMarketTime": as_of
+      } } ] }
+    })
+  }
+
+  fn mock_quote(symbol: &str, as_of: u64) -> serde_json::Value {
+    json!({
+      "quoteResponse": { "result": [ {
+        "symbol": symbol, "regularMarketPrice": 99.5, "currency": "EUR", "regularMarketTime": as_of
+      } ] }
+    })
+  }
+
+  #[test]
+  fn proxy_quote_walks_fallback_chain_in_order() {
+    use mock_upstream::{MockResponse, MockUpstream, ProxyUnderTest};
+    let now = unix_now_seconds();
+    let upstream = MockUpstream::start(vec![
+      ("/query1/v8/finance/chart/VWCE.DE", MockResponse::raw(503, "down")),
+      ("/query2/v8/finance/chart/VWCE.DE", MockResponse::raw(200, "<html>kein json</html>")),
+      ("/query1/v7/finance/quote", MockResponse::raw(404, "{}")),
+      ("/query2/v7/finance/quote", MockResponse::json(200, mock_quote("VWCE.DE", now - 60))),
+    ]);
+    let proxy = ProxyUnderTest::start(&upstream);
+
+    let (status, body) = proxy.get("/quote?symbol=vwce.de");
+    assert_eq!(status, 200);
+    assert_eq!(body["price"], 99.5);
+    assert_eq!(body["source"], "yahoo-quote");
+    assert_eq!(upstream.hits(), vec![
+      "/query1/v8/finance/chart/VWCE.DE",
+      "/query2/v8/finance/chart/VWCE.DE",
+      "/query1/v7/finance/quote",
+      "/query2/v7/finance/quote",
+    ]);
+    let codes: Vec<Option<String>> = proxy.network.audit().entries().into_iter().map(|entry| entry.code).collect();
+    assert_eq!(codes, vec![
+      Some("PROVIDER_UNAVAILABLE".to_string()),
+      Some("INVALID_RESPONSE".to_string()),
+      Some("SYMBOL_NOT_FOUND".to_string()),
+      None,
+    ]);
+    let history = proxy.network.quote_history().history("VWCE.DE");
+    assert_eq!((history.len(), history[0].price, history[0].source.as_str()), (1, 99.5, "yahoo-quote"));
+  }
+
+  #[test]
+  fn proxy_quote_reports_last_upstream_error_after_exhausted_fallbacks() {
+    use mock_upstream::{MockResponse, MockUpstream, ProxyUnderTest};
+    let upstream = MockUpstream::start(vec![
+      ("/query1/v8/finance/chart/VWCE.DE", MockResponse::raw(500, "")),
+      ("/query2/v8/finance/chart/VWCE.DE", MockResponse::raw(404, "")),
+      ("/query1/v7/finance/quote", MockResponse::raw(502, "")),
+      ("/query2/v7/finance/quote", MockResponse::raw(429, "")),
+    ]);
+    let proxy = ProxyUnderTest::start(&upstream);
+
+    let (status, body) = proxy.get("/quote?symbol=VWCE.DE");
+    assert_eq!(status, 429);
+    assert_eq!(body["code"], "PROVIDER_RATE_LIMITED");
+    assert_eq!(upstream.hits().len(), 4);
+  }
+
+  #[test]
+  fn proxy_quote_stops_fallback_on_contract_violations() {
+    use mock_upstream::{MockResponse, MockUpstream, ProxyUnderTest};
+    let now = unix_now_seconds();
+    for (chart, expected_code) in [
+      (mock_chart("VWCE.DE", "USD", now - 60), "UNSUPPORTED_CURRENCY"),
+      (mock_chart("VWRL.L", "EUR", now - 60), "SYMBOL_MISMATCH"),
+      (mock_chart("VWCE.DE", "EUR", now - QUOTE_MAX_AGE_SECONDS - 60), "QUOTE_STALE"),
+      (mock_chart("VWCE.DE", "EUR", now + 3600), "QUOTE_FROM_FUTURE"),
+    ] {
+      let upstream = MockUpstream::start(vec![
+        ("/query1/v8/finance/chart/VWCE.DE", MockResponse::json(200, chart)),
+        ("/query2/v8/finance/chart/VWCE.DE", MockResponse::json(200, mock_chart("VWCE.DE", "EUR", now - 60))),
+      ]);
+      let proxy = ProxyUnderTest::start(&upstream);
+
+      let (status, body) = proxy.get("/quote?symbol=VWCE.DE");
+      assert_eq!(status, 422);
+      assert_eq!(body["code"], expected_code);
+      assert_eq!(upstream.hits(), vec!["/query1/v8/finance/chart/VWCE.DE"]);
+    }
+  }
+
+  #[test]
+  fn proxy_search_and_chart_map_upstream_failures() {
+    use mock_upstream::{MockResponse, MockUpstream, ProxyUnderTest};
+    let upstream = MockUpstream::start(vec![
+      ("/query1/v1/finance/search", MockResponse::raw(504, "")),
+      ("/query1/v8/finance/chart/VWCE.DE", MockResponse::raw(200, "{\"chart\":")),
+      ("/query1/v8/finance/chart/EUNL.DE", MockResponse::json(200, mock_chart("EUNL.DE", "EUR", 1))),
+    ]);
+    let proxy = ProxyUnderTest::start(&upstream);
+
+    let (status, body) = proxy.get("/search?q=vanguard");
+    assert_eq!((status, body["code"].as_str()), (502, Some("PROVIDER_UNAVAILABLE")));
+    let (status, body) = proxy.get("/chart?symbol=VWCE.DE&period1=1&period2=2");
+    assert_eq!((status, body["code"].as_str()), (502, Some("INVALID_RESPONSE")));
+    let (status, body) = proxy.get("/chart?symbol=UNKNOWN.DE&period1=1&period2=2");
+    assert_eq!((status, body["code"].as_str()), (404, Some("SYMBOL_NOT_FOUND")));
+    let (status, body) = proxy.get("/chart?symbol=EUNL.DE&period1=1&period2=2&interval=1wk");
+    assert_eq!(status, 200);
+    assert_eq!(body.pointer("/chart/result/0/meta/symbol").and_then(|value| value.as_str()), Some("EUNL.DE"));
+    let (status, body) = proxy.get("/chart?symbol=EUNL.DE");
+    assert_eq!((status, body["code"].as_str()), (400, Some("INVALID_CHART_QUERY")));
+  }
+
+  #[test]
+  fn proxy_search_returns_normalized_tradable_results() {
+    use mock_upstream::{MockResponse, MockUpstream, ProxyUnderTest};
+    let upstream = MockUpstream::start(vec![(
+      "/query1/v1/finance/search",
+      MockResponse::json(200, json!({
+        "quotes": [
+          { "symbol": "VWCE.DE", "exchange": "GER", "exchDisp": "XETRA", "quoteType": "ETF", "typeDisp": "ETF", "longname": "Vanguard FTSE All-World UCITS ETF", "isYahooFinance": true },
+          { "symbol": "^GDAXI", "exchange": "GER", "quoteType": "INDEX", "shortname": "DAX" },
+          { "symbol": "EURUSD=X", "quoteType": "CURRENCY" },
+          { "symbol": "VWCE.F", "exchange": "FRA", "quoteType": "ETF", "shortname": "VANGUARD FTSE ALL-WORLD", "currency": "eur" },
+          { "symbol": "VWCE.MI", "exchange": "MIL", "quoteType": "ETF", "shortname": "Vanguard" },
+          { "index": "quotes", "quoteType": "ETF" },
+          { "symbol": "PRIVATE", "quoteType": "EQUITY", "isYahooFinance": false }
+        ],
+        "news": [{ "uuid": "n1", "title": "Markets" }]
+      })),
+    )]);
+    let proxy = ProxyUnderTest::start(&upstream);
+
+    let (status, body) = proxy.get("/search?q=IE00BK5BQT80&limit=2");
+    assert_eq!(status, 200);
+    assert_eq!(body, json!({
+      "query": "IE00BK5BQT80",
+      "results": [
+        { "symbol": "VWCE.DE", "name": "Vanguard FTSE All-World UCITS ETF", "exchange": "GER", "exchangeName": "XETRA", "type": "etf", "currency": null, "isin": "IE00BK5BQT80" },
+        { "symbol": "VWCE.F", "name": "VANGUARD FTSE ALL-WORLD", "exchange": "FRA", "exchangeName": "FRA", "type": "etf", "currency": "EUR", "isin": "IE00BK5BQT80" }
+      ]
+    }));
+    let (_, body) = proxy.get("/search?q=%20vanguard%20%20all%20");
+    assert_eq!((body["query"].as_str(), body["results"].as_array().map(Vec::len)), (Some("vanguard all"), Some(3)));
+    assert_eq!(body["results"][0]["isin"], serde_json::Value::Null);
+
+    for query in ["/search?q=%20%20", "/search?q=vwce%3Cscript%3E", "/search?q=vwce&limit=0", "/search?q=vwce&limit=51", "/search"] {
+      let (status, body) = proxy.get(query);
+      assert_eq!((status, body["code"].as_str()), (400, Some("INVALID_SEARCH_QUERY")), "{}", query);
+    }
+    assert_eq!(proxy.get(&format!("/search?q={}", "a".repeat(65))).1["code"], "INVALID_SEARCH_QUERY");
+    assert_eq!(upstream.hits().len(), 2);
+  }
+
+  #[test]
+  fn proxy_offline_mode_answers_without_contacting_upstream() {
+    use mock_upstream::{MockUpstream, ProxyUnderTest};
+    let upstream = MockUpstream::start(Vec::new());
+    let proxy = ProxyUnderTest::start(&upstream);
+    proxy.network.update(|settings| settings.offline_mode = true).unwrap();
+
+    let (status, body) = proxy.get("/quote?symbol=VWCE.DE");
+    assert_eq!((status, body["code"].as_str()), (503, Some("OFFLINE_MODE")));
+    assert!(upstream.hits().is_empty());
+  }
+
   #[test]
   fn yahoo_symbol_contract_rejects_exchange_suffixes() {
     assert_eq!(normalize_yahoo_symbol(" vwce.de ").unwrap(), "VWCE.DE");
@@ -0,0 +1,120 @@
+//! In-process test harness: a scripted mock Yahoo upstream plus the real
+//! proxy request loop, both bound to ephemeral ports on 127.0.0.1.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tiny_http::{Header, Response, Server, StatusCode};
+
+use crate::network::{NetworkSettings, NetworkState, YahooEndpoints};
+
+#[derive(Clone, Debug)]
+pub(crate) struct MockResponse {
+  pub status: u16,
+  pub body: String,
+}
+
+impl MockResponse {
+  pub(crate) fn json(status: u16, body: serde_json::Value) -> Self {
+    MockResponse { status, body: body.to_string() }
+  }
+
+  pub(crate) fn raw(status: u16, body: &str) -> Self {
+    MockResponse { status, body: body.to_string() }
+  }
+}
+
+/// Serves `routes` keyed by request path without query. The primary Yahoo
+/// base is mounted under `/query1`, the fallback under `/query2`; unknown
+/// paths answer 404 like Yahoo does for unknown symbols.
+pub(crate) struct MockUpstream {
+  server: Arc<Server>,
+  base_url: String,
+  hits: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockUpstream {
+  pub(crate) fn start(routes: Vec<(&str, MockResponse)>) -> Self {
+    let server = Arc::new(Server::http("127.0.0.1:0").expect("mock upstream should bind"));
+    let port = server.server_addr().to_ip().expect("mock upstream uses TCP").port();
+    let routes: HashMap<String, MockResponse> = routes
+      .into_iter()
+      .map(|(path, response)| (path.to_string(), response))
+      .collect();
+    let hits = Arc::new(Mutex::new(Vec::new()));
+
+    let loop_server = server.clone();
+    let loop_hits = hits.clone();
+    thread::spawn(move || {
+      for request in loop_server.incoming_requests() {
+        let url = request.url().to_string();
+        let path = url.split('?').next().unwrap_or("").to_string();
+        loop_hits.lock().unwrap().push(path.clone());
+        let response = routes.get(&path).cloned().unwrap_or_else(|| MockResponse::raw(404, "{}"));
+        let _ = request.respond(
+          Response::from_string(response.body)
+            .with_status_code(StatusCode(response.status))
+            .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap()),
+        );
+      }
+    });
+
+    MockUpstream { server, base_url: format!("http://127.0.0.1:{}", port), hits }
+  }
+
+  pub(crate) fn endpoints(&self) -> YahooEndpoints {
+    YahooEndpoints {
+      primary: format!("{}/query1", self.base_url),
+      fallback: format!("{}/query2", self.base_url),
+    }
+  }
+
+  /// Request paths in arrival order.
+  pub(crate) fn hits(&self) -> Vec<String> {
+    self.hits.lock().unwrap().clone()
+  }
+}
+
+impl Drop for MockUpstream {
+  fn drop(&mut self) {
+    self.server.unblock();
+  }
+}
+
+/// Runs `serve_yahoo_proxy` against the mock upstream.
+pub(crate) struct ProxyUnderTest {
+  server: Arc<Server>,
+  base_url: String,
+  pub network: Arc<NetworkState>,
+}
+
+impl ProxyUnderTest {
+  pub(crate) fn start(upstream: &MockUpstream) -> Self {
+    let network = Arc::new(NetworkState::in_memory(NetworkSettings::default()).with_endpoints(upstream.endpoints()));
+    let server = Arc::new(Server::http("127.0.0.1:0").expect("proxy should bind"));
+    let port = server.server_addr().to_ip().expect("proxy uses TCP").port();
+    let loop_server = server.clone();
+    let loop_network = network.clone();
+    thread::spawn(move || crate::serve_yahoo_proxy(&loop_server, &loop_network));
+    ProxyUnderTest { server, base_url: format!("http://127.0.0.1:{}", port), network }
+  }
+
+  /// Returns HTTP status and parsed JSON body of a proxy route.
+  pub(crate) fn get(&self, path_and_query: &str) -> (u16, serde_json::Value) {
+    let response = reqwest::blocking::Client::builder()
+      .no_proxy()
+      .build()
+      .unwrap()
+      .get(format!("{}{}", self.base_url, path_and_query))
+      .send()
+      .expect("proxy should answer");
+    let status = response.status().as_u16();
+    (status, response.json().expect("proxy answers JSON"))
+  }
+}
+
+impl Drop for ProxyUnderTest {
+  fn drop(&mut self) {
+    self.server.unblock();
+  }
+}
@@ -0,0 +1,342 @@
+//! Native Monte-Carlo runner. Splits a `MonteCarloRunRequestV1` into run
+//! ranges, evaluates them on one embedded JS runtime per core and merges the
+//! chunks in run order, so the `MonteCarloRunResultV1` matches the webview
+//! worker pool for the same seed.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+use tauri::Emitter;
+
+use crate::script_runtime::{
+  job_failure as failure, script_failure, JobFailure, ScriptHost, ScriptSource, SharedNativeJobs, SCRIPT_THREAD_STACK_BYTES,
+};
+
+pub(crate) const MONTE_CARLO_PROGRESS_EVENT: &str = "ruhestand://monte-carlo-progress";
+
+const HOST_MODULE_NAME: &str = "native/monte-carlo-host.js";
+const HOST_MODULE_SOURCE: &str = include_str!("monte_carlo_host.js");
+const HOST_OBJECT: &str = "__ruhestandMonteCarloHost";
+const MIN_CHUNK_RUNS: usize = 10;
+const MAX_CHUNK_RUNS: usize = 2_000;
+/// Chunks per thread; enough that one slow chunk does not idle the others.
+const CHUNKS_PER_THREAD: usize = 16;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct NativeRunOptions {
+  /// Defaults to all available cores.
+  pub threads: Option<usize>,
+  /// Defaults to an even split into `CHUNKS_PER_THREAD` chunks per thread.
+  pub chunk_runs: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MonteCarloProgress {
+  pub completed_runs: usize,
+  pub total_runs: usize,
+  pub elapsed_ms: u64,
+}
+
+#[derive(serde::Deserialize)]
+struct RunPlan {
+  runs: usize,
+  chunkable: bool,
+}
+
+fn open_host(source: &ScriptSource, request_json: &str, interrupt: Option<Arc<AtomicBool>>) -> Result<(ScriptHost, RunPlan), JobFailure> {
+  let host = ScriptHost::new(source.clone(), interrupt).map_err(|e| failure("MC_NATIVE_ENGINE_FAILED", e))?;
+  host
+    .evaluate_module(HOST_MODULE_NAME, HOST_MODULE_SOURCE)
+    .map_err(|e| script_failure("MC_NATIVE_ENGINE_FAILED", format!("Engine-Module konnten nicht geladen werden: {}", e)))?;
+  let plan = host
+    .call_string(HOST_OBJECT, "prepare", (request_json.to_string(),))
+    .map_err(|e| script_failure("MC_NATIVE_REQUEST_INVALID", e))?;
+  let plan: RunPlan = serde_json::from_str(&plan).map_err(|e| failure("MC_NATIVE_ENGINE_FAILED", e.to_string()))?;
+  Ok((host, plan))
+}
+
+pub(crate) fn plan_chunks(runs: usize, threads: usize, chunk_runs: Option<usize>) -> Vec<(usize, usize)> {
+  let size = chunk_runs
+    .unwrap_or_else(|| runs.div_ceil(threads.max(1) * CHUNKS_PER_THREAD).clamp(MIN_CHUNK_RUNS, MAX_CHUNK_RUNS))
+    .max(1);
+  (0..runs).step_by(size).map(|start| (start, size.min(runs - start))).collect()
+}
+
+/// Runs `request` to completion. Setting `cancel` stops all threads at the
+/// next interrupt check; a failing chunk sets it too so the others stop early.
+pub(crate) fn run_monte_carlo(
+  source: &ScriptSource,
+  request: &Value,
+  options: NativeRunOptions,
+  cancel: &Arc<AtomicBool>,
+  on_progress: &(dyn Fn(MonteCarloProgress) + Sync),
+) -> Result<Value, JobFailure> {
+  let started = Instant::now();
+  let request_json = request.to_string();
+  let (coordinator, plan) = open_host(source, &request_json, None)?;
+
+  let available = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+  let threads = options.threads.unwrap_or(available).max(1);
+  let chunks = if plan.chunkable {
+    plan_chunks(plan.runs, threads, options.chunk_runs)
+  } else {
+    vec![(0, plan.runs)]
+  };
+  let threads = threads.min(chunks.len()).max(1);
+  let next_chunk = AtomicUsize::new(0);
+  let (sender, receiver) = mpsc::channel::<(usize, Result<String, JobFailure>)>();
+
+  let merged = std::thread::scope(|scope| -> Result<usize, JobFailure> {
+    for _ in 0..threads {
+      let sender = sender.clone();
+      let (chunks, next_chunk, request_json) = (&chunks, &next_chunk, &request_json);
+      let cancel = cancel.clone();
+      std::thread::Builder::new()
+        .name("ruhestand-monte-carlo".to_string())
+        .stack_size(SCRIPT_THREAD_STACK_BYTES)
+        .spawn_scoped(scope, move || {
+          let host = match open_host(source, request_json, Some(cancel.clone())) {
+            Ok((host, _)) => host,
+            Err(error) => {
+              let _ = sender.send((usize::MAX, Err(error)));
+              return;
+            }
+          };
+          while !cancel.load(Ordering::Relaxed) {
+            let index = next_chunk.fetch_add(1, Ordering::Relaxed);
+            let Some(&(start, count)) = chunks.get(index) else { break };
+            let outcome = host
+              .call_string(HOST_OBJECT, "runChunk", (start as f64, count as f64))
+              .map_err(|e| script_failure("MC_NATIVE_ENGINE_FAILED", e));
+            let failed = outcome.is_err();
+            if sender.send((index, outcome)).is_err() || failed {
+              break;
+            }
+          }
+        })
+        .map_err(|e| failure("MC_NATIVE_ENGINE_FAILED", e.to_string()))?;
+    }
+    drop(sender);
+
+    // Float sums depend on merge order, so chunks are merged strictly by
+    // index regardless of which thread finishes first.
+    let mut pending: BTreeMap<usize, String> = BTreeMap::new();
+    let mut merged = 0;
+    let mut completed_runs = 0;
+    let mut first_error = None;
+    for (index, outcome) in receiver {
+      let encoded = match outcome {
+        Ok(encoded) => encoded,
+        Err(error) => {
+          // Errors after a cancel request are just interrupted chunks.
+          if !cancel.swap(true, Ordering::Relaxed) {
+            first_error = Some(error);
+          }
+          continue;
+        }
+      };
+      if first_error.is_some() {
+        continue;
+      }
+      completed_runs += chunks[index].1;
+      pending.insert(index, encoded);
+      while let Some(encoded) = pending.remove(&merged) {
+        let (start, count) = chunks[merged];
+        if let Err(error) = coordinator.call_string(HOST_OBJECT, "mergeChunk", (encoded, start as f64, count as f64)) {
+          cancel.store(true, Ordering::Relaxed);
+          first_error = Some(script_failure("MC_NATIVE_ENGINE_FAILED", error));
+          break;
+        }
+        merged += 1;
+      }
+      on_progress(MonteCarloProgress {
+        completed_runs,
+        total_runs: plan.runs,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+      });
+    }
+    match first_error {
+      Some(error) => Err(error),
+      None => Ok(merged),
+    }
+  })?;
+
+  if merged < chunks.len() {
+    return Err(failure("MC_NATIVE_CANCELLED", "Monte-Carlo-Lauf wurde abgebrochen."));
+  }
+  let execution = serde_json::json!({
+    "mode": "native",
+    "workerCount": threads,
+    "timeBudgetMs": null,
+    "chunkConfiguration": {
+      "strategy": "native-ordered-chunks-v1",
+      "chunkCount": chunks.len(),
+      "chunkRuns": chunks.first().map_or(0, |chunk| chunk.1)
+    }
+  });
+  let result = coordinator
+    .call_string(HOST_OBJECT, "finalize", (execution.to_string(),))
+    .map_err(|e| script_failure("MC_NATIVE_ENGINE_FAILED", e))?;
+  serde_json::from_str(&result).map_err(|e| failure("MC_NATIVE_ENGINE_FAILED", e.to_string()))
+}
+
+/// Wraps a finished run into a `MonteCarloExportV1` document (canonical JSON,
+/// fingerprinted) exactly like the webview download.
+pub(crate) fn export_monte_carlo(source: &ScriptSource, request: &Value, result: &Value) -> Result<String, JobFailure> {
+  let host = ScriptHost::new(source.clone(), None).map_err(|e| failure("MC_NATIVE_ENGINE_FAILED", e))?;
+  host
+    .evaluate_module(HOST_MODULE_NAME, HOST_MODULE_SOURCE)
+    .map_err(|e| script_failure("MC_NATIVE_ENGINE_FAILED", e))?;
+  host
+    .call_string(HOST_OBJECT, "exportRun", (request.to_string(), result.to_string()))
+    .map_err(|e| script_failure("MC_NATIVE_EXPORT_INVALID", e))
+}
+
+#[tauri::command]
+pub async fn run_native_monte_carlo(
+  app: tauri::AppHandle,
+  state: tauri::State<'_, SharedNativeJobs>,
+  job_id: String,
+  request: Value,
+  dataset_id: Option<String>,
+) -> Result<Value, String> {
+  let jobs = state.inner().clone();
+  tauri::async_runtime::spawn_blocking(move || {
+    // Loading a user dataset runs its contract check on the engine runtime.
+    let source = crate::user_datasets::run_source(&app, dataset_id.as_deref())?;
+    let job = jobs.start(&job_id).map_err(|e| e.to_string())?;
+    let report = |progress: MonteCarloProgress| {
+      let _ = app.emit(
+        MONTE_CARLO_PROGRESS_EVENT,
+        serde_json::json!({ "jobId": job_id, "progress": progress }),
+      );
+    };
+    run_monte_carlo(&source, &request, NativeRunOptions::default(), job.cancel_flag(), &report).map_err(|e| e.to_string())
+  })
+  .await
+  .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn cancel_native_monte_carlo(state: tauri::State<'_, SharedNativeJobs>, job_id: String) -> bool {
+  state.cancel(&job_id)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use super::*;
+  use crate::script_runtime::{checkout_root, directory_source};
+  use std::sync::Mutex;
+
+  /// Request and result of the serial JS runner recorded under V8 by
+  /// tests/native-monte-carlo-golden.test.mjs.
+  pub(crate) fn golden() -> (ScriptSource, Value, Value) {
+    let path = checkout_root().join("tests/fixtures/native-monte-carlo-golden-v1.json");
+    let mut golden: Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    (directory_source(checkout_root()), golden["request"].take(), golden["result"].take())
+  }
+
+  /// Chunked float sums may differ from the serial order in the last bits, and
+  /// QuickJS and V8 may round transcendental functions differently; see
+  /// TECHNICAL.md for the documented tolerance.
+  pub(crate) fn collect_mismatches(path: &str, left: &Value, right: &Value, out: &mut Vec<String>) {
+    match (left, right) {
+      (Value::Number(a), Value::Number(b)) => {
+        let (a, b) = (a.as_f64().unwrap(), b.as_f64().unwrap());
+        if (a - b).abs() > 1e-9 * a.abs().max(b.abs()).max(1.0) {
+          out.push(format!("{}: {} != {}", path, a, b));
+        }
+      }
+      (Value::Object(a), Value::Object(b)) => {
+        for key in a.keys().chain(b.keys().filter(|key| !a.contains_key(*key))) {
+          let child = format!("{}.{}", path, key);
+          collect_mismatches(&child, a.get(key).unwrap_or(&Value::Null), b.get(key).unwrap_or(&Value::Null), out);
+        }
+      }
+      (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+        for (index, (x, y)) in a.iter().zip(b).enumerate() {
+          collect_mismatches(&format!("{}[{}]", path, index), x, y, out);
+        }
+      }
+      _ if left != right => out.push(format!("{}: {} != {}", path, left, right)),
+      _ => {}
+    }
+  }
+
+  #[test]
+  fn chunks_cover_every_run_once() {
+    assert_eq!(plan_chunks(25, 1, Some(10)), vec![(0, 10), (10, 10), (20, 5)]);
+    assert_eq!(plan_chunks(1_000_000, 8, None).len(), 1_000_000 / MAX_CHUNK_RUNS);
+    assert_eq!(plan_chunks(30, 4, None), vec![(0, 10), (10, 10), (20, 10)]);
+  }
+
+  #[test]
+  fn parallel_chunks_match_the_v8_golden_result() {
+    let (source, request, mut serial) = golden();
+    let progress = Mutex::new(Vec::new());
+    let cancel = Arc::new(AtomicBool::new(false));
+    let options = NativeRunOptions { threads: Some(3), chunk_runs: Some(7) };
+    let mut native = run_monte_carlo(&source, &request, options, &cancel, &|update| {
+      progress.lock().unwrap().push(update.completed_runs)
+    })
+    .unwrap();
+
+    assert_eq!(native["diagnostics"]["execution"]["mode"], "native");
+    assert_eq!(native["diagnostics"]["execution"]["chunkConfiguration"]["chunkCount"], 6);
+    native["diagnostics"]["execution"] = Value::Null;
+    serial["diagnostics"]["execution"] = Value::Null;
+    let mut mismatches = Vec::new();
+    collect_mismatches("$", &native, &serial, &mut mismatches);
+    assert!(mismatches.is_empty(), "native result drifted from the V8 golden run: {:#?}", mismatches);
+    assert_eq!(native["sampleSize"]["requestedRuns"], 40);
+
+    let progress = progress.into_inner().unwrap();
+    assert_eq!(progress.len(), 6);
+    assert!(progress.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(progress.last(), Some(&40));
+  }
+
+  /// Throughput check for large batches, e.g.
+  /// `RUHESTAND_MC_BENCH_RUNS=20000 cargo test --release bench_native_throughput -- --ignored --nocapture`.
+  #[test]
+  #[ignore]
+  fn bench_native_throughput() {
+    let runs: usize = std::env::var("RUHESTAND_MC_BENCH_RUNS").ok().and_then(|raw| raw.parse().ok()).unwrap_or(2_000);
+    let (source, mut request, _) = golden();
+    request["parameters"]["runs"] = Value::from(runs);
+    request["parameters"]["horizonYears"] = Value::from(30);
+    let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let cancel = Arc::new(AtomicBool::new(false));
+    let started = Instant::now();
+    let result = run_monte_carlo(&source, &request, NativeRunOptions::default(), &cancel, &|_| {}).unwrap();
+    let seconds = started.elapsed().as_secs_f64();
+    assert_eq!(result["sampleSize"]["requestedRuns"], runs);
+    let per_second = runs as f64 / seconds;
+    println!(
+      "{} runs x 30 years on {} threads: {:.1}s, {:.0} runs/s, 1,000,000 runs ~ {:.0} min",
+      runs,
+      threads,
+      seconds,
+      per_second,
+      1_000_000.0 / per_second / 60.0
+    );
+  }
+
+  #[test]
+  fn cancelled_and_invalid_requests_fail_with_codes() {
+    let (source, request, _) = golden();
+    let cancel = Arc::new(AtomicBool::new(true));
+    let error = run_monte_carlo(&source, &request, NativeRunOptions::default(), &cancel, &|_| {}).unwrap_err();
+    assert_eq!(error.code, "MC_NATIVE_CANCELLED");
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let invalid = serde_json::json!({ "schemaVersion": "MonteCarloRunRequestV0" });
+    let error = run_monte_carlo(&source, &invalid, NativeRunOptions::default(), &cancel, &|_| {}).unwrap_err();
+    assert_eq!(error.code, "MC_NATIVE_REQUEST_INVALID");
+    assert!(error.message.starts_with("MC_REQUEST_VERSION_UNSUPPORTED: "), "{}", error.message);
+  }
+}
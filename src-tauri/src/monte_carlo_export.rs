@@ -0,0 +1,681 @@
+//! Native `MonteCarloExportV1` verifier and KPI differ. Mirrors
+//! `validateMonteCarloExportV1` (app/simulator/monte-carlo-export.js) and the
+//! canonical SHA-256 fingerprints of historical-backtest-contract.js, so shared
+//! exports can be checked without the webview or a JS runtime. The canonical
+//! JSON is pinned to the JS output by tests/fixtures/canonical-json-corpus-v1.json.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+const SCHEMA_GOLDEN: &str = include_str!("../../tests/fixtures/monte-carlo-export-v1-schema.json");
+const FINGERPRINT_EXCLUDES: [&str; 4] = ["exportedAtUtc", "identifiers", "compatibility", "privacy"];
+const KNOWN_TOP_LEVEL_FIELDS: [&str; 12] = [
+  "schemaId",
+  "schemaVersion",
+  "exportedAtUtc",
+  "identifiers",
+  "fingerprint",
+  "app",
+  "engine",
+  "snapshotPolicy",
+  "request",
+  "result",
+  "compatibility",
+  "privacy",
+];
+/// Result fields compared by `diff_exports` and summarized by the CLI.
+pub(crate) const KPI_FIELDS: [&str; 5] = ["batchStatus", "financialMetricsValid", "sampleSize", "outcomeInventory", "kpis"];
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportContracts {
+  request: String,
+  result: String,
+  export: String,
+  scenario: String,
+  snapshot_policy: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MoneyFieldSuffixes {
+  nominal: String,
+  real: String,
+}
+
+/// tests/fixtures/monte-carlo-export-v1-schema.json, shared with the JS contract test.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportSchema {
+  schema_id: String,
+  contracts: ExportContracts,
+  required_request_fields: Vec<String>,
+  required_result_fields: Vec<String>,
+  money_field_suffixes: MoneyFieldSuffixes,
+  fingerprint_algorithm: String,
+  forward_policy: String,
+  new_exports_write_deprecated_aliases: bool,
+}
+
+fn schema() -> &'static ExportSchema {
+  static SCHEMA: OnceLock<ExportSchema> = OnceLock::new();
+  SCHEMA.get_or_init(|| serde_json::from_str(SCHEMA_GOLDEN).expect("monte-carlo-export-v1-schema.json is valid"))
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportIssue {
+  pub code: &'static str,
+  pub path: String,
+  pub message: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportVerification {
+  pub valid: bool,
+  pub request_id: Option<String>,
+  pub run_id: Option<String>,
+  /// Recomputed over the fingerprint basis, independent of the stored value.
+  pub fingerprint: Option<String>,
+  pub issues: Vec<ExportIssue>,
+  /// Ignored per the forward policy, reported for information.
+  pub unknown_fields: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct KpiDelta {
+  pub path: String,
+  pub left: Value,
+  pub right: Value,
+  pub absolute_delta: Option<f64>,
+  pub relative_delta: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ExportDiff {
+  pub same_request: bool,
+  pub same_run: bool,
+  pub compared: usize,
+  pub changed: Vec<KpiDelta>,
+}
+
+// --- Canonical JSON (sha256-canonical-json-v1) ---
+
+/// `Number.prototype.toString()` for finite values. Rust's shortest
+/// round-trip output fixes the digit count; ECMAScript then wants the closest
+/// decimal of that length (ties to even), which exact formatting provides.
+fn js_number(value: f64) -> String {
+  if value == 0.0 {
+    return "0".to_string();
+  }
+  let sign = if value < 0.0 { "-" } else { "" };
+  let shortest = format!("{:e}", value.abs());
+  let length = shortest.split('e').next().unwrap_or("").chars().filter(char::is_ascii_digit).count();
+  let scientific = format!("{:.*e}", length.saturating_sub(1), value.abs());
+  let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+  let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+  let k = digits.len() as i32;
+  let n = exponent.parse::<i32>().unwrap_or(0) + 1;
+  let body = if k <= n && n <= 21 {
+    format!("{}{}", digits, "0".repeat((n - k) as usize))
+  } else if 0 < n && n <= 21 {
+    format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+  } else if -6 < n && n <= 0 {
+    format!("0.{}{}", "0".repeat((-n) as usize), digits)
+  } else {
+    let e = n - 1;
+    let e_sign = if e < 0 { '-' } else { '+' };
+    if k == 1 {
+      format!("{}e{}{}", digits, e_sign, e.abs())
+    } else {
+      format!("{}.{}e{}{}", &digits[..1], &digits[1..], e_sign, e.abs())
+    }
+  };
+  format!("{}{}", sign, body)
+}
+
+fn is_array_index(key: &str) -> bool {
+  key == "0" || (!key.is_empty() && !key.starts_with('0') && key.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// `canonicalKeyCompare`: integer-like keys numerically first, then UTF-16 order.
+fn canonical_key_order(left: &str, right: &str) -> Ordering {
+  match (is_array_index(left), is_array_index(right)) {
+    (true, true) => left.parse::<f64>().unwrap_or(0.0).total_cmp(&right.parse::<f64>().unwrap_or(0.0)),
+    (true, false) => Ordering::Less,
+    (false, true) => Ordering::Greater,
+    (false, false) => left.encode_utf16().cmp(right.encode_utf16()),
+  }
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+  match value {
+    Value::Null => out.push_str("null"),
+    Value::Bool(flag) => out.push_str(if *flag { "true" } else { "false" }),
+    Value::Number(number) => out.push_str(&js_number(number.as_f64().unwrap_or(f64::NAN))),
+    Value::String(text) => out.push_str(&Value::String(text.clone()).to_string()),
+    Value::Array(items) => {
+      out.push('[');
+      for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+          out.push(',');
+        }
+        write_canonical(item, out);
+      }
+      out.push(']');
+    }
+    Value::Object(map) => {
+      let mut keys: Vec<&String> = map.keys().collect();
+      keys.sort_by(|a, b| canonical_key_order(a, b));
+      out.push('{');
+      for (index, key) in keys.into_iter().enumerate() {
+        if index > 0 {
+          out.push(',');
+        }
+        out.push_str(&Value::String(key.clone()).to_string());
+        out.push(':');
+        write_canonical(&map[key], out);
+      }
+      out.push('}');
+    }
+  }
+}
+
+/// `canonicalizeHistoricalContractValue` for plain JSON values.
+pub(crate) fn canonical_json(value: &Value) -> String {
+  let mut out = String::new();
+  write_canonical(value, &mut out);
+  out
+}
+
+/// Same digest as `sha256Hex` in historical-backtest-contract.js.
+pub(crate) fn sha256_hex(input: &[u8]) -> String {
+  Sha256::digest(input).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// `fingerprintMonteCarloValue(value).value`.
+pub(crate) fn fingerprint(value: &Value) -> String {
+  sha256_hex(canonical_json(value).as_bytes())
+}
+
+// --- Privacy and number checks (normalizeMonteCarloJsonValue) ---
+
+/// `FORBIDDEN_PRIVATE_KEYS` in monte-carlo-contracts.js.
+fn is_forbidden_key(key: &str) -> bool {
+  let key = key.to_ascii_lowercase();
+  if ["password", "passphrase", "secret", "token"].contains(&key.as_str()) {
+    return true;
+  }
+  [("api", "token"), ("api", "key"), ("access", "key"), ("private", "key"), ("local", "path"), ("file", "path")]
+    .iter()
+    .any(|(head, tail)| {
+      key.strip_prefix(head)
+        .and_then(|rest| rest.strip_suffix(tail))
+        .is_some_and(|middle| middle.is_empty() || middle == "_" || middle == "-")
+    })
+}
+
+/// `WINDOWS_ABSOLUTE_PATH` / `POSIX_PRIVATE_PATH`: a path at the start of the
+/// string or after whitespace or a quote, followed by at least one path char.
+fn contains_local_path(text: &str) -> bool {
+  let is_path_char = |c: char| !c.is_whitespace() && c != '"' && c != '\'';
+  let chars: Vec<char> = text.chars().collect();
+  (0..chars.len()).any(|start| {
+    if start > 0 && is_path_char(chars[start - 1]) {
+      return false;
+    }
+    let rest = &chars[start..];
+    let prefix_len = if rest.len() >= 3 && rest[0].is_ascii_alphabetic() && rest[1] == ':' && rest[2] == '\\' {
+      3
+    } else if rest.starts_with(&['\\', '\\']) {
+      2
+    } else if rest.starts_with(&"/Users/".chars().collect::<Vec<_>>()) {
+      7
+    } else if rest.starts_with(&"/home/".chars().collect::<Vec<_>>()) {
+      6
+    } else {
+      return false;
+    };
+    rest.get(prefix_len).is_some_and(|c| is_path_char(*c))
+  })
+}
+
+fn collect_privacy_issues(path: &str, value: &Value, issues: &mut Vec<ExportIssue>) {
+  match value {
+    Value::String(text) if contains_local_path(text) => {
+      issues.push(issue("MC_EXPORT_LOCAL_PATH_FORBIDDEN", path, "enthaelt einen lokalen Dateipfad."));
+    }
+    Value::Array(items) => {
+      for (index, item) in items.iter().enumerate() {
+        collect_privacy_issues(&format!("{}[{}]", path, index), item, issues);
+      }
+    }
+    Value::Object(map) => {
+      for (key, child) in map {
+        let child_path = format!("{}.{}", path, key);
+        if is_forbidden_key(key) {
+          issues.push(issue("MC_EXPORT_PRIVATE_KEY_FORBIDDEN", &child_path, "gehoert nicht zum portablen Laufvertrag."));
+        }
+        collect_privacy_issues(&child_path, child, issues);
+      }
+    }
+    _ => {}
+  }
+}
+
+/// JSON has no NaN/Infinity; the JS writer rejects them before serializing, so
+/// bare tokens or overflowing literals mean a hand-edited or foreign file.
+fn non_finite_token(raw: &str) -> Option<&'static str> {
+  let mut in_string = false;
+  let mut escaped = false;
+  let bytes = raw.as_bytes();
+  for (index, byte) in bytes.iter().enumerate() {
+    if in_string {
+      match (escaped, byte) {
+        (true, _) => escaped = false,
+        (false, b'\\') => escaped = true,
+        (false, b'"') => in_string = false,
+        _ => {}
+      }
+      continue;
+    }
+    match byte {
+      b'"' => in_string = true,
+      b'N' if raw[index..].starts_with("NaN") => return Some("NaN"),
+      b'I' if raw[index..].starts_with("Infinity") => return Some("Infinity"),
+      _ => {}
+    }
+  }
+  None
+}
+
+// --- Verification ---
+
+fn issue(code: &'static str, path: &str, message: &str) -> ExportIssue {
+  ExportIssue { code, path: path.to_string(), message: message.to_string() }
+}
+
+fn object_at<'a>(value: &'a Value, path: &str, issues: &mut Vec<ExportIssue>) -> Option<&'a serde_json::Map<String, Value>> {
+  let object = value.as_object();
+  if object.is_none() {
+    issues.push(issue("MC_EXPORT_REQUIRED_OBJECT", path, "muss ein Objekt sein."));
+  }
+  object
+}
+
+fn require_string(value: &Value, path: &str, issues: &mut Vec<ExportIssue>) {
+  if !value.as_str().is_some_and(|text| !text.trim().is_empty()) {
+    issues.push(issue("MC_EXPORT_REQUIRED_STRING", path, "muss ein nicht-leerer String sein."));
+  }
+}
+
+fn require_value(value: &Value, expected: &str, code: &'static str, path: &str, issues: &mut Vec<ExportIssue>) {
+  if value.as_str() != Some(expected) {
+    issues.push(ExportIssue { code, path: path.to_string(), message: format!("erwartet {}, gefunden {}.", expected, value) });
+  }
+}
+
+/// `YYYY-MM-DDTHH:MM:SS[.fff]Z`, as written by `Date.prototype.toISOString()`.
+fn is_utc_timestamp(text: &str) -> bool {
+  let bytes = text.as_bytes();
+  let digits = |range: std::ops::Range<usize>| bytes.get(range.clone()).is_some_and(|part| part.iter().all(u8::is_ascii_digit));
+  let number = |range: std::ops::Range<usize>| text.get(range).and_then(|part| part.parse::<u32>().ok()).unwrap_or(99);
+  let fraction_ok = match bytes.get(19) {
+    Some(b'Z') => bytes.len() == 20,
+    Some(b'.') => bytes.len() > 21 && bytes.len() <= 24 && bytes[bytes.len() - 1] == b'Z' && digits(20..bytes.len() - 1),
+    _ => false,
+  };
+  fraction_ok
+    && digits(0..4)
+    && digits(5..7)
+    && digits(8..10)
+    && digits(11..13)
+    && digits(14..16)
+    && digits(17..19)
+    && bytes[4] == b'-'
+    && bytes[7] == b'-'
+    && bytes[10] == b'T'
+    && bytes[13] == b':'
+    && bytes[16] == b':'
+    && (1..=12).contains(&number(5..7))
+    && (1..=31).contains(&number(8..10))
+    && number(11..13) < 24
+    && number(14..16) < 60
+    && number(17..19) < 60
+}
+
+fn check_fingerprint(stored: &Value, basis: &Value, path: &str, issues: &mut Vec<ExportIssue>) -> String {
+  let expected = fingerprint(basis);
+  match stored.as_object() {
+    None => issues.push(issue("MC_EXPORT_REQUIRED_OBJECT", path, "muss ein Objekt sein.")),
+    Some(stored) if stored.get("algorithm").and_then(Value::as_str) != Some(&schema().fingerprint_algorithm) => {
+      issues.push(issue("MC_EXPORT_FINGERPRINT_ALGORITHM_UNSUPPORTED", &format!("{}.algorithm", path), "Algorithmus wird nicht unterstuetzt."));
+    }
+    Some(stored) if stored.get("value").and_then(Value::as_str) != Some(&expected) => issues.push(ExportIssue {
+      code: "MC_EXPORT_FINGERPRINT_MISMATCH",
+      path: format!("{}.value", path),
+      message: format!("passt nicht zum kanonischen Inhalt (erwartet {}).", expected),
+    }),
+    Some(_) => {}
+  }
+  expected
+}
+
+fn check_request(request: &Value, issues: &mut Vec<ExportIssue>) {
+  let schema = schema();
+  let Some(fields) = object_at(request, "$.request", issues) else { return };
+  require_value(&request["schemaVersion"], &schema.contracts.request, "MC_REQUEST_VERSION_UNSUPPORTED", "$.request.schemaVersion", issues);
+  for field in &schema.required_request_fields {
+    if !fields.contains_key(field) {
+      issues.push(issue("MC_EXPORT_REQUIRED_FIELD_MISSING", &format!("$.request.{}", field), "fehlt."));
+    }
+  }
+  let scenario = &request["scenario"];
+  if scenario.is_object() {
+    require_value(&scenario["schemaVersion"], &schema.contracts.scenario, "MC_REQUEST_SCENARIO_VERSION_UNSUPPORTED", "$.request.scenario.schemaVersion", issues);
+    let basis = serde_json::json!({
+      "schemaVersion": scenario["schemaVersion"],
+      "normalizedInputs": scenario["normalizedInputs"],
+      "widowOptions": scenario["widowOptions"]
+    });
+    check_fingerprint(&scenario["fingerprint"], &basis, "$.request.scenario.fingerprint", issues);
+  }
+  if request["data"].is_object() {
+    check_fingerprint(&request["data"]["fingerprint"], &request["data"]["version"], "$.request.data.fingerprint", issues);
+  }
+  require_value(&request["snapshotPolicy"]["schemaVersion"], &schema.contracts.snapshot_policy, "MC_REQUEST_SNAPSHOT_POLICY_UNSUPPORTED", "$.request.snapshotPolicy.schemaVersion", issues);
+}
+
+fn check_result(result: &Value, issues: &mut Vec<ExportIssue>) {
+  let schema = schema();
+  let Some(fields) = object_at(result, "$.result", issues) else { return };
+  require_value(&result["schemaVersion"], &schema.contracts.result, "MC_RESULT_VERSION_UNSUPPORTED", "$.result.schemaVersion", issues);
+  for field in &schema.required_result_fields {
+    if !fields.contains_key(field) {
+      issues.push(issue("MC_EXPORT_REQUIRED_FIELD_MISSING", &format!("$.result.{}", field), "fehlt."));
+    }
+  }
+  let units = &result["unitContract"];
+  if units["nominalMoneyFieldSuffix"].as_str() != Some(&schema.money_field_suffixes.nominal)
+    || units["realMoneyFieldSuffix"].as_str() != Some(&schema.money_field_suffixes.real)
+  {
+    issues.push(issue("MC_RESULT_UNIT_CONTRACT_INVALID", "$.result.unitContract", "Geldfeld-Suffixe muessen explizit sein."));
+  }
+  let requested = result["sampleSize"]["requestedRuns"].as_u64();
+  let inventory = &result["outcomeInventory"];
+  let classified: Option<u64> = ["ruin", "all_dead", "horizon_exhausted", "technical_error"]
+    .iter()
+    .map(|field| inventory[*field].as_u64())
+    .sum();
+  if requested.is_none() || classified != requested {
+    issues.push(issue(
+      "MC_RESULT_OUTCOME_INVENTORY_INCONSISTENT",
+      "$.result.outcomeInventory",
+      "muss jeden angeforderten Lauf genau einmal klassifizieren.",
+    ));
+  }
+  let technical_errors = result["technicalErrorCount"].as_u64();
+  let consistent = match result["batchStatus"].as_str() {
+    Some("completed") => technical_errors == Some(0) && result["financialMetricsValid"] == Value::Bool(true),
+    Some("technical_error") => technical_errors.is_some_and(|count| count >= 1) && result["financialMetricsValid"] == Value::Bool(false),
+    _ => false,
+  };
+  if !consistent {
+    issues.push(issue(
+      "MC_RESULT_BATCH_VALIDITY_INCONSISTENT",
+      "$.result.batchStatus",
+      "batchStatus, technische Fehler und financialMetricsValid widersprechen sich.",
+    ));
+  }
+}
+
+/// Validates a serialized export; collects every finding instead of stopping
+/// at the first, so a shared file can be judged in one pass.
+pub(crate) fn verify_export(raw: &str) -> ExportVerification {
+  let document: Value = match serde_json::from_str(raw) {
+    Ok(document) => document,
+    Err(error) => {
+      let found = non_finite_token(raw).map(|token| format!("enthaelt {}.", token)).or_else(|| {
+        error.to_string().contains("number out of range").then(|| format!("enthaelt eine nicht-endliche Zahl ({}).", error))
+      });
+      let finding = match found {
+        Some(message) => ExportIssue { code: "MC_EXPORT_NON_FINITE_NUMBER", path: "$".to_string(), message },
+        None => ExportIssue { code: "MC_EXPORT_JSON_INVALID", path: "$".to_string(), message: error.to_string() },
+      };
+      return ExportVerification { issues: vec![finding], ..ExportVerification::default() };
+    }
+  };
+  verify_export_value(&document)
+}
+
+pub(crate) fn verify_export_value(document: &Value) -> ExportVerification {
+  let schema = schema();
+  let mut issues = Vec::new();
+  let Some(fields) = object_at(document, "$", &mut issues) else {
+    return ExportVerification { issues, ..ExportVerification::default() };
+  };
+  require_value(&document["schemaId"], &schema.schema_id, "MC_EXPORT_SCHEMA_ID_UNSUPPORTED", "$.schemaId", &mut issues);
+  require_value(&document["schemaVersion"], &schema.contracts.export, "MC_EXPORT_VERSION_UNSUPPORTED", "$.schemaVersion", &mut issues);
+  if !document["exportedAtUtc"].as_str().is_some_and(is_utc_timestamp) {
+    issues.push(issue("MC_EXPORT_TIMESTAMP_INVALID", "$.exportedAtUtc", "muss ein gueltiger UTC-Zeitstempel sein."));
+  }
+  if object_at(&document["identifiers"], "$.identifiers", &mut issues).is_some() {
+    require_string(&document["identifiers"]["requestId"], "$.identifiers.requestId", &mut issues);
+    require_string(&document["identifiers"]["runId"], "$.identifiers.runId", &mut issues);
+  }
+  if object_at(&document["app"], "$.app", &mut issues).is_some() {
+    require_string(&document["app"]["applicationId"], "$.app.applicationId", &mut issues);
+    require_string(&document["app"]["packageVersion"], "$.app.packageVersion", &mut issues);
+  }
+  object_at(&document["engine"], "$.engine", &mut issues);
+  require_value(
+    &document["snapshotPolicy"]["schemaVersion"],
+    &schema.contracts.snapshot_policy,
+    "MC_EXPORT_SNAPSHOT_POLICY_UNSUPPORTED",
+    "$.snapshotPolicy.schemaVersion",
+    &mut issues,
+  );
+  check_request(&document["request"], &mut issues);
+  check_result(&document["result"], &mut issues);
+
+  let basis: serde_json::Map<String, Value> = fields
+    .iter()
+    .filter(|(key, _)| {
+      ["schemaId", "schemaVersion", "app", "engine", "snapshotPolicy", "request", "result"].contains(&key.as_str())
+    })
+    .map(|(key, value)| (key.clone(), value.clone()))
+    .collect();
+  let run_fingerprint = check_fingerprint(&document["fingerprint"], &Value::Object(basis), "$.fingerprint", &mut issues);
+  let excludes_ok = document["fingerprint"]["excludes"]
+    .as_array()
+    .is_some_and(|excludes| excludes.iter().filter_map(Value::as_str).eq(FINGERPRINT_EXCLUDES));
+  if document["fingerprint"].is_object() && !excludes_ok {
+    issues.push(issue("MC_EXPORT_FINGERPRINT_EXCLUDES_INVALID", "$.fingerprint.excludes", "weicht von der V1-Ausschlussliste ab."));
+  }
+  let expected_run_id = format!("mcrun_{}", run_fingerprint);
+  let expected_request_id = format!("mcrq_{}", fingerprint(&document["request"]));
+  if document["identifiers"]["runId"].as_str() != Some(&expected_run_id)
+    || document["identifiers"]["requestId"].as_str() != Some(&expected_request_id)
+  {
+    issues.push(issue("MC_EXPORT_IDENTIFIER_MISMATCH", "$.identifiers", "passen nicht zu den kanonischen Fingerprints."));
+  }
+  let compatibility = &document["compatibility"];
+  if compatibility["forwardPolicy"].as_str() != Some(&schema.forward_policy)
+    || compatibility["newExportsWriteDeprecatedAliases"].as_bool() != Some(schema.new_exports_write_deprecated_aliases)
+  {
+    issues.push(issue("MC_EXPORT_FORWARD_POLICY_INVALID", "$.compatibility", "Kompatibilitaetsrichtlinie fehlt oder ist inkompatibel."));
+  }
+  object_at(&document["privacy"], "$.privacy", &mut issues);
+  collect_privacy_issues("$", document, &mut issues);
+
+  let unknown_fields = fields
+    .keys()
+    .filter(|key| !KNOWN_TOP_LEVEL_FIELDS.contains(&key.as_str()))
+    .map(|key| format!("$.{}", key))
+    .collect();
+  ExportVerification {
+    valid: issues.is_empty(),
+    request_id: Some(expected_request_id),
+    run_id: Some(expected_run_id),
+    fingerprint: Some(run_fingerprint),
+    issues,
+    unknown_fields,
+  }
+}
+
+// --- KPI diff ---
+
+/// Flattens JSON into `(path, leaf)` rows using dotted keys and `[index]`.
+/// Object keys are visited in sorted order, independent of the key order of
+/// the result document.
+pub(crate) fn flatten_json(path: &str, value: &Value, rows: &mut Vec<(String, Value)>) {
+  match value {
+    Value::Object(map) => {
+      let mut entries: Vec<_> = map.iter().collect();
+      entries.sort_by(|a, b| a.0.cmp(b.0));
+      for (key, child) in entries {
+        let child_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+        flatten_json(&child_path, child, rows);
+      }
+    }
+    Value::Array(items) => {
+      for (index, child) in items.iter().enumerate() {
+        flatten_json(&format!("{}[{}]", path, index), child, rows);
+      }
+    }
+    leaf => rows.push((path.to_string(), leaf.clone())),
+  }
+}
+
+/// The comparable KPI leaves of a `MonteCarloRunResultV1`.
+pub(crate) fn kpi_rows(result: &Value) -> Vec<(String, Value)> {
+  let mut rows = Vec::new();
+  for field in KPI_FIELDS {
+    if let Some(value) = result.get(field) {
+      flatten_json(field, value, &mut rows);
+    }
+  }
+  rows
+}
+
+/// Compares the KPI leaves of two exports. Numbers within `tolerance`
+/// (relative, at least absolute for values below 1) count as equal.
+pub(crate) fn diff_exports(left: &Value, right: &Value, tolerance: f64) -> ExportDiff {
+  let mut leaves: BTreeMap<String, (Value, Value)> = BTreeMap::new();
+  for (path, value) in kpi_rows(&left["result"]) {
+    leaves.entry(path).or_insert((Value::Null, Value::Null)).0 = value;
+  }
+  for (path, value) in kpi_rows(&right["result"]) {
+    leaves.entry(path).or_insert((Value::Null, Value::Null)).1 = value;
+  }
+  let compared = leaves.len();
+  let changed = leaves
+    .into_iter()
+    .filter_map(|(path, (left, right))| match (left.as_f64(), right.as_f64()) {
+      (Some(a), Some(b)) => {
+        let delta = b - a;
+        (delta.abs() > tolerance * a.abs().max(b.abs()).max(1.0)).then(|| KpiDelta {
+          path,
+          left,
+          right,
+          absolute_delta: Some(delta),
+          relative_delta: (a != 0.0).then(|| delta / a.abs()),
+        })
+      }
+      _ => (left != right).then_some(KpiDelta { path, left, right, absolute_delta: None, relative_delta: None }),
+    })
+    .collect();
+  ExportDiff {
+    same_request: left["identifiers"]["requestId"] == right["identifiers"]["requestId"],
+    same_run: left["identifiers"]["runId"] == right["identifiers"]["runId"],
+    compared,
+    changed,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::monte_carlo::export_monte_carlo;
+  use crate::monte_carlo::tests::golden;
+
+  #[test]
+  fn canonical_json_matches_the_js_corpus() {
+    let path = crate::script_runtime::checkout_root().join("tests/fixtures/canonical-json-corpus-v1.json");
+    let corpus: Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let entries = corpus["entries"].as_array().unwrap();
+    assert!(entries.len() > 200);
+    for entry in entries {
+      let input = entry["input"].as_str().unwrap();
+      let value: Value = serde_json::from_str(input).unwrap();
+      assert_eq!(canonical_json(&value), entry["canonical"].as_str().unwrap(), "{}", input);
+      assert_eq!(fingerprint(&value), entry["sha256"].as_str().unwrap(), "{}", input);
+    }
+  }
+
+  #[test]
+  fn exports_verify_and_tampering_is_reported() {
+    let (source, request, result) = golden();
+    let raw = export_monte_carlo(&source, &request, &result).unwrap();
+    let report = verify_export(&raw);
+    assert!(report.valid, "{:?}", report.issues);
+    let document: Value = serde_json::from_str(&raw).unwrap();
+    assert_eq!(report.fingerprint.as_deref(), document["fingerprint"]["value"].as_str());
+    assert_eq!(report.run_id.as_deref(), document["identifiers"]["runId"].as_str());
+
+    let mut tampered = document.clone();
+    tampered["result"]["kpis"]["successRate"] = serde_json::json!(0.5);
+    tampered["request"]["scenario"]["normalizedInputs"]["note"] = serde_json::json!("siehe C:\\Users\\Oma\\szenario.json");
+    tampered["request"]["scenario"]["normalizedInputs"]["api_key"] = serde_json::json!("x");
+    tampered["futureField"] = serde_json::json!(1);
+    let report = verify_export_value(&tampered);
+    let codes: Vec<&str> = report.issues.iter().map(|issue| issue.code).collect();
+    for code in [
+      "MC_EXPORT_FINGERPRINT_MISMATCH",
+      "MC_EXPORT_IDENTIFIER_MISMATCH",
+      "MC_EXPORT_LOCAL_PATH_FORBIDDEN",
+      "MC_EXPORT_PRIVATE_KEY_FORBIDDEN",
+    ] {
+      assert!(codes.contains(&code), "{} missing in {:?}", code, codes);
+    }
+    assert_eq!(report.unknown_fields, vec!["$.futureField".to_string()]);
+
+    let non_finite = raw.replacen("\"seed\": 20260718", "\"seed\": NaN", 1);
+    assert_ne!(non_finite, raw);
+    assert_eq!(verify_export(&non_finite).issues[0].code, "MC_EXPORT_NON_FINITE_NUMBER");
+    assert_eq!(verify_export(&raw.replacen("\"seed\": 20260718", "\"seed\": 1e999", 1)).issues[0].code, "MC_EXPORT_NON_FINITE_NUMBER");
+
+    let mut shifted = document.clone();
+    let before = document["result"]["outcomeInventory"]["ruin"].as_f64().unwrap();
+    shifted["result"]["outcomeInventory"]["ruin"] = serde_json::json!(before + 1.0);
+    shifted["result"]["batchStatus"] = serde_json::json!("technical_error");
+    let diff = diff_exports(&document, &shifted, 0.0);
+    assert!(diff.same_request && diff.same_run);
+    let paths: Vec<&str> = diff.changed.iter().map(|delta| delta.path.as_str()).collect();
+    assert_eq!(paths, vec!["batchStatus", "outcomeInventory.ruin"]);
+    assert_eq!(diff.changed[1].absolute_delta, Some(1.0));
+    assert!(diff_exports(&document, &document, 0.0).changed.is_empty());
+  }
+
+  #[test]
+  fn local_paths_and_private_keys_follow_the_js_patterns() {
+    for text in ["C:\\Users\\x", "see \\\\server\\share", "'/home/oma/x'", "/Users/a"] {
+      assert!(contains_local_path(text), "{}", text);
+    }
+    for text in ["https://example.org/home/x", "C:\\", "ratio 1:2", "/homework", "x/Users/a"] {
+      assert!(!contains_local_path(text), "{}", text);
+    }
+    for key in ["Password", "API-Key", "apikey", "file_path", "localPath"] {
+      assert!(is_forbidden_key(key), "{}", key);
+    }
+    for key in ["tokens", "api__key", "filePathPrefix", "keyPath"] {
+      assert!(!is_forbidden_key(key), "{}", key);
+    }
+  }
+}
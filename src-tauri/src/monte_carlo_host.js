@@ -0,0 +1,138 @@
+// Native Monte-Carlo host, evaluated inside the embedded QuickJS runtime of
+// the desktop build (see monte_carlo.rs). It drives the same chunk, merge and
+// finalize steps as workers/mc-worker.js and simulator-monte-carlo.js, so the
+// native runner shares every line of engine code with the webview.
+
+import { prepareHistoricalDataOnce } from '../app/simulator/simulator-engine-helpers.js';
+import { EngineAPI } from '../engine/index.mjs';
+import {
+    attachMonteCarloBatchOutcome,
+    buildMonteCarloAggregates,
+    runMonteCarloChunk
+} from '../app/simulator/monte-carlo-runner.js';
+import {
+    createMonteCarloChunkAccumulatorV1,
+    finalizeMonteCarloChunkAccumulatorV1,
+    mergeMonteCarloChunkResultV1
+} from '../app/simulator/monte-carlo-chunk-result.js';
+import {
+    createMonteCarloRunResultV1,
+    extractMonteCarloReplayArgsV1
+} from '../app/simulator/monte-carlo-contracts.js';
+import { normalizeMonteCarloParametersV1 } from '../app/simulator/monte-carlo-parameters.js';
+import {
+    buildMonteCarloExportV1,
+    captureMonteCarloEngineProvenance,
+    serializeMonteCarloExportV1
+} from '../app/simulator/monte-carlo-export.js';
+
+let replayArgs = null;
+let accumulator = null;
+
+// Chunk results carry typed arrays, NaN/-0 and Maps that plain JSON would
+// lose; they cross thread boundaries as tagged JSON.
+function encodeTransfer(value) {
+    return JSON.stringify(value, (key, entry) => {
+        if (typeof entry === 'number') {
+            if (Number.isNaN(entry)) return { __num: 'NaN' };
+            if (entry === Infinity) return { __num: 'Infinity' };
+            if (entry === -Infinity) return { __num: '-Infinity' };
+            if (Object.is(entry, -0)) return { __num: '-0' };
+            return entry;
+        }
+        if (ArrayBuffer.isView(entry)) return { __typed: entry.constructor.name, data: Array.from(entry) };
+        if (entry instanceof Map) return { __map: Array.from(entry.entries()) };
+        if (entry instanceof Set) return { __set: Array.from(entry.values()) };
+        return entry;
+    });
+}
+
+function decodeTransfer(text) {
+    return JSON.parse(text, (key, entry) => {
+        if (!entry || typeof entry !== 'object' || Array.isArray(entry)) return entry;
+        if (typeof entry.__num === 'string') return Number(entry.__num);
+        if (typeof entry.__typed === 'string') return new globalThis[entry.__typed](entry.data);
+        if (Array.isArray(entry.__map)) return new Map(entry.__map);
+        if (Array.isArray(entry.__set)) return new Set(entry.__set);
+        return entry;
+    });
+}
+
+function requireRequest() {
+    if (!replayArgs) throw new Error('Native Monte-Carlo host used before prepare().');
+    return replayArgs;
+}
+
+function prepare(requestJson) {
+    prepareHistoricalDataOnce();
+    try {
+        replayArgs = extractMonteCarloReplayArgsV1(JSON.parse(requestJson));
+    } catch (error) {
+        throw new Error(error?.code ? `${error.code}: ${error.message}` : String(error?.message || error));
+    }
+    accumulator = null;
+    const { monteCarloParams } = replayArgs;
+    return JSON.stringify({
+        runs: monteCarloParams.anzahl,
+        // legacy-stream shares one RNG across runs and cannot be split, the
+        // webview keeps it off the worker pool for the same reason.
+        chunkable: monteCarloParams.rngMode !== 'legacy-stream'
+    });
+}
+
+async function runChunk(start, count) {
+    const { inputs, widowOptions, monteCarloParams, useCapeSampling } = requireRequest();
+    const normalizedParameters = normalizeMonteCarloParametersV1({ ...monteCarloParams, anzahl: count }, { inputs });
+    const result = await runMonteCarloChunk({
+        inputs,
+        widowOptions,
+        monteCarloParams: normalizedParameters,
+        useCapeSampling,
+        runRange: { start, count },
+        engine: EngineAPI
+    });
+    return encodeTransfer(result);
+}
+
+function mergeChunk(encoded, start, count) {
+    if (!accumulator) {
+        // Per-run metadata only feeds the webview's scenario analyzer and
+        // would dominate memory for million-run batches.
+        accumulator = createMonteCarloChunkAccumulatorV1(requireRequest().monteCarloParams.anzahl, { retainRunMeta: false });
+    }
+    mergeMonteCarloChunkResultV1(accumulator, decodeTransfer(encoded), {
+        expectedStart: start,
+        expectedCount: count
+    });
+}
+
+function finalize(executionJson) {
+    const { inputs, monteCarloParams } = requireRequest();
+    const finalized = finalizeMonteCarloChunkAccumulatorV1(accumulator);
+    const aggregatedResults = attachMonteCarloBatchOutcome(buildMonteCarloAggregates({
+        inputs,
+        totalRuns: monteCarloParams.anzahl,
+        buffers: finalized.buffers,
+        heatmap: finalized.heatmap,
+        bins: finalized.bins,
+        totals: finalized.totals,
+        lists: finalized.lists,
+        allRealWithdrawalsSample: finalized.allRealWithdrawalsSample
+    }), finalized.technicalInventory);
+    return JSON.stringify(createMonteCarloRunResultV1({
+        aggregatedResults,
+        samplingDiagnostics: finalized.samplingDiagnostics,
+        executionDiagnostics: JSON.parse(executionJson),
+        requestedRuns: monteCarloParams.anzahl
+    }));
+}
+
+function exportRun(requestJson, resultJson) {
+    return serializeMonteCarloExportV1(buildMonteCarloExportV1({
+        request: JSON.parse(requestJson),
+        result: JSON.parse(resultJson),
+        engine: captureMonteCarloEngineProvenance(EngineAPI)
+    }));
+}
+
+globalThis.__ruhestandMonteCarloHost = { prepare, runChunk, mergeChunk, finalize, exportRun };
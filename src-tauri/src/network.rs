@@ -0,0 +1,684 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::Emitter;
+
+use crate::distributions::{self, DistributionCache};
+use crate::network_audit::{self, NetworkAuditLog};
+use crate::quote_history::{self, QuoteHistoryStore};
+use crate::reference_rates::{self, RateCache};
+use crate::symbol_resolver::{self, SymbolCache};
+use crate::upstream_fixtures::{self, ActiveFixtures, UpstreamFixtureMode, UpstreamFixtureSettings};
+
+pub(crate) const NETWORK_SETTINGS_FILENAME: &str = "ruhestand_suite_network.json";
+pub(crate) const OFFLINE_MODE_CHANGED_EVENT: &str = "ruhestand://offline-mode-changed";
+pub(crate) const YAHOO_BASE_URLS_ENV: &str = "RUHESTAND_YAHOO_BASE_URLS";
+const DEFAULT_YAHOO_PRIMARY_BASE: &str = "https://query1.finance.yahoo.com";
+const DEFAULT_YAHOO_FALLBACK_BASE: &str = "https://query2.finance.yahoo.com";
+
+/// Yahoo hosts used by the proxy routes. Overridable via
+/// `RUHESTAND_YAHOO_BASE_URLS=<primary>,<fallback>` or `with_endpoints`, which
+/// lets the fallback chain run against a local mock upstream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct YahooEndpoints {
+  pub primary: String,
+  pub fallback: String,
+}
+
+impl Default for YahooEndpoints {
+  fn default() -> Self {
+    YahooEndpoints {
+      primary: DEFAULT_YAHOO_PRIMARY_BASE.to_string(),
+      fallback: DEFAULT_YAHOO_FALLBACK_BASE.to_string(),
+    }
+  }
+}
+
+impl YahooEndpoints {
+  pub(crate) fn parse(raw: &str) -> Option<Self> {
+    let bases: Vec<&str> = raw
+      .split(',')
+      .map(|base| base.trim().trim_end_matches('/'))
+      .filter(|base| !base.is_empty())
+      .collect();
+    let valid = |base: &&str| reqwest::Url::parse(base).is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+    match bases.as_slice() {
+      [primary] if valid(primary) => Some(YahooEndpoints { primary: primary.to_string(), fallback: primary.to_string() }),
+      [primary, fallback] if valid(primary) && valid(fallback) => {
+        Some(YahooEndpoints { primary: primary.to_string(), fallback: fallback.to_string() })
+      }
+      _ => None,
+    }
+  }
+
+  fn from_env() -> Self {
+    std::env::var(YAHOO_BASE_URLS_ENV)
+      .ok()
+      .and_then(|raw| YahooEndpoints::parse(&raw))
+      .unwrap_or_default()
+  }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub(crate) struct NetworkSettings {
+  pub offline_mode: bool,
+  pub upstream: UpstreamConnectionSettings,
+  pub fixtures: UpstreamFixtureSettings,
+}
+
+/// Corporate network settings applied to every outbound client. Without an
+/// explicit proxy reqwest keeps honouring the system `HTTP(S)_PROXY` variables.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct UpstreamConnectionSettings {
+  pub proxy_url: Option<String>,
+  pub proxy_username: Option<String>,
+  /// Kept in memory for the running session only and never written to the
+  /// settings file; it has to be entered again after a restart.
+  #[serde(skip_serializing)]
+  pub proxy_password: Option<String>,
+  /// Comma separated host list in `NO_PROXY` syntax. Applies to the explicit
+  /// proxy and to proxies taken from the `HTTP(S)_PROXY`/`ALL_PROXY`
+  /// variables; OS-level proxy configuration outside the environment is left
+  /// to the platform.
+  pub no_proxy: Option<String>,
+  /// PEM file with additional trusted root certificates, e.g. the root of a
+  /// TLS-inspecting firewall.
+  pub ca_certificate_path: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct UpstreamClientError {
+  pub step: &'static str,
+  pub message: String,
+}
+
+fn client_error(step: &'static str, message: impl Into<String>) -> UpstreamClientError {
+  UpstreamClientError { step, message: message.into() }
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+  value.as_deref().map(str::trim).filter(|value| !value.is_empty())
+}
+
+/// Applies proxy and certificate settings to a fresh client builder. Errors
+/// name the failing configuration step so `test_connectivity` can report it.
+pub(crate) fn upstream_client_builder(
+  settings: &UpstreamConnectionSettings,
+) -> Result<reqwest::blocking::ClientBuilder, UpstreamClientError> {
+  let mut builder = reqwest::blocking::Client::builder();
+
+  if let Some(proxy_url) = non_empty(&settings.proxy_url) {
+    let parsed = reqwest::Url::parse(proxy_url)
+      .map_err(|e| client_error("proxy", format!("Ungueltige Proxy-URL: {}", e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+      return Err(client_error("proxy", format!("Proxy-Schema {} wird nicht unterstuetzt.", parsed.scheme())));
+    }
+    let mut proxy = reqwest::Proxy::all(parsed)
+      .map_err(|e| client_error("proxy", e.to_string()))?;
+    if let Some(username) = non_empty(&settings.proxy_username) {
+      proxy = proxy.basic_auth(username, settings.proxy_password.as_deref().unwrap_or(""));
+    }
+    proxy = proxy.no_proxy(non_empty(&settings.no_proxy).and_then(reqwest::NoProxy::from_string));
+    builder = builder.proxy(proxy);
+  } else if let Some(no_proxy) = non_empty(&settings.no_proxy) {
+    for proxy in environment_proxies(|name| std::env::var(name).ok(), no_proxy)? {
+      builder = builder.proxy(proxy);
+    }
+  }
+
+  if let Some(ca_path) = non_empty(&settings.ca_certificate_path) {
+    let pem = fs::read(ca_path)
+      .map_err(|e| client_error("certificates", format!("Zertifikatsdatei nicht lesbar: {}", e)))?;
+    let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+      .map_err(|e| client_error("certificates", format!("Ungueltige PEM-Datei: {}", e)))?;
+    if certificates.is_empty() {
+      return Err(client_error("certificates", "PEM-Datei enthaelt keine Zertifikate."));
+    }
+    for certificate in certificates {
+      builder = builder.add_root_certificate(certificate);
+    }
+  }
+
+  Ok(builder)
+}
+
+/// Rebuilds the environment proxies reqwest would pick up on its own, with the
+/// configured exclusions added to any `NO_PROXY` from the environment. Adding
+/// them explicitly replaces reqwest's automatic system proxy.
+fn environment_proxies<F>(lookup: F, no_proxy: &str) -> Result<Vec<reqwest::Proxy>, UpstreamClientError>
+where
+  F: Fn(&str) -> Option<String>,
+{
+  let variable = |name: &str| {
+    lookup(name)
+      .or_else(|| lookup(&name.to_ascii_lowercase()))
+      .filter(|value| !value.trim().is_empty())
+  };
+  let exclusions = match variable("NO_PROXY") {
+    Some(inherited) => format!("{},{}", no_proxy, inherited),
+    None => no_proxy.to_string(),
+  };
+  let mut proxies = Vec::new();
+  for name in ["HTTPS_PROXY", "HTTP_PROXY", "ALL_PROXY"] {
+    let Some(url) = variable(name) else { continue };
+    let proxy = match name {
+      "HTTPS_PROXY" => reqwest::Proxy::https(url.trim()),
+      "HTTP_PROXY" => reqwest::Proxy::http(url.trim()),
+      _ => reqwest::Proxy::all(url.trim()),
+    }
+    .map_err(|e| client_error("proxy", format!("Ungueltiger System-Proxy in {}: {}", name, e)))?;
+    proxies.push(proxy.no_proxy(reqwest::NoProxy::from_string(&exclusions)));
+  }
+  Ok(proxies)
+}
+
+/// Shared between the Tauri commands and the local Yahoo proxy thread. Every
+/// outbound call has to consult this state before touching the network.
+pub(crate) struct NetworkState {
+  settings: Mutex<NetworkSettings>,
+  settings_path: Option<PathBuf>,
+  app_dir: Option<PathBuf>,
+  audit: NetworkAuditLog,
+  quote_history: QuoteHistoryStore,
+  symbol_cache: SymbolCache,
+  distribution_cache: DistributionCache,
+  rate_cache: RateCache,
+  endpoints: YahooEndpoints,
+  /// Newest recording time of the active replay directory, read once per
+  /// fixture configuration instead of on every quote.
+  replay_clock: Mutex<Option<(PathBuf, Option<u64>)>>,
+}
+
+pub(crate) type SharedNetworkState = Arc<NetworkState>;
+
+impl NetworkState {
+  pub(crate) fn in_memory(settings: NetworkSettings) -> Self {
+    NetworkState {
+      settings: Mutex::new(settings),
+      settings_path: None,
+      app_dir: None,
+      audit: NetworkAuditLog::in_memory(),
+      quote_history: QuoteHistoryStore::in_memory(),
+      symbol_cache: SymbolCache::in_memory(),
+      distribution_cache: DistributionCache::in_memory(),
+      rate_cache: RateCache::in_memory(),
+      endpoints: YahooEndpoints::default(),
+      replay_clock: Mutex::new(None),
+    }
+  }
+
+  #[cfg(test)]
+  pub(crate) fn with_endpoints(mut self, endpoints: YahooEndpoints) -> Self {
+    self.endpoints = endpoints;
+    self
+  }
+
+  /// Loads persisted settings, audit log, quote history and the symbol,
+  /// distribution and rate caches from the app data dir; missing or
+  /// unreadable files fall back to defaults so they can never block the start.
+  /// A proxy password left in the file by older versions is used for this
+  /// session and removed from disk.
+  pub(crate) fn load(app_dir: &Path) -> Self {
+    let settings_path = app_dir.join(NETWORK_SETTINGS_FILENAME);
+    let settings = fs::read_to_string(&settings_path)
+      .ok()
+      .and_then(|raw| serde_json::from_str::<NetworkSettings>(&raw).ok())
+      .unwrap_or_default();
+    if settings.upstream.proxy_password.is_some() {
+      let _ = write_settings_file(&settings_path, &settings);
+    }
+    NetworkState {
+      settings: Mutex::new(settings),
+      settings_path: Some(settings_path),
+      app_dir: Some(app_dir.to_path_buf()),
+      audit: NetworkAuditLog::load(app_dir.join(network_audit::NETWORK_AUDIT_FILENAME)),
+      quote_history: QuoteHistoryStore::load(app_dir.join(quote_history::QUOTE_HISTORY_FILENAME)),
+      symbol_cache: SymbolCache::load(app_dir.join(symbol_resolver::SYMBOL_CACHE_FILENAME)),
+      distribution_cache: DistributionCache::load(app_dir.join(distributions::DISTRIBUTION_CACHE_FILENAME)),
+      rate_cache: RateCache::load(app_dir.join(reference_rates::RATE_CACHE_FILENAME)),
+      endpoints: YahooEndpoints::from_env(),
+      replay_clock: Mutex::new(None),
+    }
+  }
+
+  pub(crate) fn audit(&self) -> &NetworkAuditLog {
+    &self.audit
+  }
+
+  pub(crate) fn quote_history(&self) -> &QuoteHistoryStore {
+    &self.quote_history
+  }
+
+  pub(crate) fn symbol_cache(&self) -> &SymbolCache {
+    &self.symbol_cache
+  }
+
+  pub(crate) fn distribution_cache(&self) -> &DistributionCache {
+    &self.distribution_cache
+  }
+
+  pub(crate) fn rate_cache(&self) -> &RateCache {
+    &self.rate_cache
+  }
+
+  pub(crate) fn yahoo_endpoints(&self) -> &YahooEndpoints {
+    &self.endpoints
+  }
+
+  pub(crate) fn is_offline(&self) -> bool {
+    self.settings.lock().map(|settings| settings.offline_mode).unwrap_or(true)
+  }
+
+  pub(crate) fn upstream_settings(&self) -> UpstreamConnectionSettings {
+    self.settings.lock().unwrap_or_else(|e| e.into_inner()).upstream.clone()
+  }
+
+  pub(crate) fn active_fixtures(&self) -> ActiveFixtures {
+    let settings = self.settings.lock().unwrap_or_else(|e| e.into_inner()).fixtures.clone();
+    upstream_fixtures::resolve_fixtures(
+      &settings,
+      std::env::var(upstream_fixtures::FIXTURE_MODE_ENV).ok(),
+      std::env::var(upstream_fixtures::FIXTURE_DIR_ENV).ok(),
+      self.app_dir.as_deref(),
+    )
+  }
+
+  /// Replay serves only local fixtures, so it stays available in offline mode.
+  pub(crate) fn is_replaying(&self) -> bool {
+    self.active_fixtures().mode == UpstreamFixtureMode::Replay
+  }
+
+  /// Clock for quote age checks; fixed to the recording time during replay.
+  pub(crate) fn now_seconds(&self) -> u64 {
+    let fixtures = self.active_fixtures();
+    match (fixtures.mode, fixtures.directory) {
+      (UpstreamFixtureMode::Replay, Some(directory)) => {
+        let mut cached = self.replay_clock.lock().unwrap_or_else(|e| e.into_inner());
+        let clock = match cached.as_ref() {
+          Some((cached_directory, clock)) if *cached_directory == directory => *clock,
+          _ => {
+            let clock = upstream_fixtures::replay_clock(&directory);
+            *cached = Some((directory, clock));
+            clock
+          }
+        };
+        clock.unwrap_or_else(crate::unix_now_seconds)
+      }
+      _ => crate::unix_now_seconds(),
+    }
+  }
+
+  pub(crate) fn update<F>(&self, apply: F) -> Result<NetworkSettings, String>
+  where
+    F: FnOnce(&mut NetworkSettings),
+  {
+    let mut settings = self.settings.lock().map_err(|e| e.to_string())?;
+    let mut next = settings.clone();
+    apply(&mut next);
+    if let Some(path) = &self.settings_path {
+      write_settings_file(path, &next)?;
+    }
+    *settings = next.clone();
+    *self.replay_clock.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    Ok(next)
+  }
+}
+
+fn write_settings_file(path: &Path, settings: &NetworkSettings) -> Result<(), String> {
+  let content = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+  crate::write_atomic(path, &content).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_offline_mode(state: tauri::State<'_, SharedNetworkState>) -> bool {
+  state.is_offline()
+}
+
+#[tauri::command]
+pub fn set_offline_mode(
+  app: tauri::AppHandle,
+  state: tauri::State<'_, SharedNetworkState>,
+  enabled: bool,
+) -> Result<bool, String> {
+  let previous = state.is_offline();
+  let settings = state.update(|settings| settings.offline_mode = enabled)?;
+  if previous != settings.offline_mode {
+    let _ = app.emit(
+      OFFLINE_MODE_CHANGED_EVENT,
+      serde_json::json!({ "offlineMode": settings.offline_mode }),
+    );
+  }
+  Ok(settings.offline_mode)
+}
+
+/// The stored password is never sent back to the webview.
+fn redacted_upstream_settings(settings: &UpstreamConnectionSettings) -> serde_json::Value {
+  serde_json::json!({
+    "proxyUrl": settings.proxy_url,
+    "proxyUsername": settings.proxy_username,
+    "hasProxyPassword": non_empty(&settings.proxy_password).is_some(),
+    "noProxy": settings.no_proxy,
+    "caCertificatePath": settings.ca_certificate_path,
+  })
+}
+
+#[tauri::command]
+pub fn get_upstream_connection(state: tauri::State<'_, SharedNetworkState>) -> serde_json::Value {
+  redacted_upstream_settings(&state.upstream_settings())
+}
+
+/// Validates before persisting so a broken proxy or PEM file is reported
+/// immediately instead of on the next quote fetch. A missing password keeps
+/// the stored one when the proxy user is unchanged.
+#[tauri::command]
+pub fn set_upstream_connection(
+  state: tauri::State<'_, SharedNetworkState>,
+  settings: UpstreamConnectionSettings,
+) -> Result<serde_json::Value, String> {
+  let current = state.upstream_settings();
+  let mut next = settings;
+  if next.proxy_password.is_none() && next.proxy_username == current.proxy_username {
+    next.proxy_password = current.proxy_password;
+  }
+  let _ = upstream_client_builder(&next).map_err(|error| format!("{}: {}", error.step, error.message))?;
+  let saved = state.update(|settings| settings.upstream = next)?;
+  Ok(redacted_upstream_settings(&saved.upstream))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ConnectivityStep {
+  pub step: &'static str,
+  pub ok: bool,
+  pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ConnectivityReport {
+  pub ok: bool,
+  pub failed_step: Option<&'static str>,
+  pub steps: Vec<ConnectivityStep>,
+}
+
+impl ConnectivityReport {
+  fn pass(&mut self, step: &'static str, message: impl Into<String>) {
+    self.steps.push(ConnectivityStep { step, ok: true, message: message.into() });
+  }
+
+  fn fail(mut self, step: &'static str, message: impl Into<String>) -> Self {
+    self.steps.push(ConnectivityStep { step, ok: false, message: message.into() });
+    self.ok = false;
+    self.failed_step = Some(step);
+    self
+  }
+}
+
+/// Walks offline check, proxy, certificates, client, connect/TLS and HTTP in
+/// order and stops at the first failing step.
+pub(crate) fn run_connectivity_test(network: &NetworkState, url: &str) -> ConnectivityReport {
+  let mut report = ConnectivityReport { ok: true, failed_step: None, steps: Vec::new() };
+  if network.is_offline() {
+    return report.fail("offline", "Offline-Modus aktiv; es wurde keine Verbindung aufgebaut.");
+  }
+  report.pass("offline", "Offline-Modus inaktiv.");
+
+  let settings = network.upstream_settings();
+  let builder = match upstream_client_builder(&settings) {
+    Ok(builder) => builder,
+    Err(error) => return report.fail(error.step, error.message),
+  };
+  report.pass("proxy", match non_empty(&settings.proxy_url) {
+    Some(proxy_url) => format!("Proxy {} konfiguriert.", redact_proxy_credentials(proxy_url)),
+    None => "Kein expliziter Proxy; System-Proxy-Variablen gelten.".to_string(),
+  });
+  report.pass("certificates", match non_empty(&settings.ca_certificate_path) {
+    Some(_) => "Zusaetzliche Stammzertifikate geladen.",
+    None => "Nur eingebaute Stammzertifikate.",
+  });
+
+  let client = match crate::configure_upstream_client(builder).build() {
+    Ok(client) => client,
+    Err(error) => return report.fail("client", error.to_string()),
+  };
+  report.pass("client", "HTTP-Client erstellt.");
+
+  let started = std::time::Instant::now();
+  let mut observation = network_audit::UpstreamObservation::default();
+  let outcome = client.get(url).send();
+  let failure_code = match &outcome {
+    Ok(response) => {
+      observation.status = Some(response.status().as_u16());
+      (!response.status().is_success()).then_some("HTTP_ERROR")
+    }
+    Err(_) => Some("CONNECTIVITY_FAILED"),
+  };
+  network.audit().record(url, crate::unix_now_seconds(), observation, failure_code, started.elapsed());
+
+  match outcome {
+    Err(error) => {
+      let step = classify_send_error(&error);
+      report.fail(step, error_chain_message(&error))
+    }
+    Ok(response) => {
+      report.pass("connect", "Verbindung und TLS-Handshake erfolgreich.");
+      let status = response.status();
+      if status.as_u16() == 407 {
+        report.fail("proxy", "Proxy verlangt Authentifizierung (HTTP 407).")
+      } else if status.is_success() {
+        report.pass("http", format!("HTTP {}.", status.as_u16()));
+        report
+      } else {
+        report.fail("http", format!("HTTP {}.", status.as_u16()))
+      }
+    }
+  }
+}
+
+fn error_chain_message(error: &(dyn std::error::Error + 'static)) -> String {
+  let mut parts = vec![error.to_string()];
+  let mut source = error.source();
+  while let Some(inner) = source {
+    parts.push(inner.to_string());
+    source = inner.source();
+  }
+  parts.join(": ")
+}
+
+fn classify_send_error(error: &reqwest::Error) -> &'static str {
+  let chain = error_chain_message(error).to_ascii_lowercase();
+  if error.is_timeout() {
+    "timeout"
+  } else if chain.contains("certificate") || chain.contains("tls") || chain.contains("handshake") {
+    "tls"
+  } else {
+    "connect"
+  }
+}
+
+fn redact_proxy_credentials(proxy_url: &str) -> String {
+  match reqwest::Url::parse(proxy_url) {
+    Ok(mut parsed) => {
+      let _ = parsed.set_username("");
+      let _ = parsed.set_password(None);
+      parsed.to_string()
+    }
+    Err(_) => proxy_url.to_string(),
+  }
+}
+
+#[tauri::command]
+pub async fn test_connectivity(
+  state: tauri::State<'_, SharedNetworkState>,
+) -> Result<ConnectivityReport, String> {
+  let network = state.inner().clone();
+  tauri::async_runtime::spawn_blocking(move || {
+    let url = format!("{}/v1/finance/search?q=VWCE", network.yahoo_endpoints().primary);
+    run_connectivity_test(&network, &url)
+  })
+  .await
+  .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_upstream_fixtures(state: tauri::State<'_, SharedNetworkState>) -> ActiveFixtures {
+  state.active_fixtures()
+}
+
+/// Environment variables still win over the stored setting; the returned
+/// value is the effective configuration.
+#[tauri::command]
+pub fn set_upstream_fixtures(
+  state: tauri::State<'_, SharedNetworkState>,
+  settings: UpstreamFixtureSettings,
+) -> Result<ActiveFixtures, String> {
+  state.update(|current| current.fixtures = settings)?;
+  Ok(state.active_fixtures())
+}
+
+#[tauri::command]
+pub fn list_network_audit(state: tauri::State<'_, SharedNetworkState>) -> serde_json::Value {
+  let entries = state.audit().entries();
+  serde_json::json!({
+    "hosts": network_audit::summarize_hosts(&entries),
+    "entries": entries,
+  })
+}
+
+#[tauri::command]
+pub fn clear_network_audit(state: tauri::State<'_, SharedNetworkState>) -> Result<(), String> {
+  state.audit().clear()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn offline_mode_survives_reload_and_defaults_to_online() {
+    let dir = std::env::temp_dir().join(format!("ruhestand-network-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(NETWORK_SETTINGS_FILENAME);
+    let _ = fs::remove_file(&path);
+
+    let state = NetworkState::load(&dir);
+    assert!(!state.is_offline());
+    state.update(|settings| settings.offline_mode = true).unwrap();
+    assert!(NetworkState::load(&dir).is_offline());
+
+    fs::write(&path, "{not json").unwrap();
+    assert!(!NetworkState::load(&dir).is_offline());
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn yahoo_endpoints_parse_one_or_two_http_bases() {
+    let both = YahooEndpoints::parse("http://127.0.0.1:9000/a/, http://127.0.0.1:9000/b").unwrap();
+    assert_eq!(both.primary, "http://127.0.0.1:9000/a");
+    assert_eq!(both.fallback, "http://127.0.0.1:9000/b");
+    assert_eq!(YahooEndpoints::parse("https://mirror.example").unwrap().fallback, "https://mirror.example");
+    assert!(YahooEndpoints::parse("ftp://mirror.example").is_none());
+    assert!(YahooEndpoints::parse("").is_none());
+  }
+
+  #[test]
+  fn upstream_client_builder_reports_failing_configuration_step() {
+    let invalid_proxy = UpstreamConnectionSettings {
+      proxy_url: Some("socks5://proxy.example:1080".to_string()),
+      ..Default::default()
+    };
+    assert_eq!(upstream_client_builder(&invalid_proxy).unwrap_err().step, "proxy");
+
+    let missing_ca = UpstreamConnectionSettings {
+      proxy_url: Some("http://proxy.example:3128".to_string()),
+      proxy_username: Some("user".to_string()),
+      proxy_password: Some("secret".to_string()),
+      no_proxy: Some("localhost,127.0.0.1".to_string()),
+      ca_certificate_path: Some("/nonexistent/corporate-root.pem".to_string()),
+    };
+    assert_eq!(upstream_client_builder(&missing_ca).unwrap_err().step, "certificates");
+
+    let proxy_only = UpstreamConnectionSettings { ca_certificate_path: None, ..missing_ca };
+    assert!(upstream_client_builder(&proxy_only).is_ok());
+    assert_eq!(redacted_upstream_settings(&proxy_only)["hasProxyPassword"], true);
+    assert!(redacted_upstream_settings(&proxy_only).get("proxyPassword").is_none());
+  }
+
+  #[test]
+  fn proxy_password_is_never_written_and_legacy_files_are_scrubbed() {
+    let dir = std::env::temp_dir().join(format!("ruhestand-network-password-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(NETWORK_SETTINGS_FILENAME);
+    fs::write(&path, r#"{"upstream":{"proxyUrl":"http://proxy.example:3128","proxyUsername":"user","proxyPassword":"secret"}}"#)
+      .unwrap();
+
+    let state = NetworkState::load(&dir);
+    assert_eq!(state.upstream_settings().proxy_password.as_deref(), Some("secret"));
+    assert!(!fs::read_to_string(&path).unwrap().contains("secret"));
+
+    state.update(|settings| settings.upstream.proxy_password = Some("changed".to_string())).unwrap();
+    assert_eq!(state.upstream_settings().proxy_password.as_deref(), Some("changed"));
+    assert!(!fs::read_to_string(&path).unwrap().contains("changed"));
+    assert_eq!(NetworkState::load(&dir).upstream_settings().proxy_password, None);
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn no_proxy_applies_to_environment_proxies() {
+    let upstream = crate::mock_upstream::MockUpstream::start(vec![(
+      "/query1/v1/finance/search",
+      crate::mock_upstream::MockResponse::raw(200, "{}"),
+    )]);
+    let environment = |name: &str| match name {
+      "http_proxy" => Some("http://127.0.0.1:9".to_string()),
+      "NO_PROXY" => Some("intranet.example".to_string()),
+      _ => None,
+    };
+    let url = format!("{}/v1/finance/search?q=VWCE", upstream.endpoints().primary);
+
+    let bypassed = environment_proxies(environment, "127.0.0.1").unwrap();
+    assert_eq!(bypassed.len(), 1);
+    let mut builder = reqwest::blocking::Client::builder();
+    for proxy in bypassed {
+      builder = builder.proxy(proxy);
+    }
+    assert_eq!(builder.build().unwrap().get(&url).send().unwrap().status().as_u16(), 200);
+
+    let mut builder = reqwest::blocking::Client::builder();
+    for proxy in environment_proxies(environment, "proxy-free.example").unwrap() {
+      builder = builder.proxy(proxy);
+    }
+    assert!(builder.build().unwrap().get(&url).send().is_err());
+
+    let broken = environment_proxies(|name| (name == "HTTPS_PROXY").then(|| "::".to_string()), "localhost");
+    assert_eq!(broken.unwrap_err().step, "proxy");
+  }
+
+  #[test]
+  fn connectivity_test_stops_at_offline_mode_and_bad_certificates() {
+    let offline = NetworkState::in_memory(NetworkSettings { offline_mode: true, ..Default::default() });
+    let report = run_connectivity_test(&offline, "https://query1.finance.yahoo.com/v1/finance/search?q=VWCE");
+    assert!(!report.ok);
+    assert_eq!(report.failed_step, Some("offline"));
+    assert!(offline.audit().entries().is_empty());
+
+    let dir = std::env::temp_dir().join(format!("ruhestand-network-ca-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let pem_path = dir.join("root.pem");
+    fs::write(&pem_path, "no certificate here").unwrap();
+    let broken_ca = NetworkState::in_memory(NetworkSettings {
+      upstream: UpstreamConnectionSettings {
+        ca_certificate_path: Some(pem_path.to_string_lossy().to_string()),
+        ..Default::default()
+      },
+      ..Default::default()
+    });
+    let report = run_connectivity_test(&broken_ca, "https://query1.finance.yahoo.com/v1/finance/search?q=VWCE");
+    assert_eq!(report.failed_step, Some("certificates"));
+    assert_eq!(report.steps.iter().filter(|step| step.ok).count(), 1);
+    let _ = fs::remove_dir_all(&dir);
+  }
+}
@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub(crate) const NETWORK_AUDIT_FILENAME: &str = "ruhestand_suite_network_audit.jsonl";
+pub(crate) const NETWORK_AUDIT_MAX_ENTRIES: usize = 500;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct NetworkAuditEntry {
+  pub timestamp: u64,
+  pub host: String,
+  pub path: String,
+  /// Only the parameter names are kept; values such as search terms never
+  /// reach the log.
+  pub query_keys: Vec<String>,
+  pub status: Option<u16>,
+  pub code: Option<String>,
+  pub latency_ms: u64,
+  pub bytes: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct NetworkAuditHostSummary {
+  pub host: String,
+  pub requests: u64,
+  pub failures: u64,
+  pub bytes: u64,
+  pub last_timestamp: u64,
+}
+
+/// What a single upstream exchange produced besides its result; filled in by
+/// the fetcher while it runs so failures still carry status and size.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct UpstreamObservation {
+  pub status: Option<u16>,
+  pub bytes: u64,
+}
+
+/// The JSONL file behind the log. Entries are appended one line at a time and
+/// the file is only rewritten once it holds twice the retained entries.
+struct AuditFile {
+  path: PathBuf,
+  lines: usize,
+}
+
+/// Bounded, optionally file-backed log of every outbound request.
+pub(crate) struct NetworkAuditLog {
+  entries: Mutex<VecDeque<NetworkAuditEntry>>,
+  file: Option<Mutex<AuditFile>>,
+}
+
+impl NetworkAuditLog {
+  pub(crate) fn in_memory() -> Self {
+    NetworkAuditLog { entries: Mutex::new(VecDeque::new()), file: None }
+  }
+
+  pub(crate) fn load(path: PathBuf) -> Self {
+    let raw = fs::read_to_string(&path).unwrap_or_default();
+    let mut lines = 0;
+    let mut entries = VecDeque::new();
+    for line in raw.lines().filter(|line| !line.trim().is_empty()) {
+      lines += 1;
+      if let Ok(entry) = serde_json::from_str::<NetworkAuditEntry>(line) {
+        entries.push_back(entry);
+      }
+    }
+    while entries.len() > NETWORK_AUDIT_MAX_ENTRIES {
+      entries.pop_front();
+    }
+    NetworkAuditLog { entries: Mutex::new(entries), file: Some(Mutex::new(AuditFile { path, lines })) }
+  }
+
+  pub(crate) fn record(
+    &self,
+    url: &str,
+    timestamp: u64,
+    observation: UpstreamObservation,
+    code: Option<&str>,
+    latency: Duration,
+  ) {
+    let (host, path, query_keys) = redact_url(url);
+    let entry = NetworkAuditEntry {
+      timestamp,
+      host,
+      path,
+      query_keys,
+      status: observation.status,
+      code: code.map(str::to_string),
+      latency_ms: latency.as_millis().min(u64::MAX as u128) as u64,
+      bytes: observation.bytes,
+    };
+    let line = self.file.as_ref().and_then(|_| serde_json::to_string(&entry).ok());
+    {
+      let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+      entries.push_back(entry);
+      while entries.len() > NETWORK_AUDIT_MAX_ENTRIES {
+        entries.pop_front();
+      }
+    }
+    if let Some(line) = line {
+      self.append(&line);
+    }
+  }
+
+  pub(crate) fn entries(&self) -> Vec<NetworkAuditEntry> {
+    self.entries.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+  }
+
+  pub(crate) fn clear(&self) -> Result<(), String> {
+    let mut file = self.file.as_ref().map(|file| file.lock().unwrap_or_else(|e| e.into_inner()));
+    self.entries.lock().map_err(|e| e.to_string())?.clear();
+    if let Some(file) = file.as_mut() {
+      file.lines = 0;
+      if file.path.exists() {
+        fs::remove_file(&file.path).map_err(|e| e.to_string())?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Appends one serialized entry outside the entries lock; compacts the file
+  /// down to the retained entries once it has grown past twice the bound.
+  fn append(&self, line: &str) {
+    let Some(file) = &self.file else { return };
+    let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+    let appended = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&file.path)
+      .and_then(|mut handle| writeln!(handle, "{}", line));
+    if appended.is_err() {
+      return;
+    }
+    file.lines += 1;
+    if file.lines <= 2 * NETWORK_AUDIT_MAX_ENTRIES {
+      return;
+    }
+    let retained = self.entries();
+    let mut content = String::new();
+    for entry in &retained {
+      if let Ok(line) = serde_json::to_string(entry) {
+        content.push_str(&line);
+        content.push('\n');
+      }
+    }
+    if crate::write_atomic(&file.path, &content).is_ok() {
+      file.lines = retained.len();
+    }
+  }
+}
+
+/// Splits a URL into host, path and query parameter names.
+pub(crate) fn redact_url(url: &str) -> (String, String, Vec<String>) {
+  match reqwest::Url::parse(url) {
+    Ok(parsed) => {
+      let mut keys: Vec<String> = parsed.query_pairs().map(|(key, _)| key.into_owned()).collect();
+      keys.dedup();
+      (parsed.host_str().unwrap_or("").to_string(), parsed.path().to_string(), keys)
+    }
+    Err(_) => (String::new(), String::new(), Vec::new()),
+  }
+}
+
+pub(crate) fn summarize_hosts(entries: &[NetworkAuditEntry]) -> Vec<NetworkAuditHostSummary> {
+  let mut hosts: BTreeMap<&str, NetworkAuditHostSummary> = BTreeMap::new();
+  for entry in entries {
+    let summary = hosts.entry(entry.host.as_str()).or_insert_with(|| NetworkAuditHostSummary {
+      host: entry.host.clone(),
+      ..Default::default()
+    });
+    summary.requests += 1;
+    if entry.code.is_some() {
+      summary.failures += 1;
+    }
+    summary.bytes += entry.bytes;
+    summary.last_timestamp = summary.last_timestamp.max(entry.timestamp);
+  }
+  hosts.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn redact_url_keeps_only_host_path_and_query_names() {
+    let (host, path, keys) = redact_url("https://query1.finance.yahoo.com/v1/finance/search?q=my%20depot&lang=en");
+    assert_eq!(host, "query1.finance.yahoo.com");
+    assert_eq!(path, "/v1/finance/search");
+    assert_eq!(keys, vec!["q".to_string(), "lang".to_string()]);
+  }
+
+  #[test]
+  fn audit_log_is_bounded_and_summarized_per_host() {
+    let log = NetworkAuditLog::in_memory();
+    for index in 0..(NETWORK_AUDIT_MAX_ENTRIES + 5) {
+      let host = if index % 2 == 0 { "query1.finance.yahoo.com" } else { "query2.finance.yahoo.com" };
+      let code = if index % 5 == 0 { Some("PROVIDER_UNAVAILABLE") } else { None };
+      log.record(
+        &format!("https://{}/v7/finance/quote?symbols=VWCE.DE", host),
+        index as u64,
+        UpstreamObservation { status: Some(200), bytes: 10 },
+        code,
+        Duration::from_millis(5),
+      );
+    }
+    let entries = log.entries();
+    assert_eq!(entries.len(), NETWORK_AUDIT_MAX_ENTRIES);
+    assert_eq!(entries[0].timestamp, 5);
+
+    let summary = summarize_hosts(&entries);
+    assert_eq!(summary.len(), 2);
+    assert_eq!(summary.iter().map(|host| host.requests).sum::<u64>(), NETWORK_AUDIT_MAX_ENTRIES as u64);
+    assert_eq!(summary[0].bytes, summary[0].requests * 10);
+    assert_eq!(summary.iter().map(|host| host.failures).sum::<u64>(), 100);
+
+    log.clear().unwrap();
+    assert!(log.entries().is_empty());
+  }
+
+  #[test]
+  fn audit_file_is_appended_and_compacted_to_the_bound() {
+    let dir = std::env::temp_dir().join(format!("ruhestand-audit-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(NETWORK_AUDIT_FILENAME);
+    let log = NetworkAuditLog::load(path.clone());
+    let record = |log: &NetworkAuditLog, timestamp: u64| {
+      log.record(
+        "https://query1.finance.yahoo.com/v7/finance/quote?symbols=VWCE.DE",
+        timestamp,
+        UpstreamObservation { status: Some(200), bytes: 10 },
+        None,
+        Duration::from_millis(5),
+      )
+    };
+    for timestamp in 0..3 {
+      record(&log, timestamp);
+    }
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+    assert_eq!(NetworkAuditLog::load(path.clone()).entries(), log.entries());
+
+    for timestamp in 3..(2 * NETWORK_AUDIT_MAX_ENTRIES as u64 + 1) {
+      record(&log, timestamp);
+    }
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), NETWORK_AUDIT_MAX_ENTRIES);
+    let reloaded = NetworkAuditLog::load(path.clone());
+    assert_eq!(reloaded.entries(), log.entries());
+    assert_eq!(reloaded.entries()[0].timestamp, NETWORK_AUDIT_MAX_ENTRIES as u64 + 1);
+
+    log.clear().unwrap();
+    assert!(!path.exists());
+    let _ = fs::remove_dir_all(&dir);
+  }
+}
@@ -0,0 +1,418 @@
+//! Local quote history. Every quote that passes the quote contract is appended
+//! as one JSON line to a file in the app data dir, so the depot can be valued
+//! between annual closes without downloading history again. The file is never
+//! rewritten; unreadable lines are skipped on load.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::network::SharedNetworkState;
+use crate::script_runtime::{job_failure as failure, JobFailure};
+
+pub(crate) const QUOTE_HISTORY_FILENAME: &str = "ruhestand_suite_quote_history.jsonl";
+const SECONDS_PER_DAY: i64 = 86_400;
+/// Upper bound for a reconstructed series (about 30 years of days).
+const MAX_SERIES_DAYS: i64 = 11_000;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct QuoteRecord {
+  pub symbol: String,
+  pub price: f64,
+  pub currency: String,
+  pub as_of: u64,
+  pub source: String,
+}
+
+/// Append-only, optionally file-backed store of normalized quotes.
+pub(crate) struct QuoteHistoryStore {
+  records: Mutex<Vec<QuoteRecord>>,
+  path: Option<PathBuf>,
+}
+
+impl QuoteHistoryStore {
+  pub(crate) fn in_memory() -> Self {
+    QuoteHistoryStore { records: Mutex::new(Vec::new()), path: None }
+  }
+
+  pub(crate) fn load(path: PathBuf) -> Self {
+    let records = fs::read_to_string(&path)
+      .map(|raw| raw.lines().filter_map(|line| serde_json::from_str::<QuoteRecord>(line).ok()).collect())
+      .unwrap_or_default();
+    QuoteHistoryStore { records: Mutex::new(records), path: Some(path) }
+  }
+
+  /// Appends a normalized quote (`symbol`, `price`, `currency`, `asOf`,
+  /// `source`); repeated answers for the same `asOf` are stored once.
+  pub(crate) fn record(&self, quote: &serde_json::Value) {
+    let Ok(record) = serde_json::from_value::<QuoteRecord>(quote.clone()) else { return };
+    let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+    let known = records.iter().rev().find(|known| known.symbol == record.symbol);
+    if known.is_some_and(|known| known.as_of >= record.as_of) {
+      return;
+    }
+    if let Some(path) = &self.path {
+      let Ok(line) = serde_json::to_string(&record) else { return };
+      let appended = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{}", line));
+      if let Err(error) = appended {
+        log::warn!("Kurshistorie konnte nicht geschrieben werden: {}", error);
+        return;
+      }
+    }
+    records.push(record);
+  }
+
+  /// Records of one symbol in `asOf` order.
+  pub(crate) fn history(&self, symbol: &str) -> Vec<QuoteRecord> {
+    let records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+    let mut history: Vec<QuoteRecord> = records.iter().filter(|record| record.symbol == symbol).cloned().collect();
+    history.sort_by_key(|record| record.as_of);
+    history
+  }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct QuoteHistoryRequest {
+  pub symbol: String,
+  /// Inclusive UTC days as `YYYY-MM-DD`.
+  pub from: Option<String>,
+  pub to: Option<String>,
+  /// Keep only the last quote of each day.
+  #[serde(default)]
+  pub daily: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct QuoteHistoryPoint {
+  pub date: String,
+  pub as_of: u64,
+  pub price: f64,
+  pub currency: String,
+  pub source: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct QuoteHistory {
+  pub symbol: String,
+  pub points: Vec<QuoteHistoryPoint>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HistoryTranche {
+  pub ticker: Option<String>,
+  pub shares: f64,
+  pub purchase_price: f64,
+  /// Held from this day on; tranches without date count for every day.
+  pub purchase_date: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DepotHistoryRequest {
+  pub tranches: Vec<HistoryTranche>,
+  pub from: Option<String>,
+  pub to: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DepotValuePoint {
+  pub date: String,
+  pub market_value: f64,
+  pub cost_basis: f64,
+  pub priced_tranches: usize,
+  /// Held tranches without a stored quote up to this day.
+  pub unpriced_tranches: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SymbolCoverage {
+  pub symbol: String,
+  pub first_date: Option<String>,
+  pub last_date: Option<String>,
+  pub quotes: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DepotValueHistory {
+  pub points: Vec<DepotValuePoint>,
+  pub coverage: Vec<SymbolCoverage>,
+}
+
+fn invalid(message: impl Into<String>) -> JobFailure {
+  failure("QUOTE_HISTORY_INVALID", message)
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's algorithm).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year.div_euclid(400);
+  let year_of_era = year - era * 400;
+  let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+  let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+  era * 146_097 + day_of_era - 719_468
+}
+
+pub(crate) fn civil_from_days(days: i64) -> String {
+  let z = days + 719_468;
+  let era = z.div_euclid(146_097);
+  let day_of_era = z - era * 146_097;
+  let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let mp = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = year_of_era + era * 400 + i64::from(month <= 2);
+  format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn day_of(as_of: u64) -> i64 {
+  as_of as i64 / SECONDS_PER_DAY
+}
+
+/// `YYYY-MM-DD` to days since epoch; rejects impossible dates.
+pub(crate) fn parse_day(raw: &str) -> Option<i64> {
+  let mut parts = raw.trim().splitn(3, '-');
+  let year = parts.next()?.parse::<i64>().ok()?;
+  let month = parts.next()?.parse::<i64>().ok()?;
+  let day = parts.next()?.parse::<i64>().ok()?;
+  if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+    return None;
+  }
+  let days = days_from_civil(year, month, day);
+  (civil_from_days(days) == format!("{:04}-{:02}-{:02}", year, month, day)).then_some(days)
+}
+
+fn day_bound(raw: &Option<String>, field: &str) -> Result<Option<i64>, JobFailure> {
+  match raw.as_deref().map(str::trim).filter(|text| !text.is_empty()) {
+    Some(text) => parse_day(text).map(Some).ok_or_else(|| invalid(format!("{} muss ein Datum YYYY-MM-DD sein.", field))),
+    None => Ok(None),
+  }
+}
+
+fn in_range(day: i64, from: Option<i64>, to: Option<i64>) -> bool {
+  !from.is_some_and(|from| day < from) && !to.is_some_and(|to| day > to)
+}
+
+fn normalize_symbol(raw: &str) -> String {
+  raw.trim().to_ascii_uppercase()
+}
+
+pub(crate) fn quote_history(store: &QuoteHistoryStore, request: &QuoteHistoryRequest) -> Result<QuoteHistory, JobFailure> {
+  let symbol = normalize_symbol(&request.symbol);
+  if symbol.is_empty() {
+    return Err(invalid("symbol fehlt."));
+  }
+  let from = day_bound(&request.from, "from")?;
+  let to = day_bound(&request.to, "to")?;
+  let mut points: Vec<QuoteHistoryPoint> = Vec::new();
+  for record in store.history(&symbol) {
+    let day = day_of(record.as_of);
+    if !in_range(day, from, to) {
+      continue;
+    }
+    let point = QuoteHistoryPoint {
+      date: civil_from_days(day),
+      as_of: record.as_of,
+      price: record.price,
+      currency: record.currency,
+      source: record.source,
+    };
+    match points.last_mut() {
+      Some(last) if request.daily && last.date == point.date => *last = point,
+      _ => points.push(point),
+    }
+  }
+  Ok(QuoteHistory { symbol, points })
+}
+
+/// Daily depot value from today's holdings and the stored quotes; each symbol
+/// carries its last known price forward. Sold lots are not part of the
+/// tranche list, so earlier days only show what is still held.
+pub(crate) fn depot_value_history(store: &QuoteHistoryStore, request: &DepotHistoryRequest) -> Result<DepotValueHistory, JobFailure> {
+  let from = day_bound(&request.from, "from")?;
+  let to = day_bound(&request.to, "to")?;
+  let mut held = Vec::new();
+  for (index, tranche) in request.tranches.iter().enumerate() {
+    if !tranche.shares.is_finite() || tranche.shares < 0.0 || !tranche.purchase_price.is_finite() {
+      return Err(invalid(format!("tranches[{}] hat ungueltige Stueckzahl oder Kaufkurs.", index)));
+    }
+    let since = day_bound(&tranche.purchase_date, &format!("tranches[{}].purchaseDate", index))?;
+    let symbol = tranche.ticker.as_deref().map(normalize_symbol).filter(|symbol| !symbol.is_empty());
+    held.push((tranche, symbol, since));
+  }
+
+  let mut series: BTreeMap<String, Vec<(i64, f64)>> = BTreeMap::new();
+  for symbol in held.iter().filter_map(|(_, symbol, _)| symbol.clone()) {
+    if series.contains_key(&symbol) {
+      continue;
+    }
+    let mut prices: Vec<(i64, f64)> = Vec::new();
+    for record in store.history(&symbol) {
+      let day = day_of(record.as_of);
+      match prices.last_mut() {
+        Some(last) if last.0 == day => last.1 = record.price,
+        _ => prices.push((day, record.price)),
+      }
+    }
+    series.insert(symbol, prices);
+  }
+  let coverage = series
+    .iter()
+    .map(|(symbol, prices)| SymbolCoverage {
+      symbol: symbol.clone(),
+      first_date: prices.first().map(|(day, _)| civil_from_days(*day)),
+      last_date: prices.last().map(|(day, _)| civil_from_days(*day)),
+      quotes: prices.len(),
+    })
+    .collect();
+
+  let first = series.values().filter_map(|prices| prices.first()).map(|(day, _)| *day).min();
+  let last = series.values().filter_map(|prices| prices.last()).map(|(day, _)| *day).max();
+  let (Some(first), Some(last)) = (first, last) else {
+    return Ok(DepotValueHistory { points: Vec::new(), coverage });
+  };
+  let start = from.map_or(first, |from| from.max(first));
+  let end = to.unwrap_or(last);
+  if end - start > MAX_SERIES_DAYS {
+    return Err(invalid(format!("Zeitraum ist laenger als {} Tage.", MAX_SERIES_DAYS)));
+  }
+
+  let mut cursors: HashMap<&str, usize> = HashMap::new();
+  let mut points = Vec::new();
+  for day in start..=end {
+    let mut point = DepotValuePoint { date: civil_from_days(day), market_value: 0.0, cost_basis: 0.0, priced_tranches: 0, unpriced_tranches: 0 };
+    for (tranche, symbol, since) in &held {
+      if since.is_some_and(|since| since > day) {
+        continue;
+      }
+      let price = symbol.as_deref().and_then(|symbol| {
+        let prices = &series[symbol];
+        let cursor = cursors.entry(symbol).or_insert(0);
+        while *cursor < prices.len() && prices[*cursor].0 <= day {
+          *cursor += 1;
+        }
+        cursor.checked_sub(1).map(|index| prices[index].1)
+      });
+      match price {
+        Some(price) => {
+          point.market_value += tranche.shares * price;
+          point.cost_basis += tranche.shares * tranche.purchase_price;
+          point.priced_tranches += 1;
+        }
+        None => point.unpriced_tranches += 1,
+      }
+    }
+    if point.priced_tranches > 0 {
+      point.market_value = (point.market_value * 100.0).round() / 100.0;
+      point.cost_basis = (point.cost_basis * 100.0).round() / 100.0;
+      points.push(point);
+    }
+  }
+  Ok(DepotValueHistory { points, coverage })
+}
+
+#[tauri::command]
+pub fn get_quote_history(state: tauri::State<'_, SharedNetworkState>, request: QuoteHistoryRequest) -> Result<QuoteHistory, String> {
+  quote_history(state.quote_history(), &request).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_depot_value_history(
+  state: tauri::State<'_, SharedNetworkState>,
+  request: DepotHistoryRequest,
+) -> Result<DepotValueHistory, String> {
+  depot_value_history(state.quote_history(), &request).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  const DAY: u64 = 86_400;
+  /// 2024-01-01T12:00:00Z
+  const JAN_1: u64 = 1_704_110_400;
+
+  fn quote(symbol: &str, price: f64, as_of: u64) -> serde_json::Value {
+    json!({ "symbol": symbol, "price": price, "currency": "EUR", "asOf": as_of, "source": "yahoo-chart" })
+  }
+
+  #[test]
+  fn store_appends_new_quotes_once_and_survives_reload() {
+    let dir = std::env::temp_dir().join(format!("ruhestand-quote-history-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(QUOTE_HISTORY_FILENAME);
+    let _ = fs::remove_file(&path);
+
+    let store = QuoteHistoryStore::load(path.clone());
+    store.record(&quote("VWCE.DE", 100.0, JAN_1));
+    store.record(&quote("VWCE.DE", 100.0, JAN_1));
+    store.record(&quote("VWCE.DE", 101.0, JAN_1 + 60));
+    store.record(&quote("VWCE.DE", 99.0, JAN_1 - 60));
+    store.record(&json!({ "symbol": "BROKEN" }));
+    let mut raw = fs::read_to_string(&path).unwrap();
+    assert_eq!(raw.lines().count(), 2);
+
+    raw.push_str("{kein json\n");
+    fs::write(&path, raw).unwrap();
+    let reloaded = QuoteHistoryStore::load(path);
+    let prices: Vec<f64> = reloaded.history("VWCE.DE").iter().map(|record| record.price).collect();
+    assert_eq!(prices, vec![100.0, 101.0]);
+    let _ = fs::remove_dir_all(&dir);
+  }
+
+  #[test]
+  fn symbol_history_filters_days_and_keeps_last_quote_per_day() {
+    let store = QuoteHistoryStore::in_memory();
+    for (price, as_of) in [(100.0, JAN_1), (102.0, JAN_1 + 3600), (104.0, JAN_1 + DAY), (106.0, JAN_1 + 3 * DAY)] {
+      store.record(&quote("VWCE.DE", price, as_of));
+    }
+    let request = QuoteHistoryRequest { symbol: " vwce.de ".into(), daily: true, ..QuoteHistoryRequest::default() };
+    let history = quote_history(&store, &request).unwrap();
+    let points: Vec<(&str, f64)> = history.points.iter().map(|point| (point.date.as_str(), point.price)).collect();
+    assert_eq!(points, vec![("2024-01-01", 102.0), ("2024-01-02", 104.0), ("2024-01-04", 106.0)]);
+
+    let ranged = QuoteHistoryRequest { from: Some("2024-01-02".into()), to: Some("2024-01-03".into()), ..request };
+    assert_eq!(quote_history(&store, &ranged).unwrap().points.len(), 1);
+    let bad = QuoteHistoryRequest { symbol: "VWCE.DE".into(), from: Some("2024-02-30".into()), ..QuoteHistoryRequest::default() };
+    assert_eq!(quote_history(&store, &bad).unwrap_err().code, "QUOTE_HISTORY_INVALID");
+  }
+
+  #[test]
+  fn depot_series_carries_prices_forward_and_respects_purchase_dates() {
+    let store = QuoteHistoryStore::in_memory();
+    store.record(&quote("AAA.DE", 10.0, JAN_1));
+    store.record(&quote("AAA.DE", 12.0, JAN_1 + 2 * DAY));
+    store.record(&quote("BBB.DE", 50.0, JAN_1 + DAY));
+    let request = DepotHistoryRequest {
+      tranches: vec![
+        HistoryTranche { ticker: Some("AAA.DE".into()), shares: 10.0, purchase_price: 8.0, ..HistoryTranche::default() },
+        HistoryTranche { ticker: Some("bbb.de".into()), shares: 2.0, purchase_price: 40.0, purchase_date: Some("2024-01-02".into()) },
+        HistoryTranche { shares: 1.0, purchase_price: 1.0, ..HistoryTranche::default() },
+      ],
+      ..DepotHistoryRequest::default()
+    };
+    let history = depot_value_history(&store, &request).unwrap();
+    let points: Vec<(&str, f64, usize)> =
+      history.points.iter().map(|point| (point.date.as_str(), point.market_value, point.unpriced_tranches)).collect();
+    assert_eq!(points, vec![("2024-01-01", 100.0, 1), ("2024-01-02", 200.0, 1), ("2024-01-03", 220.0, 1)]);
+    assert_eq!(history.points[2].cost_basis, 160.0);
+    assert_eq!(history.coverage[1].first_date.as_deref(), Some("2024-01-02"));
+    assert_eq!(civil_from_days(parse_day("2000-02-29").unwrap()), "2000-02-29");
+  }
+}
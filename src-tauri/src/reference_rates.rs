@@ -0,0 +1,567 @@
+//! Official reference rates for cash buckets and the Vorabpauschale: €STR and
+//! the ECB deposit facility rate from the ECB Data Portal, and the Basiszins.
+//! The Basiszins is taken from the BMF table in vorabpauschale.rs; for years
+//! not yet in the table it is derived like the BMF does (§ 203 Abs. 2 BewG)
+//! from the Bundesbank 15-year yield on the first trading day of the year.
+//! All series are read as SDMX-JSON through `fetch_json` and cached per
+//! requested day in the app data dir.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::network::{NetworkState, SharedNetworkState};
+use crate::quote_history::{civil_from_days, parse_day};
+use crate::script_runtime::job_failure as failure;
+use crate::vorabpauschale::published_basiszins;
+use crate::{quote_failure, QuoteFailure};
+
+pub(crate) const RATE_CACHE_FILENAME: &str = "ruhestand_suite_rates.json";
+const SECONDS_PER_DAY: u64 = 86_400;
+/// Windows reaching into the current day are fetched again after this age.
+const RECENT_CACHE_MAX_AGE_SECONDS: u64 = 12 * 60 * 60;
+/// Daily series skip weekends and TARGET holidays; two weeks always contain
+/// an observation.
+const LOOKBACK_DAYS: i64 = 14;
+/// The first trading day of a year lies within its first two weeks.
+const BASISZINS_WINDOW_DAYS: i64 = 14;
+const FIRST_BASISZINS_YEAR: i32 = 2018;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RateSeries {
+  Estr,
+  DepositRate,
+  /// Zero-coupon yield of German federal securities, 15 years residual maturity.
+  Bund15y,
+}
+
+impl RateSeries {
+  fn id(self) -> &'static str {
+    match self {
+      RateSeries::Estr => "estr",
+      RateSeries::DepositRate => "ecbDepositRate",
+      RateSeries::Bund15y => "bund15y",
+    }
+  }
+
+  fn source(self) -> &'static str {
+    match self {
+      RateSeries::Estr | RateSeries::DepositRate => "ecb",
+      RateSeries::Bund15y => "bundesbank",
+    }
+  }
+
+  fn url(self, start: &str, end: &str) -> String {
+    match self {
+      RateSeries::Estr => format!(
+        "https://data-api.ecb.europa.eu/service/data/EST/B.EU000A2X2A25.WT?format=jsondata&startPeriod={}&endPeriod={}",
+        start, end
+      ),
+      RateSeries::DepositRate => format!(
+        "https://data-api.ecb.europa.eu/service/data/FM/B.U2.EUR.4F.KR.DFR.LEV?format=jsondata&startPeriod={}&endPeriod={}",
+        start, end
+      ),
+      RateSeries::Bund15y => format!(
+        "https://api.statistiken.bundesbank.de/rest/data/BBSIS/D.I.ZST.ZI.EUR.S1311.B.A604.R15XX.R.A.A._Z._Z.A?format=sdmx_json&startPeriod={}&endPeriod={}",
+        start, end
+      ),
+    }
+  }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedObservation {
+  date: String,
+  percent: f64,
+  fetched_at: u64,
+}
+
+/// `<series>@<window end>` -> observation, optionally mirrored to a JSON file.
+pub(crate) struct RateCache {
+  entries: Mutex<BTreeMap<String, CachedObservation>>,
+  path: Option<PathBuf>,
+}
+
+impl RateCache {
+  pub(crate) fn in_memory() -> Self {
+    RateCache { entries: Mutex::new(BTreeMap::new()), path: None }
+  }
+
+  pub(crate) fn load(path: PathBuf) -> Self {
+    let entries = fs::read_to_string(&path)
+      .ok()
+      .and_then(|raw| serde_json::from_str::<BTreeMap<String, CachedObservation>>(&raw).ok())
+      .unwrap_or_default();
+    RateCache { entries: Mutex::new(entries), path: Some(path) }
+  }
+
+  fn get(&self, key: &str) -> Option<CachedObservation> {
+    self.entries.lock().unwrap_or_else(|e| e.into_inner()).get(key).cloned()
+  }
+
+  /// Newest cached observation of `series` on or before `day`.
+  fn latest_before(&self, series: RateSeries, day: &str) -> Option<CachedObservation> {
+    let prefix = format!("{}@", series.id());
+    self
+      .entries
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .iter()
+      .filter(|(key, entry)| key.starts_with(&prefix) && entry.date.as_str() <= day)
+      .map(|(_, entry)| entry)
+      .max_by(|a, b| a.date.cmp(&b.date))
+      .cloned()
+  }
+
+  fn insert(&self, key: String, entry: CachedObservation) {
+    let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+    entries.insert(key, entry);
+    let Some(path) = &self.path else { return };
+    let Ok(content) = serde_json::to_string(&*entries) else { return };
+    let _ = crate::write_atomic(path, &content);
+  }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReferenceRatesRequest {
+  /// `YYYY-MM-DD`; default today. Each rate is the last one published on or
+  /// before this day.
+  pub date: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BasiszinsRequest {
+  pub year: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RateObservation {
+  pub series: &'static str,
+  pub date: String,
+  /// Rate in percent per year, as published.
+  pub percent: f64,
+  pub source: &'static str,
+  pub fetched_at: u64,
+  pub cached: bool,
+  /// Served from the cache because the provider could not be reached.
+  pub stale: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReferenceRates {
+  pub date: String,
+  pub estr: RateObservation,
+  pub deposit_rate: RateObservation,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Basiszins {
+  pub year: i32,
+  /// As a fraction, ready for `calculate_vorabpauschale`.
+  pub basiszins: f64,
+  pub percent: f64,
+  /// `official` (BMF table) or `derived` (own derivation, until the BMF
+  /// letter is in the table).
+  pub status: &'static str,
+  pub observation: Option<RateObservation>,
+}
+
+fn invalid_query(message: impl Into<String>) -> QuoteFailure {
+  quote_failure("INVALID_RATE_QUERY", message, 400)
+}
+
+fn not_available(message: impl Into<String>) -> QuoteFailure {
+  quote_failure("RATE_NOT_AVAILABLE", message, 404)
+}
+
+fn sdmx_value(value: &Value) -> Option<f64> {
+  match value {
+    Value::Number(number) => number.as_f64(),
+    Value::String(text) => text.trim().parse::<f64>().ok(),
+    _ => None,
+  }
+  .filter(|value| value.is_finite())
+}
+
+/// `(day, percent)` pairs of the first series in an SDMX-JSON data message,
+/// sorted by day. Accepts the ECB and Bundesbank flavours (top level or
+/// wrapped in `data`).
+pub(crate) fn parse_sdmx_observations(data: &Value) -> Result<Vec<(String, f64)>, QuoteFailure> {
+  let message = data.get("data").filter(|inner| inner.get("dataSets").is_some()).unwrap_or(data);
+  let periods = message
+    .pointer("/structure/dimensions/observation/0/values")
+    .and_then(Value::as_array)
+    .ok_or_else(|| quote_failure("INVALID_RESPONSE", "Zinsdaten ohne SDMX-Zeitachse.", 422))?;
+  let series = message
+    .pointer("/dataSets/0/series")
+    .and_then(Value::as_object)
+    .ok_or_else(|| quote_failure("INVALID_RESPONSE", "Zinsdaten ohne SDMX-Datensatz.", 422))?;
+  let mut observations = Vec::new();
+  if let Some(values) = series.values().next().and_then(|first| first.get("observations")).and_then(Value::as_object) {
+    for (index, observation) in values {
+      let day = index
+        .parse::<usize>()
+        .ok()
+        .and_then(|index| periods.get(index))
+        .and_then(|period| period.get("id"))
+        .and_then(Value::as_str)
+        .filter(|day| parse_day(day).is_some());
+      let value = observation.get(0).and_then(sdmx_value);
+      match (day, value) {
+        (Some(day), Some(value)) => observations.push((day.to_string(), value)),
+        // Missing values (holidays, embargoed days) are published as null.
+        (Some(_), None) if observation.get(0).is_some_and(Value::is_null) => {}
+        _ => return Err(quote_failure("INVALID_RESPONSE", "Zinsdaten mit ungueltiger Beobachtung.", 422)),
+      }
+    }
+  }
+  observations.sort_by(|a, b| a.0.cmp(&b.0));
+  Ok(observations)
+}
+
+#[derive(Clone, Copy)]
+enum Pick {
+  First,
+  Last,
+}
+
+/// Observation of `series` in `[start, end]`, from the cache when the window
+/// lay in the past at fetch time or the entry is recent.
+fn observation_with<F>(
+  cache: &RateCache,
+  series: RateSeries,
+  (start, end): (i64, i64),
+  pick: Pick,
+  now_seconds: u64,
+  fetch: &mut F,
+) -> Result<RateObservation, QuoteFailure>
+where
+  F: FnMut(&str) -> Result<Value, QuoteFailure>,
+{
+  let end_day = civil_from_days(end);
+  let key = format!("{}@{}", series.id(), end_day);
+  let observation = |entry: CachedObservation, cached: bool, stale: bool| RateObservation {
+    series: series.id(),
+    date: entry.date,
+    percent: entry.percent,
+    source: series.source(),
+    fetched_at: entry.fetched_at,
+    cached,
+    stale,
+  };
+
+  if let Some(entry) = cache.get(&key) {
+    let settled = (end as u64 + 1) * SECONDS_PER_DAY <= entry.fetched_at
+      || now_seconds.saturating_sub(entry.fetched_at) <= RECENT_CACHE_MAX_AGE_SECONDS;
+    if settled {
+      return Ok(observation(entry, true, false));
+    }
+  }
+
+  let fetched = fetch(&series.url(&civil_from_days(start), &end_day))
+    .map_err(|error| match error.code {
+      "SYMBOL_NOT_FOUND" => not_available(format!("Keine Daten fuer {} bis {}.", series.id(), end_day)),
+      _ => error,
+    })
+    .and_then(|data| parse_sdmx_observations(&data))
+    .and_then(|observations| {
+      let picked = match pick {
+        Pick::First => observations.into_iter().next(),
+        Pick::Last => observations.into_iter().next_back(),
+      };
+      picked.ok_or_else(|| not_available(format!("Keine Daten fuer {} bis {}.", series.id(), end_day)))
+    });
+  match fetched {
+    Ok((date, percent)) => {
+      let entry = CachedObservation { date, percent, fetched_at: now_seconds };
+      cache.insert(key, entry.clone());
+      Ok(observation(entry, false, false))
+    }
+    Err(error) => {
+      let fallback = match pick {
+        Pick::Last => cache.latest_before(series, &end_day),
+        Pick::First => cache.get(&key),
+      };
+      match fallback {
+        Some(entry) if crate::is_recoverable(&error) => Ok(observation(entry, true, true)),
+        _ => Err(error),
+      }
+    }
+  }
+}
+
+pub(crate) fn reference_rates_with<F>(
+  cache: &RateCache,
+  date: Option<&str>,
+  now_seconds: u64,
+  mut fetch: F,
+) -> Result<ReferenceRates, QuoteFailure>
+where
+  F: FnMut(&str) -> Result<Value, QuoteFailure>,
+{
+  let today = (now_seconds / SECONDS_PER_DAY) as i64;
+  let day = match date.map(str::trim).filter(|text| !text.is_empty()) {
+    Some(text) => parse_day(text).ok_or_else(|| invalid_query("date muss ein Datum YYYY-MM-DD sein."))?,
+    None => today,
+  };
+  if day > today {
+    return Err(invalid_query("date liegt in der Zukunft."));
+  }
+  Ok(ReferenceRates {
+    date: civil_from_days(day),
+    estr: rate_on_with(cache, RateSeries::Estr, day, now_seconds, &mut fetch)?,
+    deposit_rate: rate_on_with(cache, RateSeries::DepositRate, day, now_seconds, &mut fetch)?,
+  })
+}
+
+/// Last observation of `series` published on or before `day` (days since
+/// the epoch).
+pub(crate) fn rate_on_with<F>(
+  cache: &RateCache,
+  series: RateSeries,
+  day: i64,
+  now_seconds: u64,
+  fetch: &mut F,
+) -> Result<RateObservation, QuoteFailure>
+where
+  F: FnMut(&str) -> Result<Value, QuoteFailure>,
+{
+  observation_with(cache, series, (day - LOOKBACK_DAYS, day), Pick::Last, now_seconds, fetch)
+}
+
+/// Calendar-day average of `series` over `year` in percent, each day at the
+/// rate last published on or before it. Not cached: the update that asks for
+/// it runs once a year.
+pub(crate) fn annual_average_with<F>(series: RateSeries, year: i32, fetch: &mut F) -> Result<f64, QuoteFailure>
+where
+  F: FnMut(&str) -> Result<Value, QuoteFailure>,
+{
+  let (Some(first), Some(last)) = (parse_day(&format!("{:04}-01-01", year)), parse_day(&format!("{:04}-12-31", year))) else {
+    return Err(invalid_query(format!("Ungueltiges Jahr {}.", year)));
+  };
+  let missing = || not_available(format!("Keine vollstaendigen Daten fuer {} im Jahr {}.", series.id(), year));
+  let observations = fetch(&series.url(&civil_from_days(first - LOOKBACK_DAYS), &civil_from_days(last)))
+    .map_err(|error| match error.code {
+      "SYMBOL_NOT_FOUND" => missing(),
+      _ => error,
+    })
+    .and_then(|data| parse_sdmx_observations(&data))?;
+  if observations.last().map_or(true, |(date, _)| *date < civil_from_days(last - LOOKBACK_DAYS)) {
+    return Err(missing());
+  }
+  let mut next = observations.iter().peekable();
+  let mut current = None;
+  let mut sum = 0.0;
+  for day in first..=last {
+    let date = civil_from_days(day);
+    while let Some((_, percent)) = next.next_if(|(observed, _)| *observed <= date) {
+      current = Some(*percent);
+    }
+    sum += current.ok_or_else(missing)?;
+  }
+  Ok(((sum / (last - first + 1) as f64) * 100.0).round() / 100.0)
+}
+
+pub(crate) fn basiszins_with<F>(cache: &RateCache, year: i32, now_seconds: u64, mut fetch: F) -> Result<Basiszins, QuoteFailure>
+where
+  F: FnMut(&str) -> Result<Value, QuoteFailure>,
+{
+  if let Some(rate) = published_basiszins(year) {
+    return Ok(Basiszins {
+      year,
+      basiszins: rate,
+      percent: (rate * 10_000.0).round() / 100.0,
+      status: "official",
+      observation: None,
+    });
+  }
+  let first_day = parse_day(&format!("{:04}-01-01", year)).filter(|_| year >= FIRST_BASISZINS_YEAR);
+  let Some(first_day) = first_day else {
+    return Err(invalid_query(format!("Basiszins erst ab {} verfuegbar.", FIRST_BASISZINS_YEAR)));
+  };
+  let today = (now_seconds / SECONDS_PER_DAY) as i64;
+  if first_day > today {
+    return Err(not_available(format!("Basiszins {} ist noch nicht bestimmbar.", year)));
+  }
+  let window = (first_day, (first_day + BASISZINS_WINDOW_DAYS).min(today));
+  let observation = observation_with(cache, RateSeries::Bund15y, window, Pick::First, now_seconds, &mut fetch)?;
+  let percent = (observation.percent * 100.0).round() / 100.0;
+  Ok(Basiszins {
+    year,
+    basiszins: (percent * 100.0).round() / 10_000.0,
+    percent,
+    status: "derived",
+    observation: Some(observation),
+  })
+}
+
+pub(crate) fn fetch_reference_rates(network: &NetworkState, date: Option<&str>) -> Result<ReferenceRates, QuoteFailure> {
+  // Replayed fixtures are not observations and stay out of the cache.
+  let replay_cache = RateCache::in_memory();
+  let cache = if network.is_replaying() { &replay_cache } else { network.rate_cache() };
+  reference_rates_with(cache, date, network.now_seconds(), |url| crate::fetch_json(network, url))
+}
+
+pub(crate) fn fetch_basiszins(network: &NetworkState, year: i32) -> Result<Basiszins, QuoteFailure> {
+  let replay_cache = RateCache::in_memory();
+  let cache = if network.is_replaying() { &replay_cache } else { network.rate_cache() };
+  basiszins_with(cache, year, network.now_seconds(), |url| crate::fetch_json(network, url))
+}
+
+#[tauri::command]
+pub async fn get_reference_rates(
+  state: tauri::State<'_, SharedNetworkState>,
+  request: ReferenceRatesRequest,
+) -> Result<ReferenceRates, String> {
+  let network = state.inner().clone();
+  tauri::async_runtime::spawn_blocking(move || {
+    fetch_reference_rates(&network, request.date.as_deref()).map_err(|error| failure(error.code, error.message))
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_basiszins(
+  state: tauri::State<'_, SharedNetworkState>,
+  request: BasiszinsRequest,
+) -> Result<Basiszins, String> {
+  let network = state.inner().clone();
+  tauri::async_runtime::spawn_blocking(move || {
+    fetch_basiszins(&network, request.year).map_err(|error| failure(error.code, error.message))
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::network::NetworkSettings;
+  use crate::upstream_fixtures::{self, UpstreamFixture, UpstreamFixtureMode, UpstreamFixtureSettings};
+  use serde_json::json;
+
+  /// 2026-01-20 12:00 UTC.
+  const NOW: u64 = 1_768_910_400;
+
+  fn sdmx(days: &[&str], values: &[Value]) -> Value {
+    let periods: Vec<Value> = days.iter().map(|day| json!({ "id": day, "name": day })).collect();
+    let observations: serde_json::Map<String, Value> =
+      values.iter().enumerate().map(|(index, value)| (index.to_string(), json!([value, 0, 0]))).collect();
+    json!({
+      "header": { "id": "fixture" },
+      "dataSets": [{ "action": "Replace", "series": { "0:0:0:0:0:0:0": { "observations": observations } } }],
+      "structure": { "dimensions": { "observation": [{ "id": "TIME_PERIOD", "values": periods }] } }
+    })
+  }
+
+  #[test]
+  fn parses_ecb_and_bundesbank_sdmx_json() {
+    let ecb = sdmx(&["2026-01-19", "2026-01-16", "2026-01-15"], &[json!(1.929), json!(1.931), Value::Null]);
+    assert_eq!(
+      parse_sdmx_observations(&ecb).unwrap(),
+      vec![("2026-01-16".to_string(), 1.931), ("2026-01-19".to_string(), 1.929)]
+    );
+    let bundesbank = json!({ "data": sdmx(&["2026-01-02"], &[json!("2.74")]) });
+    assert_eq!(parse_sdmx_observations(&bundesbank).unwrap(), vec![("2026-01-02".to_string(), 2.74)]);
+
+    assert_eq!(parse_sdmx_observations(&json!({ "dataSets": [] })).unwrap_err().code, "INVALID_RESPONSE");
+    let broken = sdmx(&["2026-01-02"], &[json!("n/a")]);
+    assert_eq!(parse_sdmx_observations(&broken).unwrap_err().code, "INVALID_RESPONSE");
+  }
+
+  #[test]
+  fn annual_average_weights_each_calendar_day() {
+    let mut url = String::new();
+    let mut fetch = |requested: &str| {
+      url = requested.to_string();
+      Ok(sdmx(&["2024-12-20", "2025-07-01", "2025-12-31"], &[json!(3.0), json!(2.0), json!(2.0)]))
+    };
+    // 181 days at 3 % and 184 days at 2 %.
+    assert_eq!(annual_average_with(RateSeries::DepositRate, 2025, &mut fetch).unwrap(), 2.5);
+    assert!(url.ends_with("startPeriod=2024-12-18&endPeriod=2025-12-31"), "{}", url);
+
+    let mut early_end = |_: &str| Ok(sdmx(&["2025-01-02", "2025-11-28"], &[json!(3.0), json!(2.0)]));
+    assert_eq!(annual_average_with(RateSeries::DepositRate, 2025, &mut early_end).unwrap_err().code, "RATE_NOT_AVAILABLE");
+    let mut late_start = |_: &str| Ok(sdmx(&["2025-01-02", "2025-12-31"], &[json!(3.0), json!(2.0)]));
+    assert_eq!(annual_average_with(RateSeries::DepositRate, 2025, &mut late_start).unwrap_err().code, "RATE_NOT_AVAILABLE");
+  }
+
+  #[test]
+  fn caches_observations_and_falls_back_to_older_rates_offline() {
+    let cache = RateCache::in_memory();
+    let mut urls = Vec::new();
+    let rates = reference_rates_with(&cache, Some("2025-12-31"), NOW, |url| {
+      urls.push(url.to_string());
+      Ok(if url.contains("/EST/") { sdmx(&["2025-12-30", "2025-12-31"], &[json!(1.93), json!(1.92)]) } else { sdmx(&["2025-12-31"], &[json!(2.0)]) })
+    })
+    .unwrap();
+    assert_eq!(urls.len(), 2);
+    assert!(urls[0].ends_with("startPeriod=2025-12-17&endPeriod=2025-12-31"));
+    assert_eq!((rates.estr.date.as_str(), rates.estr.percent, rates.estr.cached), ("2025-12-31", 1.92, false));
+    assert_eq!((rates.deposit_rate.series, rates.deposit_rate.source), ("ecbDepositRate", "ecb"));
+
+    // A past day is never fetched twice.
+    let again = reference_rates_with(&cache, Some("2025-12-31"), NOW + 86_400, |_| panic!("cached")).unwrap();
+    assert!(again.estr.cached && !again.estr.stale);
+
+    // Without network today's rates fall back to the newest cached ones.
+    let offline = reference_rates_with(&cache, None, NOW, |_| Err(quote_failure("OFFLINE_MODE", "Offline", 503))).unwrap();
+    assert_eq!((offline.date.as_str(), offline.estr.date.as_str(), offline.estr.stale), ("2026-01-20", "2025-12-31", true));
+    let error = reference_rates_with(&RateCache::in_memory(), None, NOW, |_| Err(quote_failure("OFFLINE_MODE", "Offline", 503)));
+    assert_eq!(error.unwrap_err().code, "OFFLINE_MODE");
+
+    for date in ["2026-13-01", "2026-01-21"] {
+      let error = reference_rates_with(&cache, Some(date), NOW, |_| panic!("invalid query")).unwrap_err();
+      assert_eq!(error.code, "INVALID_RATE_QUERY");
+    }
+    let basiszins = basiszins_with(&cache, 2024, NOW, |_| panic!("official rates need no download")).unwrap();
+    assert_eq!((basiszins.basiszins, basiszins.percent, basiszins.status), (0.0229, 2.29, "official"));
+    assert_eq!(basiszins_with(&cache, 2027, NOW, |_| panic!("future")).unwrap_err().code, "RATE_NOT_AVAILABLE");
+    assert_eq!(basiszins_with(&cache, 2017, NOW, |_| panic!("too old")).unwrap_err().code, "INVALID_RATE_QUERY");
+  }
+
+  #[test]
+  fn replays_recorded_ecb_and_bundesbank_fixtures() {
+    let dir = std::env::temp_dir().join(format!("ruhestand-rates-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let fixtures = [
+      (RateSeries::Estr.url("2026-01-06", "2026-01-20"), 200, sdmx(&["2026-01-16", "2026-01-19"], &[json!(1.931), json!(1.929)])),
+      (RateSeries::DepositRate.url("2026-01-06", "2026-01-20"), 200, sdmx(&["2026-01-19"], &[json!(2.0)])),
+      (RateSeries::Bund15y.url("2026-01-01", "2026-01-15"), 200, sdmx(&["2026-01-05", "2026-01-02"], &[json!(2.79), json!(2.7351)])),
+      (RateSeries::Estr.url("2025-12-27", "2026-01-10"), 404, json!({})),
+    ];
+    for (url, status, body) in fixtures {
+      upstream_fixtures::write_fixture(&dir, &UpstreamFixture { url, status, body: body.to_string(), recorded_at: NOW }).unwrap();
+    }
+    let network = NetworkState::in_memory(NetworkSettings {
+      offline_mode: true,
+      fixtures: UpstreamFixtureSettings { mode: UpstreamFixtureMode::Replay, directory: Some(dir.to_string_lossy().to_string()) },
+      ..Default::default()
+    });
+
+    let rates = fetch_reference_rates(&network, None).unwrap();
+    assert_eq!((rates.estr.date.as_str(), rates.estr.percent), ("2026-01-19", 1.929));
+    assert_eq!((rates.deposit_rate.percent, rates.deposit_rate.fetched_at), (2.0, NOW));
+
+    let basiszins = fetch_basiszins(&network, 2026).unwrap();
+    assert_eq!((basiszins.percent, basiszins.basiszins, basiszins.status), (2.74, 0.0274, "derived"));
+    assert_eq!(basiszins.observation.map(|observation| observation.date), Some("2026-01-02".to_string()));
+
+    assert_eq!(fetch_reference_rates(&network, Some("2026-01-10")).unwrap_err().code, "RATE_NOT_AVAILABLE");
+    assert_eq!(fetch_reference_rates(&network, Some("2026-01-19")).unwrap_err().code, "FIXTURE_MISSING");
+    assert!(network.rate_cache().get("estr@2026-01-20").is_none());
+    let _ = fs::remove_dir_all(&dir);
+  }
+}
@@ -0,0 +1,879 @@
+//! Tax-minimal sale planning over tranches. Brokers sell each security FIFO
+//! per depot, so a depot/instrument group can only be sold as a prefix of its
+//! lots in purchase order, and a plan is one cut point per group.
+//!
+//! The planner searches these cut points and settles every candidate with the
+//! cent-exact settlement in tax.rs (Pauschbetrag, both loss pots, church tax).
+//! One group closes the gap with the smallest quantity that reaches the net
+//! amount; the other groups are enumerated share by share while that stays
+//! within `SEARCH_LEAF_LIMIT` plans, which makes the result exact, and at lot
+//! boundaries otherwise. Plans are compared by tax, then gross proceeds, then
+//! the loss carry left for later years. A pooled greedy plan (lots pooled
+//! until their taxable-gain ratio never decreases, cheapest pools first)
+//! seeds the search and is kept when even the boundary search is too large.
+
+use serde::{Deserialize, Serialize};
+use std::ops::Add;
+
+use crate::script_runtime::{job_failure as failure, JobFailure};
+use crate::tax::{
+  church_rate_bp, effective_rate, settle_tax_year_exact, RawAggregate, Settlement, SettlementInput, TaxStateInput,
+};
+
+/// Quantity step for funds that allow fractional shares.
+const FRACTIONAL_SHARE_STEP: f64 = 0.0001;
+/// Plans the search may settle for one closing group; beyond it the groups
+/// are cut at lot boundaries only, or the greedy plan stands.
+const SEARCH_LEAF_LIMIT: f64 = 20_000.0;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PlannerTranche {
+  pub tranche_id: Option<String>,
+  pub name: Option<String>,
+  pub isin: Option<String>,
+  pub ticker: Option<String>,
+  /// FIFO scope; falls back to `sourceProfileId`, then to a single depot.
+  pub depot_id: Option<String>,
+  pub source_profile_id: Option<String>,
+  pub purchase_date: Option<String>,
+  pub shares: f64,
+  pub purchase_price: f64,
+  pub current_price: f64,
+  pub tqf: Option<f64>,
+  /// Single share (Aktie): gains and losses go through the Aktienverlusttopf.
+  #[serde(default)]
+  pub stock: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SalePlanRequest {
+  /// Cash needed after tax.
+  pub net_amount: f64,
+  pub tranches: Vec<PlannerTranche>,
+  /// Pauschbetrag still unused this year.
+  pub sparer_pauschbetrag: Option<f64>,
+  pub kirchensteuer_satz: Option<f64>,
+  pub tax_state_prev: Option<TaxStateInput>,
+  #[serde(default)]
+  pub fractional_shares: bool,
+}
+
+/// One sale line, named like the `breakdown` entries of `calculateSaleAndTax`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SaleLine {
+  pub tranche_id: Option<String>,
+  pub name: Option<String>,
+  pub isin: Option<String>,
+  pub depot_id: Option<String>,
+  pub purchase_date: Option<String>,
+  pub shares: f64,
+  pub price: f64,
+  pub brutto: f64,
+  pub cost_basis: f64,
+  pub tqf: f64,
+  pub realized_gain_signed: f64,
+  pub taxable_after_tqf_signed: f64,
+  /// Marginal tax of this line in sale order; negative when a loss saves tax.
+  pub steuer: f64,
+  pub spb_used: f64,
+  pub netto: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SalePlan {
+  pub target_net: f64,
+  pub brutto_verkauf_gesamt: f64,
+  pub steuer_gesamt: f64,
+  pub achieved_refill: f64,
+  /// Net amount the tranches cannot cover even when sold completely.
+  pub shortfall: f64,
+  pub pauschbetrag_verbraucht: f64,
+  pub breakdown: Vec<SaleLine>,
+  pub tax_raw_aggregate: RawAggregate,
+  pub settlement: Settlement,
+}
+
+fn invalid(message: impl Into<String>) -> JobFailure {
+  failure("SALE_PLAN_INPUT_INVALID", message)
+}
+
+fn cents(value: f64) -> i64 {
+  (value * 100.0).round() as i64
+}
+
+fn euros(cents: i64) -> f64 {
+  cents as f64 / 100.0
+}
+
+struct Lot {
+  index: usize,
+  shares: f64,
+  price: f64,
+  basis: f64,
+  tqf: f64,
+  stock: bool,
+}
+
+impl Lot {
+  /// Taxable gain after Teilfreistellung per share.
+  fn taxable(&self) -> f64 {
+    (self.price - self.basis) * (1.0 - self.tqf)
+  }
+}
+
+/// Consecutive lots of one group, sold as a whole in the pooled phase.
+struct Pool {
+  group: usize,
+  lots: std::ops::Range<usize>,
+  value: f64,
+  taxable: f64,
+}
+
+impl Pool {
+  fn ratio(&self) -> f64 {
+    self.taxable / self.value
+  }
+}
+
+/// Running totals of a plan and the linear tax estimate of the greedy seed;
+/// the search compares plans with the exact settlement.
+#[derive(Clone, Copy)]
+struct Totals {
+  gross: f64,
+  taxable: f64,
+  /// Part of `taxable` from single shares.
+  stock: f64,
+}
+
+impl Totals {
+  fn with(self, lot: &Lot, shares: f64) -> Totals {
+    let taxable = shares * lot.taxable();
+    Totals {
+      gross: self.gross + shares * lot.price,
+      taxable: self.taxable + taxable,
+      stock: self.stock + if lot.stock { taxable } else { 0.0 },
+    }
+  }
+}
+
+struct Estimator {
+  rate: f64,
+  /// Pauschbetrag plus the allgemeiner Verlusttopf.
+  allowance: f64,
+  /// Aktienverlusttopf, only against share gains.
+  stock_carry: f64,
+  target: f64,
+}
+
+impl Estimator {
+  /// Share losses stay in their own pot; other losses also offset share gains.
+  fn tax(&self, totals: Totals) -> f64 {
+    let stock = (totals.stock - self.stock_carry).max(0.0);
+    self.rate * (totals.taxable - totals.stock + stock - self.allowance).max(0.0)
+  }
+
+  fn net(&self, totals: Totals) -> f64 {
+    totals.gross - self.tax(totals)
+  }
+
+  fn reached(&self, totals: Totals) -> bool {
+    self.net(totals) >= self.target
+  }
+}
+
+fn group_key(tranche: &PlannerTranche, index: usize) -> String {
+  let depot = tranche.depot_id.as_deref().or(tranche.source_profile_id.as_deref()).unwrap_or("");
+  let instrument = tranche
+    .isin
+    .as_deref()
+    .map(|isin| format!("isin:{}", isin.trim().to_uppercase()))
+    .or_else(|| tranche.ticker.as_deref().map(|ticker| format!("ticker:{}", ticker.trim().to_uppercase())))
+    .or_else(|| tranche.name.as_deref().map(|name| format!("name:{}", name.trim())))
+    .unwrap_or_else(|| format!("tranche:{}", index));
+  format!("{}|{}", depot, instrument)
+}
+
+/// Lots per FIFO group, oldest first; lots without a date follow in input order.
+fn fifo_groups(request: &SalePlanRequest) -> Result<Vec<Vec<Lot>>, JobFailure> {
+  let mut keys: Vec<String> = Vec::new();
+  let mut groups: Vec<Vec<Lot>> = Vec::new();
+  for (index, tranche) in request.tranches.iter().enumerate() {
+    let tqf = tranche.tqf.unwrap_or(0.0);
+    let numbers = [tranche.shares, tranche.purchase_price, tranche.current_price];
+    if numbers.iter().any(|value| !value.is_finite() || *value < 0.0) || !(0.0..=1.0).contains(&tqf) {
+      return Err(invalid(format!(
+        "Tranche {}: shares und Preise muessen endlich und nicht negativ sein, tqf zwischen 0 und 1.",
+        tranche.tranche_id.as_deref().unwrap_or("?")
+      )));
+    }
+    if tranche.shares <= 0.0 || tranche.current_price <= 0.0 {
+      continue;
+    }
+    let key = group_key(tranche, index);
+    let slot = match keys.iter().position(|existing| *existing == key) {
+      Some(slot) => slot,
+      None => {
+        keys.push(key);
+        groups.push(Vec::new());
+        groups.len() - 1
+      }
+    };
+    groups[slot].push(Lot {
+      index,
+      shares: tranche.shares,
+      price: tranche.current_price,
+      basis: tranche.purchase_price,
+      tqf,
+      stock: tranche.stock,
+    });
+  }
+  for group in &mut groups {
+    group.sort_by(|a, b| {
+      let date = |lot: &Lot| request.tranches[lot.index].purchase_date.clone().filter(|date| !date.trim().is_empty());
+      match (date(a), date(b)) {
+        (Some(left), Some(right)) => left.cmp(&right),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+      }
+    });
+  }
+  Ok(groups)
+}
+
+/// Pools adjacent lots until the ratios within each group are non-decreasing.
+fn pools(groups: &[Vec<Lot>]) -> Vec<Pool> {
+  let mut all = Vec::new();
+  for (group, lots) in groups.iter().enumerate() {
+    let mut stack: Vec<Pool> = Vec::new();
+    for (position, lot) in lots.iter().enumerate() {
+      stack.push(Pool {
+        group,
+        lots: position..position + 1,
+        value: lot.shares * lot.price,
+        taxable: lot.shares * lot.taxable(),
+      });
+      while stack.len() >= 2 && stack[stack.len() - 2].ratio() > stack[stack.len() - 1].ratio() {
+        let last = stack.pop().unwrap_or_else(|| unreachable!("two pools on the stack"));
+        let previous = stack.last_mut().unwrap_or_else(|| unreachable!("two pools on the stack"));
+        previous.lots.end = last.lots.end;
+        previous.value += last.value;
+        previous.taxable += last.taxable;
+      }
+    }
+    all.extend(stack);
+  }
+  all.sort_by(|a, b| {
+    a.ratio()
+      .total_cmp(&b.ratio())
+      .then(a.group.cmp(&b.group))
+      .then(a.lots.start.cmp(&b.lots.start))
+  });
+  all
+}
+
+/// Smallest quantity of `lot` (rounded up to `step`) that reaches the target
+/// on top of `totals`.
+fn quantity_needed(estimator: &Estimator, totals: Totals, lot: &Lot, step: f64) -> f64 {
+  let (mut low, mut high) = (0.0, lot.shares);
+  for _ in 0..100 {
+    let middle = (low + high) / 2.0;
+    if estimator.reached(totals.with(lot, middle)) {
+      high = middle;
+    } else {
+      low = middle;
+    }
+  }
+  ((high / step - 1e-6).ceil() * step).min(lot.shares)
+}
+
+/// FIFO prefix of one group, starting at `cursor`, that closes the gap.
+fn finish_with(
+  estimator: &Estimator,
+  totals: Totals,
+  lots: &[Lot],
+  cursor: usize,
+  step: f64,
+) -> Option<(Vec<(usize, f64)>, Totals)> {
+  let mut totals = totals;
+  let mut taken = Vec::new();
+  for (position, lot) in lots.iter().enumerate().skip(cursor) {
+    let full = totals.with(lot, lot.shares);
+    if !estimator.reached(full) {
+      taken.push((position, lot.shares));
+      totals = full;
+      continue;
+    }
+    let shares = quantity_needed(estimator, totals, lot, step);
+    taken.push((position, shares));
+    return Some((taken, totals.with(lot, shares)));
+  }
+  None
+}
+
+/// Greedy seed: sale quantities per (group, lot position) in sale order.
+fn pooled_quantities(groups: &[Vec<Lot>], estimator: &Estimator, step: f64) -> Vec<(usize, usize, f64)> {
+  let mut sold = Vec::new();
+  let mut cursors = vec![0; groups.len()];
+  let mut totals = Totals { gross: 0.0, taxable: 0.0, stock: 0.0 };
+  for pool in pools(groups) {
+    let lots = &groups[pool.group][pool.lots.clone()];
+    let with_pool = lots.iter().fold(totals, |totals, lot| totals.with(lot, lot.shares));
+    if estimator.reached(with_pool) {
+      let best = (0..groups.len())
+        .filter_map(|group| {
+          finish_with(estimator, totals, &groups[group], cursors[group], step).map(|(taken, after)| (group, taken, after))
+        })
+        .min_by(|a, b| {
+          estimator
+            .tax(a.2)
+            .total_cmp(&estimator.tax(b.2))
+            .then(a.2.gross.total_cmp(&b.2.gross))
+        });
+      if let Some((group, taken, _)) = best {
+        sold.extend(taken.into_iter().map(|(position, shares)| (group, position, shares)));
+      }
+      return sold;
+    }
+    for position in pool.lots.clone() {
+      sold.push((pool.group, position, groups[pool.group][position].shares));
+    }
+    cursors[pool.group] = pool.lots.end;
+    totals = with_pool;
+  }
+  sold
+}
+
+/// Cent amounts of sale lines, each line rounded like the breakdown.
+#[derive(Clone, Copy, Debug, Default)]
+struct Aggregate {
+  gross: i64,
+  realized: i64,
+  taxable: i64,
+  stock: i64,
+}
+
+impl Add for Aggregate {
+  type Output = Aggregate;
+
+  fn add(self, other: Aggregate) -> Aggregate {
+    Aggregate {
+      gross: self.gross + other.gross,
+      realized: self.realized + other.realized,
+      taxable: self.taxable + other.taxable,
+      stock: self.stock + other.stock,
+    }
+  }
+}
+
+fn line(lot: &Lot, shares: f64) -> Aggregate {
+  let gross = cents(shares * lot.price);
+  let realized = gross - cents(shares * lot.basis);
+  let taxable = cents(euros(realized) * (1.0 - lot.tqf));
+  Aggregate { gross, realized, taxable, stock: if lot.stock { taxable } else { 0 } }
+}
+
+fn settle(request: &SalePlanRequest, total: Aggregate) -> Result<Settlement, JobFailure> {
+  settle_tax_year_exact(&SettlementInput {
+    tax_state_prev: request.tax_state_prev.clone(),
+    raw_aggregate: Some(RawAggregate {
+      sum_realized_gain_signed: Some(euros(total.realized)),
+      sum_taxable_after_tqf_signed: Some(euros(total.taxable)),
+      sum_stock_taxable_signed: Some(euros(total.stock)),
+    }),
+    sparer_pauschbetrag: request.sparer_pauschbetrag,
+    kirchensteuer_satz: request.kirchensteuer_satz,
+    ..SettlementInput::default()
+  })
+}
+
+/// How much of one FIFO group a plan sells: `full` lots plus `partial`
+/// shares of the next one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cut {
+  full: usize,
+  partial: f64,
+}
+
+/// Tax, gross proceeds and negated remaining loss carry, in cents; smaller
+/// is better.
+type PlanKey = (i64, i64, i64);
+
+struct Search<'a> {
+  request: &'a SalePlanRequest,
+  groups: &'a [Vec<Lot>],
+  /// Aggregate of the first `n` lots of every group.
+  prefixes: Vec<Vec<Aggregate>>,
+  step: f64,
+  target: i64,
+  best: Option<(PlanKey, Vec<Cut>)>,
+}
+
+impl<'a> Search<'a> {
+  fn new(request: &'a SalePlanRequest, groups: &'a [Vec<Lot>], step: f64) -> Self {
+    let prefixes = groups
+      .iter()
+      .map(|lots| {
+        let mut prefix = vec![Aggregate::default()];
+        for lot in lots {
+          prefix.push(prefix[prefix.len() - 1] + line(lot, lot.shares));
+        }
+        prefix
+      })
+      .collect();
+    Search { request, groups, prefixes, step, target: cents(request.net_amount), best: None }
+  }
+
+  fn aggregate(&self, group: usize, cut: Cut) -> Aggregate {
+    let full = self.prefixes[group][cut.full];
+    if cut.partial > 0.0 {
+      full + line(&self.groups[group][cut.full], cut.partial)
+    } else {
+      full
+    }
+  }
+
+  /// Tax and net proceeds in cents.
+  fn outcome(&self, total: Aggregate) -> Result<(i64, i64), JobFailure> {
+    let tax = cents(settle(self.request, total)?.tax_due);
+    Ok((tax, total.gross - tax))
+  }
+
+  fn consider(&mut self, cuts: &[Cut]) -> Result<(), JobFailure> {
+    let total = cuts.iter().enumerate().fold(Aggregate::default(), |total, (group, cut)| total + self.aggregate(group, *cut));
+    let settlement = settle(self.request, total)?;
+    let tax = cents(settlement.tax_due);
+    if total.gross - tax < self.target {
+      return Ok(());
+    }
+    let carry = cents(settlement.tax_state_next.loss_carry) + cents(settlement.tax_state_next.stock_loss_carry);
+    let key = (tax, total.gross, -carry);
+    if self.best.as_ref().map_or(true, |(best, _)| key < *best) {
+      self.best = Some((key, cuts.to_vec()));
+    }
+    Ok(())
+  }
+
+  /// Every cut of `group`, share by share or at lot boundaries.
+  fn cuts(&self, group: usize, share_by_share: bool) -> Vec<Cut> {
+    let lots = &self.groups[group];
+    let mut cuts = Vec::new();
+    for (full, lot) in lots.iter().enumerate() {
+      cuts.push(Cut { full, partial: 0.0 });
+      let mut steps = 1.0;
+      while share_by_share && steps * self.step < lot.shares - 1e-9 {
+        cuts.push(Cut { full, partial: steps * self.step });
+        steps += 1.0;
+      }
+    }
+    cuts.push(Cut { full: lots.len(), partial: 0.0 });
+    cuts
+  }
+
+  /// Number of cuts `cuts` would return, without building them.
+  fn cut_count(&self, group: usize, share_by_share: bool) -> f64 {
+    let lots = &self.groups[group];
+    let partials: f64 = if share_by_share {
+      lots.iter().map(|lot| (((lot.shares - 1e-9) / self.step).ceil() - 1.0).max(0.0)).sum()
+    } else {
+      0.0
+    };
+    lots.len() as f64 + 1.0 + partials
+  }
+
+  /// Smallest partial quantity of lot `full` of `group` for which `accept`
+  /// holds; `accept` must hold for the whole lot.
+  fn smallest(&self, group: usize, full: usize, others: Aggregate, accept: impl Fn(i64, i64) -> bool) -> Result<f64, JobFailure> {
+    let lot = &self.groups[group][full];
+    let base = others + self.prefixes[group][full];
+    let shares = |steps: f64| (steps * self.step).min(lot.shares);
+    let (mut low, mut high) = (0.0, (lot.shares / self.step - 1e-9).ceil());
+    while high - low > 1.0 {
+      let middle = ((low + high) / 2.0).floor();
+      let (tax, net) = self.outcome(base + line(lot, shares(middle)))?;
+      if accept(tax, net) {
+        high = middle;
+      } else {
+        low = middle;
+      }
+    }
+    Ok(shares(high))
+  }
+
+  /// Plans in which `group` closes the gap the other cuts leave: every lot
+  /// boundary that reaches the target, the smallest partial that does, and
+  /// within loss lots the smallest partial at the lot's lowest tax.
+  fn close(&mut self, group: usize, cuts: &mut [Cut]) -> Result<(), JobFailure> {
+    let others = cuts
+      .iter()
+      .enumerate()
+      .filter(|(other, _)| *other != group)
+      .fold(Aggregate::default(), |total, (other, cut)| total + self.aggregate(other, *cut));
+    let target = self.target;
+    for full in 0..self.groups[group].len() {
+      let (_, start) = self.outcome(others + self.prefixes[group][full])?;
+      let (end_tax, end) = self.outcome(others + self.prefixes[group][full + 1])?;
+      if start >= target {
+        cuts[group] = Cut { full, partial: 0.0 };
+        self.consider(cuts)?;
+      } else if end >= target {
+        let partial = self.smallest(group, full, others, |_, net| net >= target)?;
+        cuts[group] = Cut { full, partial };
+        self.consider(cuts)?;
+      }
+      if end >= target && self.groups[group][full].taxable() < 0.0 {
+        let partial = self.smallest(group, full, others, |tax, net| net >= target && tax <= end_tax)?;
+        cuts[group] = Cut { full, partial };
+        self.consider(cuts)?;
+      }
+    }
+    cuts[group] = Cut { full: self.groups[group].len(), partial: 0.0 };
+    self.consider(cuts)
+  }
+
+  fn explore(&mut self, order: &[usize], candidates: &[Vec<Cut>], closer: usize, cuts: &mut [Cut]) -> Result<(), JobFailure> {
+    let Some((&group, rest)) = order.split_first() else {
+      return self.close(closer, cuts);
+    };
+    for cut in &candidates[group] {
+      cuts[group] = *cut;
+      self.explore(rest, candidates, closer, cuts)?;
+    }
+    Ok(())
+  }
+
+  /// Runs the search with `group` closing the gap and the other groups cut
+  /// as in `candidates`.
+  fn run(&mut self, candidates: &[Vec<Cut>], closer: usize) -> Result<(), JobFailure> {
+    let order: Vec<usize> = (0..self.groups.len()).filter(|group| *group != closer).collect();
+    let mut cuts = vec![Cut { full: 0, partial: 0.0 }; self.groups.len()];
+    self.explore(&order, candidates, closer, &mut cuts)
+  }
+}
+
+/// Cuts per group of a plan given as sale quantities of FIFO prefixes.
+fn cuts_of(groups: &[Vec<Lot>], quantities: &[(usize, usize, f64)]) -> Vec<Cut> {
+  let mut cuts = vec![Cut { full: 0, partial: 0.0 }; groups.len()];
+  for &(group, position, shares) in quantities {
+    if shares >= groups[group][position].shares {
+      cuts[group] = Cut { full: position + 1, partial: 0.0 };
+    } else {
+      cuts[group] = Cut { full: position, partial: shares };
+    }
+  }
+  cuts
+}
+
+/// Sale quantities per (group, lot position) in pool order: lowest taxable
+/// ratio first, FIFO within every group.
+fn sale_order(groups: &[Vec<Lot>], cuts: &[Cut]) -> Vec<(usize, usize, f64)> {
+  let mut sold = Vec::new();
+  for pool in pools(groups) {
+    let cut = cuts[pool.group];
+    for position in pool.lots {
+      if position < cut.full {
+        sold.push((pool.group, position, groups[pool.group][position].shares));
+      } else if position == cut.full && cut.partial > 0.0 {
+        sold.push((pool.group, position, cut.partial));
+      }
+    }
+  }
+  sold
+}
+
+/// Tax-minimal quantities in sale order; see the module documentation.
+fn plan_quantities(request: &SalePlanRequest, groups: &[Vec<Lot>], estimator: &Estimator, step: f64) -> Result<Vec<(usize, usize, f64)>, JobFailure> {
+  let seed = cuts_of(groups, &pooled_quantities(groups, estimator, step));
+  let mut search = Search::new(request, groups, step);
+  search.consider(&seed)?;
+  if search.best.is_none() {
+    // Even selling everything falls short.
+    return Ok(sale_order(groups, &seed));
+  }
+  let domains: Vec<f64> = (0..groups.len()).map(|group| search.cut_count(group, true)).collect();
+  let closer = (0..groups.len()).max_by(|a, b| domains[*a].total_cmp(&domains[*b]).then(b.cmp(a))).unwrap_or(0);
+  let share_by_share: f64 = (0..groups.len()).filter(|group| *group != closer).map(|group| domains[group]).product();
+  if share_by_share <= SEARCH_LEAF_LIMIT {
+    // The closer is cut by `close`, not from a candidate list.
+    let candidates: Vec<Vec<Cut>> =
+      (0..groups.len()).map(|group| if group == closer { Vec::new() } else { search.cuts(group, true) }).collect();
+    search.run(&candidates, closer)?;
+  } else {
+    let candidates: Vec<Vec<Cut>> = (0..groups.len()).map(|group| search.cuts(group, false)).collect();
+    let leaves: f64 = (0..groups.len())
+      .map(|closer| (0..groups.len()).filter(|group| *group != closer).map(|group| candidates[group].len() as f64).product::<f64>())
+      .sum();
+    if leaves <= SEARCH_LEAF_LIMIT {
+      for closer in 0..groups.len() {
+        search.run(&candidates, closer)?;
+      }
+    }
+  }
+  let cuts = search.best.map(|(_, cuts)| cuts).unwrap_or(seed);
+  Ok(sale_order(groups, &cuts))
+}
+
+pub(crate) fn plan_sale(request: &SalePlanRequest) -> Result<SalePlan, JobFailure> {
+  if !request.net_amount.is_finite() || request.net_amount < 0.0 {
+    return Err(invalid("netAmount muss ein nicht negativer Betrag sein."));
+  }
+  let groups = fifo_groups(request)?;
+  // Validates Pauschbetrag, church tax and loss carry before planning.
+  let mut previous = settle(request, Aggregate::default())?;
+  let state = request.tax_state_prev.clone().unwrap_or_default();
+  let estimator = Estimator {
+    rate: effective_rate(church_rate_bp(request.kirchensteuer_satz)?),
+    allowance: request.sparer_pauschbetrag.unwrap_or(0.0).max(0.0) + state.loss_carry.unwrap_or(0.0).max(0.0),
+    stock_carry: state.stock_loss_carry.unwrap_or(0.0).max(0.0),
+    target: request.net_amount,
+  };
+  let step = if request.fractional_shares { FRACTIONAL_SHARE_STEP } else { 1.0 };
+  let quantities = if request.net_amount > 0.0 { plan_quantities(request, &groups, &estimator, step)? } else { Vec::new() };
+
+  let mut breakdown = Vec::new();
+  let mut total = Aggregate::default();
+  for (group, position, shares) in quantities {
+    let lot = &groups[group][position];
+    let tranche = &request.tranches[lot.index];
+    let sold = line(lot, shares);
+    total = total + sold;
+    let current = settle(request, total)?;
+    let steuer = cents(current.tax_due) - cents(previous.tax_due);
+    breakdown.push(SaleLine {
+      tranche_id: tranche.tranche_id.clone(),
+      name: tranche.name.clone(),
+      isin: tranche.isin.clone(),
+      depot_id: tranche.depot_id.clone().or_else(|| tranche.source_profile_id.clone()),
+      purchase_date: tranche.purchase_date.clone(),
+      shares,
+      price: lot.price,
+      brutto: euros(sold.gross),
+      cost_basis: euros(sold.gross - sold.realized),
+      tqf: lot.tqf,
+      realized_gain_signed: euros(sold.realized),
+      taxable_after_tqf_signed: euros(sold.taxable),
+      steuer: euros(steuer),
+      spb_used: euros(cents(current.details.spb_used_this_year) - cents(previous.details.spb_used_this_year)),
+      netto: euros(sold.gross - steuer),
+    });
+    previous = current;
+  }
+
+  let tax = cents(previous.tax_due);
+  let achieved = euros(total.gross - tax);
+  Ok(SalePlan {
+    target_net: request.net_amount,
+    brutto_verkauf_gesamt: euros(total.gross),
+    steuer_gesamt: euros(tax),
+    achieved_refill: achieved,
+    shortfall: euros((cents(request.net_amount) - (total.gross - tax)).max(0)),
+    pauschbetrag_verbraucht: previous.details.spb_used_this_year,
+    breakdown,
+    tax_raw_aggregate: RawAggregate {
+      sum_realized_gain_signed: Some(euros(total.realized)),
+      sum_taxable_after_tqf_signed: Some(euros(total.taxable)),
+      sum_stock_taxable_signed: Some(euros(total.stock)),
+    },
+    settlement: previous,
+  })
+}
+
+#[tauri::command]
+pub fn plan_tax_optimal_sale(request: SalePlanRequest) -> Result<SalePlan, String> {
+  plan_sale(&request).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::{json, Value};
+
+  fn request(net_amount: f64, tranches: serde_json::Value) -> SalePlanRequest {
+    serde_json::from_value(json!({ "netAmount": net_amount, "tranches": tranches })).unwrap()
+  }
+
+  fn lines(plan: &SalePlan) -> Vec<(String, f64)> {
+    plan
+      .breakdown
+      .iter()
+      .map(|line| (line.tranche_id.clone().unwrap_or_default(), line.shares))
+      .collect()
+  }
+
+  #[test]
+  fn fifo_keeps_cheap_new_lots_behind_old_ones() {
+    // etf-a: the old lot carries a large gain and must go first; pooled with
+    // the new lot the group looks cheap, but a small sale only reaches the old
+    // lot, so the low-gain etf-b is the better choice.
+    let tranches = json!([
+      { "trancheId": "a-new", "isin": "IE00A", "purchaseDate": "2023-01-02", "shares": 100, "purchasePrice": 95, "currentPrice": 100, "tqf": 0.3 },
+      { "trancheId": "a-old", "isin": "IE00A", "purchaseDate": "2015-01-02", "shares": 10, "purchasePrice": 50, "currentPrice": 100, "tqf": 0.3 },
+      { "trancheId": "b", "isin": "IE00B", "purchaseDate": "2020-01-02", "shares": 50, "purchasePrice": 90, "currentPrice": 100, "tqf": 0.3 }
+    ]);
+    let plan = plan_sale(&request(1000.0, tranches.clone())).unwrap();
+    assert_eq!(lines(&plan), vec![("b".to_string(), 11.0)]);
+    // 11 x 10 x 70 % = 77 taxable -> 19,25 KapESt + 1,05 Soli.
+    assert_eq!((plan.brutto_verkauf_gesamt, plan.steuer_gesamt, plan.achieved_refill), (1100.0, 20.3, 1079.7));
+
+    // A larger sale needs etf-a too, and its lots leave in FIFO order.
+    let plan = plan_sale(&request(9000.0, tranches)).unwrap();
+    let order: Vec<String> = lines(&plan).into_iter().map(|(id, _)| id).collect();
+    let old = order.iter().position(|id| id == "a-old").unwrap();
+    let new = order.iter().position(|id| id == "a-new").unwrap();
+    assert!(old < new, "{:?}", order);
+    assert!(plan.achieved_refill >= 9000.0 && plan.shortfall == 0.0);
+  }
+
+  #[test]
+  fn losses_allowance_and_carry_lower_the_tax() {
+    let tranches = json!([
+      { "trancheId": "gain", "isin": "IE00G", "shares": 20, "purchasePrice": 50, "currentPrice": 100, "tqf": 0.0 },
+      { "trancheId": "loss", "isin": "IE00L", "shares": 10, "purchasePrice": 120, "currentPrice": 100, "tqf": 0.0 }
+    ]);
+    let plan = plan_sale(&request(1500.0, tranches.clone())).unwrap();
+    assert_eq!(plan.breakdown[0].tranche_id.as_deref(), Some("loss"));
+    assert!(plan.breakdown[0].steuer <= 0.0);
+    let total: f64 = plan.breakdown.iter().map(|line| line.steuer).sum();
+    assert!((total - plan.steuer_gesamt).abs() < 1e-9);
+
+    let mut sheltered = request(1500.0, tranches);
+    sheltered.sparer_pauschbetrag = Some(500.0);
+    sheltered.tax_state_prev = Some(TaxStateInput { loss_carry: Some(300.0), stock_loss_carry: None });
+    let plan = plan_sale(&sheltered).unwrap();
+    assert_eq!(plan.steuer_gesamt, 0.0);
+    assert_eq!(plan.brutto_verkauf_gesamt, 1500.0);
+    // The carry is used before the Pauschbetrag: 300 - 50 taxable remain.
+    assert_eq!(plan.settlement.tax_state_next.loss_carry, 250.0);
+  }
+
+  #[test]
+  fn matches_brute_force_over_all_fifo_plans() {
+    // xorshift32, so the cases are reproducible.
+    let mut state = 0x5a1e_u32;
+    let mut next = |bound: u32| {
+      state ^= state << 13;
+      state ^= state >> 17;
+      state ^= state << 5;
+      state % bound
+    };
+    for case in 0..150 {
+      let mut tranches = Vec::new();
+      for group in 0..1 + next(3) {
+        let stock = next(3) == 0;
+        for lot in 0..1 + next(3) {
+          let price = [20.0, 35.0, 50.0, 80.0, 100.0][next(5) as usize];
+          tranches.push(json!({
+            "trancheId": format!("{}-{}", group, lot),
+            "isin": format!("DE{}", group),
+            "purchaseDate": format!("20{:02}-01-02", 10 + lot),
+            "shares": 1 + next(4),
+            "purchasePrice": price * [0.3, 0.6, 0.9, 1.0, 1.2, 1.5][next(6) as usize],
+            "currentPrice": price,
+            "tqf": if stock { 0.0 } else { [0.0, 0.3][next(2) as usize] },
+            "stock": stock
+          }));
+        }
+      }
+      let mut plan_request = request(0.0, Value::from(tranches));
+      plan_request.sparer_pauschbetrag = Some([0.0, 50.0, 200.0][next(3) as usize]);
+      plan_request.kirchensteuer_satz = Some([0.0, 0.09][next(2) as usize]);
+      plan_request.tax_state_prev = Some(TaxStateInput {
+        loss_carry: Some([0.0, 40.0][next(2) as usize]),
+        stock_loss_carry: Some([0.0, 60.0][next(2) as usize]),
+      });
+      let groups = fifo_groups(&plan_request).unwrap();
+      let available: f64 = groups.iter().flatten().map(|lot| lot.shares * lot.price).sum();
+      plan_request.net_amount = (available * f64::from(10 + next(80)) / 100.0).round();
+
+      // Every FIFO quantity per group, in whole shares.
+      let mut best: Option<(i64, i64)> = None;
+      let mut quantities = vec![0.0; groups.len()];
+      loop {
+        let mut total = Aggregate::default();
+        for (lots, quantity) in groups.iter().zip(&quantities) {
+          let mut left: f64 = *quantity;
+          for lot in lots {
+            let shares = left.min(lot.shares);
+            if shares > 0.0 {
+              total = total + line(lot, shares);
+            }
+            left -= shares;
+          }
+        }
+        let tax = cents(settle(&plan_request, total).unwrap().tax_due);
+        if total.gross - tax >= cents(plan_request.net_amount) && best.map_or(true, |best| (tax, total.gross) < best) {
+          best = Some((tax, total.gross));
+        }
+        let Some(group) = (0..groups.len()).find(|group| quantities[*group] < groups[*group].iter().map(|lot| lot.shares).sum()) else {
+          break;
+        };
+        quantities[group] += 1.0;
+        quantities[..group].iter_mut().for_each(|quantity| *quantity = 0.0);
+      }
+
+      let plan = plan_sale(&plan_request).unwrap();
+      let (tax, gross) = best.unwrap();
+      assert_eq!((cents(plan.steuer_gesamt), cents(plan.brutto_verkauf_gesamt)), (tax, gross), "case {}", case);
+    }
+  }
+
+  #[test]
+  fn the_stock_loss_pot_shelters_share_gains_only() {
+    // Same gain per euro; the share's gain is covered by the Aktienverlusttopf.
+    let tranches = json!([
+      { "trancheId": "fund", "isin": "IE00F", "shares": 20, "purchasePrice": 80, "currentPrice": 100, "tqf": 0.0 },
+      { "trancheId": "share", "isin": "DE000S", "shares": 20, "purchasePrice": 80, "currentPrice": 100, "stock": true }
+    ]);
+    let mut sheltered = request(1000.0, tranches);
+    sheltered.tax_state_prev = Some(TaxStateInput { loss_carry: None, stock_loss_carry: Some(200.0) });
+    let plan = plan_sale(&sheltered).unwrap();
+    assert_eq!(lines(&plan), vec![("share".to_string(), 10.0)]);
+    assert_eq!(plan.steuer_gesamt, 0.0);
+    assert_eq!(plan.settlement.tax_state_next.stock_loss_carry, 0.0);
+  }
+
+  #[test]
+  fn large_fractional_lots_are_counted_not_enumerated() {
+    let mut plan_request = request(
+      20_000.0,
+      json!([
+        { "trancheId": "big", "isin": "IE00A", "shares": 5000, "purchasePrice": 50, "currentPrice": 100, "tqf": 0.3 },
+        { "trancheId": "small", "isin": "IE00B", "shares": 0.5, "purchasePrice": 90, "currentPrice": 100, "tqf": 0.3 }
+      ]),
+    );
+    plan_request.fractional_shares = true;
+    let groups = fifo_groups(&plan_request).unwrap();
+    let search = Search::new(&plan_request, &groups, FRACTIONAL_SHARE_STEP);
+    for group in 0..groups.len() {
+      assert_eq!(search.cut_count(group, false), search.cuts(group, false).len() as f64);
+    }
+    assert_eq!(search.cut_count(1, true), search.cuts(1, true).len() as f64);
+    assert_eq!(search.cut_count(0, true), 50_000_001.0);
+
+    // "big" closes the gap, so only the 0.5 shares of "small" are enumerated.
+    let plan = plan_sale(&plan_request).unwrap();
+    assert_eq!(plan.shortfall, 0.0);
+    assert!(plan.achieved_refill >= 20_000.0 && plan.achieved_refill < 20_000.1, "{}", plan.achieved_refill);
+  }
+
+  #[test]
+  fn reports_shortfall_and_rejects_bad_input() {
+    let small = request(5000.0, json!([{ "trancheId": "x", "shares": 10, "purchasePrice": 100, "currentPrice": 100 }]));
+    let plan = plan_sale(&small).unwrap();
+    assert_eq!((plan.brutto_verkauf_gesamt, plan.shortfall), (1000.0, 4000.0));
+
+    let mut fractional = request(250.0, json!([{ "trancheId": "x", "shares": 10, "purchasePrice": 100, "currentPrice": 100 }]));
+    fractional.fractional_shares = true;
+    let plan = plan_sale(&fractional).unwrap();
+    assert!((plan.breakdown[0].shares - 2.5).abs() < 1e-9);
+    assert_eq!(plan.brutto_verkauf_gesamt, 250.0);
+
+    assert_eq!(plan_sale(&request(-1.0, json!([]))).unwrap_err().code, "SALE_PLAN_INPUT_INVALID");
+    let bad_tqf = request(1.0, json!([{ "shares": 1, "purchasePrice": 1, "currentPrice": 1, "tqf": 2 }]));
+    assert_eq!(plan_sale(&bad_tqf).unwrap_err().code, "SALE_PLAN_INPUT_INVALID");
+  }
+}
@@ -0,0 +1,353 @@
+//! Embedded QuickJS runtime for the simulator's ES modules. Native batch jobs
+//! evaluate the same `app/` and `engine/` sources as the webview instead of a
+//! second implementation of the model, so results can only drift by the float
+//! differences between two conforming JS engines.
+
+use rquickjs::loader::{Loader, Resolver};
+use rquickjs::module::Declared;
+use rquickjs::{CatchResultExt, Context, Ctx, Function, Module, Runtime};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+
+/// Overrides the bundled frontend assets with a source checkout, e.g. to run
+/// local engine edits without rebuilding the app.
+pub(crate) const ENGINE_SOURCES_ENV: &str = "RUHESTAND_ENGINE_SOURCES";
+
+/// Stack granted to threads that host a runtime; the engine recurses deeply
+/// while deep-freezing contracts.
+pub(crate) const SCRIPT_THREAD_STACK_BYTES: usize = 32 * 1024 * 1024;
+const SCRIPT_MAX_STACK_BYTES: usize = 24 * 1024 * 1024;
+
+/// Failure of a native batch job; `code` is the stable identifier the
+/// webview and the CLI branch on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct JobFailure {
+  pub code: &'static str,
+  pub message: String,
+}
+
+impl fmt::Display for JobFailure {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {}", self.code, self.message)
+  }
+}
+
+pub(crate) fn job_failure(code: &'static str, message: impl Into<String>) -> JobFailure {
+  JobFailure { code, message: message.into() }
+}
+
+/// Keeps the JS stack in the log but only its first line in the failure.
+pub(crate) fn script_failure(code: &'static str, error: String) -> JobFailure {
+  log::warn!("[engine] {}", error);
+  let line = error.lines().next().unwrap_or_default();
+  job_failure(code, line.strip_prefix("Error: ").unwrap_or(line).trim())
+}
+
+/// Cancellation flags of the native jobs currently in flight, keyed by the
+/// job id the webview chose.
+#[derive(Default)]
+pub(crate) struct NativeJobs {
+  running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+pub(crate) type SharedNativeJobs = Arc<NativeJobs>;
+
+/// Registration of a running job; dropping it unregisters the job id, also
+/// when the job panics.
+pub(crate) struct NativeJob<'a> {
+  jobs: &'a NativeJobs,
+  job_id: String,
+  cancel: Arc<AtomicBool>,
+}
+
+impl NativeJob<'_> {
+  pub(crate) fn cancel_flag(&self) -> &Arc<AtomicBool> {
+    &self.cancel
+  }
+}
+
+impl Drop for NativeJob<'_> {
+  fn drop(&mut self) {
+    self.jobs.finish(&self.job_id);
+  }
+}
+
+impl NativeJobs {
+  pub(crate) fn start(&self, job_id: &str) -> Result<NativeJob<'_>, JobFailure> {
+    let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+    if running.contains_key(job_id) {
+      return Err(job_failure("NATIVE_JOB_ACTIVE", format!("Lauf {} ist bereits aktiv.", job_id)));
+    }
+    let flag = Arc::new(AtomicBool::new(false));
+    running.insert(job_id.to_string(), flag.clone());
+    Ok(NativeJob { jobs: self, job_id: job_id.to_string(), cancel: flag })
+  }
+
+  pub(crate) fn cancel(&self, job_id: &str) -> bool {
+    let running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+    match running.get(job_id) {
+      Some(flag) => {
+        flag.store(true, Ordering::Relaxed);
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn finish(&self, job_id: &str) {
+    self.running.lock().unwrap_or_else(|e| e.into_inner()).remove(job_id);
+  }
+}
+
+/// Looks up module source text by root-relative path such as
+/// `app/simulator/monte-carlo-runner.js`.
+pub(crate) type ScriptSource = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+pub(crate) fn directory_source(root: PathBuf) -> ScriptSource {
+  Arc::new(move |name: &str| std::fs::read_to_string(root.join(name)).ok())
+}
+
+/// Engine sources of the running app with the user's historical dataset
+/// overlay, if any (see historical_dataset.rs).
+pub(crate) fn app_source(app: &tauri::AppHandle) -> ScriptSource {
+  let source = bundled_source(app);
+  match app.path().app_data_dir() {
+    Ok(dir) => crate::historical_dataset::overlay_source(source, dir.join(crate::historical_dataset::OVERLAY_FILENAME)),
+    Err(_) => source,
+  }
+}
+
+/// The frontend assets bundled into the binary, unless
+/// `RUHESTAND_ENGINE_SOURCES` points at a directory.
+pub(crate) fn bundled_source(app: &tauri::AppHandle) -> ScriptSource {
+  if let Some(root) = std::env::var(ENGINE_SOURCES_ENV).ok().filter(|dir| !dir.trim().is_empty()) {
+    return directory_source(PathBuf::from(root));
+  }
+  let app = app.clone();
+  Arc::new(move |name: &str| {
+    app
+      .asset_resolver()
+      .get(name.to_string())
+      .map(|asset| String::from_utf8_lossy(asset.bytes()).into_owned())
+  })
+}
+
+/// The repository checkout this crate was built from.
+#[cfg(test)]
+pub(crate) fn checkout_root() -> PathBuf {
+  Path::new(env!("CARGO_MANIFEST_DIR")).parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+/// Browser globals the simulator modules touch at import time or in
+/// diagnostics. QuickJS ships none of them. There is no event loop, so
+/// `setTimeout` only supports the `setTimeout(resolve, 0)` yield the runners
+/// use and throws for real delays instead of firing them immediately.
+const PRELUDE: &str = r#"
+globalThis.setTimeout = (callback, delay, ...args) => {
+  if (Number(delay) > 0) throw new Error(`setTimeout(${delay}) wird in der nativen Runtime nicht unterstuetzt.`);
+  Promise.resolve().then(() => callback(...args));
+  return 0;
+};
+globalThis.clearTimeout = () => {};
+globalThis.performance = globalThis.performance || { now: () => Date.now() };
+globalThis.console = {
+  log: () => {}, info: () => {}, debug: () => {},
+  warn: (...args) => __ruhestandLog('warn', args.map(String).join(' ')),
+  error: (...args) => __ruhestandLog('error', args.map(String).join(' '))
+};
+globalThis.TextEncoder = class TextEncoder {
+  encode(input = '') {
+    const text = String(input);
+    const bytes = [];
+    for (let index = 0; index < text.length; index++) {
+      let code = text.codePointAt(index);
+      if (code > 0xffff) index++;
+      if (code < 0x80) bytes.push(code);
+      else if (code < 0x800) bytes.push(0xc0 | (code >> 6), 0x80 | (code & 63));
+      else if (code < 0x10000) bytes.push(0xe0 | (code >> 12), 0x80 | ((code >> 6) & 63), 0x80 | (code & 63));
+      else bytes.push(0xf0 | (code >> 18), 0x80 | ((code >> 12) & 63), 0x80 | ((code >> 6) & 63), 0x80 | (code & 63));
+    }
+    return new Uint8Array(bytes);
+  }
+};
+globalThis.Intl = globalThis.Intl || {
+  NumberFormat: class NumberFormat {
+    constructor(_locale, options = {}) { this.options = options; }
+    format(value) {
+      const digits = this.options.maximumFractionDigits;
+      return Number.isInteger(digits) ? Number(value).toFixed(digits) : String(value);
+    }
+    formatToParts(value) { return [{ type: 'integer', value: this.format(value) }]; }
+  },
+  DateTimeFormat: class DateTimeFormat {
+    format(value) { return new Date(value).toISOString(); }
+  }
+};
+"#;
+
+/// Joins `name` onto the directory of `base` the way a browser resolves
+/// relative module specifiers; bare names are taken as root-relative.
+pub(crate) fn resolve_module_path(base: &str, name: &str) -> String {
+  let joined = if name.starts_with("./") || name.starts_with("../") {
+    Path::new(base).parent().unwrap_or(Path::new("")).join(name)
+  } else {
+    PathBuf::from(name.trim_start_matches('/'))
+  };
+  let mut parts: Vec<String> = Vec::new();
+  for component in joined.components() {
+    match component {
+      Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+      Component::ParentDir => {
+        parts.pop();
+      }
+      _ => {}
+    }
+  }
+  parts.join("/")
+}
+
+struct SourceResolver;
+
+impl Resolver for SourceResolver {
+  fn resolve<'js>(&mut self, _ctx: &Ctx<'js>, base: &str, name: &str) -> rquickjs::Result<String> {
+    Ok(resolve_module_path(base, name))
+  }
+}
+
+struct SourceLoader {
+  source: ScriptSource,
+}
+
+impl Loader for SourceLoader {
+  fn load<'js>(&mut self, ctx: &Ctx<'js>, name: &str) -> rquickjs::Result<Module<'js, Declared>> {
+    let code = (self.source)(name).ok_or_else(|| rquickjs::Error::new_loading(name))?;
+    Module::declare(ctx.clone(), name, code)
+  }
+}
+
+/// One QuickJS runtime with the prelude installed. Runtimes are bound to the
+/// thread that created them; parallel jobs create one per worker thread.
+pub(crate) struct ScriptHost {
+  _runtime: Runtime,
+  context: Context,
+}
+
+impl ScriptHost {
+  /// `interrupt` aborts running script code at the next check point once it
+  /// is set, which is how long chunks are cancelled.
+  pub(crate) fn new(source: ScriptSource, interrupt: Option<Arc<AtomicBool>>) -> Result<Self, String> {
+    let runtime = Runtime::new().map_err(|e| e.to_string())?;
+    runtime.set_max_stack_size(SCRIPT_MAX_STACK_BYTES);
+    runtime.set_loader(SourceResolver, SourceLoader { source });
+    if let Some(flag) = interrupt {
+      runtime.set_interrupt_handler(Some(Box::new(move || flag.load(Ordering::Relaxed))));
+    }
+    let context = Context::full(&runtime).map_err(|e| e.to_string())?;
+    let host = ScriptHost { _runtime: runtime, context };
+    host.with(|ctx| {
+      let log = Function::new(ctx.clone(), |level: String, message: String| {
+        if level == "error" {
+          log::error!("[engine] {}", message);
+        } else {
+          log::warn!("[engine] {}", message);
+        }
+      })
+      .catch(&ctx)
+      .map_err(|e| e.to_string())?;
+      ctx.globals().set("__ruhestandLog", log).catch(&ctx).map_err(|e| e.to_string())?;
+      ctx.eval::<(), _>(PRELUDE).catch(&ctx).map_err(|e| e.to_string())
+    })?;
+    Ok(host)
+  }
+
+  /// Evaluates an ES module whose relative imports resolve against `name`.
+  pub(crate) fn evaluate_module(&self, name: &str, code: &str) -> Result<(), String> {
+    self.with(|ctx| {
+      Module::evaluate(ctx.clone(), name, code)
+        .and_then(|promise| promise.finish::<()>())
+        .catch(&ctx)
+        .map_err(|e| e.to_string())
+    })
+  }
+
+  /// Calls `globalThis[object][method](...args)`; promises are driven to
+  /// completion and must settle with a string.
+  pub(crate) fn call_string<A>(&self, object: &str, method: &str, args: A) -> Result<String, String>
+  where
+    A: for<'js> rquickjs::function::IntoArgs<'js>,
+  {
+    self.with(|ctx| {
+      let result: rquickjs::Result<String> = (|| {
+        let target: rquickjs::Object = ctx.globals().get(object)?;
+        let function: Function = target.get(method)?;
+        let value: rquickjs::Value = function.call(args)?;
+        match value.as_promise() {
+          Some(promise) => promise.finish::<String>(),
+          None if value.is_undefined() => Ok(String::new()),
+          None => value.get::<String>(),
+        }
+      })();
+      result.catch(&ctx).map_err(|e| e.to_string())
+    })
+  }
+
+  fn with<R>(&self, f: impl FnOnce(Ctx<'_>) -> Result<R, String>) -> Result<R, String> {
+    self.context.with(f)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn module_paths_resolve_like_a_browser() {
+    assert_eq!(resolve_module_path("native/host.js", "../app/simulator/x.js"), "app/simulator/x.js");
+    assert_eq!(resolve_module_path("app/simulator/x.js", "./y.js"), "app/simulator/y.js");
+    assert_eq!(resolve_module_path("app/simulator/x.js", "../../engine/index.mjs"), "engine/index.mjs");
+    assert_eq!(resolve_module_path("app/x.js", "/types/t.js"), "types/t.js");
+  }
+
+  #[test]
+  fn host_runs_modules_against_the_prelude() {
+    let source: ScriptSource = Arc::new(|name: &str| {
+      (name == "lib/sum.js").then(|| "export const sum = (a, b) => a + b;".to_string())
+    });
+    let host = ScriptHost::new(source, None).unwrap();
+    host
+      .evaluate_module(
+        "main/entry.js",
+        "import { sum } from '../lib/sum.js';\n\
+         globalThis.probe = {\n\
+           run: async (a) => { await new Promise(resolve => setTimeout(resolve, 0)); return String(sum(a, 1)) + ':' + new TextEncoder().encode('ä').length; },\n\
+           wait: async () => { await new Promise(resolve => setTimeout(resolve, 250)); return 'late'; }\n\
+         };",
+      )
+      .unwrap();
+    assert_eq!(host.call_string("probe", "run", (41,)).unwrap(), "42:2");
+    assert!(host.call_string("probe", "wait", ()).unwrap_err().contains("setTimeout(250)"));
+    assert!(host.evaluate_module("main/broken.js", "import '../missing.js';").is_err());
+  }
+
+  #[test]
+  fn native_jobs_reject_duplicates_and_cancel() {
+    let jobs = NativeJobs::default();
+    let job = jobs.start("job-1").unwrap();
+    assert_eq!(jobs.start("job-1").err().map(|error| error.code), Some("NATIVE_JOB_ACTIVE"));
+    assert!(jobs.cancel("job-1"));
+    assert!(job.cancel_flag().load(Ordering::Relaxed));
+    drop(job);
+    assert!(!jobs.cancel("job-1"));
+
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+      let _job = jobs.start("job-2").unwrap();
+      panic!("engine bug");
+    }));
+    assert!(panicked.is_err());
+    assert!(jobs.start("job-2").is_ok());
+  }
+}
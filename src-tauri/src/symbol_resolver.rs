@@ -0,0 +1,342 @@
+//! ISIN/WKN lookup for the tranche editor. The identifier is validated (ISIN
+//! check digit), sent to the Yahoo search endpoint and the answer reduced to
+//! listings on German and other euro-area exchanges, ranked Xetra first.
+//! Non-empty results are cached in the app data dir, so known identifiers
+//! resolve again without network access and also in offline mode.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::network::SharedNetworkState;
+use crate::{QuoteFailure, SearchQuote};
+use crate::script_runtime::{job_failure as failure, JobFailure};
+
+pub(crate) const SYMBOL_CACHE_FILENAME: &str = "ruhestand_suite_symbol_cache.json";
+const CACHE_MAX_AGE_SECONDS: u64 = 30 * 24 * 60 * 60;
+
+/// Yahoo exchange code, symbol suffix and display name of every accepted
+/// venue, in ranking order. All of them usually quote in EUR; a listing Yahoo
+/// reports in another currency is dropped, and `/quote` still rejects any
+/// price that arrives in another currency.
+const EXCHANGES: &[(&str, &str, &str)] = &[
+  ("GER", ".DE", "Xetra"),
+  ("FRA", ".F", "Frankfurt"),
+  ("STU", ".SG", "Stuttgart"),
+  ("MUN", ".MU", "Muenchen"),
+  ("DUS", ".DU", "Duesseldorf"),
+  ("HAM", ".HM", "Hamburg"),
+  ("BER", ".BE", "Berlin"),
+  ("HAN", ".HA", "Hannover"),
+  ("AMS", ".AS", "Euronext Amsterdam"),
+  ("PAR", ".PA", "Euronext Paris"),
+  ("MIL", ".MI", "Borsa Italiana"),
+  ("BRU", ".BR", "Euronext Bruessel"),
+  ("MCE", ".MC", "Madrid"),
+  ("VIE", ".VI", "Wien"),
+  ("ISE", ".IR", "Euronext Dublin"),
+  ("LIS", ".LS", "Euronext Lissabon"),
+  ("HEL", ".HE", "Helsinki"),
+];
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum IdentifierKind {
+  Isin,
+  Wkn,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SymbolCandidate {
+  pub symbol: String,
+  pub name: String,
+  pub exchange: String,
+  pub exchange_name: String,
+  pub quote_type: Option<String>,
+  pub currency: String,
+  /// Yahoo confirmed EUR; otherwise the currency is only implied by the venue.
+  #[serde(default)]
+  pub currency_verified: bool,
+  /// 1 is the best match.
+  pub rank: usize,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheEntry {
+  candidates: Vec<SymbolCandidate>,
+  resolved_at: u64,
+}
+
+/// Identifier -> candidates, optionally mirrored to a JSON file.
+pub(crate) struct SymbolCache {
+  entries: Mutex<BTreeMap<String, CacheEntry>>,
+  path: Option<PathBuf>,
+}
+
+impl SymbolCache {
+  pub(crate) fn in_memory() -> Self {
+    SymbolCache { entries: Mutex::new(BTreeMap::new()), path: None }
+  }
+
+  pub(crate) fn load(path: PathBuf) -> Self {
+    let entries = fs::read_to_string(&path)
+      .ok()
+      .and_then(|raw| serde_json::from_str::<BTreeMap<String, CacheEntry>>(&raw).ok())
+      .unwrap_or_default();
+    SymbolCache { entries: Mutex::new(entries), path: Some(path) }
+  }
+
+  fn get(&self, identifier: &str) -> Option<CacheEntry> {
+    self.entries.lock().unwrap_or_else(|e| e.into_inner()).get(identifier).cloned()
+  }
+
+  fn insert(&self, identifier: String, entry: CacheEntry) {
+    let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+    entries.insert(identifier, entry);
+    let Some(path) = &self.path else { return };
+    let Ok(content) = serde_json::to_string(&*entries) else { return };
+    let _ = crate::write_atomic(path, &content);
+  }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ResolveRequest {
+  pub query: String,
+  /// Ignore the cache and ask the provider again.
+  #[serde(default)]
+  pub refresh: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SymbolResolution {
+  pub identifier: String,
+  pub kind: IdentifierKind,
+  pub candidates: Vec<SymbolCandidate>,
+  pub cached: bool,
+  pub resolved_at: u64,
+}
+
+fn letter_value(character: char) -> Option<u32> {
+  character.to_digit(36)
+}
+
+/// ISO 6166 check digit: letters become 10..35, then Luhn over the digits.
+fn isin_check_digit(body: &str) -> Option<u32> {
+  let mut digits = Vec::new();
+  for character in body.chars() {
+    let value = letter_value(character)?;
+    if value >= 10 {
+      digits.push(value / 10);
+    }
+    digits.push(value % 10);
+  }
+  let sum: u32 = digits
+    .iter()
+    .rev()
+    .enumerate()
+    .map(|(index, digit)| {
+      if index % 2 == 0 {
+        let doubled = digit * 2;
+        doubled / 10 + doubled % 10
+      } else {
+        *digit
+      }
+    })
+    .sum();
+  Some((10 - sum % 10) % 10)
+}
+
+/// Upper-cased identifier and its kind; ISINs must carry a valid check digit.
+pub(crate) fn classify_identifier(raw: &str) -> Result<(String, IdentifierKind), JobFailure> {
+  let identifier: String = raw.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_uppercase();
+  let alphanumeric = identifier.chars().all(|c| c.is_ascii_alphanumeric());
+  if identifier.len() == 12 && alphanumeric && identifier[..2].chars().all(|c| c.is_ascii_alphabetic()) {
+    let check = identifier[11..].chars().next().and_then(|c| c.to_digit(10));
+    if check.is_none() || isin_check_digit(&identifier[..11]) != check {
+      return Err(failure("ISIN_CHECKSUM_INVALID", format!("ISIN {} hat eine ungueltige Pruefziffer.", identifier)));
+    }
+    return Ok((identifier, IdentifierKind::Isin));
+  }
+  if identifier.len() == 6 && alphanumeric && !identifier.contains(['I', 'O']) {
+    return Ok((identifier, IdentifierKind::Wkn));
+  }
+  Err(failure(
+    "INVALID_IDENTIFIER",
+    format!("\"{}\" ist weder eine ISIN (12 Zeichen) noch eine WKN (6 Zeichen).", raw.trim()),
+  ))
+}
+
+fn exchange_of(quote: &SearchQuote) -> Option<(usize, &'static str, &'static str)> {
+  let code = quote.exchange.as_deref().unwrap_or("");
+  EXCHANGES
+    .iter()
+    .position(|(exchange, _, _)| *exchange == code)
+    .or_else(|| EXCHANGES.iter().position(|(_, suffix, _)| quote.symbol.ends_with(suffix)))
+    .map(|index| (index, EXCHANGES[index].0, EXCHANGES[index].2))
+}
+
+/// Euro-area listings from a Yahoo search answer (parsed like `/search`),
+/// best venue first; equal venues keep Yahoo's order.
+pub(crate) fn euro_candidates(search: &Value) -> Vec<SymbolCandidate> {
+  let mut ranked: Vec<(usize, SymbolCandidate)> = Vec::new();
+  for quote in crate::parse_search_quotes(search) {
+    let Some((venue, exchange, exchange_name)) = exchange_of(&quote) else { continue };
+    if quote.currency.as_deref().is_some_and(|currency| currency != "EUR") {
+      continue;
+    }
+    ranked.push((
+      venue,
+      SymbolCandidate {
+        currency_verified: quote.currency.is_some(),
+        symbol: quote.symbol,
+        name: quote.name,
+        exchange: exchange.to_string(),
+        exchange_name: exchange_name.to_string(),
+        quote_type: Some(quote.quote_type),
+        currency: "EUR".to_string(),
+        rank: 0,
+      },
+    ));
+  }
+  ranked.sort_by_key(|(venue, _)| *venue);
+  ranked
+    .into_iter()
+    .enumerate()
+    .map(|(index, (_, candidate))| SymbolCandidate { rank: index + 1, ..candidate })
+    .collect()
+}
+
+/// Resolves with `search` answering one query with the raw Yahoo search JSON.
+pub(crate) fn resolve_with<F>(cache: &SymbolCache, request: &ResolveRequest, now_seconds: u64, search: F) -> Result<SymbolResolution, JobFailure>
+where
+  F: FnOnce(&str) -> Result<Value, QuoteFailure>,
+{
+  let (identifier, kind) = classify_identifier(&request.query)?;
+  let cached = cache.get(&identifier);
+  let fresh = cached.as_ref().filter(|entry| now_seconds.saturating_sub(entry.resolved_at) <= CACHE_MAX_AGE_SECONDS);
+  if let Some(entry) = fresh.filter(|_| !request.refresh) {
+    return Ok(SymbolResolution { identifier, kind, candidates: entry.candidates.clone(), cached: true, resolved_at: entry.resolved_at });
+  }
+  let candidates = match search(&identifier) {
+    Ok(answer) => euro_candidates(&answer),
+    // An outdated mapping is still better than none while offline or rate limited.
+    Err(error) => match cached {
+      Some(entry) if crate::is_recoverable(&error) => {
+        return Ok(SymbolResolution { identifier, kind, candidates: entry.candidates, cached: true, resolved_at: entry.resolved_at });
+      }
+      _ => return Err(failure(error.code, error.message)),
+    },
+  };
+  if !candidates.is_empty() {
+    cache.insert(identifier.clone(), CacheEntry { candidates: candidates.clone(), resolved_at: now_seconds });
+  }
+  Ok(SymbolResolution { identifier, kind, candidates, cached: false, resolved_at: now_seconds })
+}
+
+#[tauri::command]
+pub async fn resolve_instrument(
+  state: tauri::State<'_, SharedNetworkState>,
+  request: ResolveRequest,
+) -> Result<SymbolResolution, String> {
+  let network = state.inner().clone();
+  tauri::async_runtime::spawn_blocking(move || {
+    // Replayed fixtures are not observations and stay out of the cache.
+    let replay_cache = SymbolCache::in_memory();
+    let cache = if network.is_replaying() { &replay_cache } else { network.symbol_cache() };
+    resolve_with(cache, &request, network.now_seconds(), |identifier| {
+      let url = format!("{}/v1/finance/search?q={}", network.yahoo_endpoints().primary, urlencoding::encode(identifier));
+      crate::fetch_json(&network, &url)
+    })
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  const NOW: u64 = 1_700_000_000;
+
+  fn search_answer() -> Value {
+    json!({ "quotes": [
+      { "symbol": "VWRL.L", "exchange": "LSE", "quoteType": "ETF", "shortname": "Vanguard All-World" },
+      { "symbol": "VWCE.F", "exchange": "FRA", "quoteType": "ETF", "shortname": "VANGUARD FTSE ALL-WORLD" },
+      { "symbol": "VWCE.MI", "exchange": "MIL", "quoteType": "ETF", "longname": "Vanguard FTSE All-World UCITS ETF" },
+      { "symbol": "VWCE.DE", "exchange": "GER", "quoteType": "ETF", "longname": "Vanguard FTSE All-World UCITS ETF", "shortname": "VANGUARD", "currency": "eur" },
+      { "symbol": "VWCE.SG", "quoteType": "ETF" },
+      { "symbol": "VWCEU.DE", "exchange": "GER", "quoteType": "ETF", "currency": "USD" },
+      { "symbol": "vwce.de", "exchange": "GER", "quoteType": "ETF" },
+      { "symbol": "BAD@SYM", "exchange": "GER", "quoteType": "ETF" }
+    ]})
+  }
+
+  #[test]
+  fn classifies_isin_with_check_digit_and_wkn() {
+    for isin in ["IE00BK5BQT80", "US0378331005", "DE0007164600", "ie00b4l5y983"] {
+      assert_eq!(classify_identifier(isin).unwrap(), (isin.to_ascii_uppercase(), IdentifierKind::Isin));
+    }
+    assert_eq!(classify_identifier("US0378331006").unwrap_err().code, "ISIN_CHECKSUM_INVALID");
+    assert_eq!(classify_identifier(" a2pkxg ").unwrap(), ("A2PKXG".to_string(), IdentifierKind::Wkn));
+    assert_eq!(classify_identifier("716460").unwrap().1, IdentifierKind::Wkn);
+    for invalid in ["VWCE.DE", "A2PKXO", "", "US037833100"] {
+      assert_eq!(classify_identifier(invalid).unwrap_err().code, "INVALID_IDENTIFIER");
+    }
+  }
+
+  #[test]
+  fn keeps_euro_listings_ranked_xetra_first() {
+    let candidates = euro_candidates(&search_answer());
+    let symbols: Vec<(&str, usize)> = candidates.iter().map(|c| (c.symbol.as_str(), c.rank)).collect();
+    assert_eq!(symbols, vec![("VWCE.DE", 1), ("VWCE.F", 2), ("VWCE.SG", 3), ("VWCE.MI", 4)]);
+    assert_eq!(candidates[0].name, "Vanguard FTSE All-World UCITS ETF");
+    assert_eq!((candidates[0].exchange_name.as_str(), candidates[0].currency.as_str()), ("Xetra", "EUR"));
+    assert_eq!((candidates[0].currency_verified, candidates[1].currency_verified), (true, false));
+    assert_eq!((candidates[2].exchange.as_str(), candidates[2].name.as_str()), ("STU", "VWCE.SG"));
+    assert!(euro_candidates(&json!({ "quotes": "nope" })).is_empty());
+  }
+
+  #[test]
+  fn caches_results_and_falls_back_to_cache_on_provider_errors() {
+    let dir = std::env::temp_dir().join(format!("ruhestand-symbol-cache-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(SYMBOL_CACHE_FILENAME);
+    let _ = fs::remove_file(&path);
+    let cache = SymbolCache::load(path.clone());
+    let request = ResolveRequest { query: "IE00BK5BQT80".into(), refresh: false };
+
+    let mut queries = Vec::new();
+    let first = resolve_with(&cache, &request, NOW, |q| {
+      queries.push(q.to_string());
+      Ok(search_answer())
+    })
+    .unwrap();
+    assert_eq!((first.cached, first.candidates.len(), queries), (false, 4, vec!["IE00BK5BQT80".to_string()]));
+
+    let reloaded = SymbolCache::load(path);
+    let offline = |_: &str| Err(crate::quote_failure("OFFLINE_MODE", "Offline-Modus aktiv.", 503));
+    let hit = resolve_with(&reloaded, &request, NOW + 60, |_| panic!("cache expected")).unwrap();
+    assert_eq!((hit.cached, hit.resolved_at, hit.candidates[0].symbol.as_str()), (true, NOW, "VWCE.DE"));
+    let refreshed = resolve_with(&reloaded, &ResolveRequest { refresh: true, ..request.clone() }, NOW + 60, offline).unwrap();
+    assert!(refreshed.cached);
+    let broken = resolve_with(&reloaded, &ResolveRequest { refresh: true, ..request.clone() }, NOW + 60, |_| {
+      Err(crate::quote_failure("INVALID_RESPONSE", "query1.finance.yahoo.com: HTTP 403.", 502))
+    });
+    assert_eq!(broken.unwrap_err().code, "INVALID_RESPONSE");
+    let expired = resolve_with(&reloaded, &request, NOW + CACHE_MAX_AGE_SECONDS + 1, |_| Ok(json!({ "quotes": [] }))).unwrap();
+    assert_eq!((expired.cached, expired.candidates.len()), (false, 0));
+
+    let unknown = ResolveRequest { query: "A0RPWH".into(), refresh: false };
+    assert_eq!(resolve_with(&reloaded, &unknown, NOW, offline).unwrap_err().code, "OFFLINE_MODE");
+    let _ = fs::remove_dir_all(&dir);
+  }
+}
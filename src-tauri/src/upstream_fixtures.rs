@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub(crate) const FIXTURE_MODE_ENV: &str = "RUHESTAND_UPSTREAM_MODE";
+pub(crate) const FIXTURE_DIR_ENV: &str = "RUHESTAND_UPSTREAM_FIXTURES";
+pub(crate) const DEFAULT_FIXTURE_DIRNAME: &str = "upstream-fixtures";
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpstreamFixtureMode {
+  #[default]
+  Live,
+  Record,
+  Replay,
+}
+
+impl UpstreamFixtureMode {
+  fn parse(raw: &str) -> Option<Self> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+      "" | "live" => Some(UpstreamFixtureMode::Live),
+      "record" => Some(UpstreamFixtureMode::Record),
+      "replay" => Some(UpstreamFixtureMode::Replay),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct UpstreamFixtureSettings {
+  pub mode: UpstreamFixtureMode,
+  pub directory: Option<String>,
+}
+
+/// Effective record/replay configuration after applying the environment
+/// override (`RUHESTAND_UPSTREAM_MODE`, `RUHESTAND_UPSTREAM_FIXTURES`).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ActiveFixtures {
+  pub mode: UpstreamFixtureMode,
+  pub directory: Option<PathBuf>,
+  pub source: &'static str,
+}
+
+pub(crate) fn resolve_fixtures(
+  settings: &UpstreamFixtureSettings,
+  env_mode: Option<String>,
+  env_dir: Option<String>,
+  default_dir: Option<&Path>,
+) -> ActiveFixtures {
+  let env_mode = env_mode.as_deref().and_then(UpstreamFixtureMode::parse);
+  let (mode, source) = match env_mode {
+    Some(mode) => (mode, "env"),
+    None => (settings.mode, "settings"),
+  };
+  let directory = env_dir
+    .filter(|dir| !dir.trim().is_empty())
+    .or_else(|| settings.directory.clone().filter(|dir| !dir.trim().is_empty()))
+    .map(PathBuf::from)
+    .or_else(|| default_dir.map(|dir| dir.join(DEFAULT_FIXTURE_DIRNAME)));
+  ActiveFixtures { mode, directory, source }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UpstreamFixture {
+  pub url: String,
+  pub status: u16,
+  /// Raw upstream text, so invalid JSON from a bug report replays unchanged.
+  pub body: String,
+  pub recorded_at: u64,
+}
+
+/// Stable 64-bit FNV-1a; only used to give each URL a short file name.
+fn fnv1a_64(input: &str) -> u64 {
+  let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+  for byte in input.as_bytes() {
+    hash ^= u64::from(*byte);
+    hash = hash.wrapping_mul(0x0100_0000_01b3);
+  }
+  hash
+}
+
+/// `<last path segment>-<hash>.json`, e.g. `VWCE.DE-1f0c....json`, so a
+/// captured fixture set stays readable when attached to a bug report.
+pub(crate) fn fixture_file_name(url: &str) -> String {
+  let path = url.split('?').next().unwrap_or("");
+  let label: String = path
+    .rsplit('/')
+    .find(|segment| !segment.is_empty())
+    .unwrap_or("upstream")
+    .chars()
+    .map(|character| if character.is_ascii_alphanumeric() || matches!(character, '.' | '-' | '_') { character } else { '_' })
+    .take(40)
+    .collect();
+  format!("{}-{:016x}.json", label, fnv1a_64(url))
+}
+
+pub(crate) fn write_fixture(directory: &Path, fixture: &UpstreamFixture) -> Result<(), String> {
+  fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+  let content = serde_json::to_string_pretty(fixture).map_err(|e| e.to_string())?;
+  fs::write(directory.join(fixture_file_name(&fixture.url)), content).map_err(|e| e.to_string())
+}
+
+pub(crate) fn read_fixture(directory: &Path, url: &str) -> Option<UpstreamFixture> {
+  let raw = fs::read_to_string(directory.join(fixture_file_name(url))).ok()?;
+  serde_json::from_str::<UpstreamFixture>(&raw).ok().filter(|fixture| fixture.url == url)
+}
+
+/// Replayed quotes are validated against the newest recording time instead of
+/// the wall clock, otherwise every fixture set would turn `QUOTE_STALE` after
+/// seven days.
+pub(crate) fn replay_clock(directory: &Path) -> Option<u64> {
+  fs::read_dir(directory)
+    .ok()?
+    .filter_map(|entry| entry.ok())
+    .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+    .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+    .filter_map(|raw| serde_json::from_str::<UpstreamFixture>(&raw).ok())
+    .map(|fixture| fixture.recorded_at)
+    .max()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn environment_overrides_fixture_settings() {
+    let settings = UpstreamFixtureSettings {
+      mode: UpstreamFixtureMode::Record,
+      directory: Some("/data/captured".to_string()),
+    };
+    let from_settings = resolve_fixtures(&settings, None, None, Some(Path::new("/app")));
+    assert_eq!(from_settings.mode, UpstreamFixtureMode::Record);
+    assert_eq!(from_settings.source, "settings");
+    assert_eq!(from_settings.directory, Some(PathBuf::from("/data/captured")));
+
+    let from_env = resolve_fixtures(&settings, Some("REPLAY".to_string()), Some("/tmp/fx".to_string()), None);
+    assert_eq!(from_env.mode, UpstreamFixtureMode::Replay);
+    assert_eq!(from_env.source, "env");
+    assert_eq!(from_env.directory, Some(PathBuf::from("/tmp/fx")));
+
+    let defaulted = resolve_fixtures(&UpstreamFixtureSettings::default(), Some("bogus".to_string()), None, Some(Path::new("/app")));
+    assert_eq!(defaulted.mode, UpstreamFixtureMode::Live);
+    assert_eq!(defaulted.directory, Some(Path::new("/app").join(DEFAULT_FIXTURE_DIRNAME)));
+  }
+
+  #[test]
+  fn fixtures_round_trip_by_url_and_drive_the_replay_clock() {
+    let dir = std::env::temp_dir().join(format!("ruhestand-fixtures-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let url = "https://query1.finance.yahoo.com/v8/finance/chart/VWCE.DE?interval=1d&range=1d";
+    assert!(fixture_file_name(url).starts_with("VWCE.DE-"));
+    assert_ne!(fixture_file_name(url), fixture_file_name(&url.replace("query1", "query2")));
+
+    write_fixture(&dir, &UpstreamFixture { url: url.to_string(), status: 200, body: "{}".to_string(), recorded_at: 10 }).unwrap();
+    write_fixture(&dir, &UpstreamFixture { url: format!("{}&x=1", url), status: 429, body: String::new(), recorded_at: 42 }).unwrap();
+
+    assert_eq!(read_fixture(&dir, url).map(|fixture| fixture.status), Some(200));
+    assert!(read_fixture(&dir, "https://query1.finance.yahoo.com/v1/finance/search?q=x").is_none());
+    assert_eq!(replay_clock(&dir), Some(42));
+    let _ = fs::remove_dir_all(&dir);
+  }
+}
@@ -0,0 +1,717 @@
+//! Library of user-supplied historical datasets for backtest and Monte-Carlo,
+//! e.g. a DAX-heavy or 60/40 portfolio in place of the embedded MSCI series.
+//! A CSV or JSON import is converted to the units of simulator-data.js and
+//! built into a complete `HistoricalDataManifestV1` plus records on the
+//! embedded engine runtime, where `createHistoricalBacktestContractProvider`
+//! checks it exactly like the embedded dataset. Accepted datasets are stored
+//! as `UserHistoricalDatasetV1` in the app data dir under an id derived from
+//! their content hash (the fingerprint). The simulator window picks one of
+//! them (`select_user_dataset`); simulator-data.js then loads it in place of
+//! the embedded dataset and its overlay and hands it to its workers, and
+//! native runs get it injected unless they name another dataset, so the
+//! manifest lands in the export provenance.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::Manager;
+
+use crate::bank_csv::{detect_delimiter, normalize_header, split_line};
+use crate::broker_import::parse_decimal;
+use crate::historical_dataset::{call_dataset_host, MSCI_RETURN, SERIES_IDS, SIMULATOR_DATA_MODULE};
+use crate::quote_history::civil_from_days;
+use crate::script_runtime::{job_failure as failure, JobFailure, ScriptSource};
+use crate::{quote_failure, QuoteFailure};
+
+pub(crate) const DATASETS_DIR: &str = "historical_datasets";
+/// `{ datasetId }` of the dataset the simulator picked, inside `DATASETS_DIR`.
+const SELECTION_FILENAME: &str = "selected.json";
+/// `USER_HISTORICAL_DATASET_SCHEMA_VERSION` in simulator-data.js.
+const DATASET_SCHEMA_VERSION: &str = "UserHistoricalDatasetV1";
+const DATASET_GLOBAL: &str = "__ruhestandHistoricalDataset";
+const SECONDS_PER_DAY: u64 = 86_400;
+/// Row qualities an import may declare; see `importDataset` in the host.
+const QUALITY_STATUSES: [&str; 3] = ["present", "estimated", "fallback_zero"];
+/// Below this many rows an all-below-one percent series is not suspicious.
+const FRACTION_CHECK_MIN_ROWS: usize = 5;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UserDatasetImportRequest {
+  /// Name shown in the dataset list; a JSON import may carry its own.
+  pub label: Option<String>,
+  pub content: String,
+  /// `csv` or `json`; detected from the content when empty.
+  pub format: Option<String>,
+  pub file_name: Option<String>,
+  /// Series id or `msci_return` -> unit in the file. Defaults are the units
+  /// of simulator-data.js: `index_level`, `ratio` for CAPE, otherwise
+  /// `percent_per_year`; rates may also come as `fraction_per_year`.
+  #[serde(default)]
+  pub units: BTreeMap<String, String>,
+  /// Store the dataset; otherwise only the validated preview is returned.
+  #[serde(default)]
+  pub apply: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DatasetRow {
+  pub year: i32,
+  /// Series id -> value in the unit of simulator-data.js.
+  pub values: BTreeMap<String, f64>,
+  pub msci_return: Option<f64>,
+  pub quality: Option<String>,
+}
+
+/// Rows plus what the import says about them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ParsedDataset {
+  /// `csv` or `json`.
+  pub format: &'static str,
+  pub label: Option<String>,
+  pub units: BTreeMap<String, String>,
+  /// Series id -> `label`, `currency`, `region`, `variant`, `source`,
+  /// `license` (JSON only).
+  pub series: Value,
+  pub rows: Vec<DatasetRow>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UserDatasetSummary {
+  pub dataset_id: String,
+  pub label: String,
+  pub imported_at: String,
+  pub fingerprint: String,
+  pub start_year: i64,
+  pub end_year: i64,
+  /// Years in an estimated or fallback-zero segment of any series.
+  pub flagged_years: usize,
+  pub source: Value,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct UserDatasetImport {
+  pub summary: UserDatasetSummary,
+  /// `UserHistoricalDatasetV1` with manifest and records.
+  pub dataset: Value,
+  pub written: bool,
+  /// A dataset with the same fingerprint was stored before.
+  pub replaced: bool,
+}
+
+fn invalid_dataset(message: impl Into<String>) -> QuoteFailure {
+  quote_failure("USER_DATASET_INVALID", message, 400)
+}
+
+fn invalid_unit(message: impl Into<String>) -> QuoteFailure {
+  quote_failure("USER_DATASET_UNIT_INVALID", message, 400)
+}
+
+fn column_series(header: &str) -> Option<&'static str> {
+  SERIES_IDS
+    .iter()
+    .chain(std::iter::once(&MSCI_RETURN))
+    .find(|series| normalize_header(series) == header)
+    .copied()
+}
+
+fn parse_quality(raw: &str, row: &str) -> Result<Option<String>, QuoteFailure> {
+  let quality = raw.trim().to_ascii_lowercase();
+  if quality.is_empty() {
+    return Ok(None);
+  }
+  QUALITY_STATUSES
+    .iter()
+    .find(|status| **status == quality)
+    .map(|status| Some(status.to_string()))
+    .ok_or_else(|| invalid_dataset(format!("{}: Qualitaet {} unbekannt ({}).", row, raw.trim(), QUALITY_STATUSES.join(", "))))
+}
+
+fn push_row(rows: &mut Vec<DatasetRow>, row: DatasetRow, label: &str) -> Result<(), QuoteFailure> {
+  if rows.iter().any(|existing| existing.year == row.year) {
+    return Err(invalid_dataset(format!("{}: Jahr {} doppelt.", label, row.year)));
+  }
+  rows.push(row);
+  Ok(())
+}
+
+/// Header `jahr` plus any of the series columns and `msci_return`, and an
+/// optional `qualitaet`/`quality` column per row.
+pub(crate) fn parse_csv_dataset(content: &str) -> Result<ParsedDataset, QuoteFailure> {
+  let mut parsed = ParsedDataset { format: "csv", series: Value::Null, ..Default::default() };
+  let mut lines = content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+  let Some((_, header_line)) = lines.next() else { return Err(invalid_dataset("Datei ist leer.")) };
+  let delimiter = detect_delimiter(header_line);
+  let header: Vec<String> = split_line(header_line, delimiter)
+    .map_err(invalid_dataset)?
+    .iter()
+    .map(|cell| normalize_header(cell))
+    .collect();
+  let year_column = header
+    .iter()
+    .position(|cell| cell == "jahr" || cell == "year")
+    .ok_or_else(|| invalid_dataset("CSV ohne Spalte jahr."))?;
+  let quality_column = header.iter().position(|cell| cell == "qualitaet" || cell == "quality");
+  let columns: Vec<(usize, &str)> = header
+    .iter()
+    .enumerate()
+    .filter_map(|(index, cell)| column_series(cell).map(|series| (index, series)))
+    .collect();
+  for (index, line) in lines {
+    let label = format!("Zeile {}", index + 1);
+    let cells = split_line(line, delimiter).map_err(|e| invalid_dataset(format!("{}: {}", label, e)))?;
+    let year = cells
+      .get(year_column)
+      .and_then(|cell| cell.trim().parse::<i32>().ok())
+      .ok_or_else(|| invalid_dataset(format!("{}: Jahr fehlt.", label)))?;
+    let mut row = DatasetRow { year, ..Default::default() };
+    for &(column, series) in &columns {
+      let raw = cells.get(column).map(|cell| cell.trim()).unwrap_or("");
+      if raw.is_empty() {
+        continue;
+      }
+      let value = parse_decimal(raw).ok_or_else(|| invalid_dataset(format!("{}: {} ist keine Zahl.", label, series)))?;
+      set_value(&mut row, series, value);
+    }
+    if let Some(column) = quality_column {
+      row.quality = parse_quality(cells.get(column).map(String::as_str).unwrap_or(""), &label)?;
+    }
+    push_row(&mut parsed.rows, row, &label)?;
+  }
+  Ok(parsed)
+}
+
+fn set_value(row: &mut DatasetRow, series: &str, value: f64) {
+  if series == MSCI_RETURN {
+    row.msci_return = Some(value);
+  } else {
+    row.values.insert(series.to_string(), value);
+  }
+}
+
+fn json_row(year: i32, entry: &Value, label: &str) -> Result<DatasetRow, QuoteFailure> {
+  let fields = entry.as_object().ok_or_else(|| invalid_dataset(format!("{}: kein Objekt.", label)))?;
+  let mut row = DatasetRow { year, ..Default::default() };
+  for (key, value) in fields {
+    if let Some(series) = column_series(&normalize_header(key)) {
+      let number = match value {
+        Value::Null => continue,
+        Value::String(raw) => parse_decimal(raw),
+        other => other.as_f64(),
+      };
+      set_value(&mut row, series, number.ok_or_else(|| invalid_dataset(format!("{}: {} ist keine Zahl.", label, series)))?);
+    } else if matches!(normalize_header(key).as_str(), "quality" | "qualitaet") {
+      row.quality = parse_quality(value.as_str().unwrap_or(""), label)?;
+    }
+  }
+  Ok(row)
+}
+
+/// `{ label?, units?, series?, records }` with `records` keyed by year or a
+/// list of rows with a `year` field; a bare list of rows works as well.
+pub(crate) fn parse_json_dataset(content: &str) -> Result<ParsedDataset, QuoteFailure> {
+  let document: Value = serde_json::from_str(content).map_err(|e| invalid_dataset(format!("Kein gueltiges JSON: {}", e)))?;
+  let records = if document.is_array() { &document } else { &document["records"] };
+  let mut parsed = ParsedDataset {
+    format: "json",
+    label: document.get("label").and_then(Value::as_str).map(str::to_string),
+    series: document.get("series").cloned().unwrap_or(Value::Null),
+    ..Default::default()
+  };
+  if let Some(units) = document.get("units").and_then(Value::as_object) {
+    for (series, unit) in units {
+      let unit = unit.as_str().ok_or_else(|| invalid_unit(format!("{}: Einheit muss Text sein.", series)))?;
+      parsed.units.insert(series.clone(), unit.to_string());
+    }
+  }
+  match records {
+    Value::Object(by_year) => {
+      for (key, entry) in by_year {
+        let year = key.trim().parse::<i32>().map_err(|_| invalid_dataset(format!("Ungueltiges Jahr {}.", key)))?;
+        push_row(&mut parsed.rows, json_row(year, entry, key)?, key)?;
+      }
+    }
+    Value::Array(rows) => {
+      for (index, entry) in rows.iter().enumerate() {
+        let label = format!("Eintrag {}", index + 1);
+        let year = ["year", "jahr"]
+          .iter()
+          .find_map(|field| entry.get(*field).and_then(Value::as_i64))
+          .and_then(|year| i32::try_from(year).ok())
+          .ok_or_else(|| invalid_dataset(format!("{}: Jahr fehlt.", label)))?;
+        push_row(&mut parsed.rows, json_row(year, entry, &label)?, &label)?;
+      }
+    }
+    _ => return Err(invalid_dataset("JSON ohne records.")),
+  }
+  Ok(parsed)
+}
+
+fn default_unit(series: &str) -> &'static str {
+  match series {
+    "msci_eur" => "index_level",
+    "cape" => "ratio",
+    _ => "percent_per_year",
+  }
+}
+
+/// Converts every series to the unit of simulator-data.js. Rates stated in
+/// no unit but all below one look like fractions (0,02 for 2 %) and must be
+/// declared explicitly.
+pub(crate) fn normalize_units(parsed: &mut ParsedDataset) -> Result<(), QuoteFailure> {
+  let known: Vec<&str> = SERIES_IDS.iter().copied().chain(std::iter::once(MSCI_RETURN)).collect();
+  if let Some(unknown) = parsed.units.keys().find(|series| !known.contains(&series.as_str())) {
+    return Err(invalid_unit(format!("Einheit fuer unbekannte Reihe {}.", unknown)));
+  }
+  for series in known {
+    let target = default_unit(series);
+    let declared = parsed.units.get(series).map(String::as_str);
+    let factor = match declared.unwrap_or(target) {
+      unit if unit == target => 1.0,
+      "fraction_per_year" if target == "percent_per_year" => 100.0,
+      unit => {
+        return Err(invalid_unit(format!("{}: Einheit {} wird nicht unterstuetzt (erwartet {}).", series, unit, target)));
+      }
+    };
+    let values: Vec<f64> = parsed
+      .rows
+      .iter()
+      .filter_map(|row| if series == MSCI_RETURN { row.msci_return } else { row.values.get(series).copied() })
+      .collect();
+    if declared.is_none()
+      && target == "percent_per_year"
+      && values.len() >= FRACTION_CHECK_MIN_ROWS
+      && values.iter().all(|value| value.abs() < 1.0)
+      && values.iter().any(|value| *value != 0.0)
+    {
+      return Err(invalid_unit(format!(
+        "{}: alle Werte unter 1, vermutlich Anteile; Einheit percent_per_year oder fraction_per_year angeben.",
+        series
+      )));
+    }
+    for row in &mut parsed.rows {
+      let value = if series == MSCI_RETURN { row.msci_return.as_mut() } else { row.values.get_mut(series) };
+      if let Some(value) = value {
+        *value *= factor;
+        if matches!(series, "gold_eur_perf" | MSCI_RETURN) && *value <= -100.0 {
+          return Err(invalid_unit(format!("{}: {} von {} % ist kein Jahresertrag.", row.year, series, value)));
+        }
+      }
+    }
+  }
+  Ok(())
+}
+
+pub(crate) fn parse_import(request: &UserDatasetImportRequest) -> Result<ParsedDataset, QuoteFailure> {
+  let format = match request.format.as_deref().map(str::trim).filter(|format| !format.is_empty()) {
+    Some(format) => format.to_ascii_lowercase(),
+    None if matches!(request.content.trim_start().chars().next(), Some('{' | '[')) => "json".to_string(),
+    None => "csv".to_string(),
+  };
+  let mut parsed = match format.as_str() {
+    "csv" => parse_csv_dataset(&request.content)?,
+    "json" => parse_json_dataset(&request.content)?,
+    other => return Err(invalid_dataset(format!("Format {} wird nicht unterstuetzt (csv, json).", other))),
+  };
+  parsed.units.extend(request.units.clone());
+  if let Some(label) = request.label.as_deref().map(str::trim).filter(|label| !label.is_empty()) {
+    parsed.label = Some(label.to_string());
+  }
+  if parsed.label.as_deref().map(str::trim).unwrap_or("").is_empty() {
+    return Err(invalid_dataset("Bezeichnung fehlt."));
+  }
+  normalize_units(&mut parsed)?;
+  parsed.rows.sort_by_key(|row| row.year);
+  Ok(parsed)
+}
+
+pub(crate) fn summarize(dataset: &Value) -> UserDatasetSummary {
+  let text = |pointer: &str| dataset.pointer(pointer).and_then(Value::as_str).unwrap_or("").to_string();
+  let mut flagged = std::collections::BTreeSet::new();
+  if let Some(series) = dataset.pointer("/manifest/series").and_then(Value::as_object) {
+    for entry in series.values() {
+      let segments = entry["estimatedSegments"].as_array().into_iter().flatten();
+      let zeros = entry["missingness"]["fallbackZeroSegments"].as_array().into_iter().flatten();
+      for segment in segments.chain(zeros) {
+        let (start, end) = (segment["startYear"].as_i64().unwrap_or(0), segment["endYear"].as_i64().unwrap_or(-1));
+        flagged.extend(start..=end);
+      }
+    }
+  }
+  UserDatasetSummary {
+    dataset_id: text("/datasetId"),
+    label: text("/label"),
+    imported_at: text("/importedAt"),
+    fingerprint: text("/fingerprint/value"),
+    start_year: dataset.pointer("/manifest/period/startYear").and_then(Value::as_i64).unwrap_or(0),
+    end_year: dataset.pointer("/manifest/period/endYear").and_then(Value::as_i64).unwrap_or(0),
+    flagged_years: flagged.len(),
+    source: dataset.get("source").cloned().unwrap_or(Value::Null),
+  }
+}
+
+/// Validates the import on `source`, which must be the plain bundled
+/// sources, and stores it in `dir` when requested.
+pub(crate) fn import_user_dataset_with(
+  source: &ScriptSource,
+  dir: &Path,
+  request: &UserDatasetImportRequest,
+  imported_at: &str,
+) -> Result<UserDatasetImport, JobFailure> {
+  let parsed = parse_import(request).map_err(|error| failure(error.code, error.message))?;
+  let host_request = serde_json::json!({
+    "label": parsed.label,
+    "units": parsed.units,
+    "series": parsed.series,
+    "rows": parsed.rows,
+    "importedAt": imported_at,
+    "source": { "fileName": request.file_name, "format": parsed.format },
+  });
+  let dataset = call_dataset_host(source, "importDataset", host_request.to_string(), "USER_DATASET_FAILED")?;
+  let summary = summarize(&dataset);
+  let path = dir.join(format!("{}.json", summary.dataset_id));
+  let replaced = path.is_file();
+  if request.apply {
+    write_dataset(&path, &dataset)?;
+  }
+  Ok(UserDatasetImport { summary, dataset, written: request.apply, replaced })
+}
+
+fn write_dataset(path: &Path, dataset: &Value) -> Result<(), JobFailure> {
+  let content = serde_json::to_string_pretty(dataset).map_err(|e| failure("USER_DATASET_FAILED", e.to_string()))?;
+  crate::write_atomic(path, &content).map_err(|e| failure("USER_DATASET_FAILED", e.to_string()))
+}
+
+pub(crate) fn read_dataset(path: &Path) -> Option<Value> {
+  fs::read_to_string(path)
+    .ok()
+    .and_then(|raw| serde_json::from_str::<Value>(&raw).ok())
+    .filter(|dataset| dataset["schemaVersion"] == DATASET_SCHEMA_VERSION)
+}
+
+/// Stored datasets, oldest import first.
+pub(crate) fn list_datasets(dir: &Path) -> Vec<UserDatasetSummary> {
+  let mut summaries: Vec<UserDatasetSummary> = fs::read_dir(dir)
+    .into_iter()
+    .flatten()
+    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+    .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("json"))
+    .filter_map(|path| read_dataset(&path))
+    .map(|dataset| summarize(&dataset))
+    .collect();
+  summaries.sort_by(|a, b| (&a.imported_at, &a.dataset_id).cmp(&(&b.imported_at, &b.dataset_id)));
+  summaries
+}
+
+/// Ids are `user-` plus twelve hex digits of the fingerprint; anything else
+/// never names a file in the library.
+fn dataset_path(dir: &Path, dataset_id: &str) -> Result<PathBuf, JobFailure> {
+  let valid = dataset_id
+    .strip_prefix("user-")
+    .is_some_and(|hash| hash.len() == 12 && hash.chars().all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c)));
+  if !valid {
+    return Err(failure("USER_DATASET_NOT_FOUND", format!("Unbekannter Datensatz {}.", dataset_id)));
+  }
+  Ok(dir.join(format!("{}.json", dataset_id)))
+}
+
+/// Loads a stored dataset and re-validates it against the contract, so a
+/// file edited after the import never reaches a run.
+pub(crate) fn load_checked_dataset(source: &ScriptSource, dir: &Path, dataset_id: &str) -> Result<Value, JobFailure> {
+  let dataset = read_dataset(&dataset_path(dir, dataset_id)?)
+    .ok_or_else(|| failure("USER_DATASET_NOT_FOUND", format!("Unbekannter Datensatz {}.", dataset_id)))?;
+  check_dataset(source, &dataset)?;
+  Ok(dataset)
+}
+
+/// Id of the picked dataset, if any.
+pub(crate) fn selected_dataset_id(dir: &Path) -> Option<String> {
+  fs::read_to_string(dir.join(SELECTION_FILENAME))
+    .ok()
+    .and_then(|raw| serde_json::from_str::<Value>(&raw).ok())
+    .and_then(|selection| selection["datasetId"].as_str().map(str::to_string))
+}
+
+/// Picks `dataset_id` after checking it, or goes back to the embedded
+/// dataset for `None`.
+pub(crate) fn select_dataset_with(
+  source: &ScriptSource,
+  dir: &Path,
+  dataset_id: Option<&str>,
+) -> Result<Option<UserDatasetSummary>, JobFailure> {
+  let Some(dataset_id) = dataset_id.map(str::trim).filter(|id| !id.is_empty()) else {
+    return match fs::remove_file(dir.join(SELECTION_FILENAME)) {
+      Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(failure("USER_DATASET_FAILED", error.to_string())),
+      _ => Ok(None),
+    };
+  };
+  let dataset = load_checked_dataset(source, dir, dataset_id)?;
+  let selection = serde_json::json!({ "datasetId": dataset_id }).to_string();
+  crate::write_atomic(&dir.join(SELECTION_FILENAME), &selection).map_err(|e| failure("USER_DATASET_FAILED", e.to_string()))?;
+  Ok(Some(summarize(&dataset)))
+}
+
+/// The picked dataset, checked again like before a native run.
+pub(crate) fn load_selected_dataset(source: &ScriptSource, dir: &Path) -> Result<Option<Value>, JobFailure> {
+  selected_dataset_id(dir).map(|dataset_id| load_checked_dataset(source, dir, &dataset_id)).transpose()
+}
+
+pub(crate) fn check_dataset(source: &ScriptSource, dataset: &Value) -> Result<(), JobFailure> {
+  call_dataset_host(source, "checkDataset", dataset.to_string(), "USER_DATASET_FAILED").map(|_| ())
+}
+
+/// `source` with `dataset` injected into simulator-data.js in place of the
+/// embedded dataset and its overlay.
+pub(crate) fn dataset_source(source: ScriptSource, dataset: &Value) -> ScriptSource {
+  let prelude = format!("globalThis.{} = {};", DATASET_GLOBAL, dataset);
+  Arc::new(move |name: &str| {
+    let code = source(name)?;
+    Some(if name == SIMULATOR_DATA_MODULE { format!("{}{}", prelude, code) } else { code })
+  })
+}
+
+fn datasets_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+  Ok(app.path().app_data_dir().map_err(|e| e.to_string())?.join(DATASETS_DIR))
+}
+
+/// Engine sources for a native run: the named user dataset, else the one
+/// the simulator picked, else the app sources with the overlay.
+pub(crate) fn run_source(app: &tauri::AppHandle, dataset_id: Option<&str>) -> Result<ScriptSource, String> {
+  let dir = datasets_dir(app)?;
+  let dataset_id = match dataset_id.map(str::trim).filter(|id| !id.is_empty()) {
+    Some(dataset_id) => Some(dataset_id.to_string()),
+    None => selected_dataset_id(&dir),
+  };
+  let Some(dataset_id) = dataset_id else {
+    return Ok(crate::script_runtime::app_source(app));
+  };
+  let source = crate::script_runtime::bundled_source(app);
+  let dataset = load_checked_dataset(&source, &dir, &dataset_id).map_err(|e| e.to_string())?;
+  Ok(dataset_source(source, &dataset))
+}
+
+#[tauri::command]
+pub async fn import_user_dataset(app: tauri::AppHandle, request: UserDatasetImportRequest) -> Result<UserDatasetImport, String> {
+  let source = crate::script_runtime::bundled_source(&app);
+  let dir = datasets_dir(&app)?;
+  let imported_at = civil_from_days((crate::unix_now_seconds() / SECONDS_PER_DAY) as i64);
+  tauri::async_runtime::spawn_blocking(move || import_user_dataset_with(&source, &dir, &request, &imported_at))
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_user_datasets(app: tauri::AppHandle) -> Result<Vec<UserDatasetSummary>, String> {
+  Ok(list_datasets(&datasets_dir(&app)?))
+}
+
+/// Picks the dataset for the simulator window and native runs; `None` goes
+/// back to the embedded dataset. Takes effect when the window reloads.
+#[tauri::command]
+pub async fn select_user_dataset(app: tauri::AppHandle, dataset_id: Option<String>) -> Result<Option<UserDatasetSummary>, String> {
+  let source = crate::script_runtime::bundled_source(&app);
+  let dir = datasets_dir(&app)?;
+  tauri::async_runtime::spawn_blocking(move || select_dataset_with(&source, &dir, dataset_id.as_deref()))
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn load_selected_user_dataset(app: tauri::AppHandle) -> Result<Option<Value>, String> {
+  let source = crate::script_runtime::bundled_source(&app);
+  let dir = datasets_dir(&app)?;
+  tauri::async_runtime::spawn_blocking(move || load_selected_dataset(&source, &dir))
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+/// `false` if there was no such dataset. A picked dataset that is removed
+/// is no longer picked.
+#[tauri::command]
+pub fn remove_user_dataset(app: tauri::AppHandle, dataset_id: String) -> Result<bool, String> {
+  let dir = datasets_dir(&app)?;
+  let path = dataset_path(&dir, &dataset_id).map_err(|e| e.to_string())?;
+  if selected_dataset_id(&dir).as_deref() == Some(dataset_id.as_str()) {
+    let _ = fs::remove_file(dir.join(SELECTION_FILENAME));
+  }
+  match fs::remove_file(&path) {
+    Ok(()) => Ok(true),
+    Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(false),
+    Err(error) => Err(error.to_string()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::script_runtime::{checkout_root, directory_source};
+  use serde_json::json;
+
+  fn csv_request(content: &str) -> UserDatasetImportRequest {
+    UserDatasetImportRequest { label: Some("DAX-lastig".to_string()), content: content.to_string(), ..Default::default() }
+  }
+
+  /// Contiguous rows with constant values, index returns instead of levels.
+  fn csv_rows(years: std::ops::RangeInclusive<i32>, cape: &str) -> String {
+    let mut csv = String::from("Jahr;MSCI_Return;Inflation_DE;Zinssatz_DE;Lohn_DE;Gold_EUR_Perf;CAPE;Qualitaet\n");
+    for year in years {
+      let quality = if year < 1992 { "estimated" } else { "" };
+      csv.push_str(&format!("{};6,5;2,0;3,0;2,5;0;{};{}\n", year, cape, quality));
+    }
+    csv
+  }
+
+  #[test]
+  fn imports_are_converted_to_simulator_units() {
+    let parsed = parse_import(&csv_request("jahr,msci_eur,inflation_de,qualitaet\n2001,110,2.5,\n2000,100,1.5,Estimated\n")).unwrap();
+    assert_eq!((parsed.format, parsed.label.as_deref()), ("csv", Some("DAX-lastig")));
+    assert_eq!(parsed.rows.iter().map(|row| row.year).collect::<Vec<_>>(), vec![2000, 2001]);
+    assert_eq!(parsed.rows[0].quality.as_deref(), Some("estimated"));
+    assert_eq!((parsed.rows[1].values["msci_eur"], parsed.rows[1].values["inflation_de"]), (110.0, 2.5));
+
+    let json = r#"{ "label": "60/40", "units": { "inflation_de": "fraction_per_year", "msci_return": "fraction_per_year" },
+      "records": { "2000": { "msci_return": 0.05, "inflation_de": "0,021", "quality": "present" }, "2001": { "MSCI_Return": -0.1 } } }"#;
+    let parsed = parse_import(&UserDatasetImportRequest { content: json.to_string(), ..Default::default() }).unwrap();
+    assert_eq!((parsed.format, parsed.label.as_deref()), ("json", Some("60/40")));
+    assert_eq!(parsed.rows[0].msci_return, Some(5.0));
+    assert!((parsed.rows[0].values["inflation_de"] - 2.1).abs() < 1e-9);
+    assert_eq!(parsed.rows[1].msci_return, Some(-10.0));
+    let rows = r#"[{ "year": 2000, "cape": 20 }, { "jahr": 2001, "cape": 21 }]"#;
+    let parsed = parse_import(&UserDatasetImportRequest { content: rows.to_string(), label: Some("x".into()), ..Default::default() }).unwrap();
+    assert_eq!(parsed.rows[1].values["cape"], 21.0);
+
+    // Undeclared fractions, unknown units and qualities, duplicates.
+    let fractions = "jahr;inflation_de\n2000;0,02\n2001;0,03\n2002;0,01\n2003;0,02\n2004;0,025\n";
+    let error = parse_import(&csv_request(fractions)).unwrap_err();
+    assert_eq!(error.code, "USER_DATASET_UNIT_INVALID");
+    assert!(error.message.starts_with("inflation_de: alle Werte unter 1"), "{}", error.message);
+    let declared = UserDatasetImportRequest {
+      units: BTreeMap::from([("inflation_de".to_string(), "percent_per_year".to_string())]),
+      ..csv_request(fractions)
+    };
+    assert_eq!(parse_import(&declared).unwrap().rows[0].values["inflation_de"], 0.02);
+    let cape_percent = UserDatasetImportRequest {
+      units: BTreeMap::from([("cape".to_string(), "percent_per_year".to_string())]),
+      ..csv_request("jahr;cape\n2000;20\n")
+    };
+    assert_eq!(parse_import(&cape_percent).unwrap_err().code, "USER_DATASET_UNIT_INVALID");
+    let crash = parse_import(&csv_request("jahr;gold_eur_perf\n2000;-100\n")).unwrap_err();
+    assert_eq!(crash.code, "USER_DATASET_UNIT_INVALID");
+    let error = parse_import(&csv_request("jahr;cape;quality\n2000;20;missing\n")).unwrap_err();
+    assert!(error.message.starts_with("Zeile 2: Qualitaet missing unbekannt"), "{}", error.message);
+    let error = parse_import(&csv_request("jahr;cape\n2000;20\n2000;21\n")).unwrap_err();
+    assert_eq!(error.message, "Zeile 3: Jahr 2000 doppelt.");
+    let unnamed = UserDatasetImportRequest { content: "jahr;cape\n2000;20\n".to_string(), ..Default::default() };
+    assert_eq!(parse_import(&unnamed).unwrap_err().message, "Bezeichnung fehlt.");
+  }
+
+  #[test]
+  fn validated_datasets_are_stored_listed_and_rechecked() {
+    let source = directory_source(checkout_root());
+    let dir = std::env::temp_dir().join(format!("ruhestand-user-datasets-{}", std::process::id()));
+    let request = UserDatasetImportRequest {
+      file_name: Some("dax.csv".to_string()),
+      apply: true,
+      ..csv_request(&csv_rows(1990..=2010, "20"))
+    };
+    let imported = import_user_dataset_with(&source, &dir, &request, "2026-10-18").unwrap();
+    let dataset = &imported.dataset;
+    let id = imported.summary.dataset_id.clone();
+    assert!(id.starts_with("user-") && id.len() == 17, "{}", id);
+    assert_eq!((imported.written, imported.replaced), (true, false));
+    assert_eq!(dataset["manifest"]["datasetId"], json!(id));
+    assert_eq!(dataset["fingerprint"], dataset["manifest"]["contentHash"]);
+    assert_eq!(dataset["records"]["1990"]["msci_eur"], json!(106.5));
+    assert_eq!(dataset["records"]["1991"]["msci_eur"], json!(113.4225));
+    let cape = &dataset["manifest"]["series"]["cape"];
+    assert_eq!(cape["estimatedSegments"], json!([{ "startYear": 1990, "endYear": 1991 }]));
+    assert_eq!(cape["source"]["value"], "User import: dax.csv");
+    assert_eq!(cape["license"]["status"], "unresolved");
+    // Zero gold returns are unresolved under the embedded zero-value policy.
+    assert_eq!(dataset["manifest"]["series"]["gold_eur_perf"]["missingness"]["zeroValuePolicy"], "unresolved_if_zero");
+    assert_eq!(
+      dataset["manifest"]["series"]["msci_eur"]["transformation"]["value"],
+      "Index levels chained from imported annual returns in percent, base 100 before the first year."
+    );
+    assert_eq!((imported.summary.start_year, imported.summary.end_year, imported.summary.flagged_years), (1990, 2010, 2));
+
+    let listed = list_datasets(&dir);
+    assert_eq!(listed, vec![imported.summary.clone()]);
+    let again = import_user_dataset_with(&source, &dir, &UserDatasetImportRequest { apply: false, ..request }, "2026-10-19").unwrap();
+    assert_eq!((again.summary.dataset_id.as_str(), again.written, again.replaced), (id.as_str(), false, true));
+    assert_eq!(load_checked_dataset(&source, &dir, &id).unwrap(), imported.dataset);
+
+    // The simulator's pick is stored next to the library, which still lists
+    // only datasets.
+    assert_eq!(load_selected_dataset(&source, &dir).unwrap(), None);
+    assert_eq!(select_dataset_with(&source, &dir, Some(&id)).unwrap(), Some(imported.summary.clone()));
+    assert_eq!(selected_dataset_id(&dir), Some(id.clone()));
+    assert_eq!(load_selected_dataset(&source, &dir).unwrap().as_ref(), Some(&imported.dataset));
+    assert_eq!(list_datasets(&dir).len(), 1);
+    let unknown = select_dataset_with(&source, &dir, Some("user-000000000000")).unwrap_err();
+    assert_eq!(unknown.code, "USER_DATASET_NOT_FOUND");
+    assert_eq!(selected_dataset_id(&dir), Some(id.clone()));
+    assert_eq!(select_dataset_with(&source, &dir, None).unwrap(), None);
+    assert_eq!((selected_dataset_id(&dir), select_dataset_with(&source, &dir, Some(" ")).unwrap()), (None, None));
+    select_dataset_with(&source, &dir, Some(&id)).unwrap();
+
+    // Edited files and foreign ids never reach a run.
+    let mut tampered = imported.dataset.clone();
+    tampered["records"]["2000"]["cape"] = json!(25.0);
+    write_dataset(&dir.join(format!("{}.json", id)), &tampered).unwrap();
+    let error = load_checked_dataset(&source, &dir, &id).unwrap_err();
+    assert_eq!(error.code, "HISTORICAL_DATASET_INVALID");
+    assert!(error.message.contains("HISTORICAL_DATA_HASH_MISMATCH"), "{}", error.message);
+    assert_eq!(load_selected_dataset(&source, &dir).unwrap_err().code, "HISTORICAL_DATASET_INVALID");
+    assert_eq!(load_checked_dataset(&source, &dir, "../x").unwrap_err().code, "USER_DATASET_NOT_FOUND");
+    let _ = fs::remove_dir_all(&dir);
+
+    let mut gap = csv_rows(1990..=1995, "20");
+    gap.push_str("1997;6,5;2,0;3,0;2,5;0;20;\n");
+    let error = import_user_dataset_with(&source, &dir, &csv_request(&gap), "2026-10-18").unwrap_err();
+    assert_eq!((error.code, error.message.as_str()), ("USER_DATASET_INVALID", "1997 schliesst nicht an 1995 an."));
+    let short = import_user_dataset_with(&source, &dir, &csv_request(&csv_rows(1990..=1993, "20")), "2026-10-18").unwrap_err();
+    assert_eq!(short.code, "USER_DATASET_INVALID");
+    let contract = import_user_dataset_with(&source, &dir, &csv_request(&csv_rows(1990..=2000, "0")), "2026-10-18").unwrap_err();
+    assert_eq!(contract.code, "HISTORICAL_DATASET_INVALID");
+    assert!(contract.message.contains("HISTORICAL_INDEX_LEVEL_INVALID"), "{}", contract.message);
+    let missing = import_user_dataset_with(&source, &dir, &csv_request("jahr;cape\n1990;20\n1991;20\n1992;20\n1993;20\n1994;20\n"), "2026-10-18").unwrap_err();
+    assert!(missing.message.contains("1990: msci_eur fehlt."), "{}", missing.message);
+    assert!(!dir.exists());
+  }
+
+  #[test]
+  fn backtests_on_a_user_dataset_carry_its_provenance() {
+    let source = directory_source(checkout_root());
+    let dir = std::env::temp_dir().join(format!("ruhestand-user-dataset-run-{}", std::process::id()));
+    let imported = import_user_dataset_with(&source, &dir, &csv_request(&csv_rows(1990..=2010, "20")), "2026-10-18").unwrap();
+    let fixture: Value = serde_json::from_str(
+      &fs::read_to_string(checkout_root().join("tests/fixtures/simulator-backtest-target-v1.json")).unwrap(),
+    )
+    .unwrap();
+    let case = &fixture["cases"][0];
+    let request = json!({ "schemaVersion": "BacktestRequestV1", "startYear": 2000, "endYear": 2005, "inputs": case["inputs"] });
+
+    let output = crate::backtest::run_backtest(&dataset_source(source.clone(), &imported.dataset), &request, None).unwrap();
+    let export: Value = serde_json::from_str(&output.export).unwrap();
+    let dataset = &export["request"]["dataset"];
+    assert_eq!(dataset["datasetId"], json!(imported.summary.dataset_id));
+    assert_eq!(dataset["contentHash"], json!(imported.summary.fingerprint));
+    let embedded = crate::backtest::run_backtest(&source, &request, None).unwrap();
+    assert_ne!(output.export, embedded.export);
+
+    // A dataset outside the requested period leaves the run incomplete.
+    let late = json!({ "schemaVersion": "BacktestRequestV1", "startYear": 2015, "endYear": 2020, "inputs": case["inputs"] });
+    let output = crate::backtest::run_backtest(&dataset_source(source, &imported.dataset), &late, None).unwrap();
+    assert_eq!(output.outcome.as_deref(), Some("incomplete"));
+  }
+}
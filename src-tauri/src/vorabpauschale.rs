@@ -0,0 +1,355 @@
+//! Vorabpauschale for accumulating funds (§ 18 InvStG): per tranche, the
+//! Basisertrag (year-start price x 70 % x Basiszins), capped at the year's
+//! Mehrbetrag and reduced by distributions, pro rata for the purchase year.
+//! The taxable part after Teilfreistellung runs through the cent-exact
+//! settlement of tax.rs, and the gross amount is returned as the cost-basis
+//! adjustment that keeps the later sale from taxing it twice (§ 19 InvStG).
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::script_runtime::{job_failure as failure, JobFailure};
+use crate::tax::{settle_tax_year_exact, RawAggregate, Settlement, SettlementInput, TaxStateInput};
+
+/// Share of the Basiszins that counts as Basisertrag.
+const BASISERTRAG_FACTOR: f64 = 0.7;
+
+/// Basiszins published by the BMF each January, as a fraction. Requests can
+/// add or override years via `basiszins`.
+const BASISZINS_TABLE: [(i32, f64); 8] = [
+  (2018, 0.0087),
+  (2019, 0.0052),
+  (2020, 0.0007),
+  (2021, -0.0045),
+  (2022, -0.0005),
+  (2023, 0.0255),
+  (2024, 0.0229),
+  (2025, 0.0253),
+];
+
+/// Teilfreistellung classes of § 20 InvStG.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FundType {
+  Aktienfonds,
+  Mischfonds,
+  Immobilienfonds,
+  AuslandsImmobilienfonds,
+  Sonstige,
+  /// Not an investment fund (single shares, gold ETCs): no Vorabpauschale.
+  None,
+}
+
+impl FundType {
+  fn teilfreistellung(self) -> f64 {
+    match self {
+      FundType::Aktienfonds => 0.30,
+      FundType::Mischfonds => 0.15,
+      FundType::Immobilienfonds => 0.60,
+      FundType::AuslandsImmobilienfonds => 0.80,
+      FundType::Sonstige | FundType::None => 0.0,
+    }
+  }
+
+  /// Tranches without `fundType` are classified from the canonical tranche
+  /// record: gold is never a fund, otherwise `tqf` names the class.
+  fn from_tranche(category: Option<&str>, tqf: Option<f64>) -> FundType {
+    if category == Some("gold") {
+      return FundType::None;
+    }
+    let tqf = tqf.unwrap_or(0.0);
+    [FundType::Aktienfonds, FundType::Mischfonds, FundType::Immobilienfonds, FundType::AuslandsImmobilienfonds]
+      .into_iter()
+      .find(|fund| (fund.teilfreistellung() - tqf).abs() < 1e-9)
+      .unwrap_or(FundType::Sonstige)
+  }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VorabTranche {
+  pub tranche_id: Option<String>,
+  pub name: Option<String>,
+  pub isin: Option<String>,
+  pub category: Option<String>,
+  pub tqf: Option<f64>,
+  pub fund_type: Option<FundType>,
+  pub shares: f64,
+  pub purchase_price: Option<f64>,
+  pub cost_basis: Option<f64>,
+  /// `YYYY-MM-DD`; missing means held before the year started.
+  pub purchase_date: Option<String>,
+  /// Redemption price on the first and last trading day of the year.
+  pub price_year_start: f64,
+  pub price_year_end: f64,
+  #[serde(default)]
+  pub distributions_per_share: f64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VorabpauschaleRequest {
+  pub year: i32,
+  pub tranches: Vec<VorabTranche>,
+  /// Year -> Basiszins as a fraction, on top of the built-in table.
+  #[serde(default)]
+  pub basiszins: BTreeMap<String, f64>,
+  pub sparer_pauschbetrag: Option<f64>,
+  pub kirchensteuer_satz: Option<f64>,
+  pub tax_state_prev: Option<TaxStateInput>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VorabTrancheResult {
+  pub tranche_id: Option<String>,
+  pub name: Option<String>,
+  pub isin: Option<String>,
+  pub fund_type: FundType,
+  pub teilfreistellung: f64,
+  /// Months of the year the tranche counts for (12 unless bought this year).
+  pub months: u32,
+  pub basisertrag: f64,
+  pub mehrbetrag: f64,
+  pub distributions: f64,
+  pub vorabpauschale: f64,
+  pub taxable_after_tqf: f64,
+  pub cost_basis_adjustment: f64,
+  pub cost_basis_after: Option<f64>,
+  pub purchase_price_after: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VorabpauschaleReport {
+  pub year: i32,
+  pub basiszins: f64,
+  /// The Vorabpauschale counts as received on the first working day of the
+  /// following year.
+  pub inflow_year: i32,
+  pub tranches: Vec<VorabTrancheResult>,
+  pub vorabpauschale: f64,
+  pub taxable_after_tqf: f64,
+  /// Same shape the sale engine hands to `settleTaxYear`.
+  pub tax_raw_aggregate: RawAggregateOut,
+  pub settlement: Settlement,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RawAggregateOut {
+  pub sum_realized_gain_signed: f64,
+  pub sum_taxable_after_tqf_signed: f64,
+}
+
+fn invalid(message: impl Into<String>) -> JobFailure {
+  failure("VORAB_INPUT_INVALID", message)
+}
+
+fn to_cents(value: f64) -> i64 {
+  (value * 100.0).round() as i64
+}
+
+fn euros(cents: i64) -> f64 {
+  cents as f64 / 100.0
+}
+
+/// Basiszins from the built-in BMF table.
+pub(crate) fn published_basiszins(year: i32) -> Option<f64> {
+  BASISZINS_TABLE.iter().find(|(known, _)| *known == year).map(|(_, rate)| *rate)
+}
+
+fn basiszins_for(request: &VorabpauschaleRequest) -> Result<f64, JobFailure> {
+  let rate = request
+    .basiszins
+    .get(&request.year.to_string())
+    .copied()
+    .or_else(|| published_basiszins(request.year));
+  match rate {
+    Some(rate) if rate.is_finite() && rate.abs() < 1.0 => Ok(rate),
+    Some(_) => Err(invalid(format!("Basiszins {} muss als Anteil (z. B. 0.0229) angegeben werden.", request.year))),
+    None => Err(failure("VORAB_BASISZINS_MISSING", format!("Kein Basiszins fuer {} hinterlegt.", request.year))),
+  }
+}
+
+/// Months counted for the year: 12, minus each full month before the purchase
+/// month in the purchase year, 0 when bought after the year.
+fn months_held(purchase_date: Option<&str>, year: i32) -> Result<u32, JobFailure> {
+  let Some(date) = purchase_date.filter(|date| !date.trim().is_empty()) else { return Ok(12) };
+  let mut parts = date.trim().splitn(3, '-');
+  let (Some(Ok(bought_year)), Some(Ok(month))) = (parts.next().map(str::parse::<i32>), parts.next().map(str::parse::<u32>)) else {
+    return Err(invalid(format!("purchaseDate {} ist kein Datum im Format JJJJ-MM-TT.", date)));
+  };
+  if !(1..=12).contains(&month) {
+    return Err(invalid(format!("purchaseDate {} ist kein Datum im Format JJJJ-MM-TT.", date)));
+  }
+  Ok(match bought_year.cmp(&year) {
+    std::cmp::Ordering::Less => 12,
+    std::cmp::Ordering::Equal => 13 - month,
+    std::cmp::Ordering::Greater => 0,
+  })
+}
+
+fn tranche_result(tranche: &VorabTranche, year: i32, basiszins: f64) -> Result<VorabTrancheResult, JobFailure> {
+  let numbers = [tranche.shares, tranche.price_year_start, tranche.price_year_end, tranche.distributions_per_share];
+  if numbers.iter().any(|value| !value.is_finite() || *value < 0.0) {
+    return Err(invalid(format!(
+      "Tranche {}: shares, Preise und Ausschuettungen muessen endlich und nicht negativ sein.",
+      tranche.tranche_id.as_deref().unwrap_or("?")
+    )));
+  }
+  let fund_type = tranche.fund_type.unwrap_or_else(|| FundType::from_tranche(tranche.category.as_deref(), tranche.tqf));
+  let months = if fund_type == FundType::None { 0 } else { months_held(tranche.purchase_date.as_deref(), year)? };
+  let shares = tranche.shares;
+  let held = f64::from(months.min(1));
+  let basisertrag = to_cents(tranche.price_year_start * BASISERTRAG_FACTOR * basiszins.max(0.0) * shares * held);
+  let distributions = to_cents(tranche.distributions_per_share * shares * held);
+  let mehrbetrag = to_cents((tranche.price_year_end - tranche.price_year_start) * shares * held) + distributions;
+  // § 18 Abs. 2 InvStG: the purchase year reduces the final amount, after
+  // the cap and the distributions, by one twelfth per month before purchase.
+  let full_year = (basisertrag.min(mehrbetrag.max(0)) - distributions).max(0);
+  let vorabpauschale = to_cents(euros(full_year) * f64::from(months) / 12.0);
+  let teilfreistellung = fund_type.teilfreistellung();
+  let taxable = to_cents(euros(vorabpauschale) * (1.0 - teilfreistellung));
+  let cost_basis = tranche.cost_basis.or_else(|| tranche.purchase_price.map(|price| price * shares));
+  Ok(VorabTrancheResult {
+    tranche_id: tranche.tranche_id.clone(),
+    name: tranche.name.clone(),
+    isin: tranche.isin.clone(),
+    fund_type,
+    teilfreistellung,
+    months,
+    basisertrag: euros(basisertrag),
+    mehrbetrag: euros(mehrbetrag),
+    distributions: euros(distributions),
+    vorabpauschale: euros(vorabpauschale),
+    taxable_after_tqf: euros(taxable),
+    cost_basis_adjustment: euros(vorabpauschale),
+    cost_basis_after: cost_basis.map(|basis| basis + euros(vorabpauschale)),
+    purchase_price_after: tranche
+      .purchase_price
+      .filter(|_| shares > 0.0)
+      .map(|price| price + euros(vorabpauschale) / shares),
+  })
+}
+
+pub(crate) fn calculate(request: &VorabpauschaleRequest) -> Result<VorabpauschaleReport, JobFailure> {
+  let basiszins = basiszins_for(request)?;
+  let tranches = request
+    .tranches
+    .iter()
+    .map(|tranche| tranche_result(tranche, request.year, basiszins))
+    .collect::<Result<Vec<_>, _>>()?;
+  let vorabpauschale: i64 = tranches.iter().map(|tranche| to_cents(tranche.vorabpauschale)).sum();
+  let taxable: i64 = tranches.iter().map(|tranche| to_cents(tranche.taxable_after_tqf)).sum();
+  let settlement = settle_tax_year_exact(&SettlementInput {
+    tax_state_prev: request.tax_state_prev.clone(),
+    raw_aggregate: Some(RawAggregate {
+      sum_realized_gain_signed: Some(0.0),
+      sum_taxable_after_tqf_signed: Some(euros(taxable)),
+      sum_stock_taxable_signed: None,
+    }),
+    sparer_pauschbetrag: request.sparer_pauschbetrag,
+    kirchensteuer_satz: request.kirchensteuer_satz,
+    ..SettlementInput::default()
+  })?;
+  Ok(VorabpauschaleReport {
+    year: request.year,
+    basiszins,
+    inflow_year: request.year + 1,
+    tranches,
+    vorabpauschale: euros(vorabpauschale),
+    taxable_after_tqf: euros(taxable),
+    tax_raw_aggregate: RawAggregateOut { sum_realized_gain_signed: 0.0, sum_taxable_after_tqf_signed: euros(taxable) },
+    settlement,
+  })
+}
+
+#[tauri::command]
+pub fn calculate_vorabpauschale(request: VorabpauschaleRequest) -> Result<VorabpauschaleReport, String> {
+  calculate(&request).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn request(tranches: serde_json::Value) -> VorabpauschaleRequest {
+    serde_json::from_value(json!({ "year": 2024, "tranches": tranches })).unwrap()
+  }
+
+  #[test]
+  fn accumulating_equity_fund_is_taxed_after_teilfreistellung() {
+    let report = calculate(&request(json!([
+      { "trancheId": "etf-2020", "category": "equity", "tqf": 0.3, "shares": 100, "purchasePrice": 80,
+        "purchaseDate": "2020-05-04", "priceYearStart": 100, "priceYearEnd": 110 },
+      { "trancheId": "etf-2024", "category": "equity", "tqf": 0.3, "shares": 200, "purchasePrice": 105,
+        "purchaseDate": "2024-07-01", "priceYearStart": 100, "priceYearEnd": 110 }
+    ])))
+    .unwrap();
+    // 100 x 0.7 x 2.29 % = 1.603 per share; the July purchase counts 6/12.
+    let [first, second] = [&report.tranches[0], &report.tranches[1]];
+    assert_eq!((first.fund_type, first.months, first.vorabpauschale), (FundType::Aktienfonds, 12, 160.3));
+    assert_eq!((second.months, second.vorabpauschale), (6, 160.3));
+    assert_eq!(first.taxable_after_tqf, 112.21);
+    assert_eq!(first.cost_basis_after, Some(8160.3));
+    assert!((second.purchase_price_after.unwrap() - 105.8015).abs() < 1e-9);
+    assert_eq!((report.vorabpauschale, report.taxable_after_tqf, report.inflow_year), (320.6, 224.42, 2025));
+    // 224,42 x 25 % = 56,10 KapESt (truncated), 3,08 Soli.
+    assert_eq!(report.settlement.tax_due, 59.18);
+  }
+
+  #[test]
+  fn losses_distributions_and_negative_basiszins_leave_nothing_to_tax() {
+    let falling = request(json!([{ "shares": 10, "tqf": 0.3, "priceYearStart": 100, "priceYearEnd": 90 }]));
+    assert_eq!(calculate(&falling).unwrap().vorabpauschale, 0.0);
+
+    // Distributions above the Basisertrag use it up; a small rise caps it.
+    let distributing = request(json!([{ "shares": 10, "tqf": 0.15, "priceYearStart": 100, "priceYearEnd": 101, "distributionsPerShare": 2 }]));
+    assert_eq!(calculate(&distributing).unwrap().tranches[0].vorabpauschale, 0.0);
+    let capped = request(json!([{ "shares": 10, "tqf": 0.0, "priceYearStart": 100, "priceYearEnd": 100.5 }]));
+    let capped = calculate(&capped).unwrap();
+    assert_eq!((capped.tranches[0].fund_type, capped.vorabpauschale), (FundType::Sonstige, 5.0));
+
+    let mut negative = request(json!([{ "shares": 10, "tqf": 0.3, "priceYearStart": 100, "priceYearEnd": 150 }]));
+    negative.year = 2021;
+    assert_eq!(calculate(&negative).unwrap().vorabpauschale, 0.0);
+
+    let gold = request(json!([{ "category": "gold", "shares": 10, "priceYearStart": 100, "priceYearEnd": 150 }]));
+    assert_eq!(calculate(&gold).unwrap().tranches[0].fund_type, FundType::None);
+    let later = request(json!([{ "shares": 10, "tqf": 0.3, "purchaseDate": "2025-01-02", "priceYearStart": 100, "priceYearEnd": 150 }]));
+    assert_eq!(calculate(&later).unwrap().tranches[0].months, 0);
+  }
+
+  #[test]
+  fn purchase_year_reduces_the_capped_amount() {
+    // Basisertrag 16.03 is capped at the Mehrbetrag of 10.00, then halved
+    // for the July purchase.
+    let july = request(json!([{ "shares": 10, "tqf": 0.3, "purchaseDate": "2024-07-15", "priceYearStart": 100, "priceYearEnd": 101 }]));
+    let tranche = &calculate(&july).unwrap().tranches[0];
+    assert_eq!((tranche.months, tranche.basisertrag, tranche.mehrbetrag), (6, 16.03, 10.0));
+    assert_eq!((tranche.vorabpauschale, tranche.taxable_after_tqf), (5.0, 3.5));
+
+    // Distributions are deducted before the reduction: (16.03 - 4) x 3/12.
+    let october = request(json!([{ "shares": 10, "tqf": 0.0, "purchaseDate": "2024-10-01", "priceYearStart": 100,
+      "priceYearEnd": 110, "distributionsPerShare": 0.4 }]));
+    assert_eq!(calculate(&october).unwrap().tranches[0].vorabpauschale, 3.01);
+  }
+
+  #[test]
+  fn basiszins_table_can_be_extended_and_inputs_are_checked() {
+    let mut future = request(json!([{ "shares": 1, "tqf": 0.3, "priceYearStart": 100, "priceYearEnd": 120 }]));
+    future.year = 2031;
+    assert_eq!(calculate(&future).unwrap_err().code, "VORAB_BASISZINS_MISSING");
+    future.basiszins.insert("2031".to_string(), 0.03);
+    assert_eq!(calculate(&future).unwrap().vorabpauschale, 2.1);
+    future.basiszins.insert("2031".to_string(), 3.0);
+    assert_eq!(calculate(&future).unwrap_err().code, "VORAB_INPUT_INVALID");
+
+    let bad_date = request(json!([{ "shares": 1, "purchaseDate": "03/2024", "priceYearStart": 1, "priceYearEnd": 1 }]));
+    assert_eq!(calculate(&bad_date).unwrap_err().code, "VORAB_INPUT_INVALID");
+    let negative_shares = request(json!([{ "shares": -1, "priceYearStart": 1, "priceYearEnd": 1 }]));
+    assert_eq!(calculate(&negative_shares).unwrap_err().code, "VORAB_INPUT_INVALID");
+  }
+}
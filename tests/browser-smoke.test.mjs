@@ -198,7 +198,7 @@ async function createPage(browser, label, options = {}) {
                 return;
             }
             if (url.pathname === '/search') {
-                await route.fulfill({ json: { quotes: symbol ? [{ symbol }] : [] } });
+                await route.fulfill({ json: { query: symbol, results: symbol ? [{ symbol, name: symbol, type: 'etf' }] : [] } });
                 return;
             }
             if (url.pathname === '/quote') {
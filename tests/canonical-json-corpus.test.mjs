@@ -0,0 +1,136 @@
+import fs from 'node:fs';
+import path from 'node:path';
+import { fileURLToPath } from 'node:url';
+
+import { canonicalizeHistoricalContractValue, sha256Hex } from '../app/simulator/historical-backtest-contract.js';
+
+// Corpus of canonical JSON strings and SHA-256 fingerprints produced by the
+// JS contract functions under V8. src-tauri/src/monte_carlo_export.rs
+// verifies exports without a JS runtime and checks its canonicalization
+// against every entry, so the two implementations cannot drift unnoticed.
+// Regenerate after intended changes with UPDATE_CANONICAL_CORPUS=1.
+
+const __filename = fileURLToPath(import.meta.url);
+const __dirname = path.dirname(__filename);
+const corpusPath = path.join(__dirname, 'fixtures', 'canonical-json-corpus-v1.json');
+const UPDATE_CORPUS = process.env.UPDATE_CANONICAL_CORPUS === '1';
+
+console.log('--- Canonical JSON Corpus Tests ---');
+
+// xorshift32, so the corpus is reproducible without Math.random.
+function createRandom(seed) {
+    let state = seed >>> 0;
+    return () => {
+        state ^= state << 13;
+        state >>>= 0;
+        state ^= state >>> 17;
+        state ^= state << 5;
+        state >>>= 0;
+        return state;
+    };
+}
+
+const next = createRandom(0x5eed1234);
+const view = new DataView(new ArrayBuffer(8));
+
+function randomBitsDouble() {
+    for (;;) {
+        view.setUint32(0, next());
+        view.setUint32(4, next());
+        const value = view.getFloat64(0);
+        if (Number.isFinite(value)) return value;
+    }
+}
+
+function randomScaledDouble() {
+    const exponent = (next() % 60) - 30;
+    const mantissa = next() / 0x100000000;
+    const sign = next() % 2 === 0 ? 1 : -1;
+    return sign * mantissa * 10 ** exponent;
+}
+
+function randomDecimal() {
+    const digits = next() % 7;
+    return (next() % 20000000 - 10000000) / 10 ** digits;
+}
+
+function randomString() {
+    const pool = ['a', 'Z', '0', ' ', '"', '\\', '/', '\n', '\t', '\u0001', '\u001f', '\u007f', 'ä', 'ß', '€', ' ', '', '😀'];
+    let text = '';
+    const length = next() % 12;
+    for (let index = 0; index < length; index++) text += pool[next() % pool.length];
+    return text;
+}
+
+function randomKey() {
+    switch (next() % 5) {
+        case 0: return String(next() % 1000);
+        case 1: return `0${next() % 10}`;
+        case 2: return String(next());
+        default: return randomString();
+    }
+}
+
+function randomValue(depth) {
+    const kind = next() % (depth > 3 ? 6 : 8);
+    switch (kind) {
+        case 0: return null;
+        case 1: return next() % 2 === 0;
+        case 2: return randomBitsDouble();
+        case 3: return randomScaledDouble();
+        case 4: return randomDecimal();
+        case 5: return randomString();
+        case 6: return Array.from({ length: next() % 5 }, () => randomValue(depth + 1));
+        default: {
+            const object = {};
+            const size = next() % 6;
+            for (let index = 0; index < size; index++) object[randomKey()] = randomValue(depth + 1);
+            return object;
+        }
+    }
+}
+
+const boundaryNumbers = [
+    0, -0, 1, -1, 0.1, 0.2, 0.30000000000000004, 1 / 3, 2 / 3, 100, 1e6,
+    1e20, 1e21, 1e21 - 65536, 123456789012345680000, 2 ** 53, 2 ** 53 + 2, 2 ** 64,
+    1e-6, 1e-7, 0.000001234, 0.0000001234, 1.5e-7, 5e-324, 2.2250738585072014e-308,
+    Number.MAX_VALUE, Number.EPSILON, Number.MIN_SAFE_INTEGER, 1.7976931348623157e308,
+    9007199254740993, 0.1 + 0.7, 4.35, 1.005, 12345.678901234567
+];
+
+function buildEntries() {
+    const entries = [];
+    const add = value => {
+        const canonical = canonicalizeHistoricalContractValue(value);
+        entries.push({ input: JSON.stringify(value), canonical, sha256: sha256Hex(canonical) });
+    };
+    add(boundaryNumbers);
+    for (let batch = 0; batch < 20; batch++) {
+        add(Array.from({ length: 50 }, () => randomBitsDouble()));
+        add(Array.from({ length: 50 }, () => randomScaledDouble()));
+        add(Array.from({ length: 50 }, () => randomDecimal()));
+    }
+    for (let index = 0; index < 200; index++) add(randomValue(0));
+    add({ b: [1.5e300, -0, 1e-7, '\u0001\n"', null, true], 10: 2, 2: 3, a: { '': 1, '😀': 2 }, 0: 0.1, '01': 4 });
+    add({ '': 1, ' ': 2, 4294967295: 3, 4294967296: 4, '-1': 5, '1.5': 6 });
+    add('x'.repeat(200) + '€'.repeat(40));
+    return entries;
+}
+
+const actual = buildEntries();
+
+if (UPDATE_CORPUS) {
+    fs.writeFileSync(corpusPath, `${JSON.stringify({ schemaVersion: 'CanonicalJsonCorpusV1', entries: actual }, null, 1)}\n`, 'utf8');
+    console.log(`Updated ${path.relative(path.join(__dirname, '..'), corpusPath)}`);
+}
+
+const corpus = JSON.parse(fs.readFileSync(corpusPath, 'utf8'));
+assertEqual(corpus.entries.length, actual.length, 'corpus size matches the generator');
+let mismatches = 0;
+for (const [index, entry] of corpus.entries.entries()) {
+    const canonical = canonicalizeHistoricalContractValue(JSON.parse(entry.input));
+    if (canonical !== entry.canonical || sha256Hex(canonical) !== entry.sha256 || entry.input !== actual[index].input) mismatches++;
+}
+assertEqual(mismatches, 0, 'JS canonicalization reproduces every corpus entry');
+
+console.log('✅ Canonical JSON corpus tests passed');
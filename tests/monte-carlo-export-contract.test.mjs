@@ -28,6 +28,7 @@ import {
     prepareHistoricalDataOnce
 } from '../app/simulator/simulator-engine-helpers.js';
 import { canonicalizeHistoricalContractValue } from '../app/simulator/historical-backtest-contract.js';
+import { HISTORICAL_DATA_MANIFEST } from '../app/simulator/simulator-data.js';
 
 console.log('--- Monte Carlo Export Contract Tests ---');
 
@@ -212,6 +213,8 @@ const schemaGolden = JSON.parse(fs.readFileSync(
     assertEqual(document.exportedAtUtc, exportedAt, 'export timestamp is normalized to UTC');
     assertEqual(document.app.packageVersion, MONTE_CARLO_APP_VERSION.packageVersion, 'export identifies the app package version');
     assertEqual(document.engine.apiVersion, EngineAPI.getVersion().api, 'export identifies the Engine API version');
+    assertEqual(document.engine.dataset.datasetId, HISTORICAL_DATA_MANIFEST.datasetId, 'export names the historical dataset');
+    assertEqual(document.engine.dataset.contentHash, HISTORICAL_DATA_MANIFEST.contentHash.value, 'export pins the dataset content hash');
     assertEqual(document.snapshotPolicy.schemaVersion, 'MonteCarloSnapshotPolicyV1', 'export identifies the snapshot policy version');
     assert(document.identifiers.requestId.startsWith('mcrq_'), 'export exposes a stable request id');
     assert(document.identifiers.runId.startsWith('mcrun_'), 'export exposes a stable run id');
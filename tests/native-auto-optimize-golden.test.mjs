@@ -0,0 +1,74 @@
+import fs from 'node:fs';
+import path from 'node:path';
+import { fileURLToPath } from 'node:url';
+
+import { runMonteCarloAutoOptimizeSerial } from '../app/simulator/auto-optimize-worker.js';
+import { evaluateCandidate } from '../app/simulator/auto-optimize-evaluate.js';
+import { EngineAPI } from '../engine/index.mjs';
+
+// Golden champion of runAutoOptimize (auto_optimize.js) under V8 with real
+// Monte-Carlo evaluations. src-tauri/src/auto_optimize.rs runs the same
+// config natively and must pick the same champion. The objective is the
+// success rate, so most candidates tie and the tie-breakers of
+// compareByObjective decide the ranking.
+// Regenerate after intended engine changes with UPDATE_NATIVE_AUTO_OPT_GOLDEN=1.
+
+const __filename = fileURLToPath(import.meta.url);
+const __dirname = path.dirname(__filename);
+const goldenPath = path.join(__dirname, 'fixtures', 'native-auto-optimize-golden-v1.json');
+const monteCarloGoldenPath = path.join(__dirname, 'fixtures', 'native-monte-carlo-golden-v1.json');
+const UPDATE_GOLDEN = process.env.UPDATE_NATIVE_AUTO_OPT_GOLDEN === '1';
+
+console.log('--- Native Auto-Optimize Golden Tests ---');
+
+const config = {
+    objective: { metric: 'SuccessRate', direction: 'max' },
+    params: {
+        targetEq: { min: 40, max: 80, step: 5 },
+        runwayMinM: { min: 12, max: 36, step: 6 }
+    },
+    runsPerCandidate: 10,
+    seedsTrain: 2,
+    seedsTest: 1,
+    constraints: { sr99: false, noex: false, ts45: false, dd55: false },
+    maxDauer: 8
+};
+
+// Same base inputs as the native golden run; the simulator form is not read.
+const baseInputs = JSON.parse(fs.readFileSync(monteCarloGoldenPath, 'utf8')).request.scenario.normalizedInputs;
+const runBatch = args => runMonteCarloAutoOptimizeSerial({ ...args, engine: EngineAPI });
+
+const prevDocument = global.document;
+const prevLocalStorage = global.localStorage;
+const prevWindow = global.window;
+let actual;
+try {
+    global.document = { getElementById: () => ({ value: '0', checked: false }) };
+    global.localStorage = { getItem: () => null, setItem: () => { }, removeItem: () => { } };
+    global.window = {};
+    const { runAutoOptimize } = await import('../app/simulator/auto_optimize.js');
+    const result = await runAutoOptimize({
+        ...config,
+        evaluateCandidateFn: (candidate, _inputs, runs, maxDauer, seeds, constraints) =>
+            evaluateCandidate(candidate, baseInputs, runs, maxDauer, seeds, constraints, runBatch)
+    });
+    actual = JSON.parse(JSON.stringify({
+        config,
+        result: { championCfg: result.championCfg, metricsTest: result.metricsTest, stability: result.stability }
+    }));
+} finally {
+    global.document = prevDocument;
+    global.localStorage = prevLocalStorage;
+    global.window = prevWindow;
+}
+
+if (UPDATE_GOLDEN) {
+    fs.writeFileSync(goldenPath, `${JSON.stringify(actual, null, 2)}\n`, 'utf8');
+    console.log(`Updated ${path.relative(path.join(__dirname, '..'), goldenPath)}`);
+}
+
+const golden = JSON.parse(fs.readFileSync(goldenPath, 'utf8'));
+assertEqual(JSON.stringify(actual.config), JSON.stringify(golden.config), 'golden config matches the test config');
+assertEqual(JSON.stringify(actual.result), JSON.stringify(golden.result), 'V8 optimizer reproduces the golden champion');
+
+console.log('✅ Native auto-optimize golden tests passed');
@@ -0,0 +1,106 @@
+import fs from 'node:fs';
+import path from 'node:path';
+import { fileURLToPath } from 'node:url';
+
+import { createMonteCarloRunRequestV1, createMonteCarloRunResultV1 } from '../app/simulator/monte-carlo-contracts.js';
+import { runMonteCarloSimulation } from '../app/simulator/monte-carlo-runner.js';
+import { getDataVersion, prepareHistoricalDataOnce } from '../app/simulator/simulator-engine-helpers.js';
+import { EngineAPI } from '../engine/index.mjs';
+
+// Golden run of the serial JS runner under V8 (Node, same engine as the
+// webview). src-tauri/src/monte_carlo.rs replays the stored request on the
+// embedded QuickJS runtime and compares against this result, so the native
+// runner is checked against a different JS engine, not against itself.
+// Regenerate after intended engine changes with UPDATE_NATIVE_MC_GOLDEN=1.
+
+const __filename = fileURLToPath(import.meta.url);
+const __dirname = path.dirname(__filename);
+const goldenPath = path.join(__dirname, 'fixtures', 'native-monte-carlo-golden-v1.json');
+const UPDATE_GOLDEN = process.env.UPDATE_NATIVE_MC_GOLDEN === '1';
+
+console.log('--- Native Monte Carlo Golden Tests ---');
+
+const inputs = {
+    startAlter: 65,
+    accumulationPhase: { enabled: false },
+    transitionYear: 0,
+    startVermoegen: 500000,
+    depotwertAlt: 500000,
+    einstandAlt: 400000,
+    tagesgeld: 20000,
+    geldmarktEtf: 0,
+    zielLiquiditaet: 20000,
+    startFloorBedarf: 24000,
+    startFlexBedarf: 6000,
+    flexBudgetAnnual: 0,
+    flexBudgetRecharge: 0,
+    renteMonatlich: 0,
+    renteStartOffsetJahre: 0,
+    rentAdjPct: 0,
+    goldAktiv: false,
+    goldZielProzent: 0,
+    goldFloorProzent: 0,
+    goldSteuerfrei: false,
+    risikoprofil: 'sicherheits-dynamisch',
+    rebalancingBand: 20,
+    runwayTargetMonths: 36,
+    runwayMinMonths: 24,
+    targetEq: 60,
+    maxSkimPctOfEq: 10,
+    maxBearRefillPctOfEq: 5,
+    marketCapeRatio: 20,
+    capeRatio: 20,
+    kirchensteuerSatz: 0,
+    startSPB: 1000,
+    dynamicFlex: false,
+    horizonMethod: 'survival_quantile',
+    horizonYears: 30,
+    survivalQuantile: 0.85,
+    goGoActive: false,
+    goGoMultiplier: 1,
+    pflegefallLogikAktivieren: false,
+    geschlecht: 'm',
+    partner: { aktiv: false },
+    stressPreset: 'NONE'
+};
+const widowOptions = { mode: 'stop', percent: 0, marriageOffsetYears: 0, minMarriageYears: 0 };
+const monteCarloParams = {
+    anzahl: 40,
+    maxDauer: 20,
+    blockSize: 5,
+    seed: 20260718,
+    methode: 'block',
+    rngMode: 'per-run-seed',
+    startYearMode: 'UNIFORM'
+};
+
+prepareHistoricalDataOnce();
+const results = await runMonteCarloSimulation({ inputs, widowOptions, monteCarloParams, useCapeSampling: false, engine: EngineAPI });
+const request = createMonteCarloRunRequestV1({
+    inputs,
+    widowOptions,
+    monteCarloParams,
+    useCapeSampling: false,
+    samplingDiagnostics: results.samplingDiagnostics,
+    dataVersion: results.samplingDiagnostics?.dataVersion || getDataVersion(),
+    execution: { mode: 'serial' }
+});
+const result = createMonteCarloRunResultV1({
+    aggregatedResults: results.aggregatedResults,
+    samplingDiagnostics: results.samplingDiagnostics,
+    executionDiagnostics: {},
+    requestedRuns: monteCarloParams.anzahl
+});
+const actual = JSON.parse(JSON.stringify({ request, result }));
+
+if (UPDATE_GOLDEN) {
+    fs.writeFileSync(goldenPath, `${JSON.stringify(actual, null, 2)}\n`, 'utf8');
+    console.log(`Updated ${path.relative(path.join(__dirname, '..'), goldenPath)}`);
+}
+
+const golden = JSON.parse(fs.readFileSync(goldenPath, 'utf8'));
+assertEqual(JSON.stringify(actual.request), JSON.stringify(golden.request), 'golden request matches the current contract builder');
+assertEqual(JSON.stringify(actual.result), JSON.stringify(golden.result), 'V8 runner reproduces the golden result bit for bit');
+assertEqual(golden.result.sampleSize.requestedRuns, monteCarloParams.anzahl, 'golden covers every requested run');
+
+console.log('✅ Native Monte Carlo golden tests passed');
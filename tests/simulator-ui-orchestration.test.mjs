@@ -55,6 +55,11 @@ class MockElement {
         return child;
     }
 
+    replaceChildren(...children) {
+        this.children = [];
+        children.forEach(child => this.appendChild(child));
+    }
+
     dispatchEvent(event) {
         const nextEvent = event || { type: '' };
         nextEvent.target = nextEvent.target || this;
@@ -209,7 +214,8 @@ async function runSimulatorUiOrchestrationTests() {
         sweepUiModule,
         optimizerModule,
         monteCarloUiModule,
-        backtestUiModule
+        backtestUiModule,
+        datasetModule
     ] = await Promise.all([
         import('../app/simulator/simulator-main.js'),
         import('../app/simulator/simulator-main-tabs.js'),
@@ -220,7 +226,8 @@ async function runSimulatorUiOrchestrationTests() {
         import('../app/simulator/simulator-main-sweep-ui.js'),
         import('../app/simulator/simulator-optimizer.js'),
         import('../app/simulator/monte-carlo-ui.js'),
-        import('../app/simulator/simulator-backtest.js')
+        import('../app/simulator/simulator-backtest.js'),
+        import('../app/simulator/simulator-main-dataset.js')
     ]);
 
     void mainModule;
@@ -467,6 +474,46 @@ async function runSimulatorUiOrchestrationTests() {
         assertEqual(cohortHorizon.disabled, true, 'Cohort horizon remains disabled until explicitly selected');
     }
 
+    console.log('Test 10: dataset picker lists user datasets and reloads after a pick');
+    {
+        const row = registerElement(documentRef, 'historicalDatasetRow', { tagName: 'div' });
+        row.hidden = true;
+        const select = registerElement(documentRef, 'historicalDatasetSelect', { tagName: 'select' });
+        const status = registerElement(documentRef, 'historicalDatasetStatus', { tagName: 'small' });
+        await datasetModule.initHistoricalDatasetPicker();
+        assertEqual(row.hidden, true, 'Picker stays hidden outside the desktop app');
+
+        const calls = [];
+        let reloads = 0;
+        window.location.reload = () => { reloads++; };
+        window.__TAURI__ = {
+            core: {
+                invoke: async (command, args) => {
+                    calls.push([command, args]);
+                    if (command === 'list_user_datasets') {
+                        return [{ datasetId: 'user-0123456789ab', label: 'DAX-lastig', startYear: 1990, endYear: 2010 }];
+                    }
+                    return null;
+                }
+            }
+        };
+        try {
+            await datasetModule.initHistoricalDatasetPicker();
+            assertEqual(row.hidden, false, 'Picker is shown in the desktop app');
+            assertEqual(select.children.map(option => option.value).join(','), ',user-0123456789ab', 'Embedded dataset plus every user dataset');
+            assertEqual(select.children[1].textContent, 'DAX-lastig (1990-2010)', 'Option names label and period');
+            assertEqual(select.value, '', 'Embedded dataset is active without a pick');
+            assert(status.textContent.includes('eingebetteter Bestand'), 'Status names the active dataset');
+
+            select.value = 'user-0123456789ab';
+            await select.listeners.change[0]();
+            assertEqual(JSON.stringify(calls[1]), JSON.stringify(['select_user_dataset', { datasetId: 'user-0123456789ab' }]), 'Pick is stored natively');
+            assertEqual(reloads, 1, 'Window reloads to load the picked dataset');
+        } finally {
+            delete window.__TAURI__;
+        }
+    }
+
     console.log('Simulator UI orchestration tests passed');
     console.log('--- Simulator UI Orchestration Tests Completed ---');
 }
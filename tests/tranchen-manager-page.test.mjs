@@ -320,7 +320,7 @@ async function runTranchenManagerPageTests() {
             const parsed = new NodeURL(String(url));
             if (parsed.pathname === '/search') {
                 const query = parsed.searchParams.get('q');
-                return { ok: true, status: 200, json: async () => ({ quotes: [{ symbol: query }] }) };
+                return { ok: true, status: 200, json: async () => ({ query, results: [{ symbol: query, name: query, type: 'etf' }] }) };
             }
             const symbol = parsed.searchParams.get('symbol');
             quoteCalls.set(symbol, (quoteCalls.get(symbol) || 0) + 1);
@@ -195,14 +195,14 @@ try {
         global.fetch = async (url) => {
             const query = decodeURIComponent(String(url).split('q=')[1] || '');
             if (query === 'name') {
-                return jsonResponse({ quotes: [
-                    { symbol: 'AAA', shortname: 'Other Fund', exchange: 'XETRA' },
-                    { symbol: 'BBB.DE', shortname: 'Vanguard FTSE', exchange: 'GER' }
+                return jsonResponse({ query, results: [
+                    { symbol: 'AAA', name: 'Other Fund', exchange: 'XETRA', type: 'etf' },
+                    { symbol: 'BBB.DE', name: 'Vanguard FTSE', exchange: 'GER', type: 'etf' }
                 ] });
             }
-            return jsonResponse({ quotes: [
-                { symbol: 'INVALID@GER', exchange: 'GER' },
-                { symbol: query.toUpperCase(), exchange: 'GER' }
+            return jsonResponse({ query, results: [
+                { symbol: 'INVALID@GER', exchange: 'GER', type: 'etf' },
+                { symbol: query.toUpperCase(), exchange: 'GER', type: 'etf' }
             ] });
         };
 
@@ -234,6 +234,20 @@ try {
             ));
             assertEqual(error?.code, 'UNSUPPORTED_CURRENCY', `Node proxy should reject ${currency}`);
         }
+
+        const search = nodeProxy.normalizeSearchResults('IE00BK5BQT80', { quotes: [
+            { symbol: 'vwce.de', exchange: 'GER', exchDisp: 'XETRA', quoteType: 'ETF', longname: 'Vanguard FTSE All-World' },
+            { symbol: '^GDAXI', exchange: 'GER', quoteType: 'INDEX' },
+            { symbol: 'VWCE.F', exchange: 'FRA', quoteType: 'ETF', currency: 'eur' }
+        ], news: [{ title: 'Markets' }] }, 1);
+        assertEqual(JSON.stringify(search), JSON.stringify({ query: 'IE00BK5BQT80', results: [{
+            symbol: 'VWCE.DE', name: 'Vanguard FTSE All-World', exchange: 'GER', exchangeName: 'XETRA', type: 'etf', currency: null, isin: 'IE00BK5BQT80'
+        }] }), 'Node proxy should return the normalized search shape');
+        assertEqual(nodeProxy.normalizeSearchQuery('  vanguard   all '), 'vanguard all', 'Node proxy should collapse blanks');
+        for (const run of [() => nodeProxy.normalizeSearchQuery('<script>'), () => nodeProxy.normalizeSearchQuery(' '), () => nodeProxy.parseSearchLimit('51')]) {
+            const error = await captureError(async () => run());
+            assertEqual(error?.code, 'INVALID_SEARCH_QUERY', 'Node proxy should reject invalid search input');
+        }
     }
     console.log('✓ Node/browser parity OK');
 
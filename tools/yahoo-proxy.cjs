@@ -245,10 +245,99 @@ const proxyQuote = async (rawSymbol, res) => {
   sendError(res, lastError);
 };
 
-const proxySearch = async (query, res) => {
+const SEARCH_QUERY_MAX_CHARS = 64;
+const SEARCH_DEFAULT_LIMIT = 10;
+const SEARCH_MAX_LIMIT = 50;
+const SEARCH_QUERY_PATTERN = /^[\p{L}\p{N} .\-&'^=/+,]+$/u;
+const SEARCH_INSTRUMENT_TYPES = { EQUITY: 'equity', ETF: 'etf', MUTUALFUND: 'fund', MONEYMARKET: 'money_market' };
+
+const normalizeSearchQuery = (raw) => {
+  const query = String(raw || '').trim().split(/\s+/).filter(Boolean).join(' ');
+  const length = Array.from(query).length;
+  if (length === 0 || length > SEARCH_QUERY_MAX_CHARS) {
+    throw quoteError('INVALID_SEARCH_QUERY', `Suchbegriff muss 1 bis ${SEARCH_QUERY_MAX_CHARS} Zeichen lang sein.`, 400);
+  }
+  if (!SEARCH_QUERY_PATTERN.test(query)) {
+    const character = Array.from(query).find(char => !SEARCH_QUERY_PATTERN.test(char));
+    throw quoteError('INVALID_SEARCH_QUERY', `Suchbegriff enthaelt ungueltiges Zeichen '${character}'.`, 400);
+  }
+  return query;
+};
+
+const parseSearchLimit = (raw) => {
+  if (raw === null || raw === undefined) return SEARCH_DEFAULT_LIMIT;
+  const limit = /^\d+$/.test(String(raw).trim()) ? Number(String(raw).trim()) : NaN;
+  if (!Number.isInteger(limit) || limit < 1 || limit > SEARCH_MAX_LIMIT) {
+    throw quoteError('INVALID_SEARCH_QUERY', `limit muss zwischen 1 und ${SEARCH_MAX_LIMIT} liegen.`, 400);
+  }
+  return limit;
+};
+
+/** ISO 6166 check digit (letters 10..35, then Luhn). */
+const isValidIsin = (value) => {
+  const isin = String(value || '').replace(/\s+/g, '').toUpperCase();
+  if (!/^[A-Z]{2}[A-Z0-9]{9}[0-9]$/.test(isin)) return false;
+  const digits = Array.from(isin.slice(0, 11)).map(char => parseInt(char, 36)).join('');
+  let sum = 0;
+  Array.from(digits).reverse().forEach((digit, index) => {
+    const value = Number(digit) * (index % 2 === 0 ? 2 : 1);
+    sum += Math.floor(value / 10) + (value % 10);
+  });
+  return (10 - (sum % 10)) % 10 === Number(isin[11]);
+};
+
+const searchText = (quote, fields) => {
+  for (const field of fields) {
+    const value = typeof quote?.[field] === 'string' ? quote[field].trim() : '';
+    if (value) return value;
+  }
+  return null;
+};
+
+/** Same `/search` contract as the Tauri proxy in src-tauri/src/lib.rs. */
+const normalizeSearchResults = (query, data, limit = SEARCH_DEFAULT_LIMIT) => {
+  const isin = isValidIsin(query) ? query.replace(/\s+/g, '').toUpperCase() : null;
+  const results = [];
+  for (const quote of Array.isArray(data?.quotes) ? data.quotes : []) {
+    if (results.length >= limit) break;
+    if (quote?.isYahooFinance === false) continue;
+    const type = SEARCH_INSTRUMENT_TYPES[quote?.quoteType];
+    if (!type) continue;
+    let symbol;
+    try {
+      symbol = normalizeYahooSymbol(quote?.symbol);
+    } catch {
+      continue;
+    }
+    if (results.some(result => result.symbol === symbol)) continue;
+    const exchange = searchText(quote, ['exchange']);
+    const currency = searchText(quote, ['currency']);
+    results.push({
+      symbol,
+      name: searchText(quote, ['longname', 'shortname']) || symbol,
+      exchange,
+      exchangeName: searchText(quote, ['exchDisp']) || exchange,
+      type,
+      currency: currency ? currency.toUpperCase() : null,
+      isin
+    });
+  }
+  return { query, results };
+};
+
+const proxySearch = async (rawQuery, rawLimit, res) => {
+  let query;
+  let limit;
+  try {
+    query = normalizeSearchQuery(rawQuery);
+    limit = parseSearchLimit(rawLimit);
+  } catch (error) {
+    sendError(res, error);
+    return;
+  }
   const url = `https://query1.finance.yahoo.com/v1/finance/search?q=${encodeURIComponent(query)}`;
   try {
-    sendJson(res, 200, await fetchJson(url));
+    sendJson(res, 200, normalizeSearchResults(query, await fetchJson(url), limit));
   } catch (error) {
     sendError(res, error);
   }
@@ -292,7 +381,7 @@ const createServer = () => http.createServer((req, res) => {
       sendError(res, quoteError('INVALID_SEARCH_QUERY', 'Suchbegriff fehlt.', 400));
       return;
     }
-    void proxySearch(query, res);
+    void proxySearch(query, url.searchParams.get('limit'), res);
     return;
   }
 
@@ -324,7 +413,10 @@ module.exports = {
   QUOTE_MAX_AGE_SECONDS,
   createServer,
   normalizeProviderQuote,
+  normalizeSearchQuery,
+  normalizeSearchResults,
   normalizeYahooSymbol,
+  parseSearchLimit,
   pickChartCandidate,
   pickQuoteCandidate
 };